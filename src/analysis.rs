@@ -0,0 +1,854 @@
+//! A summary of a stretch of binlog, for incident review.
+//!
+//! An [`Analyzer`] is fed every event, including the internal ones, like a
+//! [`TextWriter`](crate::text::TextWriter), and its [`Report`] gives:
+//!
+//! - inserted, updated and deleted rows and the bytes of rows events, per table
+//! - the largest transactions by bytes, and the longest from their first event to their commit
+//! - every DDL statement, in binlog order
+//! - transactions, row changes and bytes per minute
+//! - the rows changed most often, by primary key, for the tables whose table map events carry
+//!   one
+//!
+//! The report prints as text and serializes to JSON.
+//!
+//! ```no_run
+//! use mysqlbinlog_network::analysis::Analyzer;
+//!
+//! let mut analyzer = Analyzer::new();
+//! analyzer.add_file("mysql-bin.000001").unwrap();
+//! analyzer.add_file("mysql-bin.000002").unwrap();
+//! print!("{}", analyzer.report());
+//! ```
+//!
+//! Times come from the event headers, which hold when the statement started on the source, so
+//! the duration of a transaction is from its first statement to its commit, in whole seconds.
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::Path;
+
+use serde_derive::Serialize;
+
+use crate::client::sync::Runner;
+use crate::mysql_binlog::binlog_file::BinlogFile;
+use crate::mysql_binlog::errors::BinlogParseError;
+use crate::mysql_binlog::event::{EventData, RowEvent, TypeCode, EVENT_HEADER_SIZE};
+use crate::mysql_binlog::table_map::TableMap;
+use crate::pkg::event::Event;
+use crate::sql::statement::{classify, Statement};
+use crate::sql::{value_literal, TableDef};
+use crate::text::{civil_datetime, EventInfo};
+
+/// Row changes and bytes of the rows events of one table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TableStats {
+    pub schema: String,
+    pub table: String,
+    pub inserts: u64,
+    pub updates: u64,
+    pub deletes: u64,
+    pub bytes: u64,
+}
+
+/// One committed or rolled back transaction, or a statement logged on its own
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransactionStats {
+    /// The GTID, or `file:offset` of its first event without one
+    pub id: String,
+    pub file: String,
+    pub offset: u64,
+    pub start: u32,
+    pub end: u32,
+    /// Seconds from the first event to the commit
+    pub duration: u32,
+    pub events: u64,
+    pub rows: u64,
+    pub bytes: u64,
+    /// `schema.table` of every table changed
+    pub tables: Vec<String>,
+}
+
+/// A DDL statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DdlStatement {
+    pub timestamp: u32,
+    pub file: String,
+    pub offset: u64,
+    pub gtid: Option<String>,
+    /// The default database the statement ran in
+    pub schema: String,
+    pub query: String,
+}
+
+/// What was written in one minute
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MinuteStats {
+    /// Unix time of the start of the minute
+    pub minute: u32,
+    /// Transactions committed
+    pub transactions: u64,
+    pub inserts: u64,
+    pub updates: u64,
+    pub deletes: u64,
+    /// Bytes of all events
+    pub bytes: u64,
+}
+
+/// A row and how often it was changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HotRow {
+    pub schema: String,
+    pub table: String,
+    /// `column=value` of each key column
+    pub key: String,
+    pub changes: u64,
+}
+
+/// What an [`Analyzer`] found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub events: u64,
+    pub bytes: u64,
+    pub transactions: u64,
+    pub first_timestamp: Option<u32>,
+    pub last_timestamp: Option<u32>,
+    /// Most bytes first
+    pub tables: Vec<TableStats>,
+    pub largest_transactions: Vec<TransactionStats>,
+    pub longest_transactions: Vec<TransactionStats>,
+    pub ddl: Vec<DdlStatement>,
+    pub minutes: Vec<MinuteStats>,
+    pub hot_rows: Vec<HotRow>,
+}
+
+/// The transaction being read
+struct Transaction {
+    stats: TransactionStats,
+    /// Whether it started with `BEGIN`, rather than being a single statement
+    begun: bool,
+    tables: BTreeSet<String>,
+}
+
+// rows whose changes are counted, per hot row reported
+const HOT_ROWS_PER_TOP: usize = 1000;
+
+/// Collects the statistics of a [`Report`] from a stream of events
+pub struct Analyzer {
+    top: usize,
+    table_map: TableMap,
+    file: String,
+    gtid: Option<String>,
+    transaction: Option<Transaction>,
+    events: u64,
+    bytes: u64,
+    transactions: u64,
+    first_timestamp: Option<u32>,
+    last_timestamp: Option<u32>,
+    tables: BTreeMap<(String, String), TableStats>,
+    largest: Vec<TransactionStats>,
+    longest: Vec<TransactionStats>,
+    ddl: Vec<DdlStatement>,
+    minutes: BTreeMap<u32, MinuteStats>,
+    hot_rows: HashMap<(String, String, String), u64>,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Analyzer::new()
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Analyzer {
+            top: 10,
+            table_map: TableMap::new(),
+            file: String::new(),
+            gtid: None,
+            transaction: None,
+            events: 0,
+            bytes: 0,
+            transactions: 0,
+            first_timestamp: None,
+            last_timestamp: None,
+            tables: BTreeMap::new(),
+            largest: Vec::new(),
+            longest: Vec::new(),
+            ddl: Vec::new(),
+            minutes: BTreeMap::new(),
+            hot_rows: HashMap::new(),
+        }
+    }
+
+    /// How many of the largest and longest transactions and of the hot rows to report.
+    /// Defaults to 10. The changes of up to 1000 rows per hot row reported are counted.
+    pub fn set_top(&mut self, top: usize) {
+        self.top = top;
+    }
+
+    /// The tables mapped so far, for decoding the rows events to pass in
+    pub fn table_map(&self) -> &TableMap {
+        &self.table_map
+    }
+
+    /// Name the binlog file the following events are read from. Not needed when reading from a
+    /// server, which starts every file with a rotate event.
+    pub fn set_file(&mut self, file: &str) {
+        self.file = file.to_owned();
+    }
+
+    /// Read every event of a local binlog file
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), BinlogParseError> {
+        let path = path.as_ref();
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        self.set_file(&name.to_string_lossy());
+        for event in BinlogFile::try_from_path(path)?.events(Some(4)) {
+            let event = event?;
+            let data = event.inner(Some(&self.table_map))?;
+            self.record_event(&EventInfo::from_event(&event), event.data(), data.as_ref());
+        }
+        Ok(())
+    }
+
+    /// Record the event `runner.get_event()` returned last
+    pub fn add_runner_event(&mut self, runner: &Runner, event: &Event) {
+        if let Some((info, body)) =
+            EventInfo::from_bytes(runner.last_event_bytes(), runner.checksum_length())
+        {
+            self.record_event(&info, body, event.event.as_ref());
+        }
+    }
+
+    /// Record one event. `body` is the event without its common header and checksum, `data`
+    /// what it decoded to, if anything.
+    pub fn record_event(&mut self, info: &EventInfo, body: &[u8], data: Option<&EventData>) {
+        if info.type_code == TypeCode::HeartbeatLogEvent {
+            return;
+        }
+        let size = (EVENT_HEADER_SIZE + body.len() + info.checksum.map_or(0, |_| 4)) as u64;
+        self.events += 1;
+        self.bytes += size;
+        // artificial events, like the rotate a dump starts with, have no time
+        if info.timestamp != 0 {
+            self.first_timestamp.get_or_insert(info.timestamp);
+            self.last_timestamp = Some(info.timestamp);
+            self.minute(info.timestamp).bytes += size;
+        }
+        match data {
+            Some(EventData::RotateEvent { next_log_name, .. }) => {
+                self.file = next_log_name.clone();
+                return;
+            }
+            Some(EventData::GtidLogEvent {
+                uuid, coordinate, ..
+            }) => {
+                self.gtid = Some(format!("{}:{}", uuid.to_hyphenated(), coordinate));
+                self.start(info);
+            }
+            Some(EventData::MariadbGtidEvent {
+                domain_id, seq_no, ..
+            }) => {
+                self.gtid = Some(format!("{}-{}-{}", domain_id, info.server_id, seq_no));
+                self.start(info);
+            }
+            _ if info.type_code == TypeCode::AnonymousGtidLogEvent => {
+                self.gtid = None;
+                self.start(info);
+            }
+            _ => {}
+        }
+        if let Some(ref mut transaction) = self.transaction {
+            transaction.stats.events += 1;
+            transaction.stats.bytes += size;
+        }
+        match data {
+            Some(EventData::QueryEvent { query, schema, .. }) => {
+                let statement = classify(query, None);
+                if statement == Statement::Begin {
+                    if self.transaction.is_none() {
+                        self.start(info);
+                        self.count(size);
+                    }
+                    if let Some(ref mut transaction) = self.transaction {
+                        transaction.begun = true;
+                    }
+                } else if let Statement::Commit | Statement::Rollback = statement {
+                    self.end(info);
+                } else {
+                    if let Statement::Ddl { .. } = statement {
+                        self.ddl.push(DdlStatement {
+                            timestamp: info.timestamp,
+                            file: self.file.clone(),
+                            offset: info.offset,
+                            gtid: self.gtid.clone(),
+                            schema: schema.clone(),
+                            query: query.clone(),
+                        });
+                    }
+                    // a statement logged on its own, like DDL, is a transaction of its own
+                    match self.transaction {
+                        Some(ref transaction) if transaction.begun => {}
+                        Some(_) => self.end(info),
+                        None => {
+                            self.start(info);
+                            self.count(size);
+                            self.end(info);
+                        }
+                    }
+                }
+            }
+            Some(EventData::XIDEvent { .. }) => self.end(info),
+            Some(EventData::TableMapEvent {
+                table_id,
+                schema_name,
+                table_name,
+                columns,
+                metadata,
+                ..
+            }) => self.table_map.handle_with_metadata(
+                *table_id,
+                schema_name.clone(),
+                table_name.clone(),
+                columns.clone(),
+                metadata.clone(),
+            ),
+            Some(EventData::WriteRowsEvent { table_id, rows }) => {
+                self.record_rows(info, *table_id, rows, size, (rows.len() as u64, 0, 0))
+            }
+            Some(EventData::UpdateRowsEvent { table_id, rows }) => {
+                self.record_rows(info, *table_id, rows, size, (0, rows.len() as u64, 0))
+            }
+            Some(EventData::DeleteRowsEvent { table_id, rows }) => {
+                self.record_rows(info, *table_id, rows, size, (0, 0, rows.len() as u64))
+            }
+            _ => {}
+        }
+    }
+
+    /// What has been recorded so far. A transaction still open is left out.
+    pub fn report(&self) -> Report {
+        let mut tables: Vec<TableStats> = self.tables.values().cloned().collect();
+        tables.sort_by_key(|t| Reverse(t.bytes));
+        let mut largest = self.largest.clone();
+        sort_largest(&mut largest, self.top);
+        let mut longest = self.longest.clone();
+        sort_longest(&mut longest, self.top);
+        let mut hot_rows: Vec<HotRow> = self
+            .hot_rows
+            .iter()
+            .map(|((schema, table, key), changes)| HotRow {
+                schema: schema.clone(),
+                table: table.clone(),
+                key: key.clone(),
+                changes: *changes,
+            })
+            .collect();
+        hot_rows.sort_by(|a, b| {
+            b.changes
+                .cmp(&a.changes)
+                .then_with(|| (&a.schema, &a.table, &a.key).cmp(&(&b.schema, &b.table, &b.key)))
+        });
+        hot_rows.truncate(self.top);
+        Report {
+            events: self.events,
+            bytes: self.bytes,
+            transactions: self.transactions,
+            first_timestamp: self.first_timestamp,
+            last_timestamp: self.last_timestamp,
+            tables,
+            largest_transactions: largest,
+            longest_transactions: longest,
+            ddl: self.ddl.clone(),
+            minutes: self.minutes.values().cloned().collect(),
+            hot_rows,
+        }
+    }
+
+    fn minute(&mut self, timestamp: u32) -> &mut MinuteStats {
+        let minute = timestamp - timestamp % 60;
+        self.minutes.entry(minute).or_insert_with(|| MinuteStats {
+            minute,
+            ..Default::default()
+        })
+    }
+
+    /// Start a transaction at this event, ending one left open
+    fn start(&mut self, info: &EventInfo) {
+        if self.transaction.is_some() {
+            self.end(info);
+        }
+        self.transaction = Some(Transaction {
+            stats: TransactionStats {
+                id: match self.gtid {
+                    Some(ref gtid) => gtid.clone(),
+                    None => format!("{}:{}", self.file, info.offset),
+                },
+                file: self.file.clone(),
+                offset: info.offset,
+                start: info.timestamp,
+                end: info.timestamp,
+                duration: 0,
+                events: 0,
+                rows: 0,
+                bytes: 0,
+                tables: Vec::new(),
+            },
+            begun: false,
+            tables: BTreeSet::new(),
+        });
+    }
+
+    /// Count an event into a transaction started after it was seen
+    fn count(&mut self, size: u64) {
+        if let Some(ref mut transaction) = self.transaction {
+            transaction.stats.events += 1;
+            transaction.stats.bytes += size;
+        }
+    }
+
+    fn end(&mut self, info: &EventInfo) {
+        let Transaction {
+            mut stats, tables, ..
+        } = match self.transaction.take() {
+            Some(transaction) => transaction,
+            None => return,
+        };
+        self.gtid = None;
+        stats.end = info.timestamp.max(stats.start);
+        stats.duration = stats.end - stats.start;
+        stats.tables = tables.into_iter().collect();
+        self.transactions += 1;
+        if info.timestamp != 0 {
+            self.minute(info.timestamp).transactions += 1;
+        }
+        // keep the lists from growing with the stream
+        self.largest.push(stats.clone());
+        if self.largest.len() >= 2 * self.top.max(1) {
+            sort_largest(&mut self.largest, self.top);
+        }
+        self.longest.push(stats);
+        if self.longest.len() >= 2 * self.top.max(1) {
+            sort_longest(&mut self.longest, self.top);
+        }
+    }
+
+    fn record_rows(
+        &mut self,
+        info: &EventInfo,
+        table_id: u64,
+        rows: &[RowEvent],
+        size: u64,
+        (inserts, updates, deletes): (u64, u64, u64),
+    ) {
+        let table = match self.table_map.get(table_id) {
+            Some(table) => TableDef::from_table_map(table),
+            None => return,
+        };
+        let count = rows.len() as u64;
+        let stats = self
+            .tables
+            .entry((table.schema_name.clone(), table.table_name.clone()))
+            .or_insert_with(|| TableStats {
+                schema: table.schema_name.clone(),
+                table: table.table_name.clone(),
+                ..Default::default()
+            });
+        stats.bytes += size;
+        stats.inserts += inserts;
+        stats.updates += updates;
+        stats.deletes += deletes;
+        if info.timestamp != 0 {
+            let minute = self.minute(info.timestamp);
+            minute.inserts += inserts;
+            minute.updates += updates;
+            minute.deletes += deletes;
+        }
+        if let Some(ref mut transaction) = self.transaction {
+            transaction.stats.rows += count;
+            transaction
+                .tables
+                .insert(format!("{}.{}", table.schema_name, table.table_name));
+        }
+        // rows are known by their primary key; without one there is nothing telling two rows
+        // apart from two versions of the same row
+        if table.primary_key.is_empty() {
+            return;
+        }
+        let key_columns = table.primary_key.clone();
+        for row in rows {
+            let cols = match row {
+                RowEvent::NewRow { cols } | RowEvent::DeletedRow { cols } => cols,
+                RowEvent::UpdatedRow { before_cols, .. } => before_cols,
+            };
+            let key: Option<Vec<String>> = key_columns
+                .iter()
+                .map(|&i| {
+                    let value = cols.get(i)?.as_ref()?;
+                    let name = table.columns.get(i).map_or("", |c| c.name.as_str());
+                    Some(format!(
+                        "{}={}",
+                        name,
+                        value_literal(value, table.columns.get(i))
+                    ))
+                })
+                .collect();
+            if let Some(key) = key {
+                *self
+                    .hot_rows
+                    .entry((
+                        table.schema_name.clone(),
+                        table.table_name.clone(),
+                        key.join(", "),
+                    ))
+                    .or_insert(0) += 1;
+            }
+        }
+        // keep the counts from growing with the stream: past the limit only the most changed
+        // rows are kept, and a row forgotten this way starts over if it shows up again
+        let limit = HOT_ROWS_PER_TOP * self.top.max(1);
+        if self.hot_rows.len() >= limit {
+            let mut hot_rows: Vec<_> = self.hot_rows.drain().collect();
+            hot_rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            hot_rows.truncate(limit / 2);
+            self.hot_rows = hot_rows.into_iter().collect();
+        }
+    }
+}
+
+fn sort_largest(transactions: &mut Vec<TransactionStats>, top: usize) {
+    transactions.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(b.rows.cmp(&a.rows)));
+    transactions.truncate(top);
+}
+
+fn sort_longest(transactions: &mut Vec<TransactionStats>, top: usize) {
+    transactions.sort_by(|a, b| b.duration.cmp(&a.duration).then(b.bytes.cmp(&a.bytes)));
+    transactions.truncate(top);
+}
+
+/// `YYYY-MM-DD hh:mm:ss` in UTC
+fn datetime(timestamp: u32) -> String {
+    let (year, month, day, hour, minute, second) = civil_datetime(timestamp);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+fn write_transactions(
+    f: &mut fmt::Formatter,
+    title: &str,
+    transactions: &[TransactionStats],
+) -> fmt::Result {
+    writeln!(f, "\n{}", title)?;
+    writeln!(
+        f,
+        "  {:<19}  {:>8}  {:>6}  {:>8}  {:>12}  {:<24}  id, tables",
+        "start", "duration", "events", "rows", "bytes", "position"
+    )?;
+    for t in transactions {
+        writeln!(
+            f,
+            "  {:<19}  {:>7}s  {:>6}  {:>8}  {:>12}  {:<24}  {}, {}",
+            datetime(t.start),
+            t.duration,
+            t.events,
+            t.rows,
+            t.bytes,
+            format!("{}:{}", t.file, t.offset),
+            t.id,
+            t.tables.join(" ")
+        )?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} events, {} bytes, {} transactions",
+            self.events, self.bytes, self.transactions
+        )?;
+        match (self.first_timestamp, self.last_timestamp) {
+            (Some(first), Some(last)) => {
+                writeln!(f, " from {} to {} UTC", datetime(first), datetime(last))?
+            }
+            _ => writeln!(f)?,
+        }
+
+        writeln!(f, "\nTables")?;
+        writeln!(
+            f,
+            "  {:<40}  {:>10}  {:>10}  {:>10}  {:>12}",
+            "table", "inserts", "updates", "deletes", "bytes"
+        )?;
+        for t in &self.tables {
+            writeln!(
+                f,
+                "  {:<40}  {:>10}  {:>10}  {:>10}  {:>12}",
+                format!("{}.{}", t.schema, t.table),
+                t.inserts,
+                t.updates,
+                t.deletes,
+                t.bytes
+            )?;
+        }
+
+        write_transactions(f, "Largest transactions", &self.largest_transactions)?;
+        write_transactions(f, "Longest transactions", &self.longest_transactions)?;
+
+        writeln!(f, "\nDDL")?;
+        for d in &self.ddl {
+            writeln!(
+                f,
+                "  {}  {}:{}  {}  {}",
+                datetime(d.timestamp),
+                d.file,
+                d.offset,
+                if d.schema.is_empty() { "-" } else { &d.schema },
+                d.query.split_whitespace().collect::<Vec<_>>().join(" ")
+            )?;
+        }
+
+        writeln!(f, "\nWrites per minute")?;
+        writeln!(
+            f,
+            "  {:<16}  {:>12}  {:>10}  {:>10}  {:>10}  {:>12}",
+            "minute", "transactions", "inserts", "updates", "deletes", "bytes"
+        )?;
+        for m in &self.minutes {
+            writeln!(
+                f,
+                "  {:<16}  {:>12}  {:>10}  {:>10}  {:>10}  {:>12}",
+                &datetime(m.minute)[..16],
+                m.transactions,
+                m.inserts,
+                m.updates,
+                m.deletes,
+                m.bytes
+            )?;
+        }
+
+        writeln!(f, "\nHot rows")?;
+        for r in &self.hot_rows {
+            writeln!(f, "  {:>8}  {}.{}  {}", r.changes, r.schema, r.table, r.key)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mysql_binlog::binlog_writer::BinlogFileWriter;
+    use crate::mysql_binlog::bit_set::BitSet;
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::event::{EventData, RowEvent};
+    use crate::mysql_binlog::table_map::TableMetadata;
+    use crate::mysql_binlog::value::MySQLValue;
+
+    use super::Analyzer;
+
+    fn query(query: &str) -> EventData {
+        EventData::QueryEvent {
+            thread_id: 7,
+            exec_time: 0,
+            error_code: 0,
+            schema: "shop".to_owned(),
+            query: query.to_owned(),
+        }
+    }
+
+    fn table_map() -> EventData {
+        EventData::TableMapEvent {
+            table_id: 108,
+            schema_name: "shop".to_owned(),
+            table_name: "orders".to_owned(),
+            columns: vec![ColumnType::VarChar(20), ColumnType::Long],
+            null_bitmap: BitSet::new(2),
+            metadata: TableMetadata {
+                column_names: vec!["note".to_owned(), "id".to_owned()],
+                primary_key: vec![1],
+                ..Default::default()
+            },
+        }
+    }
+
+    fn row(id: i32, note: &str) -> Vec<Option<MySQLValue>> {
+        vec![
+            Some(MySQLValue::String(note.to_owned())),
+            Some(MySQLValue::SignedInteger(i64::from(id))),
+        ]
+    }
+
+    #[test]
+    fn test_report() {
+        let path = std::env::temp_dir().join(format!("analysis-{}.000001", std::process::id()));
+        let uuid = uuid::Uuid::parse_str("0575a804-6403-11ea-8d3d-e454e8d4a4fe").unwrap();
+        let mut writer = BinlogFileWriter::create(&path, 1).unwrap();
+        let events = vec![
+            (
+                100,
+                EventData::GtidLogEvent {
+                    flags: 1,
+                    uuid,
+                    coordinate: 1,
+                    last_committed: Some(0),
+                    sequence_number: Some(1),
+                },
+            ),
+            (100, query("BEGIN")),
+            (100, table_map()),
+            (
+                100,
+                EventData::WriteRowsEvent {
+                    table_id: 108,
+                    rows: vec![
+                        RowEvent::NewRow { cols: row(1, "a") },
+                        RowEvent::NewRow { cols: row(2, "a") },
+                    ],
+                },
+            ),
+            (130, EventData::XIDEvent { xid: 10 }),
+            (150, query("BEGIN")),
+            (150, table_map()),
+            (
+                150,
+                EventData::UpdateRowsEvent {
+                    table_id: 108,
+                    rows: vec![RowEvent::UpdatedRow {
+                        before_cols: row(1, "a"),
+                        after_cols: row(1, "b"),
+                    }],
+                },
+            ),
+            (155, EventData::XIDEvent { xid: 11 }),
+            (200, query("ALTER TABLE orders ADD COLUMN total INT")),
+            (210, query("BEGIN")),
+            (210, table_map()),
+            (
+                210,
+                EventData::DeleteRowsEvent {
+                    table_id: 108,
+                    rows: vec![RowEvent::DeletedRow { cols: row(1, "b") }],
+                },
+            ),
+            (211, query("COMMIT")),
+            // left open at the end
+            (220, query("BEGIN")),
+        ];
+        for (timestamp, event) in &events {
+            writer.write_event(*timestamp, event).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut analyzer = Analyzer::new();
+        analyzer.set_top(2);
+        analyzer.add_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let report = analyzer.report();
+
+        assert_eq!(report.transactions, 4);
+        assert_eq!(report.last_timestamp, Some(220));
+        assert_eq!(report.tables.len(), 1);
+        let orders = &report.tables[0];
+        assert_eq!((orders.inserts, orders.updates, orders.deletes), (2, 1, 1));
+
+        assert_eq!(report.largest_transactions.len(), 2);
+        let longest = &report.longest_transactions[0];
+        assert_eq!(longest.id, "0575a804-6403-11ea-8d3d-e454e8d4a4fe:1");
+        assert_eq!((longest.start, longest.duration), (100, 30));
+        assert_eq!((longest.events, longest.rows), (5, 2));
+        assert_eq!(longest.tables, vec!["shop.orders".to_owned()]);
+        assert_eq!(report.longest_transactions[1].duration, 5);
+        assert!(report.largest_transactions[0].bytes >= report.largest_transactions[1].bytes);
+
+        assert_eq!(report.ddl.len(), 1);
+        assert_eq!(report.ddl[0].timestamp, 200);
+        assert_eq!(report.ddl[0].schema, "shop");
+        assert!(report.ddl[0].file.starts_with("analysis-"));
+
+        // the format description event holds the time the file was written; transactions count
+        // in the minute they commit
+        let minutes: Vec<_> = report
+            .minutes
+            .iter()
+            .filter(|m| m.minute < 3600)
+            .map(|m| (m.minute, m.transactions, m.inserts, m.updates, m.deletes))
+            .collect();
+        assert_eq!(
+            minutes,
+            vec![(60, 0, 2, 0, 0), (120, 2, 0, 1, 0), (180, 2, 0, 0, 1)]
+        );
+
+        assert_eq!(report.hot_rows[0].key, "id=1");
+        assert_eq!(report.hot_rows[0].changes, 3);
+        assert_eq!(report.hot_rows[1].key, "id=2");
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["hot_rows"][0]["changes"], 3);
+        let text = report.to_string();
+        assert!(text.contains("1970-01-01 00:01:40"), "{}", text);
+        assert!(text.contains("shop.orders"), "{}", text);
+        assert!(
+            text.contains("ALTER TABLE orders ADD COLUMN total INT"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_hot_rows() {
+        let path = std::env::temp_dir().join(format!("analysis-hot-{}.000001", std::process::id()));
+        let mut writer = BinlogFileWriter::create(&path, 1).unwrap();
+        let mut events = vec![query("BEGIN"), table_map()];
+        for note in &["a", "b", "c"] {
+            events.push(EventData::WriteRowsEvent {
+                table_id: 108,
+                rows: vec![RowEvent::NewRow { cols: row(1, note) }],
+            });
+        }
+        // more rows than are counted with a top of 1
+        events.push(EventData::WriteRowsEvent {
+            table_id: 108,
+            rows: (2..1502)
+                .map(|id| RowEvent::NewRow { cols: row(id, "a") })
+                .collect(),
+        });
+        // no primary key, so not a source of hot rows
+        events.push(EventData::TableMapEvent {
+            table_id: 109,
+            schema_name: "shop".to_owned(),
+            table_name: "log".to_owned(),
+            columns: vec![ColumnType::VarChar(20), ColumnType::Long],
+            null_bitmap: BitSet::new(2),
+            metadata: TableMetadata {
+                column_names: vec!["note".to_owned(), "id".to_owned()],
+                ..Default::default()
+            },
+        });
+        for _ in 0..5 {
+            events.push(EventData::WriteRowsEvent {
+                table_id: 109,
+                rows: vec![RowEvent::NewRow { cols: row(7, "a") }],
+            });
+        }
+        events.push(query("COMMIT"));
+        for event in &events {
+            writer.write_event(100, event).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut analyzer = Analyzer::new();
+        analyzer.set_top(1);
+        analyzer.add_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(analyzer.hot_rows.len() < 1000);
+        let report = analyzer.report();
+        assert_eq!(report.tables.len(), 2);
+        assert_eq!(report.hot_rows.len(), 1);
+        assert_eq!(report.hot_rows[0].table, "orders");
+        assert_eq!(report.hot_rows[0].key, "id=1");
+        assert_eq!(report.hot_rows[0].changes, 3);
+    }
+}
@@ -0,0 +1,899 @@
+//! Row changes as JSON Lines, in the envelope of Debezium's MySQL connector.
+//!
+//! Every row of a rows event becomes one line:
+//!
+//! ```json
+//! {"before":null,"after":{"id":1,"note":"a"},"source":{"version":"0.1.0","connector":"mysql","name":"mysqlbinlog-network","ts_ms":1625140800000,"snapshot":"false","db":"shop","sequence":null,"table":"orders","server_id":1,"gtid":"0575a804-6403-11ea-8d3d-e454e8d4a4fe:1","file":"binlog.000001","pos":283,"row":0,"thread":7,"query":null},"op":"c","ts_ms":1625140801234,"transaction":{"id":"0575a804-6403-11ea-8d3d-e454e8d4a4fe:1","total_order":1,"data_collection_order":1}}
+//! ```
+//!
+//! - `op` is `c` for an inserted row, `u` for an updated and `d` for a deleted one.
+//! - `before` and `after` are the row images keyed by column name, `null` where the change has
+//!   none. Column names come from the table map when the source runs with
+//!   `binlog_row_metadata=FULL`; otherwise columns are called `@1`, `@2`, ... Columns left out
+//!   of a minimal row image are left out of the object.
+//! - `source` says where the change was read: `ts_ms` is when it was logged, `pos` where its
+//!   rows event starts in `file`, `row` its index in that event and `query` the statement
+//!   which made it when the source logs it (`binlog_rows_query_log_events`, or
+//!   `binlog_annotate_row_events` on MariaDB).
+//! - `ts_ms` is when the line was written.
+//! - `transaction` places the change in its transaction, `null` outside of one.
+//!
+//! The changes of a transaction are framed by marker lines, told apart from changes by their
+//! `status`:
+//!
+//! ```json
+//! {"status":"BEGIN","id":"0575a804-6403-11ea-8d3d-e454e8d4a4fe:1","ts_ms":1625140800000,"event_count":null,"data_collections":null}
+//! {"status":"END","id":"0575a804-6403-11ea-8d3d-e454e8d4a4fe:1","ts_ms":1625140800000,"event_count":1,"data_collections":[{"data_collection":"shop.orders","event_count":1}]}
+//! ```
+//!
+//! A transaction is identified by its GTID, or by the file and position of its `BEGIN` when it
+//! has none. Transactions none of whose changes reach the writer get no markers.
+//!
+//! Values are encoded like Debezium does by default, except for `DECIMAL`:
+//!
+//! | Column type | JSON |
+//! |---|---|
+//! | integers, `YEAR`, and `ENUM` (index), `SET` (bits) without labels, `BIT` (bits) | number |
+//! | `ENUM` and `SET` with labels | string, the members of a `SET` separated by commas |
+//! | `BIT(1)` | boolean |
+//! | `FLOAT`, `DOUBLE` | number, `null` when not finite |
+//! | `DECIMAL` | string, as with `decimal.handling.mode=string` |
+//! | character strings | string |
+//! | binary strings and blobs | base64 string |
+//! | `DATE` | days since the epoch, `null` for zero dates |
+//! | `TIME` | microseconds, negative for negative durations |
+//! | `DATETIME` | milliseconds since the epoch, microseconds with a precision above 3, `null` for zero dates |
+//! | `TIMESTAMP` | ISO 8601 string in UTC |
+//! | `JSON` | string |
+//! | spatial types | object with the WKB as a base64 `wkb` and the `srid` |
+//!
+//! Statements are not written; schema changes are left to the text and SQL output.
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_derive::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::mysql_binlog::column_types::ColumnType;
+use crate::mysql_binlog::event::{EventData, RowData, RowEvent, TypeCode, FL_STANDALONE};
+use crate::mysql_binlog::table_map::TableMap;
+use crate::mysql_binlog::value::MySQLValue;
+use crate::sql::statement::{classify, Statement};
+use crate::sql::{fraction, precision, unsigned_value, ColumnDef, TableDef};
+use crate::text::{civil_datetime, days_from_civil, EventInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Op {
+    #[serde(rename = "c")]
+    Create,
+    #[serde(rename = "u")]
+    Update,
+    #[serde(rename = "d")]
+    Delete,
+}
+
+/// Where a change was read
+#[derive(Debug, Clone, Serialize)]
+pub struct Source {
+    pub version: &'static str,
+    pub connector: &'static str,
+    /// The name the writer was created with
+    pub name: String,
+    pub ts_ms: u64,
+    pub snapshot: &'static str,
+    pub db: String,
+    pub sequence: Option<String>,
+    pub table: String,
+    pub server_id: u32,
+    pub gtid: Option<String>,
+    pub file: String,
+    pub pos: u64,
+    pub row: usize,
+    pub thread: Option<u32>,
+    pub query: Option<String>,
+}
+
+/// The place of a change in its transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionBlock {
+    pub id: String,
+    /// Position among all changes of the transaction, from 1
+    pub total_order: u64,
+    /// Position among the changes of the transaction to the same table, from 1
+    pub data_collection_order: u64,
+}
+
+/// One changed row
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    pub before: Option<Map<String, Value>>,
+    pub after: Option<Map<String, Value>>,
+    pub source: Source,
+    pub op: Op,
+    pub ts_ms: u64,
+    pub transaction: Option<TransactionBlock>,
+}
+
+/// The number of changes a transaction made to one table
+#[derive(Debug, Clone, Serialize)]
+pub struct DataCollection {
+    /// `schema.table`
+    pub data_collection: String,
+    pub event_count: u64,
+}
+
+/// The line written before the first and after the last change of a transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionMarker {
+    /// `BEGIN` or `END`
+    pub status: &'static str,
+    pub id: String,
+    pub ts_ms: u64,
+    /// Only set at the `END`
+    pub event_count: Option<u64>,
+    pub data_collections: Option<Vec<DataCollection>>,
+}
+
+/// The transaction being read
+struct Transaction {
+    id: String,
+    timestamp: u32,
+    /// The number of changes written so far, 0 until the `BEGIN` marker is written
+    total: u64,
+    collections: Vec<DataCollection>,
+}
+
+/// Writes the changes of a stream of events. Like [`TextWriter`] it is fed every event, in
+/// order, as an [`EventInfo`] with the event's body and what it decoded to.
+///
+/// [`TextWriter`]: crate::text::TextWriter
+pub struct ChangeEventWriter {
+    name: String,
+    file: String,
+    table_map: TableMap,
+    gtid: Option<String>,
+    thread: Option<u32>,
+    query: Option<String>,
+    transaction: Option<Transaction>,
+}
+
+impl ChangeEventWriter {
+    /// `name` is the `source.name` of every change, the logical name of the source server
+    pub fn new(name: &str) -> Self {
+        ChangeEventWriter {
+            name: name.to_owned(),
+            file: String::new(),
+            table_map: TableMap::new(),
+            gtid: None,
+            thread: None,
+            query: None,
+            transaction: None,
+        }
+    }
+
+    /// The tables mapped so far, for decoding the rows events to pass in
+    pub fn table_map(&self) -> &TableMap {
+        &self.table_map
+    }
+
+    /// Name the binlog file the following events are read from. Not needed when reading from a
+    /// server, which starts every file with a rotate event.
+    pub fn set_file(&mut self, file: &str) {
+        self.file = file.to_owned();
+    }
+
+    /// Handle one event, writing a line per changed row and the transaction markers. `body` is
+    /// the event without its common header and checksum, `data` what it decoded to, if
+    /// anything.
+    pub fn write_event(
+        &mut self,
+        out: &mut dyn Write,
+        info: &EventInfo,
+        body: &[u8],
+        data: Option<&EventData>,
+    ) -> io::Result<()> {
+        match data {
+            Some(EventData::GtidLogEvent {
+                uuid, coordinate, ..
+            }) => self.gtid = Some(format!("{}:{}", uuid.to_hyphenated(), coordinate)),
+            Some(EventData::MariadbGtidEvent {
+                domain_id,
+                seq_no,
+                flags,
+                ..
+            }) => {
+                self.gtid = Some(format!("{}-{}-{}", domain_id, info.server_id, seq_no));
+                // MariaDB logs no BEGIN, the GTID event opens the transaction
+                if flags & FL_STANDALONE == 0 {
+                    self.begin(info, None);
+                }
+            }
+            Some(EventData::QueryEvent {
+                thread_id, query, ..
+            }) => match classify(query, None) {
+                Statement::Begin => self.begin(info, Some(*thread_id)),
+                Statement::Commit | Statement::Rollback => self.end(out, info)?,
+                _ => {}
+            },
+            Some(EventData::XIDEvent { .. }) => self.end(out, info)?,
+            Some(EventData::TableMapEvent {
+                table_id,
+                schema_name,
+                table_name,
+                columns,
+                metadata,
+                ..
+            }) => self.table_map.handle_with_metadata(
+                *table_id,
+                schema_name.clone(),
+                table_name.clone(),
+                columns.clone(),
+                metadata.clone(),
+            ),
+            Some(EventData::RotateEvent { next_log_name, .. }) => self.file = next_log_name.clone(),
+            Some(EventData::AnnotateRowsEvent { query }) => self.query = Some(query.clone()),
+            Some(EventData::WriteRowsEvent { table_id, rows })
+            | Some(EventData::UpdateRowsEvent { table_id, rows })
+            | Some(EventData::DeleteRowsEvent { table_id, rows }) => {
+                self.write_rows(out, info, *table_id, rows)?
+            }
+            None if info.type_code == TypeCode::AnonymousGtidLogEvent => self.gtid = None,
+            // not decoded by the parser: a length byte, which is ignored, and the statement
+            None if info.type_code == TypeCode::RowsQueryLogEvent => {
+                self.query = body
+                    .get(1..)
+                    .map(|query| String::from_utf8_lossy(query).into_owned())
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn write_rows(
+        &mut self,
+        out: &mut dyn Write,
+        info: &EventInfo,
+        table_id: u64,
+        rows: &[RowEvent],
+    ) -> io::Result<()> {
+        let table = match self.table_map.get(table_id) {
+            Some(table) => TableDef::from_table_map(table),
+            None => return Ok(()),
+        };
+        let collection = format!("{}.{}", table.schema_name, table.table_name);
+        let now = now_ms();
+        for (row, change) in rows.iter().enumerate() {
+            let (op, before, after) = match change {
+                RowEvent::NewRow { cols } => (Op::Create, None, Some(row_object(&table, cols))),
+                RowEvent::DeletedRow { cols } => (Op::Delete, Some(row_object(&table, cols)), None),
+                RowEvent::UpdatedRow {
+                    before_cols,
+                    after_cols,
+                } => (
+                    Op::Update,
+                    Some(row_object(&table, before_cols)),
+                    Some(row_object(&table, after_cols)),
+                ),
+            };
+            let transaction = match self.transaction {
+                Some(ref mut transaction) => {
+                    if transaction.total == 0 {
+                        write_line(
+                            out,
+                            &TransactionMarker {
+                                status: "BEGIN",
+                                id: transaction.id.clone(),
+                                ts_ms: u64::from(transaction.timestamp) * 1000,
+                                event_count: None,
+                                data_collections: None,
+                            },
+                        )?;
+                    }
+                    transaction.total += 1;
+                    let index = match transaction
+                        .collections
+                        .iter()
+                        .position(|c| c.data_collection == collection)
+                    {
+                        Some(index) => index,
+                        None => {
+                            transaction.collections.push(DataCollection {
+                                data_collection: collection.clone(),
+                                event_count: 0,
+                            });
+                            transaction.collections.len() - 1
+                        }
+                    };
+                    transaction.collections[index].event_count += 1;
+                    Some(TransactionBlock {
+                        id: transaction.id.clone(),
+                        total_order: transaction.total,
+                        data_collection_order: transaction.collections[index].event_count,
+                    })
+                }
+                None => None,
+            };
+            let event = ChangeEvent {
+                before,
+                after,
+                source: Source {
+                    version: env!("CARGO_PKG_VERSION"),
+                    connector: "mysql",
+                    name: self.name.clone(),
+                    ts_ms: u64::from(info.timestamp) * 1000,
+                    snapshot: "false",
+                    db: table.schema_name.clone(),
+                    sequence: None,
+                    table: table.table_name.clone(),
+                    server_id: info.server_id,
+                    gtid: self.gtid.clone(),
+                    file: self.file.clone(),
+                    pos: info.offset,
+                    row,
+                    thread: self.thread,
+                    query: self.query.clone(),
+                },
+                op,
+                ts_ms: now,
+                transaction,
+            };
+            write_line(out, &event)?;
+        }
+        Ok(())
+    }
+
+    /// Open a transaction at `info`, by `thread` when a BEGIN query gave one
+    fn begin(&mut self, info: &EventInfo, thread: Option<u32>) {
+        self.thread = thread;
+        self.query = None;
+        self.transaction = Some(Transaction {
+            id: match self.gtid {
+                Some(ref gtid) => gtid.clone(),
+                None => format!("{}:{}", self.file, info.offset),
+            },
+            timestamp: info.timestamp,
+            total: 0,
+            collections: Vec::new(),
+        });
+    }
+
+    /// Close the transaction being read, writing its `END` marker if it had changes
+    fn end(&mut self, out: &mut dyn Write, info: &EventInfo) -> io::Result<()> {
+        self.query = None;
+        match self.transaction.take() {
+            Some(transaction) if transaction.total > 0 => write_line(
+                out,
+                &TransactionMarker {
+                    status: "END",
+                    id: transaction.id,
+                    ts_ms: u64::from(info.timestamp) * 1000,
+                    event_count: Some(transaction.total),
+                    data_collections: Some(transaction.collections),
+                },
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn write_line<T: serde::Serialize>(out: &mut dyn Write, line: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, line)?;
+    writeln!(out)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn row_object(table: &TableDef, cols: &RowData) -> Map<String, Value> {
+    cols.iter()
+        .enumerate()
+        .filter_map(|(i, c)| c.as_ref().map(|v| (i, v)))
+        .map(|(i, value)| {
+            let column = table.columns.get(i);
+            let name = match column {
+                Some(c) => c.name.clone(),
+                None => format!("@{}", i + 1),
+            };
+            (name, json_value(value, column))
+        })
+        .collect()
+}
+
+/// The JSON a column value is written as; see the table in the [module documentation](self)
+pub fn json_value(value: &MySQLValue, column: Option<&ColumnDef>) -> Value {
+    let column_type = column.and_then(|c| c.column_type.as_ref());
+    match value {
+        MySQLValue::SignedInteger(i) => match column_type {
+            Some(ColumnType::Bit(1, 0)) => Value::Bool(*i != 0),
+            Some(ColumnType::Bit(..)) | Some(ColumnType::Set(_)) => {
+                Value::from(unsigned_value(*i, column_type))
+            }
+            _ if column.is_some_and(|c| c.unsigned) => Value::from(unsigned_value(*i, column_type)),
+            _ => Value::from(*i),
+        },
+        MySQLValue::Float(f) => Value::from(f64::from(*f)),
+        MySQLValue::Double(f) => Value::from(*f),
+        MySQLValue::String(s) => Value::from(s.as_str()),
+        MySQLValue::Enum {
+            label: Some(label), ..
+        } => Value::from(label.as_str()),
+        MySQLValue::Enum { index, .. } => Value::from(*index),
+        MySQLValue::Set(labels) => Value::from(labels.join(",")),
+        MySQLValue::Blob(b) => Value::from(base64::encode(b)),
+        MySQLValue::Geometry { srid, wkb } => json!({"wkb": base64::encode(wkb), "srid": srid}),
+        MySQLValue::Year(y) => Value::from(*y),
+        MySQLValue::Date { year, month, day } => match epoch_days(*year, *month, *day) {
+            Some(days) => Value::from(days),
+            None => Value::Null,
+        },
+        MySQLValue::Time {
+            negative,
+            hours,
+            minutes,
+            seconds,
+            subseconds,
+        } => {
+            let seconds = i64::from(*hours) * 3600 + i64::from(*minutes) * 60 + i64::from(*seconds);
+            let micros = seconds * 1_000_000 + i64::from(*subseconds);
+            Value::from(if *negative { -micros } else { micros })
+        }
+        MySQLValue::DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            subsecond,
+        } => match epoch_days(*year, *month, *day) {
+            Some(days) => {
+                let seconds = days * 86400
+                    + i64::from(*hour) * 3600
+                    + i64::from(*minute) * 60
+                    + i64::from(*second);
+                let micros = i64::from(*subsecond);
+                if precision(column_type).is_some_and(|fsp| fsp > 3) {
+                    Value::from(seconds * 1_000_000 + micros)
+                } else {
+                    Value::from(seconds * 1000 + micros / 1000)
+                }
+            }
+            None => Value::Null,
+        },
+        MySQLValue::Json(j) => Value::from(j.to_string()),
+        MySQLValue::Decimal(d) => Value::from(d.to_string()),
+        MySQLValue::Timestamp {
+            unix_time,
+            subsecond,
+        } => {
+            let (year, month, day, hour, minute, second) = civil_datetime(*unix_time as u32);
+            Value::from(format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}Z",
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                fraction(*subsecond, column_type)
+            ))
+        }
+        MySQLValue::Null => Value::Null,
+    }
+}
+
+/// Days since the epoch of a date, `None` for MySQL's zero dates
+fn epoch_days(year: u32, month: u32, day: u32) -> Option<i64> {
+    if month == 0 || day == 0 {
+        return None;
+    }
+    Some(days_from_civil(
+        i64::from(year),
+        i64::from(month),
+        i64::from(day),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::{json, Value};
+
+    use crate::mysql_binlog::binlog_file::BinlogFile;
+    use crate::mysql_binlog::binlog_writer::BinlogFileWriter;
+    use crate::mysql_binlog::bit_set::BitSet;
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::event::{EventData, RowEvent, FL_STANDALONE};
+    use crate::mysql_binlog::table_map::TableMetadata;
+    use crate::mysql_binlog::value::MySQLValue;
+    use crate::sql::ColumnDef;
+    use crate::text::EventInfo;
+
+    use super::{json_value, ChangeEventWriter};
+
+    fn column(column_type: ColumnType, unsigned: bool) -> ColumnDef {
+        ColumnDef {
+            name: "c".to_owned(),
+            column_type: Some(column_type),
+            unsigned,
+        }
+    }
+
+    #[test]
+    fn test_json_value() {
+        let cases = vec![
+            (
+                MySQLValue::SignedInteger(-1),
+                column(ColumnType::Long, true),
+                json!(4294967295u32),
+            ),
+            (
+                MySQLValue::SignedInteger(-1),
+                column(ColumnType::Long, false),
+                json!(-1),
+            ),
+            (
+                MySQLValue::SignedInteger(1),
+                column(ColumnType::Bit(1, 0), false),
+                json!(true),
+            ),
+            (
+                MySQLValue::Double(f64::NAN),
+                column(ColumnType::Double(8), false),
+                Value::Null,
+            ),
+            (
+                MySQLValue::Blob(vec![0xff, 0x00].into()),
+                column(ColumnType::Blob(2), false),
+                json!("/wA="),
+            ),
+            (
+                MySQLValue::Date {
+                    year: 2021,
+                    month: 7,
+                    day: 1,
+                },
+                column(ColumnType::NewDate, false),
+                json!(18809),
+            ),
+            (
+                MySQLValue::Date {
+                    year: 0,
+                    month: 0,
+                    day: 0,
+                },
+                column(ColumnType::NewDate, false),
+                Value::Null,
+            ),
+            (
+                MySQLValue::Time {
+                    negative: false,
+                    hours: 1,
+                    minutes: 2,
+                    seconds: 3,
+                    subseconds: 500_000,
+                },
+                column(ColumnType::Time2(1), false),
+                json!(3_723_500_000u64),
+            ),
+            (
+                MySQLValue::Time {
+                    negative: true,
+                    hours: 838,
+                    minutes: 59,
+                    seconds: 59,
+                    subseconds: 0,
+                },
+                column(ColumnType::Time2(0), false),
+                json!(-3_020_399_000_000i64),
+            ),
+            (
+                MySQLValue::DateTime {
+                    year: 2021,
+                    month: 7,
+                    day: 1,
+                    hour: 12,
+                    minute: 0,
+                    second: 0,
+                    subsecond: 500_000,
+                },
+                column(ColumnType::DateTime2(2), false),
+                json!(1_625_140_800_500u64),
+            ),
+            (
+                MySQLValue::DateTime {
+                    year: 2021,
+                    month: 7,
+                    day: 1,
+                    hour: 12,
+                    minute: 0,
+                    second: 0,
+                    subsecond: 7,
+                },
+                column(ColumnType::DateTime2(6), false),
+                json!(1_625_140_800_000_007u64),
+            ),
+            (
+                MySQLValue::Timestamp {
+                    unix_time: 1_625_140_800,
+                    subsecond: 120_000,
+                },
+                column(ColumnType::Timestamp2(3), false),
+                json!("2021-07-01T12:00:00.120Z"),
+            ),
+            (
+                MySQLValue::Decimal("12.50".parse().unwrap()),
+                column(ColumnType::NewDecimal(10, 2), false),
+                json!("12.50"),
+            ),
+            (
+                MySQLValue::Json(json!({"a": [1]})),
+                column(ColumnType::Json(4), false),
+                json!("{\"a\":[1]}"),
+            ),
+        ];
+        for (value, column, expected) in cases {
+            assert_eq!(json_value(&value, Some(&column)), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_write_events() {
+        let uuid = uuid::Uuid::parse_str("0575a804-6403-11ea-8d3d-e454e8d4a4fe").unwrap();
+        let mut writer = BinlogFileWriter::new(Cursor::new(Vec::new()), 1).unwrap();
+        writer
+            .write_event(
+                100,
+                &EventData::GtidLogEvent {
+                    flags: 1,
+                    uuid,
+                    coordinate: 1,
+                    last_committed: Some(0),
+                    sequence_number: Some(1),
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                100,
+                &EventData::QueryEvent {
+                    thread_id: 7,
+                    exec_time: 0,
+                    error_code: 0,
+                    schema: "shop".to_owned(),
+                    query: "BEGIN".to_owned(),
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                100,
+                &EventData::TableMapEvent {
+                    table_id: 108,
+                    schema_name: "shop".to_owned(),
+                    table_name: "orders".to_owned(),
+                    columns: vec![ColumnType::Long, ColumnType::VarChar(20)],
+                    null_bitmap: BitSet::new(2),
+                    metadata: TableMetadata {
+                        column_names: vec!["id".to_owned(), "note".to_owned()],
+                        ..Default::default()
+                    },
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                100,
+                &EventData::UpdateRowsEvent {
+                    table_id: 108,
+                    rows: vec![RowEvent::UpdatedRow {
+                        before_cols: vec![
+                            Some(MySQLValue::SignedInteger(1)),
+                            Some(MySQLValue::String("a".to_owned())),
+                        ],
+                        after_cols: vec![
+                            Some(MySQLValue::SignedInteger(1)),
+                            Some(MySQLValue::String("b".to_owned())),
+                        ],
+                    }],
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                100,
+                &EventData::DeleteRowsEvent {
+                    table_id: 108,
+                    rows: vec![RowEvent::DeletedRow {
+                        cols: vec![Some(MySQLValue::SignedInteger(2)), None],
+                    }],
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(100, &EventData::XIDEvent { xid: 10 })
+            .unwrap();
+        // a transaction without changes gets no markers
+        writer
+            .write_event(
+                200,
+                &EventData::QueryEvent {
+                    thread_id: 7,
+                    exec_time: 0,
+                    error_code: 0,
+                    schema: "shop".to_owned(),
+                    query: "BEGIN".to_owned(),
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(200, &EventData::XIDEvent { xid: 11 })
+            .unwrap();
+        let lines = change_lines(writer.finish().unwrap().into_inner());
+        assert_eq!(lines.len(), 4);
+        let gtid = "0575a804-6403-11ea-8d3d-e454e8d4a4fe:1";
+        assert_eq!(
+            lines[0],
+            json!({"status": "BEGIN", "id": gtid, "ts_ms": 100_000, "event_count": null, "data_collections": null})
+        );
+        let update = &lines[1];
+        assert_eq!(update["op"], "u");
+        assert_eq!(update["before"], json!({"id": 1, "note": "a"}));
+        assert_eq!(update["after"], json!({"id": 1, "note": "b"}));
+        assert_eq!(
+            update["source"],
+            json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "connector": "mysql",
+                "name": "db1",
+                "ts_ms": 100_000,
+                "snapshot": "false",
+                "db": "shop",
+                "sequence": null,
+                "table": "orders",
+                "server_id": 1,
+                "gtid": gtid,
+                "file": "binlog.000001",
+                "pos": update["source"]["pos"],
+                "row": 0,
+                "thread": 7,
+                "query": null,
+            })
+        );
+        assert_eq!(
+            update["transaction"],
+            json!({"id": gtid, "total_order": 1, "data_collection_order": 1})
+        );
+        let delete = &lines[2];
+        assert_eq!(delete["op"], "d");
+        assert_eq!(delete["before"], json!({"id": 2}));
+        assert_eq!(delete["after"], Value::Null);
+        assert_eq!(delete["transaction"]["total_order"], 2);
+        assert!(delete["source"]["pos"].as_u64() > update["source"]["pos"].as_u64());
+        assert_eq!(
+            lines[3],
+            json!({
+                "status": "END",
+                "id": gtid,
+                "ts_ms": 100_000,
+                "event_count": 2,
+                "data_collections": [{"data_collection": "shop.orders", "event_count": 2}],
+            })
+        );
+
+        // MariaDB: DDL in a standalone event group, then a transaction without a BEGIN
+        let mut writer = BinlogFileWriter::new(Cursor::new(Vec::new()), 1).unwrap();
+        writer
+            .write_event(
+                100,
+                &EventData::MariadbGtidEvent {
+                    seq_no: 4,
+                    domain_id: 0,
+                    flags: FL_STANDALONE,
+                    commit_id: None,
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                100,
+                &EventData::QueryEvent {
+                    thread_id: 7,
+                    exec_time: 0,
+                    error_code: 0,
+                    schema: "shop".to_owned(),
+                    query: "CREATE TABLE orders (id INT)".to_owned(),
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                200,
+                &EventData::MariadbGtidEvent {
+                    seq_no: 5,
+                    domain_id: 0,
+                    flags: 0,
+                    commit_id: None,
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                200,
+                &EventData::TableMapEvent {
+                    table_id: 108,
+                    schema_name: "shop".to_owned(),
+                    table_name: "orders".to_owned(),
+                    columns: vec![ColumnType::Long],
+                    null_bitmap: BitSet::new(1),
+                    metadata: TableMetadata {
+                        column_names: vec!["id".to_owned()],
+                        ..Default::default()
+                    },
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                200,
+                &EventData::WriteRowsEvent {
+                    table_id: 108,
+                    rows: vec![RowEvent::NewRow {
+                        cols: vec![Some(MySQLValue::SignedInteger(1))],
+                    }],
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(200, &EventData::XIDEvent { xid: 10 })
+            .unwrap();
+
+        let lines = change_lines(writer.finish().unwrap().into_inner());
+        assert_eq!(lines.len(), 3);
+        let gtid = "0-1-5";
+        assert_eq!(lines[0]["status"], "BEGIN");
+        assert_eq!(lines[0]["id"], gtid);
+        assert_eq!(lines[1]["op"], "c");
+        assert_eq!(lines[1]["after"], json!({"id": 1}));
+        assert_eq!(lines[1]["source"]["gtid"], gtid);
+        assert_eq!(lines[1]["source"]["thread"], Value::Null);
+        assert_eq!(
+            lines[1]["transaction"],
+            json!({"id": gtid, "total_order": 1, "data_collection_order": 1})
+        );
+        assert_eq!(lines[2]["status"], "END");
+        assert_eq!(lines[2]["event_count"], 1);
+    }
+
+    /// The lines a writer produces for a binlog
+    fn change_lines(binlog: Vec<u8>) -> Vec<Value> {
+        let mut changes = ChangeEventWriter::new("db1");
+        changes.set_file("binlog.000001");
+        let mut out = Vec::new();
+        for event in BinlogFile::try_from_reader(Cursor::new(binlog))
+            .unwrap()
+            .events(None)
+        {
+            let event = event.unwrap();
+            let data = event.inner(Some(changes.table_map())).unwrap();
+            changes
+                .write_event(
+                    &mut out,
+                    &EventInfo::from_event(&event),
+                    event.data(),
+                    data.as_ref(),
+                )
+                .unwrap();
+        }
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
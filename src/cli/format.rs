@@ -0,0 +1,207 @@
+//! The `--format`s: the writers fed every event and the formatters printing [`BinlogEvent`]s
+use std::io::{self, Write};
+
+use mysqlbinlog_network::analysis::Analyzer;
+use mysqlbinlog_network::change_event::ChangeEventWriter;
+use mysqlbinlog_network::mysql_binlog::event::EventData;
+use mysqlbinlog_network::mysql_binlog::table_map::TableMap;
+use mysqlbinlog_network::mysql_binlog::BinlogEvent;
+use mysqlbinlog_network::sql::statement::Statement;
+use mysqlbinlog_network::sql::{self, quote_identifier};
+use mysqlbinlog_network::text::{EventInfo, TextWriter};
+
+use super::Format;
+
+pub trait Formatter {
+    fn event(&mut self, out: &mut dyn Write, event: &BinlogEvent) -> io::Result<()>;
+
+    /// Called once after the last event
+    fn finish(&mut self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The formatter for `format`, if it prints [`BinlogEvent`]s rather than every event
+pub fn formatter(format: Format) -> Option<Box<dyn Formatter>> {
+    match format {
+        Format::Text | Format::Changes => None,
+        Format::Json => Some(Box::new(Json)),
+        Format::Sql => Some(Box::new(Sql::default())),
+    }
+}
+
+/// The outputs which need every event, internal ones included, rather than just statements and
+/// row changes
+pub enum EventWriter {
+    Text(TextWriter),
+    Changes(ChangeEventWriter),
+    /// `--analyze`: the report, as JSON or text, once every event has been read
+    Analysis {
+        analyzer: Box<Analyzer>,
+        json: bool,
+    },
+}
+
+impl EventWriter {
+    /// The writer for `format`, if it is fed every event
+    pub fn new(format: Format) -> Option<Self> {
+        match format {
+            Format::Text => Some(EventWriter::Text(TextWriter::new())),
+            Format::Changes => Some(EventWriter::Changes(ChangeEventWriter::new(env!(
+                "CARGO_PKG_NAME"
+            )))),
+            Format::Json | Format::Sql => None,
+        }
+    }
+
+    /// The writer of the `--analyze` report
+    pub fn analysis(format: Format) -> Self {
+        EventWriter::Analysis {
+            analyzer: Box::new(Analyzer::new()),
+            json: format == Format::Json,
+        }
+    }
+
+    pub fn table_map(&self) -> &TableMap {
+        match self {
+            EventWriter::Text(writer) => writer.table_map(),
+            EventWriter::Changes(writer) => writer.table_map(),
+            EventWriter::Analysis { analyzer, .. } => analyzer.table_map(),
+        }
+    }
+
+    /// Called once before the first event
+    pub fn begin(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        match self {
+            EventWriter::Text(writer) => writer.begin(out),
+            EventWriter::Changes(_) | EventWriter::Analysis { .. } => Ok(()),
+        }
+    }
+
+    /// Called before the events of each local binlog file
+    pub fn set_file(&mut self, file: &str) {
+        match self {
+            EventWriter::Text(_) => {}
+            EventWriter::Changes(writer) => writer.set_file(file),
+            EventWriter::Analysis { analyzer, .. } => analyzer.set_file(file),
+        }
+    }
+
+    pub fn write_event(
+        &mut self,
+        out: &mut dyn Write,
+        info: &EventInfo,
+        body: &[u8],
+        data: Option<&EventData>,
+    ) -> io::Result<()> {
+        match self {
+            EventWriter::Text(writer) => writer.write_event(out, info, body, data),
+            EventWriter::Changes(writer) => writer.write_event(out, info, body, data),
+            EventWriter::Analysis { analyzer, .. } => {
+                analyzer.record_event(info, body, data);
+                Ok(())
+            }
+        }
+    }
+
+    /// Called once after the last event
+    pub fn end(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        match self {
+            EventWriter::Text(writer) => writer.end(out),
+            EventWriter::Changes(_) => Ok(()),
+            EventWriter::Analysis { analyzer, json } => {
+                let report = analyzer.report();
+                if *json {
+                    serde_json::to_writer_pretty(&mut *out, &report)?;
+                    writeln!(out)
+                } else {
+                    write!(out, "{}", report)
+                }
+            }
+        }
+    }
+}
+
+/// One serialized [`BinlogEvent`] per line
+struct Json;
+
+impl Formatter for Json {
+    fn event(&mut self, out: &mut dyn Write, event: &BinlogEvent) -> io::Result<()> {
+        serde_json::to_writer(&mut *out, event)?;
+        writeln!(out)
+    }
+}
+
+/// Executable statements. Row changes are grouped into the transactions they were logged in;
+/// since the XID event closing a transaction is not passed on, the `COMMIT` is written when a
+/// statement of another transaction comes along.
+#[derive(Default)]
+struct Sql {
+    schema: Option<String>,
+    /// Set while a `BEGIN` has been written, with the GTID of its transaction if it has one
+    transaction: Option<Option<String>>,
+}
+
+fn gtid_of(event: &BinlogEvent) -> Option<String> {
+    match (event.gtid, event.mariadb_gtid) {
+        (Some(gtid), _) => Some(gtid.to_string()),
+        (None, Some(gtid)) => Some(gtid.to_string()),
+        (None, None) => None,
+    }
+}
+
+impl Sql {
+    fn commit(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        if self.transaction.take().is_some() {
+            writeln!(out, "COMMIT;")?;
+        }
+        Ok(())
+    }
+}
+
+impl Formatter for Sql {
+    fn event(&mut self, out: &mut dyn Write, event: &BinlogEvent) -> io::Result<()> {
+        let gtid = gtid_of(event);
+        if self
+            .transaction
+            .as_ref()
+            .is_some_and(|t| gtid.is_some() && *t != gtid)
+        {
+            self.commit(out)?;
+        }
+        let query = match event.query {
+            Some(ref query) => query.trim().trim_end_matches(';'),
+            None => {
+                for statement in sql::binlog_event_statements(event) {
+                    writeln!(out, "{}", statement)?;
+                }
+                return Ok(());
+            }
+        };
+        match event.statement() {
+            Some(Statement::Begin) => {
+                self.commit(out)?;
+                self.transaction = Some(gtid);
+                return writeln!(out, "BEGIN;");
+            }
+            Some(Statement::Commit) => {
+                self.transaction = None;
+                return writeln!(out, "COMMIT;");
+            }
+            _ => {}
+        }
+        if event.schema_name.is_some() && event.schema_name != self.schema {
+            self.schema = event.schema_name.clone();
+            writeln!(
+                out,
+                "USE {};",
+                quote_identifier(self.schema.as_deref().unwrap_or_default())
+            )?;
+        }
+        writeln!(out, "{};", query)
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        self.commit(out)
+    }
+}
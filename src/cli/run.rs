@@ -0,0 +1,661 @@
+//! Reading the selected events and handing them to the output
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::Path;
+use std::result;
+
+use mysql::prelude::Queryable;
+use mysqlbinlog_network::client::sync::EndOfBinlog;
+use mysqlbinlog_network::flashback::Flashback;
+use mysqlbinlog_network::metrics::Metrics;
+use mysqlbinlog_network::mysql_binlog::binlog_file::BinlogFile;
+use mysqlbinlog_network::mysql_binlog::binlog_writer::BinlogFileWriter;
+use mysqlbinlog_network::mysql_binlog::event::{EventData, TypeCode};
+use mysqlbinlog_network::mysql_binlog::table_map::TableMap;
+use mysqlbinlog_network::mysql_binlog::{BinlogEvent, EventContext};
+use mysqlbinlog_network::sql::statement::classify;
+use mysqlbinlog_network::text::EventInfo;
+use mysqlbinlog_network::{Gtid, OffsetConfig, Runner};
+
+use super::format::{formatter, EventWriter, Formatter};
+use super::{Input, Options};
+
+type Result<T> = result::Result<T, Box<dyn Error>>;
+
+/// What an event is about, as far as the `--database` and `--table` filters go
+enum Scope<'a> {
+    /// Not tied to a database, like GTID and XID events or `BEGIN`
+    Any,
+    /// A statement run in this default database
+    Statement(Option<&'a str>),
+    /// A change to, or the table map of, this table
+    Table(&'a str, &'a str),
+}
+
+/// The `--database`, `--table`, GTID and time filters
+struct Filter<'a> {
+    options: &'a Options,
+    /// `--table`s split into an optional schema and a table name
+    tables: Vec<(Option<&'a str>, &'a str)>,
+}
+
+impl<'a> Filter<'a> {
+    fn new(options: &'a Options) -> Self {
+        let tables = options
+            .tables
+            .iter()
+            .map(|t| match t.find('.') {
+                Some(i) => (Some(&t[..i]), &t[i + 1..]),
+                None => (None, t.as_str()),
+            })
+            .collect();
+        Filter { options, tables }
+    }
+
+    fn allows(&self, timestamp: u32, scope: Scope, gtid: Option<(uuid::Uuid, u64)>) -> bool {
+        let options = self.options;
+        if options.start_datetime.is_some_and(|t| timestamp < t) {
+            return false;
+        }
+        let in_databases = |schema: Option<&str>| {
+            options.databases.is_empty()
+                || schema.is_some_and(|s| options.databases.iter().any(|d| d == s))
+        };
+        match scope {
+            Scope::Any => {}
+            // only row changes can be attributed to a table
+            Scope::Statement(schema) => {
+                if !self.tables.is_empty() || !in_databases(schema) {
+                    return false;
+                }
+            }
+            Scope::Table(schema, table) => {
+                if !in_databases(Some(schema))
+                    || !self.tables.is_empty()
+                        && !self
+                            .tables
+                            .iter()
+                            .any(|(s, t)| *t == table && s.is_none_or(|s| s == schema))
+                {
+                    return false;
+                }
+            }
+        }
+        let in_set = |set: &Gtid| {
+            gtid.is_some_and(|(uuid, gno)| {
+                set.contains(&uuid.to_hyphenated().to_string(), gno as i64)
+            })
+        };
+        !(options.include_gtids.as_ref().is_some_and(|s| !in_set(s))
+            || options.exclude_gtids.as_ref().is_some_and(in_set))
+    }
+
+    fn matches(&self, event: &BinlogEvent) -> bool {
+        let scope = match (&event.query, &event.schema_name, &event.table_name) {
+            (Some(_), schema, _) => Scope::Statement(schema.as_deref()),
+            (None, Some(schema), Some(table)) => Scope::Table(schema, table),
+            _ => Scope::Any,
+        };
+        let gtid = event.gtid.map(|g| (g.uuid(), g.sequence()));
+        self.allows(event.timestamp, scope, gtid)
+    }
+
+    fn matches_raw(
+        &self,
+        info: &EventInfo,
+        data: Option<&EventData>,
+        table_map: &TableMap,
+        gtid: Option<(uuid::Uuid, u64)>,
+    ) -> bool {
+        let scope = match data {
+            Some(EventData::QueryEvent { schema, query, .. }) => {
+                if is_transaction_control(query) {
+                    Scope::Any
+                } else {
+                    Scope::Statement(Some(schema.as_str()).filter(|s| !s.is_empty()))
+                }
+            }
+            Some(EventData::TableMapEvent {
+                schema_name,
+                table_name,
+                ..
+            }) => Scope::Table(schema_name, table_name),
+            Some(EventData::WriteRowsEvent { table_id, .. })
+            | Some(EventData::UpdateRowsEvent { table_id, .. })
+            | Some(EventData::DeleteRowsEvent { table_id, .. }) => match table_map.get(*table_id) {
+                Some(table) => Scope::Table(table.schema_name(), table.table_name()),
+                None => Scope::Any,
+            },
+            _ => Scope::Any,
+        };
+        self.allows(info.timestamp, scope, gtid)
+    }
+}
+
+fn is_transaction_control(query: &str) -> bool {
+    classify(query, None).is_transaction_control()
+}
+
+/// Events which belong to no transaction, so the GTID and time filters do not apply to them
+fn is_file_event(type_code: TypeCode) -> bool {
+    matches!(
+        type_code,
+        TypeCode::FormatDescriptionEvent
+            | TypeCode::RotateEvent
+            | TypeCode::PreviousGtidsLogEvent
+            | TypeCode::StopEvent
+            | TypeCode::MariadbGtidListEvent
+            | TypeCode::BinlogCheckpointEvent
+    )
+}
+
+/// Where selected events go: to a writer of every event, to a formatter of [`BinlogEvent`]s,
+/// or into a flashback first
+struct Printer<'a> {
+    options: &'a Options,
+    filter: Filter<'a>,
+    out: Box<dyn Write>,
+    writer: Option<EventWriter>,
+    formatter: Option<Box<dyn Formatter>>,
+    context: EventContext,
+    /// The GTID of the transaction being read
+    gtid: Option<(uuid::Uuid, u64)>,
+    flashback: Option<Flashback>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(options: &'a Options) -> Result<Self> {
+        let out: Box<dyn Write> = match options.result_file {
+            Some(ref path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(BufWriter::new(io::stdout())),
+        };
+        let mut printer = Printer {
+            options,
+            filter: Filter::new(options),
+            out,
+            writer: if options.analyze {
+                Some(EventWriter::analysis(options.format))
+            } else {
+                EventWriter::new(options.format)
+            },
+            formatter: if options.analyze {
+                None
+            } else {
+                formatter(options.format)
+            },
+            context: EventContext::new(),
+            gtid: None,
+            flashback: if options.flashback {
+                Some(Flashback::new())
+            } else {
+                None
+            },
+        };
+        if let Some(ref mut writer) = printer.writer {
+            writer.begin(&mut printer.out)?;
+        }
+        Ok(printer)
+    }
+
+    /// Offer one event, given with its body and what it decoded to. Returns false once
+    /// `--stop-datetime` has been reached.
+    fn handle(
+        &mut self,
+        info: &EventInfo,
+        body: &[u8],
+        data: Option<EventData>,
+        table_map: &TableMap,
+    ) -> Result<bool> {
+        let file_event = is_file_event(info.type_code);
+        if !file_event
+            && self
+                .options
+                .stop_datetime
+                .is_some_and(|t| info.timestamp >= t)
+        {
+            return Ok(false);
+        }
+        match data {
+            Some(EventData::GtidLogEvent {
+                uuid, coordinate, ..
+            }) => self.gtid = Some((uuid, coordinate)),
+            _ if info.type_code == TypeCode::AnonymousGtidLogEvent => self.gtid = None,
+            _ => {}
+        }
+        if let (Some(writer), None) = (&mut self.writer, &self.flashback) {
+            if file_event
+                || self
+                    .filter
+                    .matches_raw(info, data.as_ref(), table_map, self.gtid)
+            {
+                writer.write_event(&mut self.out, info, body, data.as_ref())?;
+                self.flush_followed()?;
+            }
+            return Ok(true);
+        }
+        let event = match data {
+            Some(data) => self.context.handle(
+                info.type_code,
+                info.timestamp,
+                info.server_id,
+                info.offset,
+                data,
+                table_map,
+            ),
+            None => None,
+        };
+        match event {
+            Some(event) if self.filter.matches(&event) => match self.flashback {
+                Some(ref mut flashback) => flashback.push(event),
+                None => {
+                    if let Some(ref mut formatter) = self.formatter {
+                        formatter.event(&mut self.out, &event)?;
+                    }
+                    self.flush_followed()?;
+                }
+            },
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Start on a local binlog file
+    fn start_file(&mut self, path: &Path) {
+        if let Some(ref mut writer) = self.writer {
+            let name = path.file_name().unwrap_or(path.as_os_str());
+            writer.set_file(&name.to_string_lossy());
+        }
+    }
+
+    /// Keep up with a server which is being followed
+    fn flush_followed(&mut self) -> io::Result<()> {
+        if self.options.input != Input::Local {
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        if let Some(flashback) = self.flashback.take() {
+            match (&mut self.formatter, &mut self.writer) {
+                (Some(formatter), _) => {
+                    for event in flashback.into_events() {
+                        formatter.event(&mut self.out, &event)?;
+                    }
+                }
+                // the writers take binlog events, so write the rollback as a binlog
+                (None, Some(writer)) => {
+                    let mut binlog =
+                        BinlogFileWriter::new(Cursor::new(Vec::new()), self.options.server_id)?;
+                    flashback.write_binlog(&mut binlog)?;
+                    let binlog = binlog.finish()?.into_inner();
+                    for event in BinlogFile::try_from_reader(Cursor::new(binlog))?.events(None) {
+                        let event = event?;
+                        let data = event.inner(Some(writer.table_map()))?;
+                        writer.write_event(
+                            &mut self.out,
+                            &EventInfo::from_event(&event),
+                            event.data(),
+                            data.as_ref(),
+                        )?;
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+        if let Some(ref mut formatter) = self.formatter {
+            formatter.finish(&mut self.out)?;
+        }
+        if let Some(ref mut writer) = self.writer {
+            writer.end(&mut self.out)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    let mut printer = Printer::new(options)?;
+    match options.input {
+        Input::Local => read_files(options, &mut printer)?,
+        Input::Remote { ref url } => read_remote(options, url, &mut printer)?,
+    }
+    printer.finish()
+}
+
+fn read_files(options: &Options, printer: &mut Printer) -> Result<()> {
+    let last = options.files.len() - 1;
+    for (i, path) in options.files.iter().enumerate() {
+        let with_path = |e: &dyn Error| format!("{}: {}", path.display(), e);
+        printer.start_file(path);
+        let open = || BinlogFile::try_from_path(path).map_err(|e| with_path(&e));
+        // the format description event is printed even when starting further in
+        let start = match options.start_position {
+            Some(pos) if i == 0 && pos > 4 => {
+                if let Some(fde) = open()?.events(Some(4)).next() {
+                    let fde = fde.map_err(|e| with_path(&e))?;
+                    let data = fde.inner(None).map_err(|e| with_path(&e))?;
+                    printer.handle(
+                        &EventInfo::from_event(&fde),
+                        fde.data(),
+                        data,
+                        &TableMap::new(),
+                    )?;
+                }
+                pos
+            }
+            _ => 4,
+        };
+        let mut table_map = TableMap::new();
+        for event in open()?.events(Some(start)) {
+            let event = event.map_err(|e| with_path(&e))?;
+            if i == last && options.stop_position.is_some_and(|p| event.offset() >= p) {
+                return Ok(());
+            }
+            let data = event.inner(Some(&table_map)).map_err(|e| with_path(&e))?;
+            if let Some(EventData::TableMapEvent {
+                table_id,
+                schema_name,
+                table_name,
+                columns,
+                metadata,
+                ..
+            }) = &data
+            {
+                table_map.handle_with_metadata(
+                    *table_id,
+                    schema_name.clone(),
+                    table_name.clone(),
+                    columns.clone(),
+                    metadata.clone(),
+                );
+            }
+            if !printer.handle(
+                &EventInfo::from_event(&event),
+                event.data(),
+                data,
+                &table_map,
+            )? {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_remote(options: &Options, url: &str, printer: &mut Printer) -> Result<()> {
+    let mut runner = Runner::new(url, options.server_id)?;
+    if let Some(ref dir) = options.relay_log {
+        std::fs::create_dir_all(dir)?;
+        runner.enable_relay_log(dir);
+    }
+    runner.set_non_block(!options.stop_never);
+    // served until the run ends
+    let _metrics_server = match options.metrics_address {
+        Some(ref addr) => {
+            let metrics = Metrics::new();
+            let server = metrics.serve(addr.as_str())?;
+            runner.set_metrics(metrics);
+            Some(server)
+        }
+        None => None,
+    };
+    let mut offset = OffsetConfig::default();
+    if let Some(file) = options.files.first() {
+        let pos = options.start_position.unwrap_or(4);
+        offset.pos = Some((file.to_string_lossy().into_owned(), pos as u32));
+    } else if let Some(ref state) = options.mariadb_gtid {
+        offset.mariadb_gtid = Some(state.clone());
+    } else if let Some(ref gtid) = options.exclude_gtids {
+        offset.gtid = Some(gtid.clone());
+    } else {
+        let first: Option<mysql::Row> = runner.query_first("SHOW BINARY LOGS")?;
+        match first.and_then(|row| row.get::<String, _>(0)) {
+            Some(file) => {
+                offset.pos = Some((file, options.start_position.unwrap_or(4) as u32));
+            }
+            None => return Err(Box::from("the server has no binary logs")),
+        }
+    }
+    runner.start_sync(offset)?;
+    loop {
+        let event = match runner.get_event() {
+            Ok(event) => event,
+            Err(e) if e.is::<EndOfBinlog>() => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let (info, body) =
+            match EventInfo::from_bytes(runner.last_event_bytes(), runner.checksum_length()) {
+                Some(parts) => parts,
+                None => continue,
+            };
+        // keepalives from an idle source
+        if info.type_code == TypeCode::HeartbeatLogEvent {
+            continue;
+        }
+        if !printer.handle(&info, body, event.event, runner.table_map())? {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+
+    use mysqlbinlog_network::mysql_binlog::binlog_writer::BinlogFileWriter;
+    use mysqlbinlog_network::mysql_binlog::bit_set::BitSet;
+    use mysqlbinlog_network::mysql_binlog::column_types::ColumnType;
+    use mysqlbinlog_network::mysql_binlog::event::{EventData, RowEvent};
+    use mysqlbinlog_network::mysql_binlog::table_map::TableMetadata;
+    use mysqlbinlog_network::mysql_binlog::value::MySQLValue;
+    use mysqlbinlog_network::server::mock::{MockConfig, MockServer};
+
+    use super::run;
+    use crate::cli::{parse_args, Command, Options};
+
+    const UUID: &str = "0575a804-6403-11ea-8d3d-e454e8d4a4fe";
+
+    /// One file with an insert into shop.orders, an insert into shop.items and a DDL statement
+    fn write_binlog(dir: &Path) -> PathBuf {
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        let gtid = |gno| EventData::GtidLogEvent {
+            flags: 1,
+            uuid: uuid::Uuid::from_str(UUID).unwrap(),
+            coordinate: gno,
+            last_committed: Some(gno - 1),
+            sequence_number: Some(gno),
+        };
+        let query = |q: &str| EventData::QueryEvent {
+            thread_id: 1,
+            exec_time: 0,
+            error_code: 0,
+            schema: "shop".to_owned(),
+            query: q.to_owned(),
+        };
+        let table_map = |table_id, table_name: &str| EventData::TableMapEvent {
+            table_id,
+            schema_name: "shop".to_owned(),
+            table_name: table_name.to_owned(),
+            columns: vec![ColumnType::Long],
+            null_bitmap: BitSet::new(1),
+            metadata: TableMetadata::default(),
+        };
+        let insert = |table_id, id| EventData::WriteRowsEvent {
+            table_id,
+            rows: vec![RowEvent::NewRow {
+                cols: vec![Some(MySQLValue::SignedInteger(id))],
+            }],
+        };
+        let path = dir.join("mysql-bin.000001");
+        let mut writer = BinlogFileWriter::create(&path, 1).unwrap();
+        for (timestamp, event) in vec![
+            (100, gtid(1)),
+            (100, query("BEGIN")),
+            (100, table_map(108, "orders")),
+            (100, insert(108, 1)),
+            (100, EventData::XIDEvent { xid: 10 }),
+            (200, gtid(2)),
+            (200, query("BEGIN")),
+            (200, table_map(109, "items")),
+            (200, insert(109, 7)),
+            (200, EventData::XIDEvent { xid: 11 }),
+            (300, gtid(3)),
+            (300, query("CREATE TABLE t2 (id INT)")),
+        ] {
+            writer.write_event(timestamp, &event).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn options(args: &[&str]) -> Options {
+        match parse_args(args.iter().map(|a| a.to_string())).unwrap() {
+            Command::Run(options) => *options,
+            c => panic!("expected options, got {:?}", c),
+        }
+    }
+
+    fn output(dir: &Path, args: &[&str]) -> String {
+        let out = dir.join("out.sql");
+        let mut options = options(args);
+        options.result_file = Some(out.clone());
+        run(&options).unwrap();
+        std::fs::read_to_string(out).unwrap()
+    }
+
+    #[test]
+    fn test_run_local() {
+        let dir = std::env::temp_dir().join(format!("cli-local-{}", std::process::id()));
+        let file = write_binlog(&dir);
+        let file = file.to_str().unwrap();
+        assert_eq!(
+            output(&dir, &["--format", "sql", file]),
+            "BEGIN;\n\
+             INSERT INTO `shop`.`orders` (`@1`) VALUES (1);\n\
+             COMMIT;\n\
+             BEGIN;\n\
+             INSERT INTO `shop`.`items` (`@1`) VALUES (7);\n\
+             COMMIT;\n\
+             USE `shop`;\n\
+             CREATE TABLE t2 (id INT);\n"
+        );
+        assert_eq!(
+            output(&dir, &["--format=sql", "--table", "shop.items", file]),
+            "INSERT INTO `shop`.`items` (`@1`) VALUES (7);\n"
+        );
+        assert_eq!(
+            output(
+                &dir,
+                &[
+                    "--format=sql",
+                    "--exclude-gtids",
+                    &format!("{}:1-2", UUID),
+                    file
+                ]
+            ),
+            "USE `shop`;\nCREATE TABLE t2 (id INT);\n"
+        );
+        assert_eq!(
+            output(
+                &dir,
+                &[
+                    "--format=sql",
+                    "--start-datetime=150",
+                    "--stop-datetime=300",
+                    file
+                ]
+            ),
+            "BEGIN;\nINSERT INTO `shop`.`items` (`@1`) VALUES (7);\nCOMMIT;\n"
+        );
+        assert_eq!(
+            output(&dir, &["--format=sql", "--flashback", "-d", "shop", file]),
+            "DELETE FROM `shop`.`items` WHERE `@1`=7 LIMIT 1;\n\
+             DELETE FROM `shop`.`orders` WHERE `@1`=1 LIMIT 1;\n"
+        );
+        let text = output(&dir, &["--table=orders", file]);
+        assert!(
+            text.contains("### INSERT INTO `shop`.`orders`\n### SET\n###   @1=1"),
+            "{}",
+            text
+        );
+        assert!(
+            !text.contains("items") && text.contains("# End of log file\n"),
+            "{}",
+            text
+        );
+        let text = output(&dir, &["--flashback", "--table=orders", file]);
+        assert!(
+            text.contains("### DELETE FROM `shop`.`orders`\n### WHERE\n###   @1=1"),
+            "{}",
+            text
+        );
+        let changes = output(&dir, &["--format=changes", "--table=orders", file]);
+        let lines: Vec<serde_json::Value> = changes
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3, "{}", changes);
+        assert_eq!(lines[0]["status"], "BEGIN");
+        assert_eq!(lines[1]["op"], "c");
+        assert_eq!(lines[1]["after"]["@1"], 1);
+        assert_eq!(lines[1]["source"]["file"], "mysql-bin.000001");
+        assert_eq!(lines[1]["source"]["gtid"], format!("{}:1", UUID));
+        assert_eq!(lines[2]["status"], "END");
+        let json = output(&dir, &["--format=json", "--table=orders", file]);
+        let event: serde_json::Value = serde_json::from_str(json.trim()).unwrap();
+        assert_eq!(event["table_name"], "orders");
+        let report = output(&dir, &["--analyze", "--format=json", "-d", "shop", file]);
+        let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(report["transactions"], 3);
+        assert_eq!(report["tables"].as_array().unwrap().len(), 2);
+        assert_eq!(report["ddl"][0]["query"], "CREATE TABLE t2 (id INT)");
+        assert_eq!(report["ddl"][0]["file"], "mysql-bin.000001");
+        let report = output(&dir, &["--analyze", "--table=items", file]);
+        assert!(
+            report.contains("shop.items") && !report.contains("shop.orders"),
+            "{}",
+            report
+        );
+
+        let mut missing = options(&["no-such-file"]);
+        missing.result_file = Some(dir.join("out.sql"));
+        assert!(run(&missing)
+            .unwrap_err()
+            .to_string()
+            .contains("no-such-file"));
+    }
+
+    #[test]
+    fn test_run_remote() {
+        let dir = std::env::temp_dir().join(format!("cli-remote-{}", std::process::id()));
+        write_binlog(&dir);
+        let server = MockServer::start(MockConfig::new(&dir)).unwrap();
+        let url = server.url();
+        // no start file: the dump starts at the server's first binlog and ends with it
+        let text = output(&dir, &["--url", &url, "--table", "orders"]);
+        assert!(
+            text.contains(&format!("SET @@SESSION.GTID_NEXT= '{}:1'", UUID)),
+            "{}",
+            text
+        );
+        assert!(text.contains("### INSERT INTO `shop`.`orders`"), "{}", text);
+        assert!(!text.contains("items"), "{}", text);
+        assert_eq!(
+            output(
+                &dir,
+                &[
+                    "--url",
+                    &url,
+                    "--format=sql",
+                    "--exclude-gtids",
+                    &format!("{}:1-2", UUID)
+                ]
+            ),
+            "USE `shop`;\nCREATE TABLE t2 (id INT);\n"
+        );
+    }
+}
@@ -1 +1,4 @@
+pub mod parallel;
+pub mod relay_log;
+pub mod snapshot;
 pub mod sync;
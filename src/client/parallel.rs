@@ -0,0 +1,452 @@
+use std::error::Error;
+use std::result;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::client::sync::{EndOfBinlog, OffsetConfig, Runner};
+use crate::metrics::Metrics;
+use crate::mysql_binlog::event::{EventData, TypeCode, EVENT_HEADER_SIZE};
+use crate::mysql_binlog::table_map::TableMap;
+use crate::mysql_binlog::{BinlogEvent, EventContext};
+use crate::pkg::event::Event;
+
+type Result<T> = result::Result<T, Box<dyn Error>>;
+
+/// A [`Runner`] which decodes rows events on a pool of worker threads.
+///
+/// Once started, one thread reads the dump and decodes every event but the rows events itself,
+/// keeping the table map up to date. Each rows event is handed to a worker together with a
+/// snapshot of the one table it refers to, so a later TableMapEvent reusing the table id cannot
+/// change how it is decoded. `get_event` is the reorder stage: it takes the events in the order
+/// they were read, waiting for a worker where it has to, so events come out exactly as a
+/// [`Runner`] returns them. At most `queue_size` events are read ahead of the consumer.
+///
+/// Configure the runner (relay log, metrics, non-blocking dump) before wrapping it. The reader
+/// thread only notices the `ParallelRunner` has been dropped when its next event arrives.
+pub struct ParallelRunner {
+    runner: Option<Runner>,
+    workers: usize,
+    queue_size: usize,
+    slots: Option<Receiver<Slot>>,
+    table_map: TableMap,
+    context: EventContext,
+    metrics: Option<Metrics>,
+    checksum_length: usize,
+    last_packet: Vec<u8>,
+}
+
+/// An event packet and what decoding it gave
+struct Decoded {
+    packet: Vec<u8>,
+    checksum_length: usize,
+    event: result::Result<Event, String>,
+}
+
+/// A rows event for a worker to decode
+struct Job {
+    packet: Vec<u8>,
+    checksum_length: usize,
+    table_map: TableMap,
+    reply: SyncSender<Decoded>,
+}
+
+/// The reader's output, in binlog order
+enum Slot {
+    Decoded(Box<Decoded>),
+    Pending(Receiver<Decoded>),
+    End,
+    Failed(String),
+}
+
+impl ParallelRunner {
+    pub fn new(runner: Runner) -> Self {
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4);
+        ParallelRunner {
+            metrics: runner.metrics().cloned(),
+            checksum_length: runner.checksum_length(),
+            runner: Some(runner),
+            workers,
+            queue_size: 256,
+            slots: None,
+            table_map: TableMap::new(),
+            context: EventContext::new(),
+            last_packet: Vec::new(),
+        }
+    }
+
+    /// How many threads decode rows events. Defaults to the number of CPUs.
+    pub fn set_workers(&mut self, workers: usize) {
+        self.workers = workers.max(1);
+    }
+
+    /// How many events may be read ahead of the one `get_event` returns. Defaults to 256.
+    pub fn set_queue_size(&mut self, queue_size: usize) {
+        self.queue_size = queue_size.max(1);
+    }
+
+    pub fn table_map(&self) -> &TableMap {
+        &self.table_map
+    }
+
+    pub fn last_event_bytes(&self) -> &[u8] {
+        self.last_packet.get(1..).unwrap_or(&[])
+    }
+
+    pub fn checksum_length(&self) -> usize {
+        self.checksum_length
+    }
+
+    /// Start the dump and the threads reading and decoding it. Can only be called once.
+    pub fn start_sync(&mut self, offset: OffsetConfig) -> Result<()> {
+        let mut runner = match self.runner.take() {
+            Some(runner) => runner,
+            None => return Err(Box::from("the dump has been started already")),
+        };
+        runner.start_sync(offset)?;
+        let (slot_sender, slots) = mpsc::sync_channel(self.queue_size);
+        let (job_sender, jobs) = mpsc::sync_channel::<Job>(self.queue_size);
+        let jobs = Arc::new(Mutex::new(jobs));
+        for _ in 0..self.workers {
+            let jobs = Arc::clone(&jobs);
+            thread::spawn(move || decode(jobs));
+        }
+        thread::spawn(move || read(runner, slot_sender, job_sender));
+        self.slots = Some(slots);
+        Ok(())
+    }
+
+    pub fn get_event(&mut self) -> Result<Event> {
+        let slots = match self.slots {
+            Some(ref slots) => slots,
+            None => return Err(Box::from("the dump has not been started")),
+        };
+        let decoded = match slots.recv() {
+            Ok(Slot::Decoded(decoded)) => Some(*decoded),
+            // the worker panicked
+            Ok(Slot::Pending(pending)) => pending.recv().ok(),
+            Ok(Slot::End) => return Err(Box::new(EndOfBinlog)),
+            Ok(Slot::Failed(e)) => return Err(Box::from(e)),
+            Err(_) => return Err(Box::from("the binlog reader has stopped")),
+        };
+        let Decoded {
+            packet,
+            checksum_length,
+            event,
+        } = match decoded {
+            Some(decoded) => decoded,
+            None => return Err(self.decode_error("a decoding worker panicked".to_owned())),
+        };
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => return Err(self.decode_error(e)),
+        };
+        if let Some(EventData::TableMapEvent {
+            table_id,
+            schema_name,
+            table_name,
+            columns,
+            metadata,
+            ..
+        }) = &event.event
+        {
+            self.table_map.handle_with_metadata(
+                *table_id,
+                schema_name.clone(),
+                table_name.clone(),
+                columns.clone(),
+                metadata.clone(),
+            )
+        }
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_event(
+                &packet[1..],
+                checksum_length,
+                event.event.as_ref(),
+                &self.table_map,
+            );
+        }
+        self.checksum_length = checksum_length;
+        self.last_packet = packet;
+        Ok(event)
+    }
+
+    /// Like `Runner::get_binlog_event`
+    pub fn get_binlog_event(&mut self) -> Result<BinlogEvent> {
+        loop {
+            let e = self.get_event()?;
+            if let (
+                EventData::EventHeader {
+                    timestamp,
+                    event_type,
+                    server_id,
+                    event_size,
+                    log_pos,
+                    ..
+                },
+                Some(data),
+            ) = (e.header, e.event)
+            {
+                // log_pos is where the event ends
+                let offset = u64::from(log_pos.saturating_sub(event_size));
+                if let Some(event) = self.context.handle(
+                    event_type,
+                    timestamp,
+                    server_id,
+                    offset,
+                    data,
+                    &self.table_map,
+                ) {
+                    return Ok(event);
+                }
+            }
+        }
+    }
+
+    fn decode_error(&self, message: String) -> Box<dyn Error> {
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_decode_error();
+        }
+        Box::from(message)
+    }
+}
+
+/// The table id of a rows event packet, `None` for any other event
+fn rows_table_id(packet: &[u8]) -> Option<u64> {
+    let body = packet.get(1 + EVENT_HEADER_SIZE..1 + EVENT_HEADER_SIZE + 6)?;
+    if TypeCode::from_byte(packet[5]).is_rows_event() {
+        Some(LittleEndian::read_u48(body))
+    } else {
+        None
+    }
+}
+
+/// The reader thread: reads the dump until it ends, fails, or nobody takes its output
+fn read(mut runner: Runner, slots: SyncSender<Slot>, jobs: SyncSender<Job>) {
+    loop {
+        let packet = match runner.read_event_packet() {
+            Ok(packet) => packet,
+            Err(e) => {
+                let slot = if e.is::<EndOfBinlog>() {
+                    Slot::End
+                } else {
+                    Slot::Failed(e.to_string())
+                };
+                let _ = slots.send(slot);
+                return;
+            }
+        };
+        let slot = match rows_table_id(&packet) {
+            Some(table_id) => {
+                let (reply, pending) = mpsc::sync_channel(1);
+                let job = Job {
+                    table_map: runner.table_map().snapshot(table_id),
+                    checksum_length: runner.checksum_length(),
+                    packet,
+                    reply,
+                };
+                if jobs.send(job).is_err() {
+                    return;
+                }
+                Slot::Pending(pending)
+            }
+            None => {
+                let event = runner.parse_event(&packet).map_err(|e| e.to_string());
+                Slot::Decoded(Box::new(Decoded {
+                    packet,
+                    checksum_length: runner.checksum_length(),
+                    event,
+                }))
+            }
+        };
+        if slots.send(slot).is_err() {
+            return;
+        }
+    }
+}
+
+/// A worker thread: decodes rows events until the reader has stopped
+fn decode(jobs: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        let job = match jobs.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let event = decode_rows_event(&job).map_err(|e| e.to_string());
+        let _ = job.reply.send(Decoded {
+            packet: job.packet,
+            checksum_length: job.checksum_length,
+            event,
+        });
+    }
+}
+
+fn decode_rows_event(job: &Job) -> Result<Event> {
+    let data = &job.packet[1..];
+    let header = match EventData::parse_header(data)? {
+        Some(header) => header,
+        None => return Err(Box::from("invalid event header")),
+    };
+    let event_type = match header {
+        EventData::EventHeader { event_type, .. } => event_type,
+        _ => return Err(Box::from("invalid event header")),
+    };
+    let end = data.len().saturating_sub(job.checksum_length);
+    let event = EventData::from_data(
+        event_type,
+        &data[EVENT_HEADER_SIZE..end.max(EVENT_HEADER_SIZE)],
+        Some(&job.table_map),
+    )?;
+    Ok(Event { header, event })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::ParallelRunner;
+    use crate::client::sync::{EndOfBinlog, OffsetConfig, Runner};
+    use crate::mysql_binlog::binlog_writer::BinlogFileWriter;
+    use crate::mysql_binlog::bit_set::BitSet;
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::event::{EventData, RowEvent};
+    use crate::mysql_binlog::table_map::TableMetadata;
+    use crate::mysql_binlog::value::MySQLValue;
+    use crate::server::mock::{MockConfig, MockServer};
+
+    /// Transactions of one rows event each, the table id moving to another table half way
+    fn write_binlog(dir: &std::path::Path) {
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        let mut writer = BinlogFileWriter::create(dir.join("mysql-bin.000001"), 1).unwrap();
+        for n in 0..40 {
+            let (table_name, columns) = if n < 20 {
+                ("orders", vec![ColumnType::Long, ColumnType::VarChar(64)])
+            } else {
+                ("prices", vec![ColumnType::Long, ColumnType::LongLong])
+            };
+            let value = || {
+                if n < 20 {
+                    MySQLValue::String(format!("order {}", n))
+                } else {
+                    MySQLValue::SignedInteger(n * 100)
+                }
+            };
+            let events = vec![
+                EventData::GtidLogEvent {
+                    flags: 1,
+                    uuid: uuid::Uuid::from_str("0575a804-6403-11ea-8d3d-e454e8d4a4fe").unwrap(),
+                    coordinate: n as u64 + 1,
+                    last_committed: Some(n as u64),
+                    sequence_number: Some(n as u64 + 1),
+                },
+                EventData::QueryEvent {
+                    thread_id: 1,
+                    exec_time: 0,
+                    error_code: 0,
+                    schema: "shop".to_owned(),
+                    query: "BEGIN".to_owned(),
+                },
+                EventData::TableMapEvent {
+                    table_id: 108,
+                    schema_name: "shop".to_owned(),
+                    table_name: table_name.to_owned(),
+                    columns,
+                    null_bitmap: BitSet::new(2),
+                    metadata: TableMetadata::default(),
+                },
+                EventData::WriteRowsEvent {
+                    table_id: 108,
+                    rows: (0..50)
+                        .map(|id| RowEvent::NewRow {
+                            cols: vec![Some(MySQLValue::SignedInteger(id)), Some(value())],
+                        })
+                        .collect(),
+                },
+                EventData::XIDEvent { xid: n as u64 },
+            ];
+            for event in &events {
+                writer.write_event(n as u32, event).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    fn runner(server: &MockServer) -> Runner {
+        let mut runner = Runner::new(&server.url(), 1111).unwrap();
+        runner.set_non_block(true);
+        runner
+    }
+
+    fn start() -> OffsetConfig {
+        OffsetConfig {
+            pos: Some(("mysql-bin.000001".to_owned(), 4)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parallel_runner() {
+        let dir = std::env::temp_dir().join(format!("parallel-runner-{}", std::process::id()));
+        write_binlog(&dir);
+        let server = MockServer::start(MockConfig::new(&dir)).unwrap();
+
+        let mut sequential = runner(&server);
+        sequential.start_sync(start()).unwrap();
+        let mut expected = vec![];
+        loop {
+            match sequential.get_event() {
+                Ok(event) => expected.push((
+                    format!("{:?}", event),
+                    sequential.last_event_bytes().to_vec(),
+                )),
+                Err(e) => {
+                    assert!(e.is::<EndOfBinlog>(), "{}", e);
+                    break;
+                }
+            }
+        }
+        assert!(expected.len() > 200);
+
+        let mut parallel = ParallelRunner::new(runner(&server));
+        parallel.set_workers(4);
+        parallel.set_queue_size(3);
+        assert!(parallel.get_event().is_err());
+        parallel.start_sync(start()).unwrap();
+        let mut events = vec![];
+        loop {
+            match parallel.get_event() {
+                Ok(event) => {
+                    events.push((format!("{:?}", event), parallel.last_event_bytes().to_vec()))
+                }
+                Err(e) => {
+                    assert!(e.is::<EndOfBinlog>(), "{}", e);
+                    break;
+                }
+            }
+        }
+        assert_eq!(events, expected);
+        assert_eq!(
+            parallel.table_map().get(108).unwrap().table_name(),
+            "prices"
+        );
+        assert!(parallel.start_sync(start()).is_err());
+
+        let mut parallel = ParallelRunner::new(runner(&server));
+        parallel.start_sync(start()).unwrap();
+        let mut tables = vec![];
+        while let Ok(event) = parallel.get_binlog_event() {
+            if let Some(table) = event.table {
+                tables.push(table.table_name().to_owned());
+            }
+        }
+        assert_eq!(tables.len(), 40);
+        assert_eq!(tables[19], "orders");
+        assert_eq!(tables[20], "prices");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
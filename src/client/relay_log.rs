@@ -0,0 +1,315 @@
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use crate::mysql_binlog::binlog_file::BinlogFile;
+use crate::mysql_binlog::binlog_writer::BinlogFileWriter;
+use crate::mysql_binlog::encoder::with_checksum_algorithm;
+use crate::mysql_binlog::errors::EventEncodeError;
+use crate::mysql_binlog::event::{EventData, TypeCode, EVENT_HEADER_SIZE};
+
+// LOG_EVENT_ARTIFICIAL_F, set on the fake RotateEvent a dump starts with
+const LOG_EVENT_ARTIFICIAL_F: u16 = 0x20;
+
+/// Copies the raw event stream of a binlog dump into local files named after the source's,
+/// the way `mysqlbinlog --read-from-remote-server --raw --stop-never` does, so the source's
+/// binlog can be read back with `mysql_binlog::parse_file` after it has been purged.
+///
+/// Events are re-framed with a CRC32 checksum and with positions local to the file. These are
+/// the source's positions only if the source writes CRC32 checksums itself and the dump
+/// started at the beginning of the file; when it starts in the middle the file holds the
+/// source's FormatDescriptionEvent followed by the events from there on.
+///
+/// A file which already exists is cut back to the position the dump restarts at, so events
+/// received twice across a reconnect are kept once. A restart position which is not the end
+/// of an event of the local file is refused with `EventEncodeError::ResumePosition`.
+pub struct RelayLog {
+    dir: PathBuf,
+    file_name: Option<String>,
+    // where the dump (re)starts in `file_name`, from the fake RotateEvent it begins with
+    restart_position: u64,
+    writer: Option<BinlogFileWriter<File>>,
+}
+
+impl RelayLog {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        RelayLog {
+            dir: dir.into(),
+            file_name: None,
+            restart_position: 4,
+            writer: None,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Name of the source binlog file currently being written
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// Offset the next event will be written at in the current file
+    pub fn position(&self) -> Option<u64> {
+        self.writer.as_ref().map(|w| w.position())
+    }
+
+    /// Feed one event as received from the source: common header and body, without the
+    /// checksum
+    pub fn handle(&mut self, raw: &[u8]) -> Result<(), EventEncodeError> {
+        let (timestamp, type_code, server_id, flags) = match EventData::parse_header(raw) {
+            Ok(Some(EventData::EventHeader {
+                timestamp,
+                event_type,
+                server_id,
+                flags,
+                ..
+            })) => (timestamp, event_type, server_id, flags),
+            _ => return Ok(()),
+        };
+        let body = &raw[EVENT_HEADER_SIZE..];
+        match type_code {
+            TypeCode::RotateEvent => {
+                let (pos, next_log_name) = match EventData::from_data(type_code, body, None) {
+                    Ok(Some(EventData::RotateEvent { pos, next_log_name })) => (pos, next_log_name),
+                    _ => return Ok(()),
+                };
+                // a real rotate closes the current file; the fake one a dump starts with
+                // tells us which file follows and where in it the dump picks up
+                if flags & LOG_EVENT_ARTIFICIAL_F == 0 {
+                    if let Some(writer) = self.writer.as_mut() {
+                        writer.write_raw(timestamp, type_code, server_id, flags, body)?;
+                    }
+                    if self.file_name.as_deref() != Some(next_log_name.as_str()) {
+                        self.close()?;
+                        self.file_name = Some(next_log_name);
+                        self.restart_position = 4;
+                    }
+                } else {
+                    // reopened by the FormatDescriptionEvent which follows
+                    self.close()?;
+                    self.file_name = Some(next_log_name);
+                    self.restart_position = pos;
+                }
+                Ok(())
+            }
+            TypeCode::FormatDescriptionEvent => {
+                if self.writer.is_some() {
+                    // re-sent after a reconnect; the file already has one
+                    return Ok(());
+                }
+                let name = match self.file_name {
+                    Some(ref name) => name.clone(),
+                    None => return Ok(()),
+                };
+                let path = self.dir.join(&name);
+                let existing = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                if existing > 0 && self.restart_position > 4 {
+                    let position = self.restart_position;
+                    if !event_ends(&path)?.contains(&position) {
+                        return Err(EventEncodeError::ResumePosition {
+                            file: name,
+                            position,
+                            length: existing,
+                        });
+                    }
+                    let file = OpenOptions::new().append(true).open(&path)?;
+                    // drop whatever the source is about to send again
+                    file.set_len(position)?;
+                    self.writer = Some(BinlogFileWriter::resume(file, server_id, position));
+                    return Ok(());
+                }
+                let mut writer =
+                    BinlogFileWriter::without_format_description(File::create(&path)?, server_id)?;
+                writer.write_raw(
+                    timestamp,
+                    type_code,
+                    server_id,
+                    flags,
+                    // every event we write carries a CRC32
+                    &with_checksum_algorithm(body, 1),
+                )?;
+                self.writer = Some(writer);
+                Ok(())
+            }
+            TypeCode::HeartbeatLogEvent => Ok(()),
+            _ if flags & LOG_EVENT_ARTIFICIAL_F != 0 => Ok(()),
+            _ => match self.writer.as_mut() {
+                Some(writer) => {
+                    writer.write_raw(timestamp, type_code, server_id, flags, body)?;
+                    Ok(())
+                }
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Flush and close the current file
+    pub fn close(&mut self) -> Result<(), EventEncodeError> {
+        if let Some(writer) = self.writer.take() {
+            writer.finish()?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Offsets at which the events of a local file end, the FormatDescriptionEvent's included,
+/// up to the first incomplete or unreadable one
+fn event_ends(path: &Path) -> Result<Vec<u64>, EventEncodeError> {
+    let file = BinlogFile::try_from_path(path)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    Ok(file
+        .events(Some(4))
+        .map_while(|e| e.ok().map(|e| e.next_position()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::RelayLog;
+    use crate::mysql_binlog::encoder::{encode_event_data, frame_event};
+    use crate::mysql_binlog::errors::EventEncodeError;
+    use crate::mysql_binlog::event::{ChecksumAlgorithm, EventData, TypeCode};
+    use crate::mysql_binlog::parse_file;
+    use crate::mysql_binlog::table_map::TableMap;
+
+    /// An event as the source sends it with `@master_binlog_checksum='NONE'`
+    fn raw(flags: u16, data: EventData) -> Vec<u8> {
+        let (type_code, body) = encode_event_data(&data, &TableMap::new()).unwrap();
+        frame_event(1, type_code, 9, 0, flags, &body, false)
+    }
+
+    fn query(q: &str) -> EventData {
+        EventData::QueryEvent {
+            thread_id: 1,
+            exec_time: 0,
+            error_code: 0,
+            schema: "shop".to_owned(),
+            query: q.to_owned(),
+        }
+    }
+
+    fn rotate(name: &str) -> EventData {
+        rotate_at(name, 4)
+    }
+
+    fn rotate_at(name: &str, pos: u64) -> EventData {
+        EventData::RotateEvent {
+            pos,
+            next_log_name: name.to_owned(),
+        }
+    }
+
+    fn fde() -> EventData {
+        EventData::FormatDescriptionEvent {
+            binlog_version: 4,
+            server_version: "5.7.30-log".to_owned(),
+            create_timestamp: 1,
+            common_header_len: 19,
+            checksum_algorithm: ChecksumAlgorithm::None,
+        }
+    }
+
+    #[test]
+    fn test_relay_log_follows_rotates() {
+        let dir = std::env::temp_dir().join(format!("relay-log-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut relay = RelayLog::new(&dir);
+        for event in vec![
+            raw(0x20, rotate("mysql-bin.000001")),
+            raw(0, fde()),
+            raw(0x20, query("ignored")),
+            raw(0, query("CREATE TABLE t (id INT)")),
+            raw(0, rotate("mysql-bin.000002")),
+            raw(0x20, rotate("mysql-bin.000002")),
+            raw(0, fde()),
+            raw(0, query("DROP TABLE t")),
+            // re-sent after a reconnect
+            raw(0, fde()),
+            raw(0, query("CREATE TABLE u (id INT)")),
+        ] {
+            relay.handle(&event).unwrap();
+        }
+        assert_eq!(relay.file_name(), Some("mysql-bin.000002"));
+        relay.close().unwrap();
+
+        let first: Vec<_> = parse_file(dir.join("mysql-bin.000001"))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].query.as_deref(), Some("CREATE TABLE t (id INT)"));
+        let second: Vec<_> = parse_file(dir.join("mysql-bin.000002"))
+            .unwrap()
+            .map(|e| e.unwrap().query.unwrap())
+            .collect();
+        assert_eq!(second, vec!["DROP TABLE t", "CREATE TABLE u (id INT)"]);
+        let events: Vec<TypeCode> = crate::mysql_binlog::binlog_file::BinlogFile::try_from_path(
+            dir.join("mysql-bin.000001"),
+        )
+        .unwrap()
+        .events(None)
+        .map(|e| e.unwrap().type_code())
+        .collect();
+        assert_eq!(events, vec![TypeCode::QueryEvent, TypeCode::RotateEvent]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_relay_log_resumes_at_restart_position() {
+        let dir = std::env::temp_dir().join(format!("relay-log-resume-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut relay = RelayLog::new(&dir);
+        relay
+            .handle(&raw(0x20, rotate("mysql-bin.000001")))
+            .unwrap();
+        relay.handle(&raw(0, fde())).unwrap();
+        relay
+            .handle(&raw(0, query("CREATE TABLE t (id INT)")))
+            .unwrap();
+        let restart = relay.position().unwrap();
+        relay.handle(&raw(0, query("DROP TABLE t"))).unwrap();
+        relay.close().unwrap();
+
+        // a new process picks up from a checkpoint older than the end of the file
+        let mut relay = RelayLog::new(&dir);
+        for event in [
+            raw(0x20, rotate_at("mysql-bin.000001", restart)),
+            raw(0, fde()),
+            raw(0, query("DROP TABLE t")),
+            raw(0, query("CREATE TABLE u (id INT)")),
+        ] {
+            relay.handle(&event).unwrap();
+        }
+        relay.close().unwrap();
+        let queries: Vec<_> = parse_file(dir.join("mysql-bin.000001"))
+            .unwrap()
+            .map(|e| e.unwrap().query.unwrap())
+            .collect();
+        assert_eq!(
+            queries,
+            vec![
+                "CREATE TABLE t (id INT)",
+                "DROP TABLE t",
+                "CREATE TABLE u (id INT)"
+            ]
+        );
+
+        // not where any local event ends, so the positions are not the source's
+        let mut relay = RelayLog::new(&dir);
+        relay
+            .handle(&raw(0x20, rotate_at("mysql-bin.000001", restart + 1)))
+            .unwrap();
+        match relay.handle(&raw(0, fde())) {
+            Err(EventEncodeError::ResumePosition { position, .. }) => {
+                assert_eq!(position, restart + 1)
+            }
+            other => panic!("expected ResumePosition, got {:?}", other),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,524 @@
+//! Row changes keyed by primary key, and their net effect per key.
+//!
+//! A [`KeyedRow`] is one row change with the table it was made to and the primary key of the
+//! row, before and after the change. The key columns come from the table map when the source
+//! runs with `binlog_row_metadata=FULL`, or from a [`SchemaResolver`] otherwise.
+//!
+//! A [`Compactor`] folds the changes of a window, say a transaction, into one change per key
+//! with the same net effect, for consumers which only care about the final state of each row:
+//!
+//! - an insert followed by updates is the insert of the final row,
+//! - an insert followed by a delete is nothing at all,
+//! - a chain of updates is one update from the first before image to the last after image,
+//! - a delete followed by an insert is an update,
+//! - an update which changes the key is a delete of the old key and an insert of the new one.
+//!
+//! Changes come out in the order their keys were first changed. Changes without a key, to tables
+//! without a known primary key, are passed through as they are.
+//!
+//! ```no_run
+//! use mysqlbinlog_network::compaction::Compactor;
+//! use mysqlbinlog_network::mysql_binlog::parse_file;
+//!
+//! let mut compactor = Compactor::new();
+//! for event in parse_file("binlog.000001").unwrap() {
+//!     compactor.push_event(&event.unwrap(), None);
+//! }
+//! for change in compactor.finish() {
+//!     println!("{}.{} {:?}: {:?}", change.schema_name, change.table_name, change.key(), change.row);
+//! }
+//! ```
+use std::collections::HashMap;
+
+use crate::mysql_binlog::event::{RowData, RowEvent};
+use crate::mysql_binlog::value::MySQLValue;
+use crate::mysql_binlog::BinlogEvent;
+use crate::sql::{SchemaResolver, TableDef};
+
+/// The values of the primary key columns of a row, in key order
+pub type Key = Vec<MySQLValue>;
+
+/// One row change and the primary key of the row it changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyedRow {
+    pub schema_name: String,
+    pub table_name: String,
+    /// The key of the row before the change; `None` for inserts, or when the key is not known
+    pub before_key: Option<Key>,
+    /// The key of the row after the change; `None` for deletes, or when the key is not known
+    pub after_key: Option<Key>,
+    pub row: RowEvent,
+}
+
+impl KeyedRow {
+    /// Key a row change to `table`. The after image of an update takes the key columns it leaves
+    /// out, as minimal row images do, from the before image.
+    pub fn new(table: &TableDef, row: RowEvent) -> Self {
+        let (before_key, after_key) = match row {
+            RowEvent::NewRow { ref cols } => (None, table.key(cols)),
+            RowEvent::DeletedRow { ref cols } => (table.key(cols), None),
+            RowEvent::UpdatedRow {
+                ref before_cols,
+                ref after_cols,
+            } => (
+                table.key(before_cols),
+                table.key(&overlay(before_cols, after_cols)),
+            ),
+        };
+        KeyedRow {
+            schema_name: table.schema_name.clone(),
+            table_name: table.table_name.clone(),
+            before_key,
+            after_key,
+            row,
+        }
+    }
+
+    /// The key of the row the change leaves behind, or of the deleted row
+    pub fn key(&self) -> Option<&Key> {
+        self.after_key.as_ref().or(self.before_key.as_ref())
+    }
+
+    /// Whether the change has the keys it needs to be compacted
+    fn is_keyed(&self) -> bool {
+        match self.row {
+            RowEvent::NewRow { .. } => self.after_key.is_some(),
+            RowEvent::DeletedRow { .. } => self.before_key.is_some(),
+            RowEvent::UpdatedRow { .. } => self.before_key.is_some() && self.after_key.is_some(),
+        }
+    }
+}
+
+/// The rows of a rows event, keyed with the table map they were decoded with and, where it
+/// lacks the column names or primary key, `resolver`. Nothing for other events.
+pub fn keyed_rows(event: &BinlogEvent, resolver: Option<&dyn SchemaResolver>) -> Vec<KeyedRow> {
+    let table = match (&event.table, resolver) {
+        (Some(table), Some(resolver)) => TableDef::resolve(table, resolver),
+        (Some(table), None) => TableDef::from_table_map(table),
+        (None, _) => return Vec::new(),
+    };
+    event
+        .rows
+        .iter()
+        .map(|row| KeyedRow::new(&table, row.clone()))
+        .collect()
+}
+
+/// `base` with the columns present in `image` replaced
+fn overlay(base: &RowData, image: &RowData) -> RowData {
+    (0..base.len().max(image.len()))
+        .map(|i| match image.get(i) {
+            Some(Some(value)) => Some(value.clone()),
+            _ => base.get(i).cloned().flatten(),
+        })
+        .collect()
+}
+
+/// Identifies a row across the changes to it
+type RowId = (String, String, Vec<String>);
+
+fn row_id(schema_name: &str, table_name: &str, key: &[MySQLValue]) -> RowId {
+    (
+        schema_name.to_owned(),
+        table_name.to_owned(),
+        key.iter().map(ToString::to_string).collect(),
+    )
+}
+
+/// Folds row changes into their net effect per key; see the [module documentation](self)
+#[derive(Debug, Default)]
+pub struct Compactor {
+    /// The net change of each key in the order the keys were first changed, `None` where the
+    /// changes cancelled out
+    changes: Vec<Option<KeyedRow>>,
+    /// The index in `changes` of each key's net change
+    index: HashMap<RowId, usize>,
+}
+
+impl Compactor {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add the rows of a rows event; see [`keyed_rows`]
+    pub fn push_event(&mut self, event: &BinlogEvent, resolver: Option<&dyn SchemaResolver>) {
+        for row in keyed_rows(event, resolver) {
+            self.push(row);
+        }
+    }
+
+    /// Add the next row change
+    pub fn push(&mut self, change: KeyedRow) {
+        if !change.is_keyed() {
+            self.changes.push(Some(change));
+            return;
+        }
+        let KeyedRow {
+            schema_name,
+            table_name,
+            before_key,
+            after_key,
+            row,
+        } = change;
+        match row {
+            RowEvent::NewRow { cols } => {
+                self.insert(schema_name, table_name, after_key.unwrap_or_default(), cols)
+            }
+            RowEvent::DeletedRow { cols } => self.delete(
+                schema_name,
+                table_name,
+                before_key.unwrap_or_default(),
+                cols,
+            ),
+            RowEvent::UpdatedRow {
+                before_cols,
+                after_cols,
+            } => {
+                let before_key = before_key.unwrap_or_default();
+                let after_key = after_key.unwrap_or_default();
+                if before_key == after_key {
+                    self.update(schema_name, table_name, after_key, before_cols, after_cols);
+                } else {
+                    let after_cols = overlay(&before_cols, &after_cols);
+                    self.delete(
+                        schema_name.clone(),
+                        table_name.clone(),
+                        before_key,
+                        before_cols,
+                    );
+                    self.insert(schema_name, table_name, after_key, after_cols);
+                }
+            }
+        }
+    }
+
+    /// The number of changes, as they stand
+    pub fn len(&self) -> usize {
+        self.changes.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The net changes, in the order their keys were first changed
+    pub fn finish(self) -> Vec<KeyedRow> {
+        self.changes.into_iter().flatten().collect()
+    }
+
+    fn insert(&mut self, schema_name: String, table_name: String, key: Key, cols: RowData) {
+        let (slot, earlier) = self.take(&schema_name, &table_name, &key);
+        let row = match earlier {
+            // inserted over an earlier insert of the same key, which the server doesn't log
+            None | Some(RowEvent::NewRow { .. }) => RowEvent::NewRow { cols },
+            Some(RowEvent::UpdatedRow { before_cols, .. })
+            | Some(RowEvent::DeletedRow { cols: before_cols }) => RowEvent::UpdatedRow {
+                before_cols,
+                after_cols: cols,
+            },
+        };
+        self.put(slot, schema_name, table_name, key, row);
+    }
+
+    fn update(
+        &mut self,
+        schema_name: String,
+        table_name: String,
+        key: Key,
+        before_cols: RowData,
+        after_cols: RowData,
+    ) {
+        let (slot, earlier) = self.take(&schema_name, &table_name, &key);
+        let row = match earlier {
+            None => RowEvent::UpdatedRow {
+                before_cols,
+                after_cols,
+            },
+            Some(RowEvent::NewRow { cols }) => RowEvent::NewRow {
+                cols: overlay(&cols, &after_cols),
+            },
+            Some(RowEvent::UpdatedRow {
+                before_cols,
+                after_cols: cols,
+            }) => RowEvent::UpdatedRow {
+                before_cols,
+                after_cols: overlay(&cols, &after_cols),
+            },
+            // an update of a deleted row, which the server doesn't log
+            Some(RowEvent::DeletedRow { cols }) => RowEvent::UpdatedRow {
+                before_cols: cols,
+                after_cols,
+            },
+        };
+        self.put(slot, schema_name, table_name, key, row);
+    }
+
+    fn delete(&mut self, schema_name: String, table_name: String, key: Key, cols: RowData) {
+        let (slot, earlier) = self.take(&schema_name, &table_name, &key);
+        let row = match earlier {
+            None => RowEvent::DeletedRow { cols },
+            Some(RowEvent::NewRow { .. }) => return,
+            Some(RowEvent::UpdatedRow { before_cols, .. }) => {
+                RowEvent::DeletedRow { cols: before_cols }
+            }
+            Some(row @ RowEvent::DeletedRow { .. }) => row,
+        };
+        self.put(slot, schema_name, table_name, key, row);
+    }
+
+    /// Take the net change of a key out, leaving its place in the order empty
+    fn take(
+        &mut self,
+        schema_name: &str,
+        table_name: &str,
+        key: &[MySQLValue],
+    ) -> (Option<usize>, Option<RowEvent>) {
+        match self.index.remove(&row_id(schema_name, table_name, key)) {
+            Some(index) => (
+                Some(index),
+                self.changes[index].take().map(|change| change.row),
+            ),
+            None => (None, None),
+        }
+    }
+
+    /// Store the net change of a key in `slot`, the place of its earlier changes, or at the end
+    fn put(
+        &mut self,
+        slot: Option<usize>,
+        schema_name: String,
+        table_name: String,
+        key: Key,
+        row: RowEvent,
+    ) {
+        let id = row_id(&schema_name, &table_name, &key);
+        let (before_key, after_key) = match row {
+            RowEvent::NewRow { .. } => (None, Some(key)),
+            RowEvent::DeletedRow { .. } => (Some(key), None),
+            RowEvent::UpdatedRow { .. } => (Some(key.clone()), Some(key)),
+        };
+        let change = KeyedRow {
+            schema_name,
+            table_name,
+            before_key,
+            after_key,
+            row,
+        };
+        let index = match slot {
+            Some(index) => {
+                self.changes[index] = Some(change);
+                index
+            }
+            None => {
+                self.changes.push(Some(change));
+                self.changes.len() - 1
+            }
+        };
+        self.index.insert(id, index);
+    }
+}
+
+/// The net effect of a sequence of row changes; see [`Compactor`]
+pub fn compact<I: IntoIterator<Item = KeyedRow>>(changes: I) -> Vec<KeyedRow> {
+    let mut compactor = Compactor::new();
+    for change in changes {
+        compactor.push(change);
+    }
+    compactor.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{compact, keyed_rows, Compactor, KeyedRow};
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::event::{RowData, RowEvent, TypeCode};
+    use crate::mysql_binlog::table_map::{TableMap, TableMetadata};
+    use crate::mysql_binlog::value::MySQLValue;
+    use crate::mysql_binlog::BinlogEvent;
+    use crate::sql::{ColumnDef, TableDef};
+
+    fn table(primary_key: Vec<usize>) -> TableDef {
+        let column = |name: &str| ColumnDef {
+            name: name.to_owned(),
+            column_type: None,
+            unsigned: false,
+        };
+        TableDef {
+            schema_name: "shop".to_owned(),
+            table_name: "orders".to_owned(),
+            columns: vec![column("id"), column("note")],
+            primary_key,
+        }
+    }
+
+    fn cols(id: i64, note: &str) -> RowData {
+        vec![
+            Some(MySQLValue::SignedInteger(id)),
+            Some(MySQLValue::String(note.to_owned())),
+        ]
+    }
+
+    fn insert(id: i64, note: &str) -> KeyedRow {
+        KeyedRow::new(
+            &table(vec![0]),
+            RowEvent::NewRow {
+                cols: cols(id, note),
+            },
+        )
+    }
+
+    fn update(before: RowData, after: RowData) -> KeyedRow {
+        KeyedRow::new(
+            &table(vec![0]),
+            RowEvent::UpdatedRow {
+                before_cols: before,
+                after_cols: after,
+            },
+        )
+    }
+
+    fn delete(id: i64, note: &str) -> KeyedRow {
+        KeyedRow::new(
+            &table(vec![0]),
+            RowEvent::DeletedRow {
+                cols: cols(id, note),
+            },
+        )
+    }
+
+    fn key(id: i64) -> Option<Vec<MySQLValue>> {
+        Some(vec![MySQLValue::SignedInteger(id)])
+    }
+
+    #[test]
+    fn test_keys() {
+        let change = update(cols(1, "a"), cols(2, "b"));
+        assert_eq!(change.before_key, key(1));
+        assert_eq!(change.after_key, key(2));
+        assert_eq!(change.key(), key(2).as_ref());
+        // a minimal after image leaves out the unchanged key
+        let change = update(cols(1, "a"), vec![None, cols(1, "b")[1].clone()]);
+        assert_eq!(change.after_key, key(1));
+        assert_eq!(insert(3, "c").key(), key(3).as_ref());
+        assert_eq!(delete(3, "c").before_key, key(3));
+        let unkeyed = KeyedRow::new(&table(vec![]), RowEvent::NewRow { cols: cols(3, "c") });
+        assert_eq!(unkeyed.key(), None);
+    }
+
+    #[test]
+    fn test_insert_then_updates() {
+        let changes = compact(vec![
+            insert(1, "a"),
+            update(cols(1, "a"), cols(1, "b")),
+            update(cols(1, "b"), vec![None, cols(1, "c")[1].clone()]),
+        ]);
+        assert_eq!(changes, vec![insert(1, "c")]);
+    }
+
+    #[test]
+    fn test_insert_then_delete() {
+        let changes = compact(vec![
+            insert(1, "a"),
+            insert(2, "b"),
+            update(cols(1, "a"), cols(1, "b")),
+            delete(1, "b"),
+        ]);
+        assert_eq!(changes, vec![insert(2, "b")]);
+    }
+
+    #[test]
+    fn test_update_chain() {
+        let changes = compact(vec![
+            update(cols(1, "a"), cols(1, "b")),
+            update(cols(2, "x"), cols(2, "y")),
+            update(cols(1, "b"), cols(1, "c")),
+        ]);
+        assert_eq!(
+            changes,
+            vec![
+                update(cols(1, "a"), cols(1, "c")),
+                update(cols(2, "x"), cols(2, "y")),
+            ]
+        );
+        let changes = compact(vec![update(cols(1, "a"), cols(1, "b")), delete(1, "b")]);
+        assert_eq!(changes, vec![delete(1, "a")]);
+    }
+
+    #[test]
+    fn test_delete_then_insert() {
+        let changes = compact(vec![delete(1, "a"), insert(1, "b")]);
+        assert_eq!(changes, vec![update(cols(1, "a"), cols(1, "b"))]);
+        // cancelled out, then inserted again
+        let changes = compact(vec![insert(1, "a"), delete(1, "a"), insert(1, "b")]);
+        assert_eq!(changes, vec![insert(1, "b")]);
+    }
+
+    #[test]
+    fn test_key_change() {
+        let changes = compact(vec![
+            insert(1, "a"),
+            update(cols(1, "a"), cols(2, "a")),
+            update(cols(3, "c"), cols(4, "c")),
+        ]);
+        assert_eq!(
+            changes,
+            vec![insert(2, "a"), delete(3, "c"), insert(4, "c")]
+        );
+    }
+
+    #[test]
+    fn test_unkeyed_changes_pass_through() {
+        let unkeyed = |id| KeyedRow::new(&table(vec![]), RowEvent::NewRow { cols: cols(id, "") });
+        let mut compactor = Compactor::new();
+        compactor.push(unkeyed(1));
+        compactor.push(insert(1, "a"));
+        compactor.push(unkeyed(1));
+        compactor.push(delete(1, "a"));
+        assert_eq!(compactor.len(), 2);
+        assert_eq!(compactor.finish(), vec![unkeyed(1), unkeyed(1)]);
+    }
+
+    #[test]
+    fn test_keyed_rows() {
+        let mut table_map = TableMap::new();
+        let columns = vec![ColumnType::LongLong, ColumnType::VarChar(255)];
+        table_map.handle_with_metadata(
+            1,
+            "shop".to_owned(),
+            "orders".to_owned(),
+            columns.clone(),
+            TableMetadata {
+                primary_key: vec![0],
+                ..TableMetadata::default()
+            },
+        );
+        table_map.handle(2, "shop".to_owned(), "orders".to_owned(), columns);
+        let event = |table: Arc<_>| BinlogEvent {
+            type_code: TypeCode::WriteRowsEventV2,
+            timestamp: 0,
+            gtid: None,
+            mariadb_gtid: None,
+            logical_timestamp: None,
+            schema_name: Some("shop".to_owned()),
+            table_name: Some("orders".to_owned()),
+            table: Some(table),
+            rows: vec![RowEvent::NewRow { cols: cols(1, "a") }],
+            query: None,
+            offset: 0,
+        };
+
+        let with_metadata = event(table_map.get_shared(1).unwrap());
+        assert_eq!(keyed_rows(&with_metadata, None)[0].after_key, key(1));
+        let without = event(table_map.get_shared(2).unwrap());
+        assert_eq!(keyed_rows(&without, None)[0].after_key, None);
+        let resolver = vec![table(vec![0])];
+        assert_eq!(keyed_rows(&without, Some(&resolver))[0], insert(1, "a"));
+
+        let mut compactor = Compactor::new();
+        compactor.push_event(&with_metadata, None);
+        compactor.push_event(&without, Some(&resolver));
+        assert_eq!(compactor.finish(), vec![insert(1, "a")]);
+    }
+}
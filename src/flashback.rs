@@ -0,0 +1,473 @@
+//! Undo row changes by generating their inverse.
+//!
+//! Feed the [`BinlogEvent`]s of a range (from `mysql_binlog::parse_file` or
+//! `Runner::get_binlog_event`) into a [`Flashback`]; the rows events which pass its filters are
+//! kept and handed back inverted, newest first: inserted rows are deleted, deleted rows are
+//! inserted and updates get their before and after images swapped. This is the same thing
+//! binlog2sql's `--flashback` does.
+//!
+//! Every kept event is held in memory until the flashback is finished, so narrow the range
+//! with the filters before running it over a large binlog.
+//!
+//! ```no_run
+//! use mysqlbinlog_network::flashback::Flashback;
+//! use mysqlbinlog_network::mysql_binlog::parse_file;
+//!
+//! let mut flashback = Flashback::new()
+//!     .schema("shop")
+//!     .table("orders")
+//!     .start_time(1625140800);
+//! for event in parse_file("binlog.000001").unwrap() {
+//!     flashback.push(event.unwrap());
+//! }
+//! for statement in flashback.statements(None).unwrap() {
+//!     println!("{}", statement);
+//! }
+//! ```
+//!
+//! The statements need column names. Table maps only carry them when the source runs with
+//! `binlog_row_metadata=FULL`; otherwise pass a [`SchemaResolver`], like a
+//! `schema_history::SchemaHistory`, to name the columns.
+//!
+//! The rollback can also be written as a binlog file with [`Flashback::write_binlog`], to be
+//! replayed with `mysqlbinlog rollback.000001 | mysql`.
+use std::io::Write;
+
+use thiserror::Error;
+
+use crate::mysql_binlog::binlog_writer::BinlogFileWriter;
+use crate::mysql_binlog::bit_set::BitSet;
+use crate::mysql_binlog::errors::EventEncodeError;
+use crate::mysql_binlog::event::{EventData, RowEvent, TypeCode};
+use crate::mysql_binlog::BinlogEvent;
+use crate::pkg::mysql_gtid::Gtid;
+use crate::sql::{self, SchemaResolver, TableDef};
+
+#[derive(Debug, Error)]
+pub enum FlashbackError {
+    #[error("column names of {0}.{1} are unknown; run the source with binlog_row_metadata=FULL or pass a SchemaResolver")]
+    UnknownColumns(String, String),
+    #[error("a row of {0}.{1} has no column to identify it by")]
+    UnidentifiedRow(String, String),
+}
+
+#[derive(Default)]
+pub struct Flashback {
+    schemas: Vec<String>,
+    tables: Vec<String>,
+    start_time: Option<u32>,
+    stop_time: Option<u32>,
+    gtid_set: Option<Gtid>,
+    events: Vec<BinlogEvent>,
+}
+
+impl Flashback {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Only undo changes to this schema. Can be given several times.
+    pub fn schema<S: Into<String>>(mut self, schema: S) -> Self {
+        self.schemas.push(schema.into());
+        self
+    }
+
+    /// Only undo changes to this table. Can be given several times.
+    pub fn table<S: Into<String>>(mut self, table: S) -> Self {
+        self.tables.push(table.into());
+        self
+    }
+
+    /// Skip events logged before this unix timestamp
+    pub fn start_time(mut self, timestamp: u32) -> Self {
+        self.start_time = Some(timestamp);
+        self
+    }
+
+    /// Skip events logged after this unix timestamp
+    pub fn stop_time(mut self, timestamp: u32) -> Self {
+        self.stop_time = Some(timestamp);
+        self
+    }
+
+    /// Only undo the transactions in this GTID set
+    pub fn gtid_set(mut self, gtid: Gtid) -> Self {
+        self.gtid_set = Some(gtid);
+        self
+    }
+
+    fn matches(&self, event: &BinlogEvent) -> bool {
+        if event.rows.is_empty() || invert_type_code(event.type_code).is_none() {
+            return false;
+        }
+        let in_list = |list: &[String], name: &Option<String>| {
+            list.is_empty() || name.as_ref().is_some_and(|n| list.contains(n))
+        };
+        if !in_list(&self.schemas, &event.schema_name) || !in_list(&self.tables, &event.table_name)
+        {
+            return false;
+        }
+        if self.start_time.is_some_and(|t| event.timestamp < t)
+            || self.stop_time.is_some_and(|t| event.timestamp > t)
+        {
+            return false;
+        }
+        match (&self.gtid_set, event.gtid) {
+            (None, _) => true,
+            (Some(set), Some(gtid)) => set.contains(
+                &gtid.uuid().to_hyphenated().to_string(),
+                gtid.sequence() as i64,
+            ),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Offer an event to the flashback. Events which are not rows events or do not pass the
+    /// filters are dropped.
+    pub fn push(&mut self, event: BinlogEvent) {
+        if self.matches(&event) {
+            self.events.push(event);
+        }
+    }
+
+    /// Number of rows events kept so far
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The inverted events, newest first
+    pub fn into_events(self) -> Vec<BinlogEvent> {
+        self.events.into_iter().rev().filter_map(invert).collect()
+    }
+
+    /// The rollback as SQL statements, in the order they have to be applied. Column names come
+    /// from the table maps, or from `resolver` when the table maps lack them; rolling back a
+    /// table whose column names neither knows is an error, as is a row which can't be matched.
+    pub fn statements(
+        self,
+        resolver: Option<&dyn SchemaResolver>,
+    ) -> Result<Vec<String>, FlashbackError> {
+        let mut statements = Vec::new();
+        for event in self.into_events() {
+            let table = match event.table {
+                Some(ref table) => table,
+                None => {
+                    return Err(FlashbackError::UnknownColumns(
+                        event.schema_name.unwrap_or_default(),
+                        event.table_name.unwrap_or_default(),
+                    ))
+                }
+            };
+            let def = match resolver {
+                Some(resolver) => TableDef::resolve(table, resolver),
+                None => TableDef::from_table_map(table),
+            };
+            let named = !table.metadata().column_names.is_empty()
+                || resolver
+                    .and_then(|r| r.table_def(&def.schema_name, &def.table_name))
+                    .is_some_and(|known| known.columns.len() == def.columns.len());
+            if !named {
+                return Err(FlashbackError::UnknownColumns(
+                    def.schema_name,
+                    def.table_name,
+                ));
+            }
+            for row in &event.rows {
+                match sql::row_statement(&def, row) {
+                    Some(statement) => statements.push(statement),
+                    None => {
+                        return Err(FlashbackError::UnidentifiedRow(
+                            def.schema_name,
+                            def.table_name,
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(statements)
+    }
+
+    /// Write the rollback into a binlog file, one transaction per inverted rows event. Events
+    /// whose table map is unknown are skipped.
+    pub fn write_binlog<W: Write>(
+        self,
+        writer: &mut BinlogFileWriter<W>,
+    ) -> Result<(), EventEncodeError> {
+        let mut tables: Vec<(String, String)> = Vec::new();
+        for (xid, event) in self.into_events().into_iter().enumerate() {
+            let table = match event.table {
+                Some(ref table) => table,
+                None => continue,
+            };
+            let name = (
+                table.schema_name().to_owned(),
+                table.table_name().to_owned(),
+            );
+            let table_id = match tables.iter().position(|t| *t == name) {
+                Some(i) => i as u64 + 1,
+                None => {
+                    tables.push(name);
+                    tables.len() as u64
+                }
+            };
+            let mut null_bitmap = BitSet::new(table.columns().len());
+            for i in 0..table.columns().len() {
+                null_bitmap.set(i);
+            }
+            let timestamp = event.timestamp;
+            writer.write_event(
+                timestamp,
+                &EventData::QueryEvent {
+                    thread_id: 0,
+                    exec_time: 0,
+                    error_code: 0,
+                    schema: table.schema_name().to_owned(),
+                    query: "BEGIN".to_owned(),
+                },
+            )?;
+            writer.write_event(
+                timestamp,
+                &EventData::TableMapEvent {
+                    table_id,
+                    schema_name: table.schema_name().to_owned(),
+                    table_name: table.table_name().to_owned(),
+                    columns: table.columns().to_vec(),
+                    null_bitmap,
+                    metadata: table.metadata().clone(),
+                },
+            )?;
+            let rows = event.rows;
+            let data = match event.type_code {
+                TypeCode::WriteRowsEventV1 | TypeCode::WriteRowsEventV2 => {
+                    EventData::WriteRowsEvent { table_id, rows }
+                }
+                TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsEventV2 => {
+                    EventData::UpdateRowsEvent { table_id, rows }
+                }
+                _ => EventData::DeleteRowsEvent { table_id, rows },
+            };
+            writer.write_event(timestamp, &data)?;
+            writer.write_event(
+                timestamp,
+                &EventData::XIDEvent {
+                    xid: xid as u64 + 1,
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn invert_type_code(type_code: TypeCode) -> Option<TypeCode> {
+    Some(match type_code {
+        TypeCode::WriteRowsEventV1 | TypeCode::WriteRowsCompressedEventV1 => {
+            TypeCode::DeleteRowsEventV1
+        }
+        TypeCode::DeleteRowsEventV1 | TypeCode::DeleteRowsCompressedEventV1 => {
+            TypeCode::WriteRowsEventV1
+        }
+        TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsCompressedEventV1 => {
+            TypeCode::UpdateRowsEventV1
+        }
+        TypeCode::WriteRowsEventV2 | TypeCode::WriteRowsCompressedEvent => {
+            TypeCode::DeleteRowsEventV2
+        }
+        TypeCode::DeleteRowsEventV2 | TypeCode::DeleteRowsCompressedEvent => {
+            TypeCode::WriteRowsEventV2
+        }
+        TypeCode::UpdateRowsEventV2 | TypeCode::UpdateRowsCompressedEvent => {
+            TypeCode::UpdateRowsEventV2
+        }
+        _ => return None,
+    })
+}
+
+fn invert_row(row: RowEvent) -> RowEvent {
+    match row {
+        RowEvent::NewRow { cols } => RowEvent::DeletedRow { cols },
+        RowEvent::DeletedRow { cols } => RowEvent::NewRow { cols },
+        RowEvent::UpdatedRow {
+            before_cols,
+            after_cols,
+        } => RowEvent::UpdatedRow {
+            before_cols: after_cols,
+            after_cols: before_cols,
+        },
+    }
+}
+
+/// Invert a single rows event: the operation is reversed and so is the order of its rows.
+/// Returns `None` for events which are not rows events.
+pub fn invert(mut event: BinlogEvent) -> Option<BinlogEvent> {
+    event.type_code = invert_type_code(event.type_code)?;
+    event.rows = event.rows.into_iter().rev().map(invert_row).collect();
+    Some(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{Flashback, FlashbackError};
+    use crate::mysql_binlog::binlog_writer::BinlogFileWriter;
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::event::{RowEvent, TypeCode};
+    use crate::mysql_binlog::parse_reader;
+    use crate::mysql_binlog::table_map::TableMap;
+    use crate::mysql_binlog::value::MySQLValue;
+    use crate::mysql_binlog::BinlogEvent;
+    use crate::sql::{ColumnDef, TableDef};
+
+    fn rows_event(type_code: TypeCode, timestamp: u32, rows: Vec<RowEvent>) -> BinlogEvent {
+        BinlogEvent {
+            type_code,
+            timestamp,
+            gtid: None,
+            mariadb_gtid: None,
+            logical_timestamp: None,
+            schema_name: Some("shop".to_owned()),
+            table_name: Some("orders".to_owned()),
+            table: None,
+            rows,
+            query: None,
+            offset: 0,
+        }
+    }
+
+    fn cols(id: i64) -> Vec<Option<MySQLValue>> {
+        vec![Some(MySQLValue::SignedInteger(id))]
+    }
+
+    #[test]
+    fn test_flashback_inverts_in_reverse_order() {
+        let mut flashback = Flashback::new().table("orders").start_time(10);
+        flashback.push(rows_event(
+            TypeCode::WriteRowsEventV2,
+            5,
+            vec![RowEvent::NewRow { cols: cols(0) }],
+        ));
+        flashback.push(rows_event(
+            TypeCode::WriteRowsEventV2,
+            10,
+            vec![
+                RowEvent::NewRow { cols: cols(1) },
+                RowEvent::NewRow { cols: cols(2) },
+            ],
+        ));
+        flashback.push(rows_event(
+            TypeCode::UpdateRowsEventV2,
+            11,
+            vec![RowEvent::UpdatedRow {
+                before_cols: cols(1),
+                after_cols: cols(3),
+            }],
+        ));
+        assert_eq!(flashback.len(), 2);
+        let events = flashback.into_events();
+        assert_eq!(events[0].type_code, TypeCode::UpdateRowsEventV2);
+        match events[0].rows[0] {
+            RowEvent::UpdatedRow {
+                ref before_cols,
+                ref after_cols,
+            } => {
+                assert_eq!(format!("{:?}", before_cols), format!("{:?}", cols(3)));
+                assert_eq!(format!("{:?}", after_cols), format!("{:?}", cols(1)));
+            }
+            ref r => panic!("unexpected row {:?}", r),
+        }
+        assert_eq!(events[1].type_code, TypeCode::DeleteRowsEventV2);
+        let ids: Vec<String> = events[1]
+            .rows
+            .iter()
+            .map(|r| format!("{:?}", r.cols().unwrap()))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![format!("{:?}", cols(2)), format!("{:?}", cols(1))]
+        );
+    }
+
+    #[test]
+    fn test_flashback_statements() {
+        let mut table_map = TableMap::new();
+        table_map.handle(
+            42,
+            "shop".to_owned(),
+            "orders".to_owned(),
+            vec![ColumnType::LongLong],
+        );
+        let flashback = || {
+            let mut event = rows_event(
+                TypeCode::DeleteRowsEventV2,
+                10,
+                vec![RowEvent::DeletedRow { cols: cols(1) }],
+            );
+            event.table = table_map.get_shared(42);
+            let mut flashback = Flashback::new();
+            flashback.push(event);
+            flashback
+        };
+        // the table map has no column names and nothing else knows them
+        assert!(matches!(
+            flashback().statements(None),
+            Err(FlashbackError::UnknownColumns(..))
+        ));
+        let known = vec![TableDef {
+            schema_name: "shop".to_owned(),
+            table_name: "orders".to_owned(),
+            columns: vec![ColumnDef {
+                name: "id".to_owned(),
+                column_type: Some(ColumnType::LongLong),
+                unsigned: false,
+            }],
+            primary_key: vec![0],
+        }];
+        assert_eq!(
+            flashback().statements(Some(&known)).unwrap(),
+            vec!["INSERT INTO `shop`.`orders` (`id`) VALUES (1);"]
+        );
+        assert!(matches!(
+            flashback().statements(Some(&Vec::new())),
+            Err(FlashbackError::UnknownColumns(..))
+        ));
+    }
+
+    #[test]
+    fn test_flashback_write_binlog() {
+        let mut table_map = TableMap::new();
+        table_map.handle(
+            42,
+            "shop".to_owned(),
+            "orders".to_owned(),
+            vec![ColumnType::LongLong],
+        );
+        let mut event = rows_event(
+            TypeCode::WriteRowsEventV2,
+            10,
+            vec![RowEvent::NewRow { cols: cols(1) }],
+        );
+        event.table = table_map.get_shared(42);
+        let mut flashback = Flashback::new();
+        flashback.push(event);
+
+        let mut writer = BinlogFileWriter::new(Vec::new(), 1).unwrap();
+        flashback.write_binlog(&mut writer).unwrap();
+        let data = writer.finish().unwrap();
+        let events = parse_reader(Cursor::new(data))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].query.as_deref(), Some("BEGIN"));
+        assert_eq!(events[1].type_code, TypeCode::DeleteRowsEventV2);
+        assert_eq!(events[1].table_name.as_deref(), Some("orders"));
+        assert_eq!(
+            format!("{:?}", events[1].rows[0].cols().unwrap()),
+            format!("{:?}", cols(1))
+        );
+    }
+}
@@ -17,6 +17,7 @@
 //!             //! pos: Some(("binlog.000002".to_string(), 34834)),
 //!             pos: None,
 //!             gtid: Some("0575a804-6403-11ea-8d3d-e454e8d4a4fe:1-1469903".into()),
+//!             mariadb_gtid: None,
 //!         })
 //!         .unwrap();
 //!     loop {
@@ -24,13 +25,26 @@
 //!         dbg!(e);
 //!     }
 //! ```
+pub mod analysis;
+pub mod change_event;
 pub mod client;
+pub mod compaction;
+pub mod flashback;
+pub mod metrics;
 pub mod mysql_binlog;
 pub mod pkg;
+pub mod schema_history;
+pub mod server;
+pub mod sink;
+pub mod sql;
+pub mod text;
 
+pub use client::parallel::ParallelRunner;
+pub use client::sync::Flavor;
 pub use client::sync::OffsetConfig;
 pub use client::sync::Runner;
 pub use mysql::Value;
 pub use mysql_binlog::EventIterator;
 pub use pkg::event::Event;
+pub use pkg::mariadb_gtid::MariadbGtidSet;
 pub use pkg::mysql_gtid::Gtid;
@@ -22,8 +22,8 @@ pub struct BinlogEvents<I: Seek + Read> {
 }
 
 impl<I: Seek + Read> BinlogEvents<I> {
-    pub fn new(mut bf: BinlogFile<I>, start_offset: u64) -> Self {
-        bf.file.seek(io::SeekFrom::Start(start_offset)).unwrap();
+    /// Events from `start_offset` on; reading each event seeks to it
+    pub fn new(bf: BinlogFile<I>, start_offset: u64) -> Self {
         BinlogEvents {
             offset: Some(start_offset),
             file: bf,
@@ -0,0 +1,528 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mysql_binlog::encoder::{self, BINLOG_MAGIC};
+use crate::mysql_binlog::errors::EventEncodeError;
+use crate::mysql_binlog::event::{ChecksumAlgorithm, EventData, TypeCode, EVENT_HEADER_SIZE};
+use crate::mysql_binlog::table_map::TableMap;
+
+/// Server version announced in the FormatDescriptionEvent of files we write
+pub const WRITER_SERVER_VERSION: &str = "5.7.0-mysqlbinlog-network";
+
+/// Name of the `index`th file of a binlog sequence, e.g. `mysql-bin.000001`
+pub fn binlog_file_name(base: &str, index: u32) -> String {
+    format!("{}.{:06}", base, index)
+}
+
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or(0)
+}
+
+/// Writes events into a binlog file which MySQL's own tools (and [`BinlogFile`]) can read.
+///
+/// The magic bytes and a FormatDescriptionEvent announcing CRC32 checksums are written on
+/// creation; every event after that gets a correct `log_pos` and checksum. Table map events
+/// are remembered so the rows events referring to them can be encoded.
+///
+/// [`BinlogFile`]: crate::mysql_binlog::binlog_file::BinlogFile
+pub struct BinlogFileWriter<W: Write> {
+    inner: W,
+    server_id: u32,
+    position: u64,
+    table_map: TableMap,
+}
+
+impl BinlogFileWriter<BufWriter<File>> {
+    /// Create (or truncate) the binlog file at `path`
+    pub fn create<P: AsRef<Path>>(path: P, server_id: u32) -> Result<Self, EventEncodeError> {
+        Self::new(BufWriter::new(File::create(path)?), server_id)
+    }
+}
+
+impl<W: Write> BinlogFileWriter<W> {
+    pub fn new(inner: W, server_id: u32) -> Result<Self, EventEncodeError> {
+        let mut writer = Self::without_format_description(inner, server_id)?;
+        let timestamp = now();
+        writer.write_event(
+            timestamp,
+            &EventData::FormatDescriptionEvent {
+                binlog_version: 4,
+                server_version: WRITER_SERVER_VERSION.to_owned(),
+                create_timestamp: timestamp,
+                common_header_len: EVENT_HEADER_SIZE as u8,
+                checksum_algorithm: ChecksumAlgorithm::CRC32,
+            },
+        )?;
+        Ok(writer)
+    }
+
+    /// Start a file with only the magic bytes, for callers which copy the
+    /// FormatDescriptionEvent of another binlog with [`BinlogFileWriter::write_raw`]
+    pub fn without_format_description(
+        mut inner: W,
+        server_id: u32,
+    ) -> Result<Self, EventEncodeError> {
+        inner.write_all(&BINLOG_MAGIC)?;
+        Ok(Self::resume(inner, server_id, BINLOG_MAGIC.len() as u64))
+    }
+
+    /// Continue a binlog file whose first `position` bytes are already written
+    pub fn resume(inner: W, server_id: u32, position: u64) -> Self {
+        BinlogFileWriter {
+            inner,
+            server_id,
+            position,
+            table_map: TableMap::new(),
+        }
+    }
+
+    /// Offset the next event will be written at
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Tables announced by the table map events written so far
+    pub fn table_map(&self) -> &TableMap {
+        &self.table_map
+    }
+
+    /// Encode and append an event, returning the offset it was written at. Rows events must
+    /// follow a table map event for their table.
+    pub fn write_event(
+        &mut self,
+        timestamp: u32,
+        data: &EventData,
+    ) -> Result<u64, EventEncodeError> {
+        let (type_code, body) = encoder::encode_event_data(data, &self.table_map)?;
+        let offset = self.write_raw(timestamp, type_code, self.server_id, 0, &body)?;
+        if let EventData::TableMapEvent {
+            table_id,
+            schema_name,
+            table_name,
+            columns,
+            metadata,
+            ..
+        } = data
+        {
+            self.table_map.handle_with_metadata(
+                *table_id,
+                schema_name.clone(),
+                table_name.clone(),
+                columns.clone(),
+                metadata.clone(),
+            );
+        }
+        Ok(offset)
+    }
+
+    /// Append an already encoded event body under a fresh header and checksum, returning the
+    /// offset it was written at
+    pub fn write_raw(
+        &mut self,
+        timestamp: u32,
+        type_code: TypeCode,
+        server_id: u32,
+        flags: u16,
+        body: &[u8],
+    ) -> Result<u64, EventEncodeError> {
+        let offset = self.position;
+        let log_pos = offset + encoder::event_size(body.len(), true) as u64;
+        let event = encoder::frame_event(
+            timestamp,
+            type_code,
+            server_id,
+            log_pos as u32,
+            flags,
+            body,
+            true,
+        );
+        self.inner.write_all(&event)?;
+        self.position = log_pos;
+        Ok(offset)
+    }
+
+    pub fn flush(&mut self) -> Result<(), EventEncodeError> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    /// Close this file with a RotateEvent pointing at the next one and hand back the writer
+    pub fn rotate(mut self, next_log_name: &str) -> Result<W, EventEncodeError> {
+        self.write_event(
+            now(),
+            &EventData::RotateEvent {
+                pos: BINLOG_MAGIC.len() as u64,
+                next_log_name: next_log_name.to_owned(),
+            },
+        )?;
+        self.finish()
+    }
+
+    /// Flush and hand back the writer
+    pub fn finish(mut self) -> Result<W, EventEncodeError> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use assert_matches::assert_matches;
+    use bigdecimal::BigDecimal;
+    use uuid::Uuid;
+
+    use super::{binlog_file_name, BinlogFileWriter};
+    use crate::mysql_binlog::binlog_file::BinlogFile;
+    use crate::mysql_binlog::bit_set::BitSet;
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::event::{ChecksumAlgorithm, EventData, RowEvent, TypeCode};
+    use crate::mysql_binlog::parse_reader;
+    use crate::mysql_binlog::table_map::{TableMap, TableMetadata};
+    use crate::mysql_binlog::value::MySQLValue;
+
+    fn table_map_event() -> EventData {
+        let columns = vec![
+            ColumnType::LongLong,
+            ColumnType::VarChar(1024),
+            ColumnType::NewDecimal(10, 2),
+            ColumnType::DateTime2(0),
+            ColumnType::Blob(2),
+            ColumnType::Json(4),
+            ColumnType::Enum(1),
+            ColumnType::MyString(40),
+            ColumnType::Set(1),
+        ];
+        let mut null_bitmap = BitSet::new(columns.len());
+        null_bitmap.set(1);
+        EventData::TableMapEvent {
+            table_id: 108,
+            schema_name: "shop".to_owned(),
+            table_name: "orders".to_owned(),
+            columns,
+            null_bitmap,
+            metadata: TableMetadata {
+                column_names: [
+                    "id", "note", "amount", "created", "raw", "doc", "state", "code", "tags",
+                ]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+                unsigned_columns: vec![
+                    true, false, false, false, false, false, false, false, false,
+                ],
+                primary_key: vec![0],
+                enum_values: vec![vec!["new".to_owned(), "paid".to_owned()]],
+                set_values: vec![vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]],
+            },
+        }
+    }
+
+    fn row(id: i64, note: Option<&str>) -> Vec<Option<MySQLValue>> {
+        vec![
+            Some(MySQLValue::SignedInteger(id)),
+            Some(match note {
+                Some(n) => MySQLValue::String(n.to_owned()),
+                None => MySQLValue::Null,
+            }),
+            Some(MySQLValue::Decimal("-9.05".parse().unwrap())),
+            Some(MySQLValue::DateTime {
+                year: 2021,
+                month: 7,
+                day: 1,
+                hour: 13,
+                minute: 4,
+                second: 9,
+                subsecond: 0,
+            }),
+            Some(MySQLValue::Blob(vec![0, 1, 2].into())),
+            Some(MySQLValue::String(r#"{"a":[1,"b"]}"#.to_owned())),
+            Some(MySQLValue::Enum {
+                index: 2,
+                label: Some("paid".to_owned()),
+            }),
+            Some(MySQLValue::String("x".to_owned())),
+            Some(MySQLValue::Set(vec!["a".to_owned(), "c".to_owned()])),
+        ]
+    }
+
+    fn write_sample() -> Vec<u8> {
+        let mut writer = BinlogFileWriter::new(Vec::new(), 7).unwrap();
+        writer
+            .write_event(
+                1,
+                &EventData::PreviousGtidsLogEvent {
+                    gtid_sets: vec![(Uuid::nil(), vec![(1, 5)])],
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                2,
+                &EventData::GtidLogEvent {
+                    flags: 1,
+                    uuid: Uuid::nil(),
+                    coordinate: 5,
+                    last_committed: Some(3),
+                    sequence_number: Some(4),
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                2,
+                &EventData::QueryEvent {
+                    thread_id: 9,
+                    exec_time: 0,
+                    error_code: 0,
+                    schema: "shop".to_owned(),
+                    query: "BEGIN".to_owned(),
+                },
+            )
+            .unwrap();
+        writer.write_event(2, &table_map_event()).unwrap();
+        writer
+            .write_event(
+                2,
+                &EventData::WriteRowsEvent {
+                    table_id: 108,
+                    rows: vec![
+                        RowEvent::NewRow {
+                            cols: row(1, Some("first")),
+                        },
+                        RowEvent::NewRow {
+                            cols: row(-1, None),
+                        },
+                    ],
+                },
+            )
+            .unwrap();
+        let mut partial = row(1, Some("second"));
+        partial[4] = None;
+        writer
+            .write_event(
+                2,
+                &EventData::UpdateRowsEvent {
+                    table_id: 108,
+                    rows: vec![RowEvent::UpdatedRow {
+                        before_cols: row(1, Some("first")),
+                        after_cols: partial,
+                    }],
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(
+                2,
+                &EventData::DeleteRowsEvent {
+                    table_id: 108,
+                    rows: vec![RowEvent::DeletedRow { cols: row(1, None) }],
+                },
+            )
+            .unwrap();
+        writer
+            .write_event(2, &EventData::XIDEvent { xid: 77 })
+            .unwrap();
+        writer.rotate(&binlog_file_name("mysql-bin", 2)).unwrap()
+    }
+
+    #[test]
+    fn test_binlog_file_name() {
+        assert_eq!(binlog_file_name("mysql-bin", 12), "mysql-bin.000012");
+    }
+
+    #[test]
+    fn test_round_trip_events() {
+        let data = write_sample();
+        let fde_len = 119;
+        assert_eq!(&data[..4], &[0xfe, 0x62, 0x69, 0x6e]);
+        // event size, then the end position of the FDE
+        assert_eq!(
+            u32::from_le_bytes([data[13], data[14], data[15], data[16]]),
+            fde_len
+        );
+        assert_eq!(
+            u32::from_le_bytes([data[17], data[18], data[19], data[20]]),
+            4 + fde_len
+        );
+
+        let file = BinlogFile::try_from_reader(Cursor::new(data.clone())).unwrap();
+        let mut table_map = TableMap::new();
+        let mut decoded = Vec::new();
+        let mut expected_offset = 4 + u64::from(fde_len);
+        for event in file.events(None) {
+            let event = event.unwrap();
+            assert_eq!(event.offset(), expected_offset);
+            let start = event.offset() as usize;
+            let end = event.next_position() as usize;
+            // the trailing CRC32 covers the header and body
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(&data[start..end - 4]);
+            let crc = hasher.finalize();
+            assert_eq!(&data[end - 4..end], &crc.to_le_bytes());
+            expected_offset = event.next_position();
+            let inner = event.inner(Some(&table_map)).unwrap().unwrap();
+            if let EventData::TableMapEvent {
+                table_id,
+                ref schema_name,
+                ref table_name,
+                ref columns,
+                ref metadata,
+                ..
+            } = inner
+            {
+                table_map.handle_with_metadata(
+                    table_id,
+                    schema_name.clone(),
+                    table_name.clone(),
+                    columns.clone(),
+                    metadata.clone(),
+                );
+            }
+            decoded.push((event.type_code(), inner));
+        }
+        assert_eq!(expected_offset, data.len() as u64);
+
+        let types: Vec<TypeCode> = decoded.iter().map(|(t, _)| *t).collect();
+        assert_eq!(
+            types,
+            vec![
+                TypeCode::PreviousGtidsLogEvent,
+                TypeCode::GtidLogEvent,
+                TypeCode::QueryEvent,
+                TypeCode::TableMapEvent,
+                TypeCode::WriteRowsEventV2,
+                TypeCode::UpdateRowsEventV2,
+                TypeCode::DeleteRowsEventV2,
+                TypeCode::XidEvent,
+                TypeCode::RotateEvent,
+            ]
+        );
+        assert_matches!(
+            &decoded[0].1,
+            EventData::PreviousGtidsLogEvent { gtid_sets } if gtid_sets[0].1 == vec![(1, 5)]
+        );
+        assert_matches!(
+            decoded[1].1,
+            EventData::GtidLogEvent {
+                flags: 1,
+                coordinate: 5,
+                last_committed: Some(3),
+                sequence_number: Some(4),
+                ..
+            }
+        );
+        assert_matches!(
+            &decoded[2].1,
+            EventData::QueryEvent { thread_id: 9, schema, query, .. }
+                if schema == "shop" && query == "BEGIN"
+        );
+        match &decoded[3].1 {
+            EventData::TableMapEvent {
+                table_id,
+                columns,
+                null_bitmap,
+                metadata,
+                ..
+            } => {
+                assert_eq!(*table_id, 108);
+                if let EventData::TableMapEvent {
+                    columns: ref expected,
+                    ..
+                } = table_map_event()
+                {
+                    assert_eq!(columns, expected);
+                }
+                assert!(null_bitmap.is_set(1) && !null_bitmap.is_set(0));
+                assert_eq!(metadata.column_names[7], "code");
+                assert!(metadata.unsigned_columns[0]);
+                assert_eq!(metadata.primary_key, vec![0]);
+                assert_eq!(metadata.enum_values, vec![vec!["new", "paid"]]);
+                assert_eq!(metadata.set_values[0].len(), 3);
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+        let expected_rows = format!(
+            "{:?}",
+            vec![
+                RowEvent::NewRow {
+                    cols: row(1, Some("first"))
+                },
+                RowEvent::NewRow {
+                    cols: row(-1, None)
+                },
+            ]
+        );
+        assert_matches!(
+            &decoded[4].1,
+            EventData::WriteRowsEvent { rows, .. } if format!("{:?}", rows) == expected_rows
+        );
+        match &decoded[5].1 {
+            EventData::UpdateRowsEvent { rows, .. } => match &rows[0] {
+                RowEvent::UpdatedRow {
+                    before_cols,
+                    after_cols,
+                } => {
+                    assert_eq!(
+                        format!("{:?}", before_cols),
+                        format!("{:?}", row(1, Some("first")))
+                    );
+                    assert!(after_cols[4].is_none());
+                    assert_matches!(after_cols[1], Some(MySQLValue::String(ref s)) if s == "second");
+                    assert_matches!(after_cols[2], Some(MySQLValue::Decimal(ref d))
+                        if *d == "-9.05".parse::<BigDecimal>().unwrap());
+                }
+                r => panic!("unexpected row {:?}", r),
+            },
+            e => panic!("unexpected event {:?}", e),
+        }
+        assert_matches!(&decoded[6].1, EventData::DeleteRowsEvent { rows, .. } if rows.len() == 1);
+        assert_matches!(decoded[7].1, EventData::XIDEvent { xid: 77 });
+        assert_matches!(
+            &decoded[8].1,
+            EventData::RotateEvent { pos: 4, next_log_name } if next_log_name == "mysql-bin.000002"
+        );
+    }
+
+    #[test]
+    fn test_fde_round_trip() {
+        let data = write_sample();
+        let file = BinlogFile::try_from_reader(Cursor::new(data)).unwrap();
+        let fde = file.events(Some(4)).next().unwrap().unwrap();
+        assert_eq!(fde.type_code(), TypeCode::FormatDescriptionEvent);
+        assert_matches!(
+            fde.inner(None).unwrap(),
+            Some(EventData::FormatDescriptionEvent {
+                binlog_version: 4,
+                checksum_algorithm: ChecksumAlgorithm::CRC32,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_reader_reads_written_file() {
+        let events = parse_reader(Cursor::new(write_sample()))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let types: Vec<TypeCode> = events.iter().map(|e| e.type_code).collect();
+        assert_eq!(
+            types,
+            vec![
+                TypeCode::QueryEvent,
+                TypeCode::WriteRowsEventV2,
+                TypeCode::UpdateRowsEventV2,
+                TypeCode::DeleteRowsEventV2,
+            ]
+        );
+        assert_eq!(events[1].gtid.unwrap().sequence(), 5);
+        assert_eq!(events[1].table_name.as_deref(), Some("orders"));
+    }
+}
@@ -34,9 +34,14 @@ impl Error for BitSetError {
     }
 }
 
+/// The number of bytes holding `num_elems` bits
+pub(crate) fn byte_len(num_elems: usize) -> usize {
+    (num_elems >> 3) + usize::from(num_elems & 7 != 0)
+}
+
 impl BitSet {
     pub fn new(num_elems: usize) -> Self {
-        let vec_len = (num_elems + 7) >> 3;
+        let vec_len = byte_len(num_elems);
         BitSet {
             num_elems,
             inner: vec![0u8; vec_len],
@@ -44,7 +49,7 @@ impl BitSet {
     }
 
     pub fn from_slice(num_elems: usize, slice: &[u8]) -> Result<Self, BitSetError> {
-        let vec_len = (num_elems + 7) >> 3;
+        let vec_len = byte_len(num_elems);
         if slice.len() < vec_len {
             return Err(BitSetError::SliceTooSmall);
         }
@@ -94,8 +99,22 @@ impl BitSet {
         out
     }
 
+    /// The underlying bytes, in the layout MySQL writes them
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.inner
+    }
+
+    /// The number of items set; padding bits past the last item don't count
     pub fn bits_set(&self) -> usize {
-        self.inner.iter().map(|c| c.count_ones() as usize).sum()
+        let full_bytes = self.num_elems >> 3;
+        let full: usize = self.inner[..full_bytes]
+            .iter()
+            .map(|c| c.count_ones() as usize)
+            .sum();
+        let partial = self.inner.get(full_bytes).map_or(0, |c| {
+            (c & !(0xffu8 << (self.num_elems & 0x07))).count_ones() as usize
+        });
+        full + partial
     }
 }
 
@@ -128,5 +147,7 @@ mod tests {
         let b = BitSet::from_slice(9, &[255u8, 0u8]).expect("should construct");
         assert!(b.is_set(0));
         assert!(!b.is_set(8));
+        assert_eq!(b.bits_set(), 8);
+        assert_eq!(BitSet::from_slice(3, &[255u8]).unwrap().bits_set(), 3);
     }
 }
@@ -1,13 +1,14 @@
 use std::io::{self, Read};
 
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use serde_derive::{Deserialize, Serialize};
 
-use crate::mysql_binlog::errors::ColumnParseError;
+use crate::mysql_binlog::errors::{ColumnParseError, GeometryParseError};
 use crate::mysql_binlog::jsonb;
 use crate::mysql_binlog::packet_helpers::*;
 use crate::mysql_binlog::value::MySQLValue;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub enum ColumnType {
     Decimal,
     Tiny,
@@ -43,8 +44,8 @@ pub enum ColumnType {
 }
 
 impl ColumnType {
-    pub(crate) fn from_byte(b: u8) -> Self {
-        match b {
+    pub(crate) fn from_byte(b: u8) -> Result<Self, ColumnParseError> {
+        Ok(match b {
             0 => ColumnType::Decimal,
             1 => ColumnType::Tiny,
             2 => ColumnType::Short,
@@ -76,11 +77,63 @@ impl ColumnType {
             253 => ColumnType::VarString, // not implemented
             254 => ColumnType::MyString(0),
             255 => ColumnType::Geometry(0), // not implemented
-            i => unimplemented!("unhandled column type {}", i),
+            i => return Err(ColumnParseError::UnknownType(i)),
+        })
+    }
+
+    /// The type byte for this column, as [`ColumnType::from_byte`] reads it
+    pub(crate) fn to_byte(&self) -> u8 {
+        match self {
+            ColumnType::Decimal => 0,
+            ColumnType::Tiny => 1,
+            ColumnType::Short => 2,
+            ColumnType::Long => 3,
+            ColumnType::Float(_) => 4,
+            ColumnType::Double(_) => 5,
+            ColumnType::Null => 6,
+            ColumnType::Timestamp => 7,
+            ColumnType::LongLong => 8,
+            ColumnType::Int24 => 9,
+            ColumnType::Date => 10,
+            ColumnType::Time => 11,
+            ColumnType::DateTime => 12,
+            ColumnType::Year => 13,
+            ColumnType::NewDate => 14,
+            ColumnType::VarChar(_) => 15,
+            ColumnType::Bit(..) => 16,
+            ColumnType::Timestamp2(_) => 17,
+            ColumnType::DateTime2(_) => 18,
+            ColumnType::Time2(_) => 19,
+            ColumnType::Json(_) => 245,
+            ColumnType::NewDecimal(..) => 246,
+            ColumnType::Enum(_) => 247,
+            ColumnType::Set(_) => 248,
+            ColumnType::TinyBlob => 249,
+            ColumnType::MediumBlob => 250,
+            ColumnType::LongBlob => 251,
+            ColumnType::Blob(_) => 252,
+            ColumnType::VarString => 253,
+            ColumnType::MyString(_) => 254,
+            ColumnType::Geometry(_) => 255,
         }
     }
 
-    pub(crate) fn read_metadata<R: Read>(self, cursor: &mut R) -> Result<Self, io::Error> {
+    /// Whether the table map's signedness metadata carries a bit for this column
+    pub(crate) fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            ColumnType::Tiny
+                | ColumnType::Short
+                | ColumnType::Int24
+                | ColumnType::Long
+                | ColumnType::LongLong
+                | ColumnType::NewDecimal(..)
+                | ColumnType::Float(..)
+                | ColumnType::Double(..)
+        )
+    }
+
+    pub(crate) fn read_metadata<R: Read>(self, cursor: &mut R) -> Result<Self, ColumnParseError> {
         Ok(match self {
             ColumnType::Float(_) => {
                 let pack_length = cursor.read_u8()?;
@@ -116,14 +169,16 @@ impl ColumnType {
                 let f1 = cursor.read_u8()?;
                 let f2 = cursor.read_u8()?;
                 let real_type = f1;
-                let real_type = ColumnType::from_byte(real_type);
+                let real_type = ColumnType::from_byte(real_type)?;
                 let real_size: u16 = f2.into();
                 // XXX todo this actually includes some of the bits from f1
                 match real_type {
                     ColumnType::Enum(_) => ColumnType::Enum(real_size),
                     ColumnType::Set(_) => ColumnType::Set(real_size),
                     ColumnType::MyString(_) => ColumnType::MyString(real_size),
-                    i => unimplemented!("unimplemented stringy type {:?}", i),
+                    column_type => {
+                        return Err(ColumnParseError::UnimplementedTypeError { column_type })
+                    }
                 }
             }
             ColumnType::Enum(_) => {
@@ -138,6 +193,34 @@ impl ColumnType {
         })
     }
 
+    /// Resolve the ENUM index or SET bitmask [`read_value`](Self::read_value) returns to the
+    /// column's labels, as listed in the table map metadata. Values the labels don't cover are
+    /// returned as they are.
+    pub(crate) fn with_labels(&self, value: MySQLValue, labels: &[String]) -> MySQLValue {
+        match (self, value) {
+            (ColumnType::Enum(_), MySQLValue::Enum { index, label: None }) => {
+                let label = match index {
+                    0 => Some(String::new()),
+                    i => labels.get(usize::from(i) - 1).cloned(),
+                };
+                MySQLValue::Enum { index, label }
+            }
+            (ColumnType::Set(_), MySQLValue::SignedInteger(bits))
+                if (bits as u64).checked_shr(labels.len() as u32).unwrap_or(0) == 0 =>
+            {
+                MySQLValue::Set(
+                    labels
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| (bits as u64).checked_shr(*i as u32).unwrap_or(0) & 1 != 0)
+                        .map(|(_, label)| label.clone())
+                        .collect(),
+                )
+            }
+            (_, value) => value,
+        }
+    }
+
     pub fn read_value<R: Read>(&self, r: &mut R) -> Result<MySQLValue, ColumnParseError> {
         match self {
             &ColumnType::Tiny => Ok(MySQLValue::SignedInteger(i64::from(r.read_i8()?))),
@@ -166,60 +249,42 @@ impl ColumnType {
                 Ok(MySQLValue::String(value))
             }
             &ColumnType::Year => Ok(MySQLValue::Year(u32::from(r.read_u8()?) + 1900)),
+            // zero dates and dates with zero parts are kept as they are rather than mapped to
+            // NULL, they are distinct values in MySQL
             &ColumnType::Date => {
                 let val = read_uint24(r)?;
-                if val == 0 {
-                    Ok(MySQLValue::Null)
-                } else {
-                    let year = (val & ((1 << 15) - 1) << 9) >> 9;
-                    let month = (val & ((1 << 4) - 1) << 5) >> 5;
-                    let day = val & ((1 << 5) - 1);
-                    if year == 0 || month == 0 || day == 0 {
-                        Ok(MySQLValue::Null)
-                    } else {
-                        Ok(MySQLValue::Date { year, month, day })
-                    }
-                }
+                Ok(MySQLValue::Date {
+                    year: val >> 9,
+                    month: (val >> 5) & 0x0f,
+                    day: val & 0x1f,
+                })
             }
+            // a signed HHMMSS number
             &ColumnType::Time => {
-                let val = read_uint24(r)?;
-                let hours = val / 10000;
-                let minutes = (val % 10000) / 100;
-                let seconds = val % 100;
+                let val = r.read_i24::<LittleEndian>()?;
+                let abs = val.unsigned_abs();
                 Ok(MySQLValue::Time {
-                    hours,
-                    minutes,
-                    seconds,
+                    negative: val < 0,
+                    hours: abs / 10000,
+                    minutes: (abs % 10000) / 100,
+                    seconds: abs % 100,
                     subseconds: 0,
                 })
             }
+            // a YYYYMMDDHHMMSS number
             &ColumnType::DateTime => {
                 let value = r.read_u64::<LittleEndian>()?;
-                if value == 0 {
-                    Ok(MySQLValue::Null)
-                } else {
-                    let date = value / 1000000;
-                    let time = value % 1000000;
-                    let year = (date / 10000) as u32;
-                    let month = ((date % 10000) / 100) as u32;
-                    let day = (date % 100) as u32;
-                    let hour = (time / 10000) as u32;
-                    let minute = ((time % 10000) / 100) as u32;
-                    let second = (time % 100) as u32;
-                    if year == 0 || month == 0 || day == 0 {
-                        Ok(MySQLValue::Null)
-                    } else {
-                        Ok(MySQLValue::DateTime {
-                            year,
-                            month,
-                            day,
-                            hour,
-                            minute,
-                            second,
-                            subsecond: 0,
-                        })
-                    }
-                }
+                let date = value / 1000000;
+                let time = value % 1000000;
+                Ok(MySQLValue::DateTime {
+                    year: (date / 10000) as u32,
+                    month: ((date % 10000) / 100) as u32,
+                    day: (date % 100) as u32,
+                    hour: (time / 10000) as u32,
+                    minute: ((time % 10000) / 100) as u32,
+                    second: (time % 100) as u32,
+                    subsecond: 0,
+                })
             }
             // the *2 functions are new in MySQL 5.6
             // docs are at
@@ -267,23 +332,57 @@ impl ColumnType {
                 // 10 bits hour
                 // 6 bits minute
                 // 6 bits second
-                let mut buf = [0u8; 3];
-                r.read_exact(&mut buf)?;
-                let hours = (((buf[0] & 0x3f) as u32) << 4) | (((buf[1] & 0xf0) as u32) >> 4);
-                let minutes = (((buf[1] & 0x0f) as u32) << 2) | (((buf[2] & 0xb0) as u32) >> 6);
-                let seconds = (buf[2] & 0x3f) as u32;
-                let frac_part = read_datetime_subsecond_part(r, pack_length)?;
+                // stored as an offset from 0x800000 so that the bytes sort, negative values
+                // count their fractional part down from the next whole second
+                let mut int_part = r.read_uint::<BigEndian>(3)? as i64 - 0x80_0000;
+                let frac_part = match pack_length {
+                    1 | 2 => {
+                        let frac = i64::from(r.read_u8()?);
+                        if int_part < 0 && frac != 0 {
+                            int_part += 1;
+                            (frac - 0x100) * 10_000
+                        } else {
+                            frac * 10_000
+                        }
+                    }
+                    3 | 4 => {
+                        let frac = i64::from(r.read_u16::<BigEndian>()?);
+                        if int_part < 0 && frac != 0 {
+                            int_part += 1;
+                            (frac - 0x1_0000) * 100
+                        } else {
+                            frac * 100
+                        }
+                    }
+                    5 | 6 => r.read_uint::<BigEndian>(3)? as i64,
+                    _ => 0,
+                };
+                let packed = (int_part << 24) + frac_part;
+                let abs = packed.unsigned_abs();
+                let hms = abs >> 24;
                 Ok(MySQLValue::Time {
-                    hours,
-                    minutes,
-                    seconds,
-                    subseconds: frac_part,
+                    negative: packed < 0,
+                    hours: ((hms >> 12) & 0x3ff) as u32,
+                    minutes: ((hms >> 6) & 0x3f) as u32,
+                    seconds: (hms & 0x3f) as u32,
+                    subseconds: (abs & 0xff_ffff) as u32,
                 })
             }
-            &ColumnType::Blob(length_bytes) | &ColumnType::Geometry(length_bytes) => {
+            &ColumnType::Blob(length_bytes) => {
                 let val = read_var_byte_length_prefixed_bytes(r, length_bytes)?;
                 Ok(MySQLValue::Blob(val.into()))
             }
+            &ColumnType::Geometry(length_bytes) => {
+                let mut val = read_var_byte_length_prefixed_bytes(r, length_bytes)?;
+                if val.len() < 4 {
+                    return Err(GeometryParseError::MissingSrid.into());
+                }
+                let wkb = val.split_off(4);
+                Ok(MySQLValue::Geometry {
+                    srid: LittleEndian::read_u32(&val),
+                    wkb: wkb.into(),
+                })
+            }
             &ColumnType::Float(length) | &ColumnType::Double(length) => {
                 if length == 4 {
                     Ok(MySQLValue::Float(r.read_f32::<LittleEndian>()?))
@@ -301,12 +400,16 @@ impl ColumnType {
                 Ok(MySQLValue::Decimal(body))
             }
             &ColumnType::Enum(length_bytes) => {
-                let enum_value = match (length_bytes & 0xff) as u8 {
-                    0x01 => i16::from(r.read_i8()?),
-                    0x02 => r.read_i16::<LittleEndian>()?,
-                    i => unimplemented!("unhandled Enum pack_length {:?}", i),
+                let index = match (length_bytes & 0xff) as u8 {
+                    0x01 => u16::from(r.read_u8()?),
+                    0x02 => r.read_u16::<LittleEndian>()?,
+                    _ => {
+                        return Err(ColumnParseError::UnimplementedTypeError {
+                            column_type: self.clone(),
+                        })
+                    }
                 };
-                Ok(MySQLValue::Enum(enum_value))
+                Ok(MySQLValue::Enum { index, label: None })
             }
             &ColumnType::Json(size) => {
                 let body = read_var_byte_length_prefixed_bytes(r, size)?;
@@ -323,23 +426,384 @@ impl ColumnType {
                 })
             }
             &ColumnType::Set(size) => {
-                let nbits = size * 8;
+                let nbits = size.saturating_mul(8);
                 Ok(MySQLValue::SignedInteger(little_decode_bit(
                     r, nbits, size,
                 )?))
             }
             &ColumnType::Bit(f, s) => {
-                let nbits = s * 8 + f;
-                let size = (nbits + 7) / 8;
-                Ok(MySQLValue::SignedInteger(decode_bit(
-                    r,
-                    nbits as u16,
-                    size,
-                )?))
+                let nbits = u16::from(s) * 8 + u16::from(f);
+                let size = nbits.div_ceil(8);
+                Ok(MySQLValue::SignedInteger(decode_bit(r, nbits, size)?))
             }
             &ColumnType::Decimal | &ColumnType::NewDate => {
-                unimplemented!("unhandled value type: {:?}", self);
+                Err(ColumnParseError::UnimplementedTypeError {
+                    column_type: self.clone(),
+                })
+            }
+        }
+    }
+
+    /// The number of bytes the value at the start of `data` takes, found without decoding it
+    pub(crate) fn value_length(&self, data: &[u8]) -> Result<usize, ColumnParseError> {
+        let prefixed = |prefix: usize| -> Result<usize, ColumnParseError> {
+            let mut buf = [0u8; 8];
+            match data.get(..prefix) {
+                Some(bytes) if prefix <= 8 => buf[..prefix].copy_from_slice(bytes),
+                _ => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            }
+            Ok((u64::from_le_bytes(buf) as usize).saturating_add(prefix))
+        };
+        let length = match *self {
+            ColumnType::Null => 0,
+            ColumnType::Tiny | ColumnType::Year => 1,
+            ColumnType::Short => 2,
+            ColumnType::Int24 | ColumnType::Date | ColumnType::Time => 3,
+            ColumnType::Long | ColumnType::Timestamp => 4,
+            ColumnType::LongLong | ColumnType::DateTime => 8,
+            ColumnType::VarChar(max_len) | ColumnType::MyString(max_len) => {
+                prefixed(if max_len > 255 { 2 } else { 1 })?
+            }
+            ColumnType::DateTime2(pack_length) => 5 + subsecond_length(pack_length),
+            ColumnType::Timestamp2(pack_length) => 4 + subsecond_length(pack_length),
+            ColumnType::Time2(pack_length) => 3 + subsecond_length(pack_length),
+            ColumnType::Blob(length_bytes)
+            | ColumnType::Geometry(length_bytes)
+            | ColumnType::Json(length_bytes) => prefixed(usize::from(length_bytes))?,
+            ColumnType::Float(length) | ColumnType::Double(length)
+                if length == 4 || length == 8 =>
+            {
+                usize::from(length)
+            }
+            ColumnType::NewDecimal(precision, decimal_places) => {
+                new_decimal_length(precision, decimal_places)
+            }
+            ColumnType::Enum(length_bytes) => usize::from(length_bytes & 0xff),
+            ColumnType::Set(size) => usize::from(size),
+            ColumnType::Bit(f, s) => (usize::from(s) * 8 + usize::from(f)).div_ceil(8),
+            _ => {
+                return Err(ColumnParseError::UnimplementedTypeError {
+                    column_type: self.clone(),
+                })
+            }
+        };
+        if data.len() < length {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::ColumnType;
+    use crate::mysql_binlog::encoder::write_value;
+    use crate::mysql_binlog::value::MySQLValue;
+
+    fn decode(column_type: &ColumnType, bytes: &[u8]) -> MySQLValue {
+        let mut cursor = Cursor::new(bytes);
+        let decoded = column_type.read_value(&mut cursor).expect("should decode");
+        assert_eq!(cursor.position() as usize, bytes.len());
+        decoded
+    }
+
+    fn time(negative: bool, hours: u32, minutes: u32, seconds: u32, micros: u32) -> MySQLValue {
+        MySQLValue::Time {
+            negative,
+            hours,
+            minutes,
+            seconds,
+            subseconds: micros,
+        }
+    }
+
+    #[test]
+    fn test_time2_fixtures() {
+        // as MySQL writes them, at each precision
+        let fixtures: &[(u8, &[u8], MySQLValue)] = &[
+            (0, &[0x80, 0x00, 0x00], time(false, 0, 0, 0, 0)),
+            (0, &[0x80, 0xc8, 0xb8], time(false, 12, 34, 56, 0)),
+            (0, &[0x7f, 0xff, 0xff], time(true, 0, 0, 1, 0)),
+            (0, &[0x7f, 0xf0, 0x00], time(true, 1, 0, 0, 0)),
+            (0, &[0x4b, 0x91, 0x05], time(true, 838, 59, 59, 0)),
+            (
+                1,
+                &[0x80, 0xc8, 0xb8, 0x0a],
+                time(false, 12, 34, 56, 100_000),
+            ),
+            (1, &[0x7f, 0xff, 0xfe, 0xf6], time(true, 0, 0, 1, 100_000)),
+            (
+                1,
+                &[0x4b, 0x91, 0x04, 0xa6],
+                time(true, 838, 59, 59, 900_000),
+            ),
+            (
+                2,
+                &[0x80, 0xc8, 0xb8, 0x0c],
+                time(false, 12, 34, 56, 120_000),
+            ),
+            (2, &[0x7f, 0xff, 0xff, 0xff], time(true, 0, 0, 0, 10_000)),
+            (2, &[0x7f, 0xff, 0xfe, 0xf6], time(true, 0, 0, 1, 100_000)),
+            (2, &[0x7f, 0xf0, 0x00, 0x00], time(true, 1, 0, 0, 0)),
+            (
+                2,
+                &[0x4b, 0x91, 0x04, 0x9d],
+                time(true, 838, 59, 59, 990_000),
+            ),
+            (
+                3,
+                &[0x80, 0xc8, 0xb8, 0x04, 0xce],
+                time(false, 12, 34, 56, 123_000),
+            ),
+            (
+                3,
+                &[0x7f, 0xff, 0xff, 0xff, 0x9c],
+                time(true, 0, 0, 0, 10_000),
+            ),
+            (
+                3,
+                &[0x7f, 0xff, 0xfe, 0xfc, 0x18],
+                time(true, 0, 0, 1, 100_000),
+            ),
+            (
+                3,
+                &[0x4b, 0x91, 0x04, 0xd8, 0xfa],
+                time(true, 838, 59, 59, 999_000),
+            ),
+            (
+                4,
+                &[0x80, 0xc8, 0xb8, 0x04, 0xd2],
+                time(false, 12, 34, 56, 123_400),
+            ),
+            (4, &[0x7f, 0xf0, 0x00, 0x00, 0x00], time(true, 1, 0, 0, 0)),
+            (
+                4,
+                &[0x4b, 0x91, 0x04, 0xd8, 0xf1],
+                time(true, 838, 59, 59, 999_900),
+            ),
+            (
+                5,
+                &[0x80, 0xc8, 0xb8, 0x01, 0xe2, 0x3a],
+                time(false, 12, 34, 56, 123_450),
+            ),
+            (
+                5,
+                &[0x7f, 0xff, 0xfe, 0xfe, 0x79, 0x60],
+                time(true, 0, 0, 1, 100_000),
+            ),
+            (
+                5,
+                &[0x4b, 0x91, 0x04, 0xf0, 0xbd, 0xca],
+                time(true, 838, 59, 59, 999_990),
+            ),
+            (
+                6,
+                &[0x80, 0x00, 0x00, 0x00, 0x00, 0x00],
+                time(false, 0, 0, 0, 0),
+            ),
+            (
+                6,
+                &[0x80, 0xc8, 0xb8, 0x01, 0xe2, 0x40],
+                time(false, 12, 34, 56, 123_456),
+            ),
+            (
+                6,
+                &[0x7f, 0xff, 0xff, 0xff, 0xd8, 0xf0],
+                time(true, 0, 0, 0, 10_000),
+            ),
+            (
+                6,
+                &[0x7f, 0xf0, 0x00, 0x00, 0x00, 0x00],
+                time(true, 1, 0, 0, 0),
+            ),
+            (
+                6,
+                &[0x4b, 0x91, 0x04, 0xf0, 0xbd, 0xc1],
+                time(true, 838, 59, 59, 999_999),
+            ),
+        ];
+        for (fsp, bytes, value) in fixtures {
+            let column_type = ColumnType::Time2(*fsp);
+            assert_eq!(
+                &decode(&column_type, bytes),
+                value,
+                "TIME({}) {:x?}",
+                fsp,
+                bytes
+            );
+            let mut encoded = Vec::new();
+            write_value(&mut encoded, &column_type, value).unwrap();
+            assert_eq!(&encoded, bytes, "TIME({}) {:?}", fsp, value);
+        }
+    }
+
+    #[test]
+    fn test_datetime2_timestamp2_fixtures() {
+        let fixtures: &[(u8, u32, &[u8])] = &[
+            (0, 0, &[]),
+            (1, 100_000, &[0x0a]),
+            (2, 120_000, &[0x0c]),
+            (3, 123_000, &[0x04, 0xce]),
+            (4, 123_400, &[0x04, 0xd2]),
+            (5, 123_450, &[0x01, 0xe2, 0x3a]),
+            (6, 123_456, &[0x01, 0xe2, 0x40]),
+        ];
+        // 2021-07-01 13:04:09.123456 and its unix time; the digits beyond the precision are
+        // dropped when writing
+        let datetime = |subsecond| MySQLValue::DateTime {
+            year: 2021,
+            month: 7,
+            day: 1,
+            hour: 13,
+            minute: 4,
+            second: 9,
+            subsecond,
+        };
+        let timestamp = |subsecond| MySQLValue::Timestamp {
+            unix_time: 1_625_144_649,
+            subsecond,
+        };
+        for (fsp, micros, fraction) in fixtures {
+            for (column_type, whole, value) in [
+                (
+                    ColumnType::DateTime2(*fsp),
+                    &[0x99, 0xaa, 0x02, 0xd1, 0x09][..],
+                    datetime as fn(u32) -> MySQLValue,
+                ),
+                (
+                    ColumnType::Timestamp2(*fsp),
+                    &[0x60, 0xdd, 0xbd, 0x49][..],
+                    timestamp,
+                ),
+            ] {
+                let bytes = [whole, fraction].concat();
+                assert_eq!(
+                    decode(&column_type, &bytes),
+                    value(*micros),
+                    "{:?}",
+                    column_type
+                );
+                let mut encoded = Vec::new();
+                write_value(&mut encoded, &column_type, &value(123_456)).unwrap();
+                assert_eq!(encoded, bytes, "{:?}", column_type);
+            }
+        }
+    }
+
+    #[test]
+    fn test_old_temporal_types() {
+        // -12:34:56 as a signed HHMMSS number
+        assert_eq!(
+            decode(&ColumnType::Time, &[0xc0, 0x1d, 0xfe]),
+            time(true, 12, 34, 56, 0)
+        );
+        // zero dates and zero parts are values rather than NULL
+        assert_eq!(
+            decode(&ColumnType::Date, &[0, 0, 0]),
+            MySQLValue::Date {
+                year: 0,
+                month: 0,
+                day: 0
+            }
+        );
+        let zero = MySQLValue::DateTime {
+            year: 0,
+            month: 0,
+            day: 0,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            subsecond: 0,
+        };
+        assert_eq!(decode(&ColumnType::DateTime, &[0; 8]), zero);
+        assert_eq!(decode(&ColumnType::DateTime2(0), &[0x80, 0, 0, 0, 0]), zero);
+        assert_eq!(
+            decode(&ColumnType::Date, &[0xe0, 0xca, 0x0f]),
+            MySQLValue::Date {
+                year: 2021,
+                month: 7,
+                day: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_temporal_garbage() {
+        // any bytes decode to something without panicking
+        for fsp in 0..=7 {
+            for column_type in &[
+                ColumnType::Date,
+                ColumnType::Time,
+                ColumnType::DateTime,
+                ColumnType::Timestamp,
+                ColumnType::Time2(fsp),
+                ColumnType::DateTime2(fsp),
+                ColumnType::Timestamp2(fsp),
+            ] {
+                for fill in &[0x00, 0x01, 0x7f, 0x80, 0xff] {
+                    column_type
+                        .read_value(&mut Cursor::new(vec![*fill; 8]))
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_enum_set_labels() {
+        let labels: Vec<String> = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        let unlabelled = MySQLValue::Enum {
+            index: 200,
+            label: None,
+        };
+        // pack length 1 holds indexes up to 255
+        assert_eq!(decode(&ColumnType::Enum(1), &[200]), unlabelled);
+        assert_eq!(
+            ColumnType::Enum(1).with_labels(unlabelled.clone(), &labels),
+            unlabelled
+        );
+        let enum_value = decode(&ColumnType::Enum(2), &[2, 0]);
+        assert_eq!(
+            ColumnType::Enum(2).with_labels(enum_value, &labels),
+            MySQLValue::Enum {
+                index: 2,
+                label: Some("b".to_owned())
+            }
+        );
+
+        let set_value = decode(&ColumnType::Set(1), &[0b101]);
+        assert_eq!(
+            ColumnType::Set(1).with_labels(set_value.clone(), &labels),
+            MySQLValue::Set(vec!["a".to_owned(), "c".to_owned()])
+        );
+        // a member past the labels keeps the bitmask
+        assert_eq!(
+            ColumnType::Set(1).with_labels(set_value.clone(), &labels[..2]),
+            set_value
+        );
+    }
+
+    #[test]
+    fn test_read_geometry() {
+        // a length, the SRID, then the WKB of POINT(1.5 -2)
+        let mut bytes = vec![25, 0, 0, 0, 0xe6, 0x10, 0, 0, 1, 1, 0, 0, 0];
+        bytes.extend_from_slice(&1.5f64.to_le_bytes());
+        bytes.extend_from_slice(&(-2.0f64).to_le_bytes());
+        let decoded = decode(&ColumnType::Geometry(4), &bytes);
+        match decoded {
+            MySQLValue::Geometry { srid, ref wkb } => {
+                assert_eq!(srid, 4326);
+                assert_eq!(wkb.as_ref(), &bytes[8..]);
             }
+            ref other => panic!("{:?}", other),
         }
+        let geometry = decoded.geometry().unwrap().unwrap();
+        assert_eq!(geometry.to_wkt(), "POINT(1.5 -2)");
+        assert!(MySQLValue::Null.geometry().is_none());
+        assert!(ColumnType::Geometry(4)
+            .read_value(&mut Cursor::new(vec![2, 0, 0, 0, 0xe6, 0x10]))
+            .is_err());
     }
 }
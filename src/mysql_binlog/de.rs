@@ -0,0 +1,723 @@
+//! Deserialize rows into your own types with serde.
+//!
+//! A row image deserializes like a map from column names to values when the table map carries
+//! the column names (MySQL 8.0 with `binlog_row_metadata=FULL`), and like a sequence of values in
+//! column order otherwise, so a struct is filled in by field name or by field order. Columns
+//! missing from the row image are left out of the map, and are `None` in the sequence.
+//!
+//! Values deserialize as:
+//!
+//! | Value | As |
+//! |-------|----|
+//! | integers | integers, UNSIGNED columns (per the table map metadata), BIT and SET bitmasks as unsigned; `bool` for nonzero |
+//! | FLOAT, DOUBLE | floats |
+//! | DECIMAL | its text; floats too |
+//! | strings | strings, or bytes |
+//! | blobs | bytes, or strings when they are UTF-8, as TEXT columns are logged as blobs |
+//! | ENUM | its label, which fills in unit enum variants, or its index without labels |
+//! | SET | a sequence of labels, or a string of them separated by commas |
+//! | YEAR | an integer |
+//! | DATE, TIME, DATETIME | `2021-07-01`, `-12:00:01.5`, `2021-07-01T13:04:09.123456` |
+//! | TIMESTAMP | `2021-07-01T13:04:09Z`, in UTC |
+//! | JSON | the document, as it is |
+//! | GEOMETRY | the Well-Known Binary bytes |
+//! | NULL | `None`, or `()` |
+//!
+//! ```no_run
+//! use mysqlbinlog_network::mysql_binlog::event::RowEvent;
+//! use serde_derive::Deserialize;
+//!
+//! #[derive(Debug, Deserialize)]
+//! struct Order {
+//!     id: u64,
+//!     note: Option<String>,
+//! }
+//!
+//! for event in mysqlbinlog_network::mysql_binlog::parse_file("bin-log.000001").unwrap() {
+//!     let event = event.unwrap();
+//!     let table = event.table.as_deref();
+//!     for row in &event.rows {
+//!         if let RowEvent::UpdatedRow { .. } = row {
+//!             let (before, after): (Order, Order) = row.deserialize_update(table).unwrap();
+//!             println!("{:?} -> {:?}", before, after);
+//!         } else {
+//!             let order: Order = row.deserialize(table).unwrap();
+//!             println!("{:?}", order);
+//!         }
+//!     }
+//! }
+//! ```
+use std::fmt;
+
+use serde::de::value::SeqDeserializer;
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess,
+    Unexpected, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::mysql_binlog::column_types::ColumnType;
+use crate::mysql_binlog::errors::RowDeserializeError;
+use crate::mysql_binlog::table_map::SingleTableMap;
+use crate::mysql_binlog::value::MySQLValue;
+use crate::sql::{fraction, unsigned_value};
+use crate::text::civil_datetime;
+
+impl de::Error for RowDeserializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        RowDeserializeError::Message(msg.to_string())
+    }
+}
+
+impl RowDeserializeError {
+    fn in_column(self, column: String) -> Self {
+        match self {
+            RowDeserializeError::Message(message) => {
+                RowDeserializeError::Column { column, message }
+            }
+            e => e,
+        }
+    }
+}
+
+/// Deserialize a row image, `table` being the table map it was decoded with
+pub fn from_row<'de, T: Deserialize<'de>>(
+    cols: &'de [Option<MySQLValue>],
+    table: Option<&SingleTableMap>,
+) -> Result<T, RowDeserializeError> {
+    T::deserialize(RowDeserializer::new(cols, table))
+}
+
+/// A [`Deserializer`] over a row image
+#[derive(Debug, Clone, Copy)]
+pub struct RowDeserializer<'de, 't> {
+    cols: &'de [Option<MySQLValue>],
+    table: Option<&'t SingleTableMap>,
+}
+
+impl<'de, 't> RowDeserializer<'de, 't> {
+    pub fn new(cols: &'de [Option<MySQLValue>], table: Option<&'t SingleTableMap>) -> Self {
+        RowDeserializer { cols, table }
+    }
+
+    fn names(&self) -> Option<&'t [String]> {
+        self.table
+            .map(|t| t.metadata().column_names.as_slice())
+            .filter(|names| !names.is_empty())
+    }
+
+    /// The name of column `index`, or `@` and its position when the names are unknown
+    fn name(&self, index: usize) -> String {
+        match self.names().and_then(|names| names.get(index)) {
+            Some(name) => name.clone(),
+            None => format!("@{}", index + 1),
+        }
+    }
+
+    fn column(&self, index: usize) -> ColumnDeserializer<'de, 't> {
+        let metadata = self.table.map(SingleTableMap::metadata);
+        ColumnDeserializer {
+            value: self.cols[index].as_ref(),
+            column_type: self.table.and_then(|t| t.columns().get(index)),
+            unsigned: metadata
+                .and_then(|m| m.unsigned_columns.get(index))
+                .copied()
+                .unwrap_or(false),
+        }
+    }
+
+    fn columns(self) -> Columns<'de, 't> {
+        Columns {
+            row: self,
+            index: 0,
+        }
+    }
+}
+
+impl<'de, 't> Deserializer<'de> for RowDeserializer<'de, 't> {
+    type Error = RowDeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.names().is_some() {
+            self.deserialize_map(visitor)
+        } else {
+            self.deserialize_seq(visitor)
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(self.columns())
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(self.columns())
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit
+        unit_struct struct enum identifier ignored_any
+    }
+}
+
+/// The columns of a row, as a map skipping the ones missing from the image or as a sequence
+struct Columns<'de, 't> {
+    row: RowDeserializer<'de, 't>,
+    index: usize,
+}
+
+impl<'de, 't> MapAccess<'de> for Columns<'de, 't> {
+    type Error = RowDeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        while self.index < self.row.cols.len() {
+            if self.row.cols[self.index].is_some() {
+                return seed
+                    .deserialize(self.row.name(self.index).into_deserializer())
+                    .map(Some);
+            }
+            self.index += 1;
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(self.row.column(index))
+            .map_err(|e| e.in_column(self.row.name(index)))
+    }
+}
+
+impl<'de, 't> SeqAccess<'de> for Columns<'de, 't> {
+    type Error = RowDeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        if self.index >= self.row.cols.len() {
+            return Ok(None);
+        }
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(self.row.column(index))
+            .map(Some)
+            .map_err(|e| e.in_column(self.row.name(index)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.row.cols.len() - self.index)
+    }
+}
+
+/// A [`Deserializer`] over one column of a row image, `None` when it is missing from the image
+struct ColumnDeserializer<'de, 't> {
+    value: Option<&'de MySQLValue>,
+    column_type: Option<&'t ColumnType>,
+    unsigned: bool,
+}
+
+impl<'de, 't> ColumnDeserializer<'de, 't> {
+    fn value(&self) -> Result<&'de MySQLValue, RowDeserializeError> {
+        self.value
+            .ok_or_else(|| de::Error::custom("column is not part of the row image"))
+    }
+
+    /// The raw bytes of a blob, geometry or string value
+    fn bytes(&self) -> Option<&'de [u8]> {
+        match self.value? {
+            MySQLValue::Blob(b) | MySQLValue::Geometry { wkb: b, .. } => Some(b.as_ref()),
+            MySQLValue::String(s) => Some(s.as_bytes()),
+            _ => None,
+        }
+    }
+}
+
+impl<'de, 't> Deserializer<'de> for ColumnDeserializer<'de, 't> {
+    type Error = RowDeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value()? {
+            MySQLValue::SignedInteger(i) => match self.column_type {
+                Some(ColumnType::Bit(..)) | Some(ColumnType::Set(_)) => {
+                    visitor.visit_u64(unsigned_value(*i, self.column_type))
+                }
+                _ if self.unsigned => visitor.visit_u64(unsigned_value(*i, self.column_type)),
+                _ => visitor.visit_i64(*i),
+            },
+            MySQLValue::Float(f) => visitor.visit_f32(*f),
+            MySQLValue::Double(d) => visitor.visit_f64(*d),
+            MySQLValue::String(s) => visitor.visit_borrowed_str(s),
+            MySQLValue::Enum {
+                label: Some(label), ..
+            } => visitor.visit_borrowed_str(label),
+            MySQLValue::Enum { index, .. } => visitor.visit_u16(*index),
+            MySQLValue::Set(labels) => visitor.visit_seq(SeqDeserializer::<_, Self::Error>::new(
+                labels.iter().map(String::as_str),
+            )),
+            MySQLValue::Blob(b) | MySQLValue::Geometry { wkb: b, .. } => {
+                visitor.visit_borrowed_bytes(b.as_ref())
+            }
+            MySQLValue::Year(y) => visitor.visit_u32(*y),
+            MySQLValue::Date { year, month, day } => {
+                visitor.visit_string(format!("{:04}-{:02}-{:02}", year, month, day))
+            }
+            MySQLValue::Time {
+                negative,
+                hours,
+                minutes,
+                seconds,
+                subseconds,
+            } => visitor.visit_string(format!(
+                "{}{:02}:{:02}:{:02}{}",
+                if *negative { "-" } else { "" },
+                hours,
+                minutes,
+                seconds,
+                fraction(*subseconds, self.column_type)
+            )),
+            MySQLValue::DateTime {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                subsecond,
+            } => visitor.visit_string(format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                fraction(*subsecond, self.column_type)
+            )),
+            MySQLValue::Timestamp {
+                unix_time,
+                subsecond,
+            } => {
+                let (year, month, day, hour, minute, second) = civil_datetime(*unix_time as u32);
+                visitor.visit_string(format!(
+                    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}Z",
+                    year,
+                    month,
+                    day,
+                    hour,
+                    minute,
+                    second,
+                    fraction(*subsecond, self.column_type)
+                ))
+            }
+            MySQLValue::Json(j) => j.deserialize_any(visitor).map_err(de::Error::custom),
+            MySQLValue::Decimal(d) => visitor.visit_string(d.to_string()),
+            MySQLValue::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            None | Some(MySQLValue::Null) => visitor.visit_none(),
+            Some(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value()? {
+            MySQLValue::SignedInteger(i) => visitor.visit_bool(*i != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value()? {
+            MySQLValue::Decimal(d) => match d.to_string().parse() {
+                Ok(f) => visitor.visit_f64(f),
+                Err(_) => Err(de::Error::invalid_type(
+                    Unexpected::Other("DECIMAL"),
+                    &visitor,
+                )),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value()? {
+            MySQLValue::Blob(b) => match std::str::from_utf8(b.as_ref()) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => Err(de::Error::invalid_value(
+                    Unexpected::Bytes(b.as_ref()),
+                    &visitor,
+                )),
+            },
+            MySQLValue::Set(labels) => visitor.visit_string(labels.join(",")),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.bytes() {
+            Some(bytes) => visitor.visit_borrowed_bytes(bytes),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// Blobs and strings are sequences of bytes, so that they fill in a `Vec<u8>`
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.bytes() {
+            Some(bytes) => visitor.visit_seq(SeqDeserializer::<_, Self::Error>::new(
+                bytes.iter().copied(),
+            )),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value()? {
+            MySQLValue::Null => visitor.visit_unit(),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.value()? {
+            MySQLValue::String(label)
+            | MySQLValue::Enum {
+                label: Some(label), ..
+            } => visitor.visit_enum(label.as_str().into_deserializer()),
+            MySQLValue::Json(j) => j
+                .deserialize_enum(name, variants, visitor)
+                .map_err(de::Error::custom),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 u8 u16 u32 u64 char unit_struct tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use assert_matches::assert_matches;
+    use serde_derive::Deserialize;
+    use serde_json::json;
+
+    use super::from_row;
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::errors::RowDeserializeError;
+    use crate::mysql_binlog::event::{RowData, RowEvent};
+    use crate::mysql_binlog::table_map::{TableMap, TableMetadata};
+    use crate::mysql_binlog::value::MySQLValue;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Status {
+        New,
+        Shipped,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Order {
+        id: u32,
+        note: Option<String>,
+        status: Status,
+        tags: Vec<String>,
+        price: f64,
+        paid: bool,
+    }
+
+    fn table_map(with_metadata: bool) -> TableMap {
+        let columns = vec![
+            ColumnType::Long,
+            ColumnType::Blob(2),
+            ColumnType::Enum(1),
+            ColumnType::Set(1),
+            ColumnType::NewDecimal(10, 2),
+            ColumnType::Tiny,
+        ];
+        let metadata = if with_metadata {
+            TableMetadata {
+                column_names: ["id", "note", "status", "tags", "price", "paid"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+                unsigned_columns: vec![true, false, false, false, false, false],
+                ..TableMetadata::default()
+            }
+        } else {
+            TableMetadata::default()
+        };
+        let mut table_map = TableMap::new();
+        table_map.handle_with_metadata(
+            1,
+            "shop".to_owned(),
+            "orders".to_owned(),
+            columns,
+            metadata,
+        );
+        table_map
+    }
+
+    fn row(id: i64, note: MySQLValue) -> RowData {
+        vec![
+            Some(MySQLValue::SignedInteger(id)),
+            Some(note),
+            Some(MySQLValue::Enum {
+                index: 2,
+                label: Some("shipped".to_owned()),
+            }),
+            Some(MySQLValue::Set(vec![
+                "gift".to_owned(),
+                "fragile".to_owned(),
+            ])),
+            Some(MySQLValue::Decimal("19.99".parse().unwrap())),
+            Some(MySQLValue::SignedInteger(1)),
+        ]
+    }
+
+    fn order(id: u32, note: Option<&str>) -> Order {
+        Order {
+            id,
+            note: note.map(str::to_owned),
+            status: Status::Shipped,
+            tags: vec!["gift".to_owned(), "fragile".to_owned()],
+            price: 19.99,
+            paid: true,
+        }
+    }
+
+    #[test]
+    fn test_by_name() {
+        let table_map = table_map(true);
+        let table = table_map.get(1);
+        let cols = row(-1, MySQLValue::Blob(b"leave at door".to_vec().into()));
+        assert_eq!(
+            from_row::<Order>(&cols, table).unwrap(),
+            order(u32::MAX, Some("leave at door"))
+        );
+        // fields are matched by name, whatever their order
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Partial {
+            price: String,
+            id: u64,
+        }
+        assert_eq!(
+            from_row::<Partial>(&cols, table).unwrap(),
+            Partial {
+                price: "19.99".to_owned(),
+                id: u64::from(u32::MAX),
+            }
+        );
+        let cols = row(-1, MySQLValue::String("leave at door".to_owned()));
+        let map: BTreeMap<String, serde_json::Value> = from_row(&cols, table).unwrap();
+        assert_eq!(map["id"], json!(u32::MAX));
+        assert_eq!(map["tags"], json!(["gift", "fragile"]));
+        assert_eq!(map["price"], json!("19.99"));
+    }
+
+    #[test]
+    fn test_by_position() {
+        let table_map = table_map(false);
+        let table = table_map.get(1);
+        let cols = row(7, MySQLValue::Null);
+        assert_eq!(from_row::<Order>(&cols, table).unwrap(), order(7, None));
+        assert_eq!(from_row::<Order>(&cols, None).unwrap(), order(7, None));
+        let (id, note): (i64, Option<String>) = from_row(&cols[..2], None).unwrap();
+        assert_eq!((id, note), (7, None));
+    }
+
+    #[test]
+    fn test_missing_columns() {
+        let table_map = table_map(true);
+        let table = table_map.get(1);
+        let mut cols = row(7, MySQLValue::Null);
+        cols[1] = None;
+        cols[2] = None;
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Image {
+            id: u32,
+            note: Option<String>,
+            #[serde(default)]
+            status: Option<Status>,
+        }
+        let expected = Image {
+            id: 7,
+            note: None,
+            status: None,
+        };
+        assert_eq!(from_row::<Image>(&cols, table).unwrap(), expected);
+        assert_eq!(from_row::<Image>(&cols, None).unwrap(), expected);
+        assert_matches!(
+            from_row::<Order>(&cols, table),
+            Err(RowDeserializeError::Message(ref m)) if m == "missing field `status`"
+        );
+        assert_matches!(
+            from_row::<Order>(&cols, None),
+            Err(RowDeserializeError::Column { ref column, ref message })
+                if column == "@3" && message == "column is not part of the row image"
+        );
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let table_map = table_map(true);
+        let cols = row(7, MySQLValue::Blob(vec![0xff, 0xfe].into()));
+        let err = from_row::<Order>(&cols, table_map.get(1)).unwrap_err();
+        assert_matches!(err, RowDeserializeError::Column { ref column, .. } if column == "note");
+        assert!(err.to_string().starts_with("column note: invalid value"));
+
+        let cols = vec![Some(MySQLValue::String("seven".to_owned()))];
+        assert_eq!(
+            from_row::<(i32,)>(&cols, None).unwrap_err().to_string(),
+            "column @1: invalid type: string \"seven\", expected i32"
+        );
+        let cols = vec![Some(MySQLValue::SignedInteger(300))];
+        assert_matches!(
+            from_row::<(u8,)>(&cols, None),
+            Err(RowDeserializeError::Column { .. })
+        );
+    }
+
+    #[test]
+    fn test_values() {
+        let cols = vec![
+            Some(MySQLValue::Blob(vec![0, 1, 2].into())),
+            Some(MySQLValue::Json(json!({"a": [1, 2]}))),
+            Some(MySQLValue::Date {
+                year: 2021,
+                month: 7,
+                day: 1,
+            }),
+            Some(MySQLValue::Time {
+                negative: true,
+                hours: 12,
+                minutes: 0,
+                seconds: 1,
+                subseconds: 500_000,
+            }),
+            Some(MySQLValue::DateTime {
+                year: 2021,
+                month: 7,
+                day: 1,
+                hour: 13,
+                minute: 4,
+                second: 9,
+                subsecond: 0,
+            }),
+            Some(MySQLValue::Timestamp {
+                unix_time: 1_625_144_649,
+                subsecond: 0,
+            }),
+            Some(MySQLValue::Year(2021)),
+            Some(MySQLValue::Null),
+        ];
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Document {
+            a: Vec<u8>,
+        }
+        #[allow(clippy::type_complexity)]
+        let values: (Vec<u8>, Document, String, String, String, String, u16, ()) =
+            from_row(&cols, None).unwrap();
+        assert_eq!(
+            values,
+            (
+                vec![0, 1, 2],
+                Document { a: vec![1, 2] },
+                "2021-07-01".to_owned(),
+                "-12:00:01.500000".to_owned(),
+                "2021-07-01T13:04:09".to_owned(),
+                "2021-07-01T13:04:09Z".to_owned(),
+                2021,
+                (),
+            )
+        );
+    }
+
+    #[test]
+    fn test_row_events() {
+        let table_map = table_map(true);
+        let table = table_map.get(1);
+        let update = RowEvent::UpdatedRow {
+            before_cols: row(7, MySQLValue::Null),
+            after_cols: row(7, MySQLValue::String("fragile".to_owned())),
+        };
+        let (before, after): (Order, Order) = update.deserialize_update(table).unwrap();
+        assert_eq!(before, order(7, None));
+        assert_eq!(after, order(7, Some("fragile")));
+        assert_matches!(
+            update.deserialize::<Order>(table),
+            Err(RowDeserializeError::UnexpectedUpdate)
+        );
+
+        let insert = RowEvent::NewRow {
+            cols: row(8, MySQLValue::Null),
+        };
+        assert_eq!(insert.deserialize::<Order>(table).unwrap(), order(8, None));
+        assert_matches!(
+            insert.deserialize_update::<Order>(table),
+            Err(RowDeserializeError::NotAnUpdate)
+        );
+    }
+}
@@ -0,0 +1,981 @@
+//! Encoding of binlog events; the inverse of [`EventData::from_data`].
+//!
+//! [`encode_event_data`] produces the body of an event and [`frame_event`] wraps it in the
+//! common header and checksum. Most callers want the [`BinlogFileWriter`] instead, which keeps
+//! track of positions and table maps.
+//!
+//! [`BinlogFileWriter`]: crate::mysql_binlog::binlog_writer::BinlogFileWriter
+use std::io::Write;
+
+use bigdecimal::BigDecimal;
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+
+use crate::mysql_binlog::column_types::ColumnType;
+use crate::mysql_binlog::errors::EventEncodeError;
+use crate::mysql_binlog::event::{
+    ChecksumAlgorithm, EventData, RowData, RowEvent, TypeCode, BINLOG_CHECKSUM_LENGTH,
+    EVENT_HEADER_SIZE,
+};
+use crate::mysql_binlog::geometry;
+use crate::mysql_binlog::jsonb;
+use crate::mysql_binlog::table_map::{SingleTableMap, TableMap, TableMetadata};
+use crate::mysql_binlog::value::MySQLValue;
+
+/// The four bytes every binlog file starts with
+pub const BINLOG_MAGIC: [u8; 4] = [0xfe, 0x62, 0x69, 0x6e];
+
+/// Post-header lengths of every event type known to MySQL 5.7, as announced in its
+/// FormatDescriptionEvent
+const POST_HEADER_LENGTHS: [u8; 38] = [
+    56, 13, 0, 8, 0, 18, 0, 4, 4, 4, 4, 18, 0, 0, 95, 0, 4, 26, 8, 0, 0, 0, 8, 8, 8, 2, 0, 0, 0,
+    10, 10, 10, 42, 42, 0, 18, 52, 0,
+];
+
+const TABLE_MAP_SIGNEDNESS: u8 = 1;
+const TABLE_MAP_COLUMN_NAME: u8 = 4;
+const TABLE_MAP_SET_STR_VALUE: u8 = 5;
+const TABLE_MAP_ENUM_STR_VALUE: u8 = 6;
+const TABLE_MAP_SIMPLE_PRIMARY_KEY: u8 = 8;
+const ROWS_FLAG_STMT_END: u16 = 0x0001;
+const MARIADB_FL_GROUP_COMMIT_ID: u8 = 0x02;
+
+/// Size of an event on disk, header and checksum included
+pub fn event_size(body_len: usize, checksum: bool) -> usize {
+    EVENT_HEADER_SIZE + body_len + if checksum { BINLOG_CHECKSUM_LENGTH } else { 0 }
+}
+
+/// Wrap an event body in the common header and, if asked for, a trailing CRC32.
+/// `log_pos` is the position just past this event, as MySQL writes it.
+pub fn frame_event(
+    timestamp: u32,
+    type_code: TypeCode,
+    server_id: u32,
+    log_pos: u32,
+    flags: u16,
+    body: &[u8],
+    checksum: bool,
+) -> Vec<u8> {
+    let size = event_size(body.len(), checksum);
+    let mut out = Vec::with_capacity(size);
+    out.extend_from_slice(&timestamp.to_le_bytes());
+    out.push(type_code.to_byte());
+    out.extend_from_slice(&server_id.to_le_bytes());
+    out.extend_from_slice(&(size as u32).to_le_bytes());
+    out.extend_from_slice(&log_pos.to_le_bytes());
+    out.extend_from_slice(&flags.to_le_bytes());
+    out.extend_from_slice(body);
+    if checksum {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&out);
+        let crc = hasher.finalize();
+        out.extend_from_slice(&crc.to_le_bytes());
+    }
+    out
+}
+
+// the fixed part of a FormatDescriptionEvent before its post-header length table
+const FDE_TABLE_OFFSET: usize = 2 + 50 + 4 + 1;
+
+/// A FormatDescriptionEvent body, without checksum, cut after its checksum algorithm byte and
+/// with that byte set to `algorithm` (0 for none, 1 for CRC32). The byte is found through the
+/// FDE's own entry of the post-header length table.
+pub fn with_checksum_algorithm(body: &[u8], algorithm: u8) -> Vec<u8> {
+    let fde_entry = FDE_TABLE_OFFSET + TypeCode::FormatDescriptionEvent.to_byte() as usize - 1;
+    let mut body = body.to_vec();
+    if let Some(post_header_len) = body.get(fde_entry).copied() {
+        let algorithm_offset = post_header_len as usize;
+        if algorithm_offset < body.len() {
+            body.truncate(algorithm_offset + 1);
+        } else {
+            body.resize(algorithm_offset + 1, 0);
+        }
+        body[algorithm_offset] = algorithm;
+    }
+    body
+}
+
+/// Encode the body of an event. Rows events are always written in the v2 format and need the
+/// table map of the table they refer to in `table_map`.
+pub fn encode_event_data(
+    data: &EventData,
+    table_map: &TableMap,
+) -> Result<(TypeCode, Vec<u8>), EventEncodeError> {
+    let mut out = Vec::new();
+    let type_code = match data {
+        EventData::EventHeader { event_type, .. } => {
+            return Err(EventEncodeError::UnsupportedEvent(*event_type))
+        }
+        EventData::XIDEvent { xid } => {
+            out.write_u64::<LittleEndian>(*xid)?;
+            TypeCode::XidEvent
+        }
+        EventData::RotateEvent { pos, next_log_name } => {
+            out.write_u64::<LittleEndian>(*pos)?;
+            out.extend_from_slice(next_log_name.as_bytes());
+            TypeCode::RotateEvent
+        }
+        EventData::GtidLogEvent {
+            flags,
+            uuid,
+            coordinate,
+            last_committed,
+            sequence_number,
+        } => {
+            out.write_u8(*flags)?;
+            out.extend_from_slice(uuid.as_bytes());
+            out.write_u64::<LittleEndian>(*coordinate)?;
+            if let (Some(last_committed), Some(sequence_number)) = (last_committed, sequence_number)
+            {
+                // logical timestamp type code
+                out.write_u8(0x02)?;
+                out.write_u64::<LittleEndian>(*last_committed)?;
+                out.write_u64::<LittleEndian>(*sequence_number)?;
+            }
+            TypeCode::GtidLogEvent
+        }
+        EventData::QueryEvent {
+            thread_id,
+            exec_time,
+            error_code,
+            schema,
+            query,
+        } => {
+            out.write_u32::<LittleEndian>(*thread_id)?;
+            out.write_u32::<LittleEndian>(*exec_time)?;
+            out.write_u8(schema.len().min(255) as u8)?;
+            out.write_i16::<LittleEndian>(*error_code)?;
+            // no status variables
+            out.write_u16::<LittleEndian>(0)?;
+            out.extend_from_slice(&schema.as_bytes()[..schema.len().min(255)]);
+            out.write_u8(0)?;
+            out.extend_from_slice(query.as_bytes());
+            TypeCode::QueryEvent
+        }
+        EventData::FormatDescriptionEvent {
+            binlog_version,
+            server_version,
+            create_timestamp,
+            common_header_len,
+            checksum_algorithm,
+        } => {
+            out.write_u16::<LittleEndian>(*binlog_version)?;
+            let mut version_buf = [0u8; 50];
+            let version = server_version.as_bytes();
+            let len = version.len().min(version_buf.len() - 1);
+            version_buf[..len].copy_from_slice(&version[..len]);
+            out.extend_from_slice(&version_buf);
+            out.write_u32::<LittleEndian>(*create_timestamp)?;
+            out.write_u8(*common_header_len)?;
+            out.extend_from_slice(&POST_HEADER_LENGTHS);
+            out.write_u8(match checksum_algorithm {
+                ChecksumAlgorithm::None => 0,
+                ChecksumAlgorithm::CRC32 => 1,
+                ChecksumAlgorithm::Other(b) => *b,
+            })?;
+            TypeCode::FormatDescriptionEvent
+        }
+        EventData::TableMapEvent {
+            table_id,
+            schema_name,
+            table_name,
+            columns,
+            null_bitmap,
+            metadata,
+        } => {
+            write_table_id(&mut out, *table_id)?;
+            // flags
+            out.write_u16::<LittleEndian>(1)?;
+            write_one_byte_length_prefixed_name(&mut out, schema_name)?;
+            write_one_byte_length_prefixed_name(&mut out, table_name)?;
+            write_variable_length_integer(&mut out, columns.len() as u64)?;
+            let mut column_metadata = Vec::new();
+            for column in columns {
+                out.write_u8(write_column_metadata(&mut column_metadata, column)?)?;
+            }
+            write_variable_length_integer(&mut out, column_metadata.len() as u64)?;
+            out.extend(column_metadata);
+            out.extend_from_slice(null_bitmap.as_bytes());
+            write_optional_metadata(&mut out, columns, metadata)?;
+            TypeCode::TableMapEvent
+        }
+        EventData::WriteRowsEvent { table_id, rows } => {
+            write_rows_event(
+                &mut out,
+                TypeCode::WriteRowsEventV2,
+                *table_id,
+                rows,
+                table_map,
+            )?;
+            TypeCode::WriteRowsEventV2
+        }
+        EventData::UpdateRowsEvent { table_id, rows } => {
+            write_rows_event(
+                &mut out,
+                TypeCode::UpdateRowsEventV2,
+                *table_id,
+                rows,
+                table_map,
+            )?;
+            TypeCode::UpdateRowsEventV2
+        }
+        EventData::DeleteRowsEvent { table_id, rows } => {
+            write_rows_event(
+                &mut out,
+                TypeCode::DeleteRowsEventV2,
+                *table_id,
+                rows,
+                table_map,
+            )?;
+            TypeCode::DeleteRowsEventV2
+        }
+        EventData::MariadbGtidEvent {
+            seq_no,
+            domain_id,
+            flags,
+            commit_id,
+        } => {
+            out.write_u64::<LittleEndian>(*seq_no)?;
+            out.write_u32::<LittleEndian>(*domain_id)?;
+            match commit_id {
+                Some(commit_id) => {
+                    out.write_u8(*flags | MARIADB_FL_GROUP_COMMIT_ID)?;
+                    out.write_u64::<LittleEndian>(*commit_id)?;
+                }
+                None => {
+                    out.write_u8(*flags & !MARIADB_FL_GROUP_COMMIT_ID)?;
+                    out.extend_from_slice(&[0u8; 6]);
+                }
+            }
+            TypeCode::MariadbGtidEvent
+        }
+        EventData::MariadbGtidListEvent { flags, gtids } => {
+            out.write_u32::<LittleEndian>(
+                (gtids.len() as u32 & 0x0fff_ffff) | (u32::from(*flags) << 28),
+            )?;
+            for gtid in gtids {
+                out.write_u32::<LittleEndian>(gtid.domain_id)?;
+                out.write_u32::<LittleEndian>(gtid.server_id)?;
+                out.write_u64::<LittleEndian>(gtid.sequence)?;
+            }
+            TypeCode::MariadbGtidListEvent
+        }
+        EventData::BinlogCheckpointEvent { log_name } => {
+            out.write_u32::<LittleEndian>(log_name.len() as u32)?;
+            out.extend_from_slice(log_name.as_bytes());
+            TypeCode::BinlogCheckpointEvent
+        }
+        EventData::AnnotateRowsEvent { query } => {
+            out.extend_from_slice(query.as_bytes());
+            TypeCode::AnnotateRowsEvent
+        }
+        EventData::PreviousGtidsLogEvent { gtid_sets } => {
+            out.write_u64::<LittleEndian>(gtid_sets.len() as u64)?;
+            for (uuid, intervals) in gtid_sets {
+                out.extend_from_slice(uuid.as_bytes());
+                out.write_u64::<LittleEndian>(intervals.len() as u64)?;
+                for (start, end) in intervals {
+                    out.write_u64::<LittleEndian>(*start)?;
+                    out.write_u64::<LittleEndian>(*end)?;
+                }
+            }
+            TypeCode::PreviousGtidsLogEvent
+        }
+    };
+    Ok((type_code, out))
+}
+
+fn write_table_id<W: Write>(w: &mut W, table_id: u64) -> Result<(), EventEncodeError> {
+    w.write_all(&table_id.to_le_bytes()[..6])?;
+    Ok(())
+}
+
+fn write_one_byte_length_prefixed_name<W: Write>(
+    w: &mut W,
+    name: &str,
+) -> Result<(), EventEncodeError> {
+    let bytes = &name.as_bytes()[..name.len().min(255)];
+    w.write_u8(bytes.len() as u8)?;
+    w.write_all(bytes)?;
+    // nul byte
+    w.write_u8(0)?;
+    Ok(())
+}
+
+/// The length-encoded integers of the client protocol
+pub(crate) fn write_variable_length_integer<W: Write>(
+    w: &mut W,
+    value: u64,
+) -> std::io::Result<()> {
+    if value < 0xfb {
+        w.write_u8(value as u8)
+    } else if value <= 0xffff {
+        w.write_u8(0xfc)?;
+        w.write_u16::<LittleEndian>(value as u16)
+    } else if value <= 0x00ff_ffff {
+        w.write_u8(0xfd)?;
+        w.write_uint::<LittleEndian>(value, 3)
+    } else {
+        w.write_u8(0xfe)?;
+        w.write_u64::<LittleEndian>(value)
+    }
+}
+
+/// Writes the table map metadata of one column and returns the type byte to announce it with.
+/// ENUM, SET and CHAR columns all travel as strings with their real type in the metadata.
+fn write_column_metadata(out: &mut Vec<u8>, column: &ColumnType) -> Result<u8, EventEncodeError> {
+    match column {
+        ColumnType::Float(pack_length)
+        | ColumnType::Double(pack_length)
+        | ColumnType::Blob(pack_length)
+        | ColumnType::Geometry(pack_length)
+        | ColumnType::Json(pack_length)
+        | ColumnType::DateTime2(pack_length)
+        | ColumnType::Time2(pack_length)
+        | ColumnType::Timestamp2(pack_length) => out.push(*pack_length),
+        ColumnType::VarChar(max_length) => out.write_u16::<LittleEndian>(*max_length)?,
+        ColumnType::Bit(bits, bytes) => out.extend_from_slice(&[*bits, *bytes]),
+        ColumnType::NewDecimal(precision, scale) => out.extend_from_slice(&[*precision, *scale]),
+        ColumnType::Enum(size) | ColumnType::Set(size) | ColumnType::MyString(size)
+            if *size <= 0xff =>
+        {
+            out.extend_from_slice(&[column.to_byte(), *size as u8]);
+            return Ok(ColumnType::MyString(0).to_byte());
+        }
+        ColumnType::Enum(_)
+        | ColumnType::Set(_)
+        | ColumnType::MyString(_)
+        | ColumnType::VarString
+        | ColumnType::TinyBlob
+        | ColumnType::MediumBlob
+        | ColumnType::LongBlob
+        | ColumnType::Decimal
+        | ColumnType::NewDate => {
+            return Err(EventEncodeError::UnsupportedColumnType {
+                column_type: column.clone(),
+            })
+        }
+        _ => {}
+    }
+    Ok(column.to_byte())
+}
+
+fn write_optional_metadata(
+    out: &mut Vec<u8>,
+    columns: &[ColumnType],
+    metadata: &TableMetadata,
+) -> Result<(), EventEncodeError> {
+    let mut write_field = |field_type: u8, field: &[u8]| -> std::io::Result<()> {
+        out.write_u8(field_type)?;
+        write_variable_length_integer(out, field.len() as u64)?;
+        out.write_all(field)
+    };
+    if metadata.unsigned_columns.iter().any(|u| *u) {
+        // one bit per numeric column, most significant bit first
+        let mut field = Vec::new();
+        let numeric = columns
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.is_numeric())
+            .map(|(i, _)| metadata.unsigned_columns.get(i).copied().unwrap_or(false));
+        for (n, unsigned) in numeric.enumerate() {
+            if n & 0x07 == 0 {
+                field.push(0);
+            }
+            if unsigned {
+                field[n >> 3] |= 0x80 >> (n & 0x07);
+            }
+        }
+        write_field(TABLE_MAP_SIGNEDNESS, &field)?;
+    }
+    if !metadata.column_names.is_empty() {
+        let mut field = Vec::new();
+        for name in &metadata.column_names {
+            write_variable_length_integer(&mut field, name.len() as u64)?;
+            field.extend_from_slice(name.as_bytes());
+        }
+        write_field(TABLE_MAP_COLUMN_NAME, &field)?;
+    }
+    for (field_type, values) in &[
+        (TABLE_MAP_SET_STR_VALUE, &metadata.set_values),
+        (TABLE_MAP_ENUM_STR_VALUE, &metadata.enum_values),
+    ] {
+        if values.is_empty() {
+            continue;
+        }
+        // per column, the number of labels and then each label
+        let mut field = Vec::new();
+        for labels in values.iter() {
+            write_variable_length_integer(&mut field, labels.len() as u64)?;
+            for label in labels {
+                write_variable_length_integer(&mut field, label.len() as u64)?;
+                field.extend_from_slice(label.as_bytes());
+            }
+        }
+        write_field(*field_type, &field)?;
+    }
+    if !metadata.primary_key.is_empty() {
+        let mut field = Vec::new();
+        for index in &metadata.primary_key {
+            write_variable_length_integer(&mut field, *index as u64)?;
+        }
+        write_field(TABLE_MAP_SIMPLE_PRIMARY_KEY, &field)?;
+    }
+    Ok(())
+}
+
+/// One bit per column, set for the columns present in the row image
+fn present_bitmap(num_columns: usize, image: Option<&RowData>) -> Vec<u8> {
+    let mut bitmap = vec![0u8; (num_columns + 7) >> 3];
+    for i in 0..num_columns {
+        let present = image.is_none_or(|cols| matches!(cols.get(i), Some(Some(_))));
+        if present {
+            bitmap[i >> 3] |= 1 << (i & 0x07);
+        }
+    }
+    bitmap
+}
+
+fn write_rows_event(
+    out: &mut Vec<u8>,
+    type_code: TypeCode,
+    table_id: u64,
+    rows: &[RowEvent],
+    table_map: &TableMap,
+) -> Result<(), EventEncodeError> {
+    let table = table_map
+        .get(table_id)
+        .ok_or(EventEncodeError::MissingTableMap(table_id))?;
+    let num_columns = table.columns().len();
+    write_table_id(out, table_id)?;
+    out.write_u16::<LittleEndian>(ROWS_FLAG_STMT_END)?;
+    // the extra-data length includes its own two bytes
+    out.write_u16::<LittleEndian>(2)?;
+    write_variable_length_integer(out, num_columns as u64)?;
+    // every row of an event shares the column bitmaps, so take them from the first one
+    let (before, after) = match rows.first() {
+        Some(RowEvent::NewRow { cols }) | Some(RowEvent::DeletedRow { cols }) => (Some(cols), None),
+        Some(RowEvent::UpdatedRow {
+            before_cols,
+            after_cols,
+        }) => (Some(before_cols), Some(after_cols)),
+        None => (None, None),
+    };
+    let before_bitmap = present_bitmap(num_columns, before);
+    out.extend_from_slice(&before_bitmap);
+    let after_bitmap = present_bitmap(num_columns, after);
+    if type_code == TypeCode::UpdateRowsEventV2 {
+        out.extend_from_slice(&after_bitmap);
+    }
+    for row in rows {
+        match (type_code, row) {
+            (TypeCode::WriteRowsEventV2, RowEvent::NewRow { cols })
+            | (TypeCode::DeleteRowsEventV2, RowEvent::DeletedRow { cols }) => {
+                write_one_row(out, table, &before_bitmap, cols)?
+            }
+            (
+                TypeCode::UpdateRowsEventV2,
+                RowEvent::UpdatedRow {
+                    before_cols,
+                    after_cols,
+                },
+            ) => {
+                write_one_row(out, table, &before_bitmap, before_cols)?;
+                write_one_row(out, table, &after_bitmap, after_cols)?;
+            }
+            _ => return Err(EventEncodeError::UnsupportedEvent(type_code)),
+        }
+    }
+    Ok(())
+}
+
+fn write_one_row(
+    out: &mut Vec<u8>,
+    table: &SingleTableMap,
+    present_bitmap: &[u8],
+    cols: &RowData,
+) -> Result<(), EventEncodeError> {
+    let columns = table.columns();
+    if cols.len() != columns.len() {
+        return Err(EventEncodeError::ColumnCountMismatch {
+            expected: columns.len(),
+            got: cols.len(),
+        });
+    }
+    let present = |i: usize| present_bitmap[i >> 3] & (1 << (i & 0x07)) != 0;
+    let values: Vec<Option<&MySQLValue>> = cols
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| present(*i))
+        .map(|(_, v)| v.as_ref())
+        .collect();
+    let mut null_bitmap = vec![0u8; (values.len() + 7) >> 3];
+    for (i, value) in values.iter().enumerate() {
+        if let None | Some(MySQLValue::Null) = value {
+            null_bitmap[i >> 3] |= 1 << (i & 0x07);
+        }
+    }
+    out.extend(null_bitmap);
+    for ((i, column_type), value) in columns
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| present(*i))
+        .zip(values)
+    {
+        match value {
+            None | Some(MySQLValue::Null) => {}
+            // SET values are written as the bitmask of their labels' positions
+            Some(MySQLValue::Set(members)) => {
+                let labels = table.labels(i).unwrap_or(&[]);
+                let mut bits = 0i64;
+                for member in members {
+                    match labels.iter().position(|label| label == member) {
+                        Some(bit) => bits |= 1 << bit,
+                        None => {
+                            return Err(mismatch(column_type, &MySQLValue::Set(members.clone())))
+                        }
+                    }
+                }
+                write_value(out, column_type, &MySQLValue::SignedInteger(bits))?
+            }
+            Some(value) => write_value(out, column_type, value)?,
+        }
+    }
+    Ok(())
+}
+
+fn mismatch(column_type: &ColumnType, value: &MySQLValue) -> EventEncodeError {
+    EventEncodeError::ValueMismatch {
+        column_type: column_type.clone(),
+        value: format!("{:?}", value),
+    }
+}
+
+fn value_bytes(value: &MySQLValue) -> Option<&[u8]> {
+    match value {
+        MySQLValue::String(s) => Some(s.as_bytes()),
+        MySQLValue::Blob(b) => Some(b.as_ref()),
+        _ => None,
+    }
+}
+
+fn write_length_prefixed_bytes(
+    out: &mut Vec<u8>,
+    length_bytes: usize,
+    bytes: &[u8],
+) -> Result<(), EventEncodeError> {
+    if length_bytes < 8 && bytes.len() as u64 >= 1u64 << (length_bytes * 8) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "value too long for its length prefix",
+        )
+        .into());
+    }
+    out.write_uint::<LittleEndian>(bytes.len() as u64, length_bytes)?;
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+/// Microseconds without the digits a column with `pack_length` digits of precision doesn't store
+fn stored_micros(micros: u32, pack_length: u8) -> u32 {
+    match pack_length {
+        0..=6 => {
+            let unit = 10u32.pow(6 - u32::from(pack_length));
+            micros / unit * unit
+        }
+        _ => 0,
+    }
+}
+
+/// Write microseconds as the column's precision stores them, one byte per two digits
+fn write_subsecond_part(out: &mut Vec<u8>, pack_length: u8, micros: u32) -> std::io::Result<()> {
+    let micros = stored_micros(micros, pack_length);
+    match pack_length {
+        1 | 2 => out.write_u8((micros / 10_000) as u8),
+        3 | 4 => out.write_u16::<BigEndian>((micros / 100) as u16),
+        5 | 6 => out.write_uint::<BigEndian>(u64::from(micros), 3),
+        _ => Ok(()),
+    }
+}
+
+/// Encode a non-NULL value of a column, the inverse of [`ColumnType::read_value`]
+pub(crate) fn write_value(
+    out: &mut Vec<u8>,
+    column_type: &ColumnType,
+    value: &MySQLValue,
+) -> Result<(), EventEncodeError> {
+    match (column_type, value) {
+        (ColumnType::Tiny, MySQLValue::SignedInteger(i)) => out.write_i8(*i as i8)?,
+        (ColumnType::Short, MySQLValue::SignedInteger(i)) => {
+            out.write_i16::<LittleEndian>(*i as i16)?
+        }
+        (ColumnType::Int24, MySQLValue::SignedInteger(i)) => {
+            out.write_uint::<LittleEndian>(*i as u64 & 0x00ff_ffff, 3)?
+        }
+        (ColumnType::Long, MySQLValue::SignedInteger(i)) => {
+            out.write_i32::<LittleEndian>(*i as i32)?
+        }
+        (ColumnType::LongLong, MySQLValue::SignedInteger(i)) => {
+            out.write_i64::<LittleEndian>(*i)?
+        }
+        (ColumnType::Float(4), MySQLValue::Float(f)) => out.write_f32::<LittleEndian>(*f)?,
+        (ColumnType::Double(8), MySQLValue::Double(f))
+        | (ColumnType::Float(8), MySQLValue::Double(f)) => out.write_f64::<LittleEndian>(*f)?,
+        (ColumnType::Year, MySQLValue::Year(year)) => {
+            out.write_u8(year.saturating_sub(1900) as u8)?
+        }
+        (ColumnType::Date, MySQLValue::Date { year, month, day }) => {
+            if *year > 0x7fff || *month > 0x0f || *day > 0x1f {
+                return Err(mismatch(column_type, value));
+            }
+            out.write_uint::<LittleEndian>(u64::from((year << 9) | (month << 5) | day), 3)?
+        }
+        (
+            ColumnType::Time,
+            MySQLValue::Time {
+                negative,
+                hours,
+                minutes,
+                seconds,
+                ..
+            },
+        ) => {
+            let packed =
+                i64::from(*hours) * 10000 + i64::from(*minutes) * 100 + i64::from(*seconds);
+            if packed > 0x7f_ffff {
+                return Err(mismatch(column_type, value));
+            }
+            let packed = if *negative { -packed } else { packed };
+            out.write_int::<LittleEndian>(packed, 3)?
+        }
+        (
+            ColumnType::DateTime,
+            MySQLValue::DateTime {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                ..
+            },
+        ) => {
+            let date = u64::from(*year) * 10000 + u64::from(*month) * 100 + u64::from(*day);
+            let time = u64::from(*hour) * 10000 + u64::from(*minute) * 100 + u64::from(*second);
+            out.write_u64::<LittleEndian>(date * 1_000_000 + time)?
+        }
+        (
+            ColumnType::DateTime2(pack_length),
+            MySQLValue::DateTime {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                subsecond,
+            },
+        ) => {
+            // sign bit, 17 bits year*13+month, 5 bits day, 5 bits hour, 6 bits minute and
+            // 6 bits second
+            let year_month = u64::from(*year) * 13 + u64::from(*month);
+            if year_month >= 1 << 17
+                || *day > 0x1f
+                || *hour > 0x1f
+                || *minute > 0x3f
+                || *subsecond >= 1_000_000
+            {
+                return Err(mismatch(column_type, value));
+            }
+            let packed = (1u64 << 39)
+                | (year_month << 22)
+                | (u64::from(*day) << 17)
+                | (u64::from(*hour) << 12)
+                | (u64::from(*minute) << 6)
+                | u64::from(*second & 0x3f);
+            out.write_uint::<BigEndian>(packed, 5)?;
+            write_subsecond_part(out, *pack_length, *subsecond)?;
+        }
+        (ColumnType::Timestamp, MySQLValue::Timestamp { unix_time, .. }) => {
+            out.write_i32::<LittleEndian>(*unix_time)?
+        }
+        (
+            ColumnType::Timestamp2(pack_length),
+            MySQLValue::Timestamp {
+                unix_time,
+                subsecond,
+            },
+        ) => {
+            if *subsecond >= 1_000_000 {
+                return Err(mismatch(column_type, value));
+            }
+            out.write_i32::<BigEndian>(*unix_time)?;
+            write_subsecond_part(out, *pack_length, *subsecond)?;
+        }
+        (
+            ColumnType::Time2(pack_length),
+            MySQLValue::Time {
+                negative,
+                hours,
+                minutes,
+                seconds,
+                subseconds,
+            },
+        ) => {
+            // one unused bit, 10 bits hour, 6 bits minute and 6 bits second, then the
+            // microseconds, as a signed number offset by the sign bit
+            if *hours > 0x3ff || *minutes > 0x3f || *seconds > 0x3f || *subseconds >= 1_000_000 {
+                return Err(mismatch(column_type, value));
+            }
+            let hms = (i64::from(*hours) << 12) | (i64::from(*minutes) << 6) | i64::from(*seconds);
+            // drop the digits the column doesn't store first, so that they can't borrow a
+            // second from a negative value
+            let micros = i64::from(stored_micros(*subseconds, *pack_length));
+            let packed = (hms << 24) + micros;
+            let packed = if *negative { -packed } else { packed };
+            match pack_length {
+                5 | 6 => out.write_uint::<BigEndian>((packed + (0x80_0000 << 24)) as u64, 6)?,
+                _ => {
+                    // the fractional part of a negative value counts down from the next
+                    // whole second
+                    let frac = packed % (1 << 24);
+                    out.write_uint::<BigEndian>(((packed >> 24) + 0x80_0000) as u64, 3)?;
+                    match pack_length {
+                        1 | 2 => out.write_i8((frac / 10_000) as i8)?,
+                        3 | 4 => out.write_i16::<BigEndian>((frac / 100) as i16)?,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        (ColumnType::VarChar(max_length), v) | (ColumnType::MyString(max_length), v)
+            if value_bytes(v).is_some() =>
+        {
+            let length_bytes = if *max_length > 255 { 2 } else { 1 };
+            write_length_prefixed_bytes(out, length_bytes, value_bytes(v).unwrap_or_default())?
+        }
+        (ColumnType::Geometry(length_bytes), MySQLValue::Geometry { srid, wkb }) => {
+            write_length_prefixed_bytes(
+                out,
+                usize::from(*length_bytes),
+                &geometry::internal_bytes(*srid, wkb.as_ref()),
+            )?
+        }
+        (ColumnType::Blob(length_bytes), v) | (ColumnType::Geometry(length_bytes), v)
+            if value_bytes(v).is_some() =>
+        {
+            write_length_prefixed_bytes(
+                out,
+                usize::from(*length_bytes),
+                value_bytes(v).unwrap_or_default(),
+            )?
+        }
+        (ColumnType::Json(length_bytes), MySQLValue::String(s)) => {
+            let body = jsonb::serialize(&serde_json::from_str(s)?);
+            write_length_prefixed_bytes(out, usize::from(*length_bytes), &body)?
+        }
+        (ColumnType::Json(length_bytes), MySQLValue::Json(j)) => {
+            write_length_prefixed_bytes(out, usize::from(*length_bytes), &jsonb::serialize(j))?
+        }
+        (ColumnType::NewDecimal(precision, scale), MySQLValue::Decimal(d)) => {
+            write_new_decimal(out, *precision, *scale, d)
+                .map_err(|_| mismatch(column_type, value))?
+        }
+        (ColumnType::Enum(length_bytes), MySQLValue::Enum { index, .. }) => {
+            match length_bytes & 0xff {
+                1 => out.write_u8(*index as u8)?,
+                2 => out.write_u16::<LittleEndian>(*index)?,
+                _ => return Err(mismatch(column_type, value)),
+            }
+        }
+        (ColumnType::Set(length_bytes), MySQLValue::SignedInteger(bits)) => {
+            let bytes = bits.to_le_bytes();
+            out.extend_from_slice(&bytes[..usize::from(*length_bytes).min(8)])
+        }
+        (ColumnType::Bit(bits, bytes), MySQLValue::SignedInteger(value)) => {
+            let length = (usize::from(*bytes) * 8 + usize::from(*bits))
+                .div_ceil(8)
+                .min(8);
+            out.extend_from_slice(&value.to_be_bytes()[8 - length..])
+        }
+        (ColumnType::Null, _) => {}
+        _ => return Err(mismatch(column_type, value)),
+    }
+    Ok(())
+}
+
+/// MySQL's packed decimal: groups of nine digits in four big-endian bytes, with the leftover
+/// digits at either end packed into as few bytes as they need. The sign is the inverted top bit,
+/// and negative values have every byte inverted.
+fn write_new_decimal(
+    out: &mut Vec<u8>,
+    precision: u8,
+    scale: u8,
+    value: &BigDecimal,
+) -> Result<(), ()> {
+    let compressed_byte_map = [0usize, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+    let (digits, _) = value.with_scale(i64::from(scale)).as_bigint_and_exponent();
+    let digits = digits.to_string();
+    let (is_negative, digits) = match digits.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, digits.as_str()),
+    };
+    let precision = usize::from(precision);
+    if digits.len() > precision || scale as usize > precision {
+        return Err(());
+    }
+    let digits = format!("{:0>width$}", digits, width = precision);
+    let (integral, fraction) = digits.split_at(precision - usize::from(scale));
+
+    let mut buf = Vec::new();
+    let mut write_group = |group: &str| {
+        let n: u32 = group.parse().unwrap_or(0);
+        let len = compressed_byte_map[group.len()];
+        buf.extend_from_slice(&n.to_be_bytes()[4 - len..]);
+    };
+    let leading = integral.len() % 9;
+    if leading > 0 {
+        write_group(&integral[..leading]);
+    }
+    for chunk in integral.as_bytes()[leading..].chunks(9) {
+        write_group(std::str::from_utf8(chunk).unwrap_or("0"));
+    }
+    for chunk in fraction.as_bytes().chunks(9) {
+        write_group(std::str::from_utf8(chunk).unwrap_or("0"));
+    }
+    if buf.is_empty() {
+        return Err(());
+    }
+    if is_negative {
+        for b in buf.iter_mut() {
+            *b = !*b;
+        }
+    }
+    buf[0] ^= 0x80;
+    out.extend(buf);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bigdecimal::BigDecimal;
+
+    use super::write_value;
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::value::MySQLValue;
+
+    fn round_trip(column_type: ColumnType, value: MySQLValue) -> MySQLValue {
+        let mut buf = Vec::new();
+        write_value(&mut buf, &column_type, &value).expect("should encode");
+        let mut cursor = Cursor::new(buf.as_slice());
+        let decoded = column_type.read_value(&mut cursor).expect("should decode");
+        assert_eq!(cursor.position() as usize, buf.len());
+        decoded
+    }
+
+    #[test]
+    fn test_decimal_round_trip() {
+        for (precision, scale, value) in &[
+            (10, 2, "9.05"),
+            (10, 2, "-9.05"),
+            (20, 10, "-1234567890.0000000001"),
+            (5, 5, "0.1"),
+            (18, 0, "-123456789012345678"),
+        ] {
+            let expected = value.parse::<BigDecimal>().unwrap();
+            match round_trip(
+                ColumnType::NewDecimal(*precision, *scale),
+                MySQLValue::Decimal(expected.clone()),
+            ) {
+                MySQLValue::Decimal(d) => assert_eq!(d, expected),
+                v => panic!("unexpected value {:?}", v),
+            }
+        }
+    }
+
+    #[test]
+    fn test_temporal_round_trip() {
+        let dt = round_trip(
+            ColumnType::DateTime2(3),
+            MySQLValue::DateTime {
+                year: 2021,
+                month: 7,
+                day: 1,
+                hour: 13,
+                minute: 4,
+                second: 9,
+                subsecond: 120_000,
+            },
+        );
+        assert_eq!(
+            format!("{:?}", dt),
+            "DateTime { year: 2021, month: 7, day: 1, hour: 13, minute: 4, second: 9, subsecond: 120000 }"
+        );
+        let ts = round_trip(
+            ColumnType::Timestamp2(6),
+            MySQLValue::Timestamp {
+                unix_time: 1625144709,
+                subsecond: 999999,
+            },
+        );
+        assert_eq!(
+            format!("{:?}", ts),
+            "Timestamp { unix_time: 1625144709, subsecond: 999999 }"
+        );
+        // digits beyond the precision are dropped without borrowing a second
+        assert_eq!(
+            round_trip(ColumnType::Time2(2), time(true, 0, 0, 1, 5_000)),
+            time(true, 0, 0, 1, 0)
+        );
+        assert_eq!(
+            round_trip(ColumnType::Time2(1), time(true, 0, 0, 1, 990_000)),
+            time(true, 0, 0, 1, 900_000)
+        );
+        let negative = time(true, 12, 34, 56, 0);
+        assert_eq!(round_trip(ColumnType::Time, negative.clone()), negative);
+        let longest = time(false, 838, 59, 59, 0);
+        assert_eq!(round_trip(ColumnType::Time, longest.clone()), longest);
+        // zero dates and zero parts are values rather than NULL
+        for (year, month, day) in &[(0, 0, 0), (2021, 0, 0), (2021, 7, 0)] {
+            let date = MySQLValue::Date {
+                year: *year,
+                month: *month,
+                day: *day,
+            };
+            assert_eq!(round_trip(ColumnType::Date, date.clone()), date);
+        }
+        let datetime = MySQLValue::DateTime {
+            year: 2021,
+            month: 7,
+            day: 1,
+            hour: 13,
+            minute: 4,
+            second: 9,
+            subsecond: 0,
+        };
+        assert_eq!(round_trip(ColumnType::DateTime, datetime.clone()), datetime);
+    }
+
+    fn time(negative: bool, hours: u32, minutes: u32, seconds: u32, micros: u32) -> MySQLValue {
+        MySQLValue::Time {
+            negative,
+            hours,
+            minutes,
+            seconds,
+            subseconds: micros,
+        }
+    }
+
+    #[test]
+    fn test_geometry_round_trip() {
+        let mut wkb = vec![1, 1, 0, 0, 0];
+        wkb.extend_from_slice(&1.5f64.to_le_bytes());
+        wkb.extend_from_slice(&(-2.0f64).to_le_bytes());
+        let value = MySQLValue::Geometry {
+            srid: 4326,
+            wkb: wkb.into(),
+        };
+        assert_eq!(round_trip(ColumnType::Geometry(4), value.clone()), value);
+    }
+}
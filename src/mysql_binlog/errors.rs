@@ -1,4 +1,6 @@
+use crate::mysql_binlog::bit_set::BitSetError;
 use crate::mysql_binlog::column_types;
+use crate::mysql_binlog::event::TypeCode;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -11,6 +13,16 @@ pub enum EventParseError {
     EofError,
     #[error("bad UUID in Gtid Event: {0:?}")]
     Uuid(#[from] uuid::Error),
+    #[error("invalid compressed event payload")]
+    InvalidCompressedPayload,
+    #[error("not a rows event: {0:?}")]
+    UnexpectedEventType(TypeCode),
+    #[error("no TableMapEvent seen for table id {0}")]
+    UnknownTable(u64),
+    #[error("can only parse a version 4 binary log (got {0})")]
+    UnsupportedBinlogVersion(u16),
+    #[error("invalid bitmap: {0}")]
+    Bitmap(#[from] BitSetError),
 }
 
 #[derive(Debug, Error)]
@@ -23,6 +35,8 @@ pub enum JsonbParseError {
     Io(#[from] ::std::io::Error),
     #[error("invalid JSON")]
     Json(#[from] serde_json::error::Error),
+    #[error("JSONB value nested too deeply")]
+    TooDeep,
     #[error("error parsing opaque column in json record: {inner:?}")]
     OpaqueColumnParseError {
         #[source]
@@ -42,10 +56,14 @@ pub enum ColumnParseError {
     UnimplementedTypeError {
         column_type: column_types::ColumnType,
     },
+    #[error("unknown column type {0}")]
+    UnknownType(u8),
     #[error("error parsing JSON column")]
     Json(#[from] JsonbParseError),
     #[error("error parcing Decimal column")]
     Decimal(#[from] DecimalParseError),
+    #[error("error parsing Geometry column")]
+    Geometry(#[from] GeometryParseError),
     #[error("I/O error reading column")]
     Io(#[from] std::io::Error),
 }
@@ -64,10 +82,85 @@ pub enum BinlogParseError {
     Io(#[from] std::io::Error),
 }
 
+#[derive(Debug, Error)]
+pub enum GeometryParseError {
+    #[error("I/O error reading WKB: {0:?}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid WKB byte order (got {0})")]
+    InvalidByteOrder(u8),
+    #[error("unknown WKB geometry type {0}")]
+    UnknownType(u32),
+    #[error("multi-geometry member of the wrong type")]
+    UnexpectedMember,
+    #[error("geometry collections nested too deeply")]
+    TooDeep,
+    #[error("bytes left after the geometry")]
+    TrailingBytes,
+    #[error("geometry value shorter than its SRID")]
+    MissingSrid,
+}
+
+/// A [`MySQLValue`](crate::mysql_binlog::value::MySQLValue) which doesn't fit the type it was
+/// converted to
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("cannot convert {value} to {target}")]
+pub struct ValueConversionError {
+    /// The variant of the value
+    pub value: &'static str,
+    pub target: &'static str,
+}
+
 #[derive(Debug, Error)]
 pub enum DecimalParseError {
     #[error("I/O error reading decimal")]
     Io(#[from] std::io::Error),
     #[error("Decimal parse error")]
     BigDecimalParse(#[from] bigdecimal::ParseBigDecimalError),
+    #[error("invalid DECIMAL({precision}, {scale})")]
+    InvalidPrecision { precision: u8, scale: u8 },
+}
+
+#[derive(Debug, Error)]
+pub enum EventEncodeError {
+    #[error("I/O error writing event: {0:?}")]
+    Io(#[from] std::io::Error),
+    #[error("event type {0:?} can not be encoded")]
+    UnsupportedEvent(TypeCode),
+    #[error("unable to encode column type: {column_type:?}")]
+    UnsupportedColumnType {
+        column_type: column_types::ColumnType,
+    },
+    #[error("value {value} does not fit column type {column_type:?}")]
+    ValueMismatch {
+        column_type: column_types::ColumnType,
+        value: String,
+    },
+    #[error("no table map for table id {0}")]
+    MissingTableMap(u64),
+    #[error("row has {got} columns but the table map has {expected}")]
+    ColumnCountMismatch { expected: usize, got: usize },
+    #[error("invalid JSON value")]
+    Json(#[from] serde_json::error::Error),
+    #[error("cannot resume {file} at {position}: no event of the local copy ({length} bytes) ends there")]
+    ResumePosition {
+        file: String,
+        position: u64,
+        length: u64,
+    },
+}
+
+/// A row which doesn't deserialize into the type asked for; see
+/// [`de`](crate::mysql_binlog::de)
+#[derive(Debug, Error)]
+pub enum RowDeserializeError {
+    #[error("{0}")]
+    Message(String),
+    #[error("column {column}: {message}")]
+    Column { column: String, message: String },
+    #[error("expected an inserted or deleted row, got an update")]
+    UnexpectedUpdate,
+    #[error("expected an update, got an inserted or deleted row")]
+    NotAnUpdate,
+    #[error("unable to decode row: {0:?}")]
+    Decode(#[from] ColumnParseError),
 }
@@ -1,18 +1,24 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::io::{self, Cursor, ErrorKind, Read, Seek};
 
-use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use flate2::read::ZlibDecoder;
+use serde::Deserialize;
 use serde_derive::Serialize;
 use uuid::Uuid;
 
-use crate::mysql_binlog::bit_set::BitSet;
+use crate::mysql_binlog::bit_set::{self, BitSet};
 use crate::mysql_binlog::column_types::ColumnType;
+use crate::mysql_binlog::de;
 use crate::mysql_binlog::errors::EventParseError::EofError;
-use crate::mysql_binlog::errors::{ColumnParseError, EventParseError};
+use crate::mysql_binlog::errors::{EventParseError, RowDeserializeError};
 use crate::mysql_binlog::packet_helpers::*;
-use crate::mysql_binlog::table_map::{SingleTableMap, TableMap};
+use crate::mysql_binlog::table_map::{SingleTableMap, TableMap, TableMetadata};
 use crate::mysql_binlog::tell::Tell;
 use crate::mysql_binlog::value::MySQLValue;
+use crate::mysql_binlog::MariadbGtid;
+use crate::sql::statement::{classify, Statement};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -53,6 +59,19 @@ pub enum TypeCode {
     GtidLogEvent,
     AnonymousGtidLogEvent,
     PreviousGtidsLogEvent,
+    // MariaDB-only events live in the 160+ range
+    AnnotateRowsEvent,
+    BinlogCheckpointEvent,
+    MariadbGtidEvent,
+    MariadbGtidListEvent,
+    StartEncryptionEvent,
+    QueryCompressedEvent,
+    WriteRowsCompressedEventV1,
+    UpdateRowsCompressedEventV1,
+    DeleteRowsCompressedEventV1,
+    WriteRowsCompressedEvent,
+    UpdateRowsCompressedEvent,
+    DeleteRowsCompressedEvent,
     OtherUnknown(u8),
 }
 
@@ -95,9 +114,101 @@ impl TypeCode {
             33 => TypeCode::GtidLogEvent,
             34 => TypeCode::AnonymousGtidLogEvent,
             35 => TypeCode::PreviousGtidsLogEvent,
+            160 => TypeCode::AnnotateRowsEvent,
+            161 => TypeCode::BinlogCheckpointEvent,
+            162 => TypeCode::MariadbGtidEvent,
+            163 => TypeCode::MariadbGtidListEvent,
+            164 => TypeCode::StartEncryptionEvent,
+            165 => TypeCode::QueryCompressedEvent,
+            166 => TypeCode::WriteRowsCompressedEventV1,
+            167 => TypeCode::UpdateRowsCompressedEventV1,
+            168 => TypeCode::DeleteRowsCompressedEventV1,
+            169 => TypeCode::WriteRowsCompressedEvent,
+            170 => TypeCode::UpdateRowsCompressedEvent,
+            171 => TypeCode::DeleteRowsCompressedEvent,
             i => TypeCode::OtherUnknown(i),
         }
     }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            TypeCode::Unknown => 0,
+            TypeCode::StartEventV3 => 1,
+            TypeCode::QueryEvent => 2,
+            TypeCode::StopEvent => 3,
+            TypeCode::RotateEvent => 4,
+            TypeCode::IntvarEvent => 5,
+            TypeCode::LoadEvent => 6,
+            TypeCode::SlaveEvent => 7,
+            TypeCode::CreateFileEvent => 8,
+            TypeCode::AppendBlockEvent => 9,
+            TypeCode::ExecLoadEvent => 10,
+            TypeCode::DeleteFileEvent => 11,
+            TypeCode::NewLoadEvent => 12,
+            TypeCode::RandEvent => 13,
+            TypeCode::UserVarEvent => 14,
+            TypeCode::FormatDescriptionEvent => 15,
+            TypeCode::XidEvent => 16,
+            TypeCode::BeginLoadQueryEvent => 17,
+            TypeCode::ExecuteLoadQueryEvent => 18,
+            TypeCode::TableMapEvent => 19,
+            TypeCode::PreGaWriteRowsEvent => 20,
+            TypeCode::PreGaUpdateRowsEvent => 21,
+            TypeCode::PreGaDeleteRowsEvent => 22,
+            TypeCode::WriteRowsEventV1 => 23,
+            TypeCode::UpdateRowsEventV1 => 24,
+            TypeCode::DeleteRowsEventV1 => 25,
+            TypeCode::IncidentEvent => 26,
+            TypeCode::HeartbeatLogEvent => 27,
+            TypeCode::IgnorableLogEvent => 28,
+            TypeCode::RowsQueryLogEvent => 29,
+            TypeCode::WriteRowsEventV2 => 30,
+            TypeCode::UpdateRowsEventV2 => 31,
+            TypeCode::DeleteRowsEventV2 => 32,
+            TypeCode::GtidLogEvent => 33,
+            TypeCode::AnonymousGtidLogEvent => 34,
+            TypeCode::PreviousGtidsLogEvent => 35,
+            TypeCode::AnnotateRowsEvent => 160,
+            TypeCode::BinlogCheckpointEvent => 161,
+            TypeCode::MariadbGtidEvent => 162,
+            TypeCode::MariadbGtidListEvent => 163,
+            TypeCode::StartEncryptionEvent => 164,
+            TypeCode::QueryCompressedEvent => 165,
+            TypeCode::WriteRowsCompressedEventV1 => 166,
+            TypeCode::UpdateRowsCompressedEventV1 => 167,
+            TypeCode::DeleteRowsCompressedEventV1 => 168,
+            TypeCode::WriteRowsCompressedEvent => 169,
+            TypeCode::UpdateRowsCompressedEvent => 170,
+            TypeCode::DeleteRowsCompressedEvent => 171,
+            TypeCode::OtherUnknown(i) => i,
+        }
+    }
+
+    /// Whether this is a write, update or delete rows event, compressed or not
+    pub fn is_rows_event(self) -> bool {
+        matches!(
+            self.uncompressed(),
+            TypeCode::WriteRowsEventV1
+                | TypeCode::UpdateRowsEventV1
+                | TypeCode::DeleteRowsEventV1
+                | TypeCode::WriteRowsEventV2
+                | TypeCode::UpdateRowsEventV2
+                | TypeCode::DeleteRowsEventV2
+        )
+    }
+
+    /// Maps a MariaDB compressed rows event onto the uncompressed event it wraps
+    fn uncompressed(self) -> Self {
+        match self {
+            TypeCode::WriteRowsCompressedEventV1 => TypeCode::WriteRowsEventV1,
+            TypeCode::UpdateRowsCompressedEventV1 => TypeCode::UpdateRowsEventV1,
+            TypeCode::DeleteRowsCompressedEventV1 => TypeCode::DeleteRowsEventV1,
+            TypeCode::WriteRowsCompressedEvent => TypeCode::WriteRowsEventV2,
+            TypeCode::UpdateRowsCompressedEvent => TypeCode::UpdateRowsEventV2,
+            TypeCode::DeleteRowsCompressedEvent => TypeCode::DeleteRowsEventV2,
+            t => t,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -163,6 +274,7 @@ pub enum EventData {
         table_name: String,
         columns: Vec<ColumnType>,
         null_bitmap: BitSet,
+        metadata: TableMetadata,
     },
     WriteRowsEvent {
         table_id: u64,
@@ -176,6 +288,27 @@ pub enum EventData {
         table_id: u64,
         rows: Vec<RowEvent>,
     },
+    MariadbGtidEvent {
+        seq_no: u64,
+        domain_id: u32,
+        flags: u8,
+        commit_id: Option<u64>,
+    },
+    MariadbGtidListEvent {
+        flags: u8,
+        gtids: Vec<MariadbGtid>,
+    },
+    BinlogCheckpointEvent {
+        log_name: String,
+    },
+    AnnotateRowsEvent {
+        query: String,
+    },
+    /// The GTID sets already logged before this binlog file. Intervals are half-open
+    /// (`start..end`) as on the wire.
+    PreviousGtidsLogEvent {
+        gtid_sets: Vec<(Uuid, Vec<(u64, u64)>)>,
+    },
 }
 
 struct RowsEvent {
@@ -186,19 +319,20 @@ struct RowsEvent {
 fn parse_one_row<R: Read + Seek>(
     mut cursor: &mut R,
     this_table_map: &SingleTableMap,
+    num_columns: usize,
     present_bitmask: &BitSet,
-) -> Result<RowData, ColumnParseError> {
+) -> Result<RowData, EventParseError> {
     let num_set_columns = present_bitmask.bits_set();
-    let null_bitmask_size = (num_set_columns + 7) >> 3;
+    let null_bitmask_size = bit_set::byte_len(num_set_columns);
     let mut row = Vec::with_capacity(this_table_map.columns.len());
     let null_bitmask = BitSet::from_slice(
         num_set_columns,
         &read_nbytes(&mut cursor, null_bitmask_size)?,
-    )
-    .unwrap();
+    )?;
     let mut null_index = 0;
     for (i, column_definition) in this_table_map.columns.iter().enumerate() {
-        if !present_bitmask.is_set(i) {
+        // columns the rows event doesn't know about are missing from the image
+        if i >= num_columns || !present_bitmask.is_set(i) {
             row.push(None);
             continue;
         }
@@ -207,7 +341,11 @@ fn parse_one_row<R: Read + Seek>(
             MySQLValue::Null
         } else {
             //println!("parsing column {} ({:?})", i, column_definition);
-            column_definition.read_value(&mut cursor)?
+            let value = column_definition.read_value(&mut cursor)?;
+            match this_table_map.labels(i) {
+                Some(labels) => column_definition.with_labels(value, labels),
+                None => value,
+            }
         };
         row.push(Some(val));
         null_index += 1;
@@ -216,7 +354,7 @@ fn parse_one_row<R: Read + Seek>(
     Ok(row)
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(untagged)]
 pub enum RowEvent {
     NewRow {
@@ -239,6 +377,35 @@ impl RowEvent {
             RowEvent::UpdatedRow { .. } => None,
         }
     }
+
+    /// Deserialize an inserted or deleted row into `T`, `table` being the table map the row was
+    /// decoded with. See [`de`](crate::mysql_binlog::de).
+    pub fn deserialize<'de, T: Deserialize<'de>>(
+        &'de self,
+        table: Option<&SingleTableMap>,
+    ) -> Result<T, RowDeserializeError> {
+        match self.cols() {
+            Some(cols) => de::from_row(cols, table),
+            None => Err(RowDeserializeError::UnexpectedUpdate),
+        }
+    }
+
+    /// Deserialize the images before and after an update into `T`
+    pub fn deserialize_update<'de, T: Deserialize<'de>>(
+        &'de self,
+        table: Option<&SingleTableMap>,
+    ) -> Result<(T, T), RowDeserializeError> {
+        match self {
+            RowEvent::UpdatedRow {
+                before_cols,
+                after_cols,
+            } => Ok((
+                de::from_row(before_cols, table)?,
+                de::from_row(after_cols, table)?,
+            )),
+            _ => Err(RowDeserializeError::NotAnUpdate),
+        }
+    }
 }
 
 fn parse_rows_event<R: Read + Seek>(
@@ -246,7 +413,7 @@ fn parse_rows_event<R: Read + Seek>(
     data_len: usize,
     mut cursor: &mut R,
     table_map: Option<&TableMap>,
-) -> Result<RowsEvent, ColumnParseError> {
+) -> Result<RowsEvent, EventParseError> {
     let mut table_id_buf = [0u8; 8];
     cursor.read_exact(&mut table_id_buf[0..6])?;
     let table_id = LittleEndian::read_u64(&table_id_buf);
@@ -254,71 +421,229 @@ fn parse_rows_event<R: Read + Seek>(
     cursor.seek(io::SeekFrom::Current(2))?;
     match type_code {
         TypeCode::WriteRowsEventV2 | TypeCode::UpdateRowsEventV2 | TypeCode::DeleteRowsEventV2 => {
-            let _ = cursor.read_i16::<LittleEndian>()?;
+            // the extra-data length includes its own two bytes
+            let extra_data_len = cursor.read_u16::<LittleEndian>()?;
+            if extra_data_len > 2 {
+                cursor.seek(io::SeekFrom::Current(i64::from(extra_data_len - 2)))?;
+            }
         }
         _ => {}
     }
     let num_columns = read_variable_length_integer(&mut cursor)? as usize;
-    let bitmask_size = (num_columns + 7) >> 3;
+    let bitmask_size = bit_set::byte_len(num_columns);
     let before_column_bitmask =
-        BitSet::from_slice(num_columns, &read_nbytes(&mut cursor, bitmask_size)?).unwrap();
+        BitSet::from_slice(num_columns, &read_nbytes(&mut cursor, bitmask_size)?)?;
     let after_column_bitmask = match type_code {
-        TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsEventV2 => {
-            Some(BitSet::from_slice(num_columns, &read_nbytes(&mut cursor, bitmask_size)?).unwrap())
-        }
+        TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsEventV2 => Some(BitSet::from_slice(
+            num_columns,
+            &read_nbytes(&mut cursor, bitmask_size)?,
+        )?),
         _ => None,
     };
     let mut rows = Vec::with_capacity(1);
     if let Some(table_map) = table_map {
         if let Some(this_table_map) = table_map.get(table_id) {
+            let parse_row = |cursor: &mut R, bitmask: &BitSet| {
+                parse_one_row(cursor, this_table_map, num_columns, bitmask)
+            };
             loop {
                 let pos = cursor.tell()? as usize;
-                if data_len - pos < 1 {
+                if pos >= data_len {
                     break;
                 }
-                match type_code {
-                    TypeCode::WriteRowsEventV1 | TypeCode::WriteRowsEventV2 => {
-                        rows.push(RowEvent::NewRow {
-                            cols: parse_one_row(
-                                &mut cursor,
-                                this_table_map,
-                                &before_column_bitmask,
-                            )?,
-                        });
+                rows.push(match (type_code, &after_column_bitmask) {
+                    (TypeCode::WriteRowsEventV1, _) | (TypeCode::WriteRowsEventV2, _) => {
+                        RowEvent::NewRow {
+                            cols: parse_row(cursor, &before_column_bitmask)?,
+                        }
                     }
-                    TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsEventV2 => {
-                        rows.push(RowEvent::UpdatedRow {
-                            before_cols: parse_one_row(
-                                &mut cursor,
-                                this_table_map,
-                                &before_column_bitmask,
-                            )?,
-                            after_cols: parse_one_row(
-                                &mut cursor,
-                                this_table_map,
-                                after_column_bitmask.as_ref().unwrap(),
-                            )?,
-                        })
+                    (TypeCode::UpdateRowsEventV1, Some(after_column_bitmask))
+                    | (TypeCode::UpdateRowsEventV2, Some(after_column_bitmask)) => {
+                        RowEvent::UpdatedRow {
+                            before_cols: parse_row(cursor, &before_column_bitmask)?,
+                            after_cols: parse_row(cursor, after_column_bitmask)?,
+                        }
                     }
-                    TypeCode::DeleteRowsEventV1 | TypeCode::DeleteRowsEventV2 => {
-                        rows.push(RowEvent::DeletedRow {
-                            cols: parse_one_row(
-                                &mut cursor,
-                                this_table_map,
-                                &before_column_bitmask,
-                            )?,
-                        });
+                    (TypeCode::DeleteRowsEventV1, _) | (TypeCode::DeleteRowsEventV2, _) => {
+                        RowEvent::DeletedRow {
+                            cols: parse_row(cursor, &before_column_bitmask)?,
+                        }
                     }
-                    _ => unimplemented!(),
+                    _ => return Err(EventParseError::UnexpectedEventType(type_code)),
+                });
+                // a row which takes no bytes would repeat forever
+                if cursor.tell()? as usize == pos {
+                    return Err(EofError);
                 }
             }
         }
     }
     Ok(RowsEvent { table_id, rows })
 }
+/// Decompresses a MariaDB rows event and parses the rows it carries. Uncompressed events are
+/// passed straight through.
+fn parse_maybe_compressed_rows_event(
+    type_code: TypeCode,
+    data: &[u8],
+    table_map: Option<&TableMap>,
+) -> Result<RowsEvent, EventParseError> {
+    let (inner_type, body) = uncompressed_rows_body(type_code, data)?;
+    let mut cursor = Cursor::new(body.as_ref());
+    parse_rows_event(inner_type, body.len(), &mut cursor, table_map)
+}
+
+/// The type and body of the uncompressed rows event a MariaDB compressed one wraps, or `data`
+/// itself when it isn't compressed. Everything up to the row images (the post-header, the v2
+/// extra data, the column count and the column bitmaps) is stored as is; only the rows that
+/// follow are compressed, so they are inflated and spliced back behind it.
+pub(crate) fn uncompressed_rows_body(
+    type_code: TypeCode,
+    data: &[u8],
+) -> Result<(TypeCode, Cow<'_, [u8]>), EventParseError> {
+    let inner_type = type_code.uncompressed();
+    if inner_type == type_code {
+        return Ok((type_code, Cow::Borrowed(data)));
+    }
+    let mut cursor = Cursor::new(data);
+    read_nbytes(&mut cursor, ROWS_POST_HEADER_LEN_V1)?;
+    if let TypeCode::WriteRowsEventV2 | TypeCode::UpdateRowsEventV2 | TypeCode::DeleteRowsEventV2 =
+        inner_type
+    {
+        let extra_data_len = cursor.read_u16::<LittleEndian>()?;
+        if extra_data_len > 2 {
+            read_nbytes(&mut cursor, extra_data_len - 2)?;
+        }
+    }
+    let num_columns = read_variable_length_integer(&mut cursor)? as usize;
+    let bitmaps = match inner_type {
+        TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsEventV2 => 2,
+        _ => 1,
+    };
+    read_nbytes(&mut cursor, bitmaps * bit_set::byte_len(num_columns))?;
+    let mut body = data[..cursor.position() as usize].to_vec();
+    body.extend(read_compressed_bytes(&mut cursor)?);
+    Ok((inner_type, Cow::Owned(body)))
+}
+
+/// MariaDB compresses event payloads with zlib behind a one byte header: the high bit is always
+/// set and the low three bits give the width of the big-endian uncompressed length that follows.
+fn read_compressed_bytes<R: Read>(r: &mut R) -> Result<Vec<u8>, EventParseError> {
+    let header = r.read_u8()?;
+    let length_bytes = (header & 0x07) as usize;
+    if header & 0x80 == 0 || length_bytes == 0 || length_bytes > 4 {
+        return Err(EventParseError::InvalidCompressedPayload);
+    }
+    let uncompressed_len = r.read_uint::<BigEndian>(length_bytes)? as usize;
+    let mut uncompressed = Vec::new();
+    // inflating more than the header promises is as wrong as inflating less
+    ZlibDecoder::new(r)
+        .take(uncompressed_len as u64 + 1)
+        .read_to_end(&mut uncompressed)?;
+    if uncompressed.len() != uncompressed_len {
+        return Err(EventParseError::InvalidCompressedPayload);
+    }
+    Ok(uncompressed)
+}
+
+/// Reads the TLV-encoded optional metadata which MySQL 8.0 appends to a TableMapEvent after the
+/// null bitmap. Fields we don't use are skipped.
+fn read_optional_metadata(
+    cursor: &mut Cursor<&[u8]>,
+    columns: &[ColumnType],
+) -> Result<TableMetadata, EventParseError> {
+    let mut metadata = TableMetadata::default();
+    let data_len = cursor.get_ref().len() as u64;
+    while cursor.position() < data_len {
+        let field_type = cursor.read_u8()?;
+        let field_len = read_variable_length_integer(cursor)? as usize;
+        let field = read_nbytes(cursor, field_len)?;
+        let mut field_cursor = Cursor::new(field.as_slice());
+        match field_type {
+            TABLE_MAP_SIGNEDNESS => {
+                // one bit per numeric column, most significant bit first
+                let mut numeric_index = 0;
+                metadata.unsigned_columns = columns
+                    .iter()
+                    .map(|c| {
+                        if !c.is_numeric() {
+                            return false;
+                        }
+                        let byte = field.get(numeric_index >> 3).copied().unwrap_or(0);
+                        let unsigned = byte & (0x80 >> (numeric_index & 0x07)) != 0;
+                        numeric_index += 1;
+                        unsigned
+                    })
+                    .collect();
+            }
+            TABLE_MAP_COLUMN_NAME => {
+                while (field_cursor.position() as usize) < field.len() {
+                    let name_len = read_variable_length_integer(&mut field_cursor)? as usize;
+                    let name = read_nbytes(&mut field_cursor, name_len)?;
+                    metadata
+                        .column_names
+                        .push(String::from_utf8_lossy(&name).into_owned());
+                }
+            }
+            TABLE_MAP_SET_STR_VALUE | TABLE_MAP_ENUM_STR_VALUE => {
+                // per column, the number of labels and then each label
+                let mut values = Vec::new();
+                while (field_cursor.position() as usize) < field.len() {
+                    let count = read_variable_length_integer(&mut field_cursor)?;
+                    let mut labels = Vec::new();
+                    for _ in 0..count {
+                        let label_len = read_variable_length_integer(&mut field_cursor)? as usize;
+                        let label = read_nbytes(&mut field_cursor, label_len)?;
+                        labels.push(String::from_utf8_lossy(&label).into_owned());
+                    }
+                    values.push(labels);
+                }
+                if field_type == TABLE_MAP_SET_STR_VALUE {
+                    metadata.set_values = values;
+                } else {
+                    metadata.enum_values = values;
+                }
+            }
+            TABLE_MAP_SIMPLE_PRIMARY_KEY | TABLE_MAP_PRIMARY_KEY_WITH_PREFIX => {
+                while (field_cursor.position() as usize) < field.len() {
+                    let index = read_variable_length_integer(&mut field_cursor)? as usize;
+                    if field_type == TABLE_MAP_PRIMARY_KEY_WITH_PREFIX {
+                        let _prefix_len = read_variable_length_integer(&mut field_cursor)?;
+                    }
+                    metadata.primary_key.push(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(metadata)
+}
+
+const TABLE_MAP_SIGNEDNESS: u8 = 1;
+const TABLE_MAP_COLUMN_NAME: u8 = 4;
+const TABLE_MAP_SET_STR_VALUE: u8 = 5;
+const TABLE_MAP_ENUM_STR_VALUE: u8 = 6;
+const TABLE_MAP_SIMPLE_PRIMARY_KEY: u8 = 8;
+const TABLE_MAP_PRIMARY_KEY_WITH_PREFIX: u8 = 9;
+const ROWS_POST_HEADER_LEN_V1: usize = 8;
+const MARIADB_FL_GROUP_COMMIT_ID: u8 = 0x02;
+/// FL_STANDALONE in the flags of a MariaDB GTID event: the group is a single statement and
+/// no BEGIN follows
+pub const FL_STANDALONE: u8 = 0x01;
 pub const EVENT_HEADER_SIZE: usize = 19;
 pub const BINLOG_CHECKSUM_LENGTH: usize = 4;
 impl EventData {
+    /// What kind of statement a query event carries, with unqualified table names in the
+    /// event's default schema. `None` for other events.
+    pub fn statement(&self) -> Option<Statement> {
+        match self {
+            EventData::QueryEvent { schema, query, .. } => Some(classify(
+                query,
+                Some(schema.as_str()).filter(|s| !s.is_empty()),
+            )),
+            _ => None,
+        }
+    }
+
     pub fn parse_header(data: &[u8]) -> Result<Option<EventData>, EventParseError> {
         if data.len() < EVENT_HEADER_SIZE {
             return Err(EofError);
@@ -344,7 +669,7 @@ impl EventData {
                 xid: cursor.read_u64::<LittleEndian>()?,
             })),
             TypeCode::RotateEvent => {
-                let log_name = match String::from_utf8(Vec::from(&data[8..])) {
+                let log_name = match String::from_utf8(Vec::from(data.get(8..).ok_or(EofError)?)) {
                     Ok(d) => d,
                     Err(_e) => return Err(EofError),
                 };
@@ -356,26 +681,35 @@ impl EventData {
             TypeCode::FormatDescriptionEvent => {
                 let binlog_version = cursor.read_u16::<LittleEndian>()?;
                 if binlog_version != 4 {
-                    unimplemented!("can only parse a version 4 binary log");
+                    return Err(EventParseError::UnsupportedBinlogVersion(binlog_version));
                 }
                 let mut server_version_buf = [0u8; 50];
                 cursor.read_exact(&mut server_version_buf)?;
-                let server_version = ::std::str::from_utf8(
+                let server_version = String::from_utf8_lossy(
                     server_version_buf
                         .split(|c| *c == 0x00)
                         .next()
                         .unwrap_or(&[]),
                 )
-                .unwrap()
-                .to_owned();
+                .into_owned();
                 let create_timestamp = cursor.read_u32::<LittleEndian>()?;
                 let common_header_len = cursor.read_u8()?;
-                let event_types = data.len() - 2 - 50 - 4 - 1 - 5;
+                // the post-header table has an entry for the FDE itself, which tells us how long
+                // the table is whether or not the caller stripped the trailing checksum
+                let table_start = cursor.position() as usize;
+                let fde_post_header_len = data
+                    .get(table_start + TypeCode::FormatDescriptionEvent.to_byte() as usize - 1)
+                    .copied()
+                    .ok_or(EofError)?;
+                let event_types = (fde_post_header_len as usize)
+                    .saturating_sub(table_start)
+                    .min(data.len() - table_start);
                 let mut event_sizes_tables = vec![0u8; event_types];
                 cursor.read_exact(&mut event_sizes_tables)?;
-                let checksum_algo = ChecksumAlgorithm::from(cursor.read_u8()?);
-                let mut checksum_buf = [0u8; 4];
-                cursor.read_exact(&mut checksum_buf)?;
+                let checksum_algo = match cursor.read_u8() {
+                    Ok(b) => ChecksumAlgorithm::from(b),
+                    Err(_) => ChecksumAlgorithm::None,
+                };
                 Ok(Some(EventData::FormatDescriptionEvent {
                     binlog_version,
                     server_version,
@@ -406,7 +740,7 @@ impl EventData {
                     sequence_number,
                 }))
             }
-            TypeCode::QueryEvent => {
+            TypeCode::QueryEvent | TypeCode::QueryCompressedEvent => {
                 let thread_id = cursor.read_u32::<LittleEndian>()?;
                 let execution_time = cursor.read_u32::<LittleEndian>()?;
                 let schema_len = cursor.read_u8()?;
@@ -416,7 +750,12 @@ impl EventData {
                     String::from_utf8_lossy(&read_nbytes(&mut cursor, schema_len)?).into_owned();
                 cursor.seek(io::SeekFrom::Current(1))?;
                 let mut statement = String::new();
-                cursor.read_to_string(&mut statement)?;
+                if type_code == TypeCode::QueryCompressedEvent {
+                    statement =
+                        String::from_utf8_lossy(&read_compressed_bytes(&mut cursor)?).into_owned();
+                } else {
+                    cursor.read_to_string(&mut statement)?;
+                }
                 Ok(Some(EventData::QueryEvent {
                     thread_id,
                     exec_time: execution_time,
@@ -439,9 +778,10 @@ impl EventData {
                 cursor.seek(io::SeekFrom::Current(1))?;
                 //println!("parsing table map for {}.{}", schema_name, table_name);
                 let column_count = read_variable_length_integer(&mut cursor)? as usize;
-                let mut columns = Vec::with_capacity(column_count);
+                // one byte per column type, so no more than that many are there
+                let mut columns = Vec::with_capacity(column_count.min(data.len()));
                 for _ in 0..column_count {
-                    let column_type = ColumnType::from_byte(cursor.read_u8()?);
+                    let column_type = ColumnType::from_byte(cursor.read_u8()?)?;
                     columns.push(column_type);
                 }
                 //let pos = cursor.tell()? as usize;
@@ -455,38 +795,112 @@ impl EventData {
                 //println!("finished decoding metadata; columns: {:?}", final_columns);
                 //let end_of_map_pos = cursor.seek(io::SeekFrom::Current(0))? as usize;
                 let num_columns = final_columns.len();
-                let null_bitmask_size = (num_columns + 7) >> 3;
+                let null_bitmask_size = bit_set::byte_len(num_columns);
                 let null_bitmap_source = read_nbytes(&mut cursor, null_bitmask_size)?;
-                let nullable_bitmap = BitSet::from_slice(num_columns, &null_bitmap_source).unwrap();
+                let nullable_bitmap = BitSet::from_slice(num_columns, &null_bitmap_source)?;
+                let metadata = read_optional_metadata(&mut cursor, &final_columns)?;
                 Ok(Some(EventData::TableMapEvent {
                     table_id,
                     schema_name,
                     table_name,
                     columns: final_columns,
                     null_bitmap: nullable_bitmap,
+                    metadata,
                 }))
             }
-            TypeCode::WriteRowsEventV1 | TypeCode::WriteRowsEventV2 => {
-                let ev = parse_rows_event(type_code, data.len(), &mut cursor, table_map)?;
+            TypeCode::WriteRowsEventV1
+            | TypeCode::WriteRowsEventV2
+            | TypeCode::WriteRowsCompressedEventV1
+            | TypeCode::WriteRowsCompressedEvent => {
+                let ev = parse_maybe_compressed_rows_event(type_code, data, table_map)?;
                 Ok(Some(EventData::WriteRowsEvent {
                     table_id: ev.table_id,
                     rows: ev.rows,
                 }))
             }
-            TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsEventV2 => {
-                let ev = parse_rows_event(type_code, data.len(), &mut cursor, table_map)?;
+            TypeCode::UpdateRowsEventV1
+            | TypeCode::UpdateRowsEventV2
+            | TypeCode::UpdateRowsCompressedEventV1
+            | TypeCode::UpdateRowsCompressedEvent => {
+                let ev = parse_maybe_compressed_rows_event(type_code, data, table_map)?;
                 Ok(Some(EventData::UpdateRowsEvent {
                     table_id: ev.table_id,
                     rows: ev.rows,
                 }))
             }
-            TypeCode::DeleteRowsEventV1 | TypeCode::DeleteRowsEventV2 => {
-                let ev = parse_rows_event(type_code, data.len(), &mut cursor, table_map)?;
+            TypeCode::DeleteRowsEventV1
+            | TypeCode::DeleteRowsEventV2
+            | TypeCode::DeleteRowsCompressedEventV1
+            | TypeCode::DeleteRowsCompressedEvent => {
+                let ev = parse_maybe_compressed_rows_event(type_code, data, table_map)?;
                 Ok(Some(EventData::DeleteRowsEvent {
                     table_id: ev.table_id,
                     rows: ev.rows,
                 }))
             }
+            TypeCode::MariadbGtidEvent => {
+                let seq_no = cursor.read_u64::<LittleEndian>()?;
+                let domain_id = cursor.read_u32::<LittleEndian>()?;
+                let flags = cursor.read_u8()?;
+                let commit_id = if flags & MARIADB_FL_GROUP_COMMIT_ID != 0 {
+                    Some(cursor.read_u64::<LittleEndian>()?)
+                } else {
+                    None
+                };
+                Ok(Some(EventData::MariadbGtidEvent {
+                    seq_no,
+                    domain_id,
+                    flags,
+                    commit_id,
+                }))
+            }
+            TypeCode::MariadbGtidListEvent => {
+                // low 28 bits are the count, the top 4 bits are flags
+                let count_and_flags = cursor.read_u32::<LittleEndian>()?;
+                let count = count_and_flags & 0x0fff_ffff;
+                let mut gtids = Vec::new();
+                for _ in 0..count {
+                    let domain_id = cursor.read_u32::<LittleEndian>()?;
+                    let server_id = cursor.read_u32::<LittleEndian>()?;
+                    let sequence = cursor.read_u64::<LittleEndian>()?;
+                    gtids.push(MariadbGtid {
+                        domain_id,
+                        server_id,
+                        sequence,
+                    });
+                }
+                Ok(Some(EventData::MariadbGtidListEvent {
+                    flags: (count_and_flags >> 28) as u8,
+                    gtids,
+                }))
+            }
+            TypeCode::BinlogCheckpointEvent => {
+                let name_len = cursor.read_u32::<LittleEndian>()? as usize;
+                let log_name =
+                    String::from_utf8_lossy(&read_nbytes(&mut cursor, name_len)?).into_owned();
+                Ok(Some(EventData::BinlogCheckpointEvent { log_name }))
+            }
+            TypeCode::AnnotateRowsEvent => Ok(Some(EventData::AnnotateRowsEvent {
+                query: String::from_utf8_lossy(data).into_owned(),
+            })),
+            TypeCode::PreviousGtidsLogEvent => {
+                let sid_count = cursor.read_u64::<LittleEndian>()?;
+                let mut gtid_sets = Vec::new();
+                for _ in 0..sid_count {
+                    let mut uuid_buf = [0u8; 16];
+                    cursor.read_exact(&mut uuid_buf)?;
+                    let uuid = Uuid::from_slice(&uuid_buf)?;
+                    let interval_count = cursor.read_u64::<LittleEndian>()?;
+                    let mut intervals = Vec::new();
+                    for _ in 0..interval_count {
+                        let start = cursor.read_u64::<LittleEndian>()?;
+                        let end = cursor.read_u64::<LittleEndian>()?;
+                        intervals.push((start, end));
+                    }
+                    gtid_sets.push((uuid, intervals));
+                }
+                Ok(Some(EventData::PreviousGtidsLogEvent { gtid_sets }))
+            }
             _ => Ok(None),
         }
     }
@@ -500,6 +914,7 @@ pub struct Event {
     next_position: u32,
     flags: u16,
     data: Vec<u8>,
+    checksum: Option<u32>,
     offset: u64,
 }
 
@@ -529,14 +944,22 @@ impl Event {
         let event_length = c.read_u32::<LittleEndian>()?;
         let next_position = c.read_u32::<LittleEndian>()?;
         let flags = c.read_u16::<LittleEndian>()?;
-        let mut data_length: usize = (event_length - 19) as usize;
+        let mut data_length = (event_length as usize)
+            .checked_sub(EVENT_HEADER_SIZE)
+            .ok_or(EventParseError::EofError)?;
         if HAS_CHECKSUM {
-            data_length -= 4;
+            data_length = data_length
+                .checked_sub(BINLOG_CHECKSUM_LENGTH)
+                .ok_or(EventParseError::EofError)?;
         }
         //println!("finished reading event header with type_code {:?} event_length {} and next_position {}", type_code, event_length, next_position);
-        let mut data = vec![0u8; data_length];
-        reader.read_exact(&mut data)?;
+        let data = read_nbytes(reader, data_length)?;
         //println!("finished reading body");
+        let checksum = if HAS_CHECKSUM {
+            Some(reader.read_u32::<LittleEndian>()?)
+        } else {
+            None
+        };
         Ok(Event {
             timestamp,
             type_code,
@@ -545,6 +968,7 @@ impl Event {
             next_position,
             flags,
             data,
+            checksum,
             offset,
         })
     }
@@ -557,6 +981,10 @@ impl Event {
         self.timestamp
     }
 
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+
     pub fn next_position(&self) -> u64 {
         u64::from(self.next_position)
     }
@@ -565,7 +993,7 @@ impl Event {
         &self,
         table_map: Option<&TableMap>,
     ) -> Result<Option<EventData>, EventParseError> {
-        EventData::from_data(self.type_code, &self.data, table_map).map_err(Into::into)
+        EventData::from_data(self.type_code, &self.data, table_map)
     }
 
     pub fn data(&self) -> &Vec<u8> {
@@ -583,4 +1011,367 @@ impl Event {
     pub fn offset(&self) -> u64 {
         self.offset
     }
+
+    /// The CRC32 the event was stored with
+    pub fn checksum(&self) -> Option<u32> {
+        self.checksum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use assert_matches::assert_matches;
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::{EventData, RowEvent, TypeCode};
+    use crate::mysql_binlog::bit_set::BitSet;
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::encoder::encode_event_data;
+    use crate::mysql_binlog::lazy::LazyRowsEvent;
+    use crate::mysql_binlog::table_map::{TableMap, TableMetadata};
+    use crate::mysql_binlog::tests::Xorshift;
+    use crate::mysql_binlog::value::MySQLValue;
+
+    fn compress(payload: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(payload).unwrap();
+        let compressed = encoder.finish().unwrap();
+        // the uncompressed length in as few big-endian bytes as it fits
+        let len = (payload.len() as u32).to_be_bytes();
+        let width = len.iter().skip_while(|&&b| b == 0).count().max(1);
+        let mut out = vec![0x80 | width as u8];
+        out.extend_from_slice(&len[4 - width..]);
+        out.extend(compressed);
+        out
+    }
+
+    #[test]
+    fn test_mariadb_gtid_event() {
+        let mut data = Vec::new();
+        data.write_u64::<LittleEndian>(1234).unwrap();
+        data.write_u32::<LittleEndian>(7).unwrap();
+        data.write_u8(0x02 | 0x01).unwrap();
+        data.write_u64::<LittleEndian>(99).unwrap();
+        data.extend_from_slice(&[0u8; 6]);
+        let event = EventData::from_data(TypeCode::from_byte(162), &data, None).unwrap();
+        assert_matches!(
+            event,
+            Some(EventData::MariadbGtidEvent {
+                seq_no: 1234,
+                domain_id: 7,
+                flags: 0x03,
+                commit_id: Some(99),
+            })
+        );
+    }
+
+    #[test]
+    fn test_mariadb_gtid_list_event() {
+        let mut data = Vec::new();
+        data.write_u32::<LittleEndian>(2).unwrap();
+        for (domain, server, seq) in &[(0u32, 1u32, 10u64), (1, 2, 20)] {
+            data.write_u32::<LittleEndian>(*domain).unwrap();
+            data.write_u32::<LittleEndian>(*server).unwrap();
+            data.write_u64::<LittleEndian>(*seq).unwrap();
+        }
+        match EventData::from_data(TypeCode::MariadbGtidListEvent, &data, None).unwrap() {
+            Some(EventData::MariadbGtidListEvent { flags, gtids }) => {
+                assert_eq!(flags, 0);
+                let gtids: Vec<String> = gtids.iter().map(|g| g.to_string()).collect();
+                assert_eq!(gtids, vec!["0-1-10", "1-2-20"]);
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_table_map_optional_metadata() {
+        let mut data = vec![42, 0, 0, 0, 0, 0, 1, 0];
+        data.extend_from_slice(b"\x04shop\0\x06orders\0");
+        // BIGINT, VARCHAR(255)
+        data.extend_from_slice(&[2, 8, 15, 2, 255, 0, 0b10]);
+        // signedness, column names, simple primary key
+        data.extend_from_slice(&[1, 1, 0x80]);
+        data.extend_from_slice(b"\x04\x08\x02id\x04note");
+        data.extend_from_slice(&[8, 1, 0]);
+        match EventData::from_data(TypeCode::TableMapEvent, &data, None).unwrap() {
+            Some(EventData::TableMapEvent {
+                table_id,
+                table_name,
+                metadata,
+                ..
+            }) => {
+                assert_eq!(table_id, 42);
+                assert_eq!(table_name, "orders");
+                assert_eq!(metadata.column_names, vec!["id", "note"]);
+                assert_eq!(metadata.unsigned_columns, vec![true, false]);
+                assert_eq!(metadata.primary_key, vec![0]);
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_query_compressed_event() {
+        let mut data = Vec::new();
+        data.write_u32::<LittleEndian>(1).unwrap();
+        data.write_u32::<LittleEndian>(0).unwrap();
+        data.write_u8(4).unwrap();
+        data.write_i16::<LittleEndian>(0).unwrap();
+        data.write_u16::<LittleEndian>(0).unwrap();
+        data.extend_from_slice(b"test\0");
+        data.extend(compress(b"CREATE TABLE t (id INT)"));
+        match EventData::from_data(TypeCode::QueryCompressedEvent, &data, None).unwrap() {
+            Some(EventData::QueryEvent { schema, query, .. }) => {
+                assert_eq!(schema, "test");
+                assert_eq!(query, "CREATE TABLE t (id INT)");
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_rows_compressed_events() {
+        // WRITE_ROWS_COMPRESSED_EVENT for `INSERT INTO t VALUES (7)` into an INT column, laid
+        // out like MariaDB writes it: the column count and bitmap stay uncompressed
+        let mut table_map = TableMap::new();
+        table_map.handle(3, "test".to_owned(), "t".to_owned(), vec![ColumnType::Long]);
+        let mut data = vec![3, 0, 0, 0, 0, 0, 1, 0, 2, 0, 1, 0b1];
+        data.extend(compress(&[0, 7, 0, 0, 0]));
+        match EventData::from_data(TypeCode::from_byte(169), &data, Some(&table_map)).unwrap() {
+            Some(EventData::WriteRowsEvent { table_id, rows }) => {
+                assert_eq!(table_id, 3);
+                assert_eq!(rows.len(), 1);
+                assert_matches!(
+                    rows[0].cols().unwrap()[0],
+                    Some(MySQLValue::SignedInteger(7))
+                );
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+
+        let (table_map, events) = sample_events();
+        for (type_code, body) in &events[1..4] {
+            let compressed_type = match type_code {
+                TypeCode::WriteRowsEventV2 => TypeCode::WriteRowsCompressedEvent,
+                TypeCode::UpdateRowsEventV2 => TypeCode::UpdateRowsCompressedEvent,
+                TypeCode::DeleteRowsEventV2 => TypeCode::DeleteRowsCompressedEvent,
+                t => panic!("unexpected type {:?}", t),
+            };
+            // post-header, extra data, one byte column count, then one bitmap or two
+            let bitmaps = if compressed_type == TypeCode::UpdateRowsCompressedEvent {
+                2
+            } else {
+                1
+            };
+            let header_len =
+                8 + usize::from(body[8]) + 1 + bitmaps * usize::from(body[10]).div_ceil(8);
+            let mut data = body[..header_len].to_vec();
+            data.extend(compress(&body[header_len..]));
+            assert_eq!(
+                format!(
+                    "{:?}",
+                    EventData::from_data(compressed_type, &data, Some(&table_map))
+                ),
+                format!(
+                    "{:?}",
+                    EventData::from_data(*type_code, body, Some(&table_map))
+                )
+            );
+            let lazy = LazyRowsEvent::parse(compressed_type, &data, &table_map).unwrap();
+            assert_eq!(lazy.rows().flatten().count(), 1);
+        }
+    }
+
+    /// A table map and events which decode against it, covering every kind of column, so that
+    /// corrupting them reaches deep into the parser
+    fn sample_events() -> (TableMap, Vec<(TypeCode, Vec<u8>)>) {
+        let columns = vec![
+            ColumnType::LongLong,
+            ColumnType::VarChar(300),
+            ColumnType::Blob(2),
+            ColumnType::NewDecimal(10, 2),
+            ColumnType::Date,
+            ColumnType::Time2(3),
+            ColumnType::DateTime2(6),
+            ColumnType::Timestamp2(2),
+            ColumnType::Json(4),
+            ColumnType::Enum(1),
+            ColumnType::Set(1),
+            ColumnType::Bit(3, 1),
+            ColumnType::Double(8),
+            ColumnType::Geometry(4),
+        ];
+        let metadata = TableMetadata {
+            column_names: (0..columns.len()).map(|i| format!("c{}", i)).collect(),
+            primary_key: vec![0],
+            enum_values: vec![vec!["a".to_owned(), "b".to_owned()]],
+            set_values: vec![vec!["x".to_owned(), "y".to_owned()]],
+            ..TableMetadata::default()
+        };
+        let mut null_bitmap = BitSet::new(columns.len());
+        null_bitmap.set(1);
+        let table_map_event = EventData::TableMapEvent {
+            table_id: 7,
+            schema_name: "shop".to_owned(),
+            table_name: "orders".to_owned(),
+            columns: columns.clone(),
+            null_bitmap,
+            metadata: metadata.clone(),
+        };
+        let mut table_map = TableMap::new();
+        table_map.handle_with_metadata(
+            7,
+            "shop".to_owned(),
+            "orders".to_owned(),
+            columns,
+            metadata,
+        );
+        let row = |id: i64| {
+            vec![
+                Some(MySQLValue::SignedInteger(id)),
+                Some(MySQLValue::String("note".to_owned())),
+                Some(MySQLValue::Null),
+                Some(MySQLValue::Decimal("-1234.50".parse().unwrap())),
+                Some(MySQLValue::Date {
+                    year: 2021,
+                    month: 7,
+                    day: 1,
+                }),
+                Some(MySQLValue::Time {
+                    negative: true,
+                    hours: 12,
+                    minutes: 0,
+                    seconds: 1,
+                    subseconds: 500_000,
+                }),
+                Some(MySQLValue::DateTime {
+                    year: 2021,
+                    month: 7,
+                    day: 1,
+                    hour: 13,
+                    minute: 4,
+                    second: 9,
+                    subsecond: 123_456,
+                }),
+                Some(MySQLValue::Timestamp {
+                    unix_time: 1_625_144_649,
+                    subsecond: 120_000,
+                }),
+                Some(MySQLValue::String(
+                    r#"{"a":[1,true,"x"],"b":{"c":null}}"#.to_owned(),
+                )),
+                Some(MySQLValue::Enum {
+                    index: 2,
+                    label: Some("b".to_owned()),
+                }),
+                Some(MySQLValue::Set(vec!["y".to_owned()])),
+                Some(MySQLValue::SignedInteger(5)),
+                Some(MySQLValue::Double(1.5)),
+                Some(MySQLValue::Geometry {
+                    srid: 4326,
+                    wkb: vec![
+                        1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f, 0, 0, 0, 0, 0, 0, 0, 0x40,
+                    ]
+                    .into(),
+                }),
+            ]
+        };
+        let events = [
+            table_map_event,
+            EventData::WriteRowsEvent {
+                table_id: 7,
+                rows: vec![RowEvent::NewRow { cols: row(1) }],
+            },
+            EventData::UpdateRowsEvent {
+                table_id: 7,
+                rows: vec![RowEvent::UpdatedRow {
+                    before_cols: row(1),
+                    after_cols: row(2),
+                }],
+            },
+            EventData::DeleteRowsEvent {
+                table_id: 7,
+                rows: vec![RowEvent::DeletedRow { cols: row(2) }],
+            },
+            EventData::QueryEvent {
+                thread_id: 1,
+                exec_time: 0,
+                error_code: 0,
+                schema: "shop".to_owned(),
+                query: "BEGIN".to_owned(),
+            },
+        ];
+        let encoded = events
+            .iter()
+            .map(|event| encode_event_data(event, &table_map).unwrap())
+            .collect();
+        (table_map, encoded)
+    }
+
+    /// Decode `data` every way the parser can, which must not panic whatever it holds
+    fn decode(type_code: TypeCode, data: &[u8], table_map: &TableMap) {
+        let _ = EventData::from_data(type_code, data, None);
+        let _ = EventData::from_data(type_code, data, Some(table_map));
+        if let Ok(event) = LazyRowsEvent::parse(type_code, data, table_map) {
+            for row in event.rows().flatten() {
+                let _ = row.decode();
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_events() {
+        let (table_map, events) = sample_events();
+        for (type_code, body) in &events {
+            assert!(EventData::from_data(*type_code, body, Some(&table_map))
+                .unwrap()
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn test_truncated_events() {
+        let (table_map, events) = sample_events();
+        for (type_code, body) in &events {
+            for end in 0..body.len() {
+                decode(*type_code, &body[..end], &table_map);
+            }
+        }
+    }
+
+    #[test]
+    fn test_corrupted_events() {
+        let (table_map, events) = sample_events();
+        let mut rng = Xorshift::new(0x5eed);
+        for (type_code, body) in &events {
+            for _ in 0..2000 {
+                decode(*type_code, &rng.corrupt(body), &table_map);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_events() {
+        let (table_map, _) = sample_events();
+        let mut rng = Xorshift::new(0xbad_b175);
+        for type_byte in 0..=255 {
+            for _ in 0..200 {
+                let len = rng.below(96);
+                decode(TypeCode::from_byte(type_byte), &rng.bytes(len), &table_map);
+            }
+        }
+        // lengths from the data mustn't be trusted to size allocations
+        let mut huge_table_map = vec![7, 0, 0, 0, 0, 0, 1, 0, 1, b'a', 0, 1, b'b', 0];
+        huge_table_map.extend_from_slice(&[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+        assert!(EventData::from_data(TypeCode::TableMapEvent, &huge_table_map, None).is_err());
+        assert_matches!(
+            EventData::from_data(TypeCode::FormatDescriptionEvent, &[3, 0], None),
+            Err(crate::mysql_binlog::errors::EventParseError::UnsupportedBinlogVersion(3))
+        );
+    }
 }
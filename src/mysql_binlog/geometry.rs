@@ -0,0 +1,431 @@
+//! Spatial values. MySQL stores a GEOMETRY value as its SRID (four bytes, little endian) followed
+//! by the geometry in the OGC Well-Known Binary format, which [`Geometry::from_wkb`] parses.
+//! Coordinates are kept as stored: x first, which for geographic spatial reference systems like
+//! SRID 4326 is the longitude.
+use std::fmt;
+use std::io::{Cursor, Read};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use serde_json::{json, Value as JsonValue};
+
+use crate::mysql_binlog::errors::GeometryParseError;
+
+/// How deeply geometry collections may nest
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A parsed WKB geometry
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    Point(Point),
+    LineString(Vec<Point>),
+    /// The exterior ring, then the holes
+    Polygon(Vec<Vec<Point>>),
+    MultiPoint(Vec<Point>),
+    MultiLineString(Vec<Vec<Point>>),
+    MultiPolygon(Vec<Vec<Vec<Point>>>),
+    GeometryCollection(Vec<Geometry>),
+}
+
+/// A geometry in MySQL's internal format: the SRID, then the WKB
+pub(crate) fn internal_bytes(srid: u32, wkb: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(4 + wkb.len());
+    bytes.extend_from_slice(&srid.to_le_bytes());
+    bytes.extend_from_slice(wkb);
+    bytes
+}
+
+struct WkbReader<'a> {
+    cursor: Cursor<&'a [u8]>,
+}
+
+impl WkbReader<'_> {
+    fn geometry(&mut self, depth: usize) -> Result<Geometry, GeometryParseError> {
+        if depth > MAX_DEPTH {
+            return Err(GeometryParseError::TooDeep);
+        }
+        let little_endian = match self.cursor.read_u8()? {
+            0 => false,
+            1 => true,
+            b => return Err(GeometryParseError::InvalidByteOrder(b)),
+        };
+        let geometry_type = self.u32(little_endian)?;
+        Ok(match geometry_type {
+            1 => Geometry::Point(self.point(little_endian)?),
+            2 => Geometry::LineString(self.points(little_endian)?),
+            3 => Geometry::Polygon(self.rings(little_endian)?),
+            4 => Geometry::MultiPoint(self.parts(little_endian, depth, |g| match g {
+                Geometry::Point(p) => Some(p),
+                _ => None,
+            })?),
+            5 => Geometry::MultiLineString(self.parts(little_endian, depth, |g| match g {
+                Geometry::LineString(l) => Some(l),
+                _ => None,
+            })?),
+            6 => Geometry::MultiPolygon(self.parts(little_endian, depth, |g| match g {
+                Geometry::Polygon(p) => Some(p),
+                _ => None,
+            })?),
+            7 => Geometry::GeometryCollection(self.parts(little_endian, depth, Some)?),
+            t => return Err(GeometryParseError::UnknownType(t)),
+        })
+    }
+
+    fn u32(&mut self, little_endian: bool) -> Result<u32, GeometryParseError> {
+        let mut buf = [0u8; 4];
+        self.cursor.read_exact(&mut buf)?;
+        Ok(if little_endian {
+            LittleEndian::read_u32(&buf)
+        } else {
+            BigEndian::read_u32(&buf)
+        })
+    }
+
+    fn point(&mut self, little_endian: bool) -> Result<Point, GeometryParseError> {
+        let mut buf = [0u8; 16];
+        self.cursor.read_exact(&mut buf)?;
+        Ok(if little_endian {
+            Point {
+                x: LittleEndian::read_f64(&buf[..8]),
+                y: LittleEndian::read_f64(&buf[8..]),
+            }
+        } else {
+            Point {
+                x: BigEndian::read_f64(&buf[..8]),
+                y: BigEndian::read_f64(&buf[8..]),
+            }
+        })
+    }
+
+    /// A count, then that many items. Nothing is allocated up front, so a bogus count fails at
+    /// the end of the data rather than on allocation.
+    fn counted<T, F>(
+        &mut self,
+        little_endian: bool,
+        mut item: F,
+    ) -> Result<Vec<T>, GeometryParseError>
+    where
+        F: FnMut(&mut Self) -> Result<T, GeometryParseError>,
+    {
+        let count = self.u32(little_endian)?;
+        let mut items = Vec::new();
+        for _ in 0..count {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn points(&mut self, little_endian: bool) -> Result<Vec<Point>, GeometryParseError> {
+        self.counted(little_endian, |r| r.point(little_endian))
+    }
+
+    fn rings(&mut self, little_endian: bool) -> Result<Vec<Vec<Point>>, GeometryParseError> {
+        self.counted(little_endian, |r| r.points(little_endian))
+    }
+
+    /// The members of a multi-geometry or collection, each a complete WKB geometry
+    fn parts<T, F>(
+        &mut self,
+        little_endian: bool,
+        depth: usize,
+        member: F,
+    ) -> Result<Vec<T>, GeometryParseError>
+    where
+        F: Fn(Geometry) -> Option<T>,
+    {
+        self.counted(little_endian, |r| {
+            member(r.geometry(depth + 1)?).ok_or(GeometryParseError::UnexpectedMember)
+        })
+    }
+}
+
+impl Geometry {
+    /// Parse a geometry in Well-Known Binary, without MySQL's SRID prefix
+    pub fn from_wkb(wkb: &[u8]) -> Result<Self, GeometryParseError> {
+        let mut reader = WkbReader {
+            cursor: Cursor::new(wkb),
+        };
+        let geometry = reader.geometry(0)?;
+        if reader.cursor.position() as usize != wkb.len() {
+            return Err(GeometryParseError::TrailingBytes);
+        }
+        Ok(geometry)
+    }
+
+    /// The geometry in Well-Known Text, as MySQL's `ST_AsText` prints it for a cartesian SRS
+    pub fn to_wkt(&self) -> String {
+        self.to_string()
+    }
+
+    /// The geometry as a GeoJSON geometry object
+    pub fn to_geojson(&self) -> JsonValue {
+        fn position(p: &Point) -> JsonValue {
+            json!([p.x, p.y])
+        }
+        fn line(points: &[Point]) -> JsonValue {
+            points.iter().map(position).collect()
+        }
+        fn polygon(rings: &[Vec<Point>]) -> JsonValue {
+            rings.iter().map(|r| line(r)).collect()
+        }
+        match self {
+            Geometry::Point(p) => json!({"type": "Point", "coordinates": position(p)}),
+            Geometry::LineString(l) => json!({"type": "LineString", "coordinates": line(l)}),
+            Geometry::Polygon(p) => json!({"type": "Polygon", "coordinates": polygon(p)}),
+            Geometry::MultiPoint(m) => json!({"type": "MultiPoint", "coordinates": line(m)}),
+            Geometry::MultiLineString(m) => json!({
+                "type": "MultiLineString",
+                "coordinates": polygon(m),
+            }),
+            Geometry::MultiPolygon(m) => json!({
+                "type": "MultiPolygon",
+                "coordinates": m.iter().map(|p| polygon(p)).collect::<JsonValue>(),
+            }),
+            Geometry::GeometryCollection(c) => json!({
+                "type": "GeometryCollection",
+                "geometries": c.iter().map(Geometry::to_geojson).collect::<JsonValue>(),
+            }),
+        }
+    }
+}
+
+fn write_points(f: &mut fmt::Formatter, points: &[Point]) -> fmt::Result {
+    f.write_str("(")?;
+    for (i, p) in points.iter().enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+        write!(f, "{} {}", p.x, p.y)?;
+    }
+    f.write_str(")")
+}
+
+fn write_rings(f: &mut fmt::Formatter, rings: &[Vec<Point>]) -> fmt::Result {
+    f.write_str("(")?;
+    for (i, ring) in rings.iter().enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+        write_points(f, ring)?;
+    }
+    f.write_str(")")
+}
+
+/// Well-Known Text
+impl fmt::Display for Geometry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Geometry::Point(p) => write!(f, "POINT({} {})", p.x, p.y),
+            Geometry::LineString(l) => {
+                f.write_str("LINESTRING")?;
+                write_points(f, l)
+            }
+            Geometry::Polygon(p) => {
+                f.write_str("POLYGON")?;
+                write_rings(f, p)
+            }
+            Geometry::MultiPoint(m) => {
+                f.write_str("MULTIPOINT(")?;
+                for (i, p) in m.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "({} {})", p.x, p.y)?;
+                }
+                f.write_str(")")
+            }
+            Geometry::MultiLineString(m) => {
+                f.write_str("MULTILINESTRING")?;
+                write_rings(f, m)
+            }
+            Geometry::MultiPolygon(m) => {
+                f.write_str("MULTIPOLYGON(")?;
+                for (i, p) in m.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_rings(f, p)?;
+                }
+                f.write_str(")")
+            }
+            Geometry::GeometryCollection(c) if c.is_empty() => {
+                f.write_str("GEOMETRYCOLLECTION EMPTY")
+            }
+            Geometry::GeometryCollection(c) => {
+                f.write_str("GEOMETRYCOLLECTION(")?;
+                for (i, g) in c.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", g)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+    use serde_json::json;
+
+    use super::{Geometry, Point};
+    use crate::mysql_binlog::errors::GeometryParseError;
+
+    fn header(out: &mut Vec<u8>, geometry_type: u32) {
+        out.push(1);
+        out.write_u32::<LittleEndian>(geometry_type).unwrap();
+    }
+
+    fn points(out: &mut Vec<u8>, points: &[(f64, f64)]) {
+        out.write_u32::<LittleEndian>(points.len() as u32).unwrap();
+        for (x, y) in points {
+            out.write_f64::<LittleEndian>(*x).unwrap();
+            out.write_f64::<LittleEndian>(*y).unwrap();
+        }
+    }
+
+    fn point(x: f64, y: f64) -> Vec<u8> {
+        let mut out = Vec::new();
+        header(&mut out, 1);
+        out.write_f64::<LittleEndian>(x).unwrap();
+        out.write_f64::<LittleEndian>(y).unwrap();
+        out
+    }
+
+    fn polygon() -> Vec<u8> {
+        let mut out = Vec::new();
+        header(&mut out, 3);
+        out.write_u32::<LittleEndian>(1).unwrap();
+        points(&mut out, &[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 0.0)]);
+        out
+    }
+
+    #[test]
+    fn test_wkt_and_geojson() {
+        let p = Geometry::from_wkb(&point(116.4, 39.9)).unwrap();
+        assert_eq!(p, Geometry::Point(Point { x: 116.4, y: 39.9 }));
+        assert_eq!(p.to_wkt(), "POINT(116.4 39.9)");
+        assert_eq!(
+            p.to_geojson(),
+            json!({"type": "Point", "coordinates": [116.4, 39.9]})
+        );
+
+        // big endian
+        let mut line = vec![0];
+        line.write_u32::<BigEndian>(2).unwrap();
+        line.write_u32::<BigEndian>(2).unwrap();
+        for c in &[0.0, 0.0, 1.5, -2.0] {
+            line.write_f64::<BigEndian>(*c).unwrap();
+        }
+        let line = Geometry::from_wkb(&line).unwrap();
+        assert_eq!(line.to_wkt(), "LINESTRING(0 0,1.5 -2)");
+        assert_eq!(
+            line.to_geojson(),
+            json!({"type": "LineString", "coordinates": [[0.0, 0.0], [1.5, -2.0]]})
+        );
+
+        let polygon_geometry = Geometry::from_wkb(&polygon()).unwrap();
+        assert_eq!(polygon_geometry.to_wkt(), "POLYGON((0 0,4 0,4 4,0 0))");
+
+        let mut multi_point = Vec::new();
+        header(&mut multi_point, 4);
+        multi_point.write_u32::<LittleEndian>(2).unwrap();
+        multi_point.extend(point(1.0, 2.0));
+        multi_point.extend(point(3.0, 4.0));
+        let multi_point = Geometry::from_wkb(&multi_point).unwrap();
+        assert_eq!(multi_point.to_wkt(), "MULTIPOINT((1 2),(3 4))");
+
+        let mut multi_polygon = Vec::new();
+        header(&mut multi_polygon, 6);
+        multi_polygon.write_u32::<LittleEndian>(2).unwrap();
+        multi_polygon.extend(polygon());
+        multi_polygon.extend(polygon());
+        let multi_polygon = Geometry::from_wkb(&multi_polygon).unwrap();
+        assert_eq!(
+            multi_polygon.to_wkt(),
+            "MULTIPOLYGON(((0 0,4 0,4 4,0 0)),((0 0,4 0,4 4,0 0)))"
+        );
+        assert_eq!(
+            multi_polygon.to_geojson()["coordinates"][1][0][2],
+            json!([4.0, 4.0])
+        );
+
+        let mut collection = Vec::new();
+        header(&mut collection, 7);
+        collection.write_u32::<LittleEndian>(2).unwrap();
+        collection.extend(point(1.0, 2.0));
+        collection.extend(polygon());
+        let collection = Geometry::from_wkb(&collection).unwrap();
+        assert_eq!(
+            collection.to_wkt(),
+            "GEOMETRYCOLLECTION(POINT(1 2),POLYGON((0 0,4 0,4 4,0 0)))"
+        );
+        assert_eq!(
+            collection.to_geojson()["geometries"][0],
+            json!({"type": "Point", "coordinates": [1.0, 2.0]})
+        );
+        let mut empty = Vec::new();
+        header(&mut empty, 7);
+        empty.write_u32::<LittleEndian>(0).unwrap();
+        assert_eq!(
+            Geometry::from_wkb(&empty).unwrap().to_wkt(),
+            "GEOMETRYCOLLECTION EMPTY"
+        );
+    }
+
+    #[test]
+    fn test_invalid_wkb() {
+        let p = point(1.0, 2.0);
+        assert!(matches!(
+            Geometry::from_wkb(&p[..p.len() - 1]),
+            Err(GeometryParseError::Io(_))
+        ));
+        let mut trailing = p.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Geometry::from_wkb(&trailing),
+            Err(GeometryParseError::TrailingBytes)
+        ));
+        assert!(matches!(
+            Geometry::from_wkb(&[2, 1, 0, 0, 0]),
+            Err(GeometryParseError::InvalidByteOrder(2))
+        ));
+        let mut unknown = Vec::new();
+        header(&mut unknown, 99);
+        assert!(matches!(
+            Geometry::from_wkb(&unknown),
+            Err(GeometryParseError::UnknownType(99))
+        ));
+        // a multipoint holding a polygon
+        let mut mixed = Vec::new();
+        header(&mut mixed, 4);
+        mixed.write_u32::<LittleEndian>(1).unwrap();
+        mixed.extend(polygon());
+        assert!(matches!(
+            Geometry::from_wkb(&mixed),
+            Err(GeometryParseError::UnexpectedMember)
+        ));
+        // a huge count fails at the end of the data
+        let mut huge = Vec::new();
+        header(&mut huge, 2);
+        huge.write_u32::<LittleEndian>(u32::MAX).unwrap();
+        assert!(Geometry::from_wkb(&huge).is_err());
+        let mut nested = Vec::new();
+        for _ in 0..40 {
+            header(&mut nested, 7);
+            nested.write_u32::<LittleEndian>(1).unwrap();
+        }
+        nested.extend(point(0.0, 0.0));
+        assert!(matches!(
+            Geometry::from_wkb(&nested),
+            Err(GeometryParseError::TooDeep)
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 /// MySQL uses a bizarro custom encoding that they call JSONB (no relation to the PostgreSQL column
 /// type) for JSON values. No, I don't know why they didn't just use BSON or CBOR. I think they
 /// might just hate me.
-use std::io::Cursor;
+use std::io::{self, Cursor};
 use std::iter::FromIterator;
 
 use base64;
@@ -64,9 +64,12 @@ enum CompoundType {
     Array,
 }
 
+/// How deeply MySQL lets JSON documents nest
+const MAX_DEPTH: usize = 100;
+
 pub fn parse(blob: Vec<u8>) -> Result<JsonValue, JsonbParseError> {
     let mut cursor = Cursor::new(blob.as_slice());
-    parse_any(&mut cursor)
+    parse_any(&mut cursor, 0)
 }
 
 #[derive(Debug)]
@@ -108,7 +111,12 @@ fn parse_compound(
     cursor: &mut Cursor<&[u8]>,
     compound_size: CompoundSize,
     compound_type: CompoundType,
+    depth: usize,
 ) -> Result<JsonValue, JsonbParseError> {
+    // offsets can point back at the compound itself, which would recurse forever
+    if depth >= MAX_DEPTH {
+        return Err(JsonbParseError::TooDeep);
+    }
     let data_length = cursor.get_ref().len();
     let offset_size = match compound_size {
         CompoundSize::Small => 2,
@@ -176,7 +184,10 @@ fn parse_compound(
                 CompoundType::Array => 0,
                 CompoundType::Object => key_entry_size * count,
             };
-            let tp_data = cursor.get_ref().get(entry_offset + 1).unwrap();
+            let tp_data = cursor
+                .get_ref()
+                .get(entry_offset + 1)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
             let tp = FieldType::from_byte(*tp_data)?;
             let is_inline = match tp {
                 FieldType::Uint16 | FieldType::Int16 | FieldType::Literal => true,
@@ -187,9 +198,12 @@ fn parse_compound(
                 _ => false,
             };
             if is_inline {
-                let data = &cursor.get_ref()[entry_offset + 1..entry_offset + value_entry_size + 1];
+                let data = cursor
+                    .get_ref()
+                    .get(entry_offset + 1..entry_offset + value_entry_size + 1)
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
                 let mut cur = Cursor::new(data);
-                let value = parse_any(&mut cur)?;
+                let value = parse_any(&mut cur, depth + 1)?;
                 rsl.push(value);
                 continue;
             }
@@ -198,13 +212,13 @@ fn parse_compound(
                 CompoundSize::Small => u32::from(cursor.read_u16::<LittleEndian>()?) as usize,
                 CompoundSize::Large => cursor.read_u32::<LittleEndian>()? as usize,
             };
-            if data_length < value_offset {
+            if data_length <= value_offset {
                 return Ok(JsonValue::Null);
             }
             let mut data = vec![*tp_data];
             data.extend_from_slice(&cursor.get_ref()[value_offset + 1..data_length]);
             let mut cur = Cursor::new(data.as_slice());
-            let value = parse_any(&mut cur)?;
+            let value = parse_any(&mut cur, depth + 1)?;
             rsl.push(value);
         }
         rsl
@@ -217,14 +231,15 @@ fn parse_compound(
     })
 }
 
-fn parse_any(cursor: &mut Cursor<&[u8]>) -> Result<JsonValue, JsonbParseError> {
+fn parse_any(cursor: &mut Cursor<&[u8]>, depth: usize) -> Result<JsonValue, JsonbParseError> {
     let type_indicator = FieldType::from_byte(cursor.read_u8()?)?;
-    parse_any_with_type_indicator(cursor, type_indicator)
+    parse_any_with_type_indicator(cursor, type_indicator, depth)
 }
 
 fn parse_any_with_type_indicator(
     mut cursor: &mut Cursor<&[u8]>,
     type_indicator: FieldType,
+    depth: usize,
 ) -> Result<JsonValue, JsonbParseError> {
     match type_indicator {
         FieldType::Literal => Ok(match cursor.read_u8()? {
@@ -265,17 +280,23 @@ fn parse_any_with_type_indicator(
             let val = packet_helpers::read_variable_length_string(&mut cursor)?;
             Ok(JsonValue::from(val))
         }
-        FieldType::SmallObject => {
-            parse_compound(&mut cursor, CompoundSize::Small, CompoundType::Object)
-        }
-        FieldType::LargeObject => {
-            parse_compound(&mut cursor, CompoundSize::Large, CompoundType::Object)
-        }
+        FieldType::SmallObject => parse_compound(
+            &mut cursor,
+            CompoundSize::Small,
+            CompoundType::Object,
+            depth,
+        ),
+        FieldType::LargeObject => parse_compound(
+            &mut cursor,
+            CompoundSize::Large,
+            CompoundType::Object,
+            depth,
+        ),
         FieldType::SmallArray => {
-            parse_compound(&mut cursor, CompoundSize::Small, CompoundType::Array)
+            parse_compound(&mut cursor, CompoundSize::Small, CompoundType::Array, depth)
         }
         FieldType::LargeArray => {
-            parse_compound(&mut cursor, CompoundSize::Large, CompoundType::Array)
+            parse_compound(&mut cursor, CompoundSize::Large, CompoundType::Array, depth)
         }
         FieldType::Custom => {
             /* augh apparently MySQL has this "neat" feature where it can encode any MySQL type
@@ -290,14 +311,16 @@ fn parse_any_with_type_indicator(
             let column_type = ColumnType::from_byte(raw_mysql_column_type);
             let payload = packet_helpers::read_variable_length_bytes(&mut cursor)?;
             match column_type {
-                ColumnType::NewDecimal(..)
-                | ColumnType::Date
-                | ColumnType::Time
-                | ColumnType::Timestamp
-                | ColumnType::DateTime
-                | ColumnType::DateTime2(..)
-                | ColumnType::Time2(..)
-                | ColumnType::Timestamp2(..) => {
+                Ok(
+                    column_type @ (ColumnType::NewDecimal(..)
+                    | ColumnType::Date
+                    | ColumnType::Time
+                    | ColumnType::Timestamp
+                    | ColumnType::DateTime
+                    | ColumnType::DateTime2(..)
+                    | ColumnType::Time2(..)
+                    | ColumnType::Timestamp2(..)),
+                ) => {
                     let mut cursor = Cursor::new(payload);
                     let column_type = column_type.read_metadata(&mut cursor)?;
                     let value = column_type.read_value(&mut cursor)?;
@@ -321,11 +344,167 @@ fn parse_any_with_type_indicator(
     }
 }
 
+/// Encode a JSON value in the binary format [`parse`] reads. Object keys are written in the
+/// order MySQL keeps them (by length, then bytewise) so the server can binary-search them.
+pub fn serialize(value: &JsonValue) -> Vec<u8> {
+    let (type_byte, body) = serialize_value(value);
+    let mut out = Vec::with_capacity(body.len() + 1);
+    out.push(type_byte);
+    out.extend(body);
+    out
+}
+
+fn serialize_value(value: &JsonValue) -> (u8, Vec<u8>) {
+    match value {
+        JsonValue::Null => (0x04, vec![0x00]),
+        JsonValue::Bool(true) => (0x04, vec![0x01]),
+        JsonValue::Bool(false) => (0x04, vec![0x02]),
+        JsonValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                if i >= i64::from(i16::MIN) && i <= i64::from(i16::MAX) {
+                    (0x05, (i as i16).to_le_bytes().to_vec())
+                } else if i >= i64::from(i32::MIN) && i <= i64::from(i32::MAX) {
+                    (0x07, (i as i32).to_le_bytes().to_vec())
+                } else {
+                    (0x09, i.to_le_bytes().to_vec())
+                }
+            } else if let Some(u) = n.as_u64() {
+                (0x0a, u.to_le_bytes().to_vec())
+            } else {
+                (0x0b, n.as_f64().unwrap_or(0.0).to_le_bytes().to_vec())
+            }
+        }
+        JsonValue::String(s) => {
+            let mut out = Vec::with_capacity(s.len() + 2);
+            let mut len = s.len();
+            loop {
+                let byte = (len & 0x7f) as u8;
+                len >>= 7;
+                if len == 0 {
+                    out.push(byte);
+                    break;
+                }
+                out.push(byte | 0x80);
+            }
+            out.extend_from_slice(s.as_bytes());
+            (0x0c, out)
+        }
+        JsonValue::Array(values) => {
+            let entries: Vec<(Option<&str>, &JsonValue)> =
+                values.iter().map(|v| (None, v)).collect();
+            serialize_compound(&entries, CompoundType::Array)
+        }
+        JsonValue::Object(map) => {
+            let mut entries: Vec<(Option<&str>, &JsonValue)> =
+                map.iter().map(|(k, v)| (Some(k.as_str()), v)).collect();
+            entries.sort_by(|a, b| {
+                let (a, b) = (a.0.unwrap_or(""), b.0.unwrap_or(""));
+                a.len()
+                    .cmp(&b.len())
+                    .then_with(|| a.as_bytes().cmp(b.as_bytes()))
+            });
+            serialize_compound(&entries, CompoundType::Object)
+        }
+    }
+}
+
+fn serialize_compound(
+    entries: &[(Option<&str>, &JsonValue)],
+    compound_type: CompoundType,
+) -> (u8, Vec<u8>) {
+    let small = serialize_compound_sized(entries, compound_type, CompoundSize::Small);
+    let (size, body) = match small {
+        Some(body) => (CompoundSize::Small, body),
+        None => (
+            CompoundSize::Large,
+            serialize_compound_sized(entries, compound_type, CompoundSize::Large)
+                .unwrap_or_default(),
+        ),
+    };
+    let type_byte = match (compound_type, size) {
+        (CompoundType::Object, CompoundSize::Small) => 0x00,
+        (CompoundType::Object, CompoundSize::Large) => 0x01,
+        (CompoundType::Array, CompoundSize::Small) => 0x02,
+        (CompoundType::Array, CompoundSize::Large) => 0x03,
+    };
+    (type_byte, body)
+}
+
+/// Lays out a compound with the given offset width, or returns `None` when an offset does not
+/// fit in it
+fn serialize_compound_sized(
+    entries: &[(Option<&str>, &JsonValue)],
+    compound_type: CompoundType,
+    compound_size: CompoundSize,
+) -> Option<Vec<u8>> {
+    let (offset_size, key_entry_size, value_entry_size) = match compound_size {
+        CompoundSize::Small => (2, 4, 3),
+        CompoundSize::Large => (4, 6, 5),
+    };
+    let max = match compound_size {
+        CompoundSize::Small => usize::from(u16::MAX),
+        CompoundSize::Large => u32::MAX as usize,
+    };
+    let write_offset = |out: &mut Vec<u8>, value: usize| match compound_size {
+        CompoundSize::Small => out.extend_from_slice(&(value as u16).to_le_bytes()),
+        CompoundSize::Large => out.extend_from_slice(&(value as u32).to_le_bytes()),
+    };
+    let count = entries.len();
+    let mut header_size = 2 * offset_size + count * value_entry_size;
+    if compound_type == CompoundType::Object {
+        header_size += count * key_entry_size;
+    }
+
+    let mut header = Vec::with_capacity(header_size);
+    let mut payload = Vec::new();
+    if compound_type == CompoundType::Object {
+        for (key, _) in entries {
+            let key = key.unwrap_or("");
+            if key.len() > usize::from(u16::MAX) {
+                return None;
+            }
+            write_offset(&mut header, header_size + payload.len());
+            header.extend_from_slice(&(key.len() as u16).to_le_bytes());
+            payload.extend_from_slice(key.as_bytes());
+        }
+    }
+    for (_, value) in entries {
+        let (type_byte, body) = serialize_value(value);
+        header.push(type_byte);
+        let inline = match type_byte {
+            0x04..=0x06 => true,
+            0x07 | 0x08 => compound_size == CompoundSize::Large,
+            _ => false,
+        };
+        if inline {
+            let mut entry = body;
+            entry.resize(offset_size, 0);
+            header.extend(entry);
+        } else {
+            write_offset(&mut header, header_size + payload.len());
+            payload.extend(body);
+        }
+    }
+    let total = header_size + payload.len();
+    if total > max || count > max {
+        return None;
+    }
+    let mut out = Vec::with_capacity(total);
+    write_offset(&mut out, count);
+    write_offset(&mut out, total);
+    out.extend(header);
+    out.extend(payload);
+    Some(out)
+}
+
 #[cfg(test)]
 mod tests {
+    use assert_matches::assert_matches;
     use serde_json::json;
 
-    use super::parse;
+    use super::{parse, serialize};
+    use crate::mysql_binlog::errors::JsonbParseError;
+    use crate::mysql_binlog::tests::Xorshift;
 
     #[test]
     pub fn test_i16() {
@@ -393,7 +572,58 @@ mod tests {
         let parsed = parse(blob).expect("should parse");
         assert_eq!(
             parsed,
-            json!({"date": null,"datetime":{"DateTime":{"day":7,"hour":82,"minute":69,"month":78,"second":44,"subsecond":0,"year":184640201}},"time":{"Time":{"hours":0,"minutes":0,"seconds":0,"subseconds":0}},"timestamp":{"Timestamp":{"subsecond":0,"unix_time":1291845632}}})
+            json!({"date":{"Date":{"day":0,"month":0,"year":0}},"datetime":{"DateTime":{"day":7,"hour":82,"minute":69,"month":78,"second":44,"subsecond":0,"year":184640201}},"time":{"Time":{"hours":0,"minutes":0,"negative":false,"seconds":0,"subseconds":0}},"timestamp":{"Timestamp":{"subsecond":0,"unix_time":1291845632}}})
+        );
+    }
+
+    #[test]
+    pub fn test_serialize_round_trip() {
+        for value in &[
+            json!(null),
+            json!(-3),
+            json!(70000),
+            json!(5_000_000_000u64),
+            json!(1.5),
+            json!("x".repeat(200)),
+            json!({"a":[1,2,"three",{"4":4}], "bb": true, "c": null}),
+        ] {
+            assert_eq!(&parse(serialize(value)).expect("should parse"), value);
+        }
+        // same layout as the server's
+        assert_eq!(
+            serialize(&json!({"a": null})),
+            vec![0u8, 1, 0, 12, 0, 11, 0, 1, 0, 4, 0, 0, 97]
         );
     }
+
+    #[test]
+    pub fn test_self_referencing_offset() {
+        // a small array whose only element points back at the array itself
+        let blob = vec![2u8, 1, 0, 7, 0, 2, 0, 0];
+        assert_matches!(parse(blob), Err(JsonbParseError::TooDeep));
+    }
+
+    #[test]
+    pub fn test_parse_never_panics() {
+        let mut rng = Xorshift::new(0x15_0b);
+        for value in &[
+            json!(-3),
+            json!(5_000_000_000u64),
+            json!("x".repeat(200)),
+            json!({"a":[1,2,"three",{"4":4}], "bb": true, "c": null, "d": 1.5}),
+            json!([[[[{"deep": ["er"]}]]]]),
+        ] {
+            let blob = serialize(value);
+            for end in 0..blob.len() {
+                let _ = parse(blob[..end].to_vec());
+            }
+            for _ in 0..2000 {
+                let _ = parse(rng.corrupt(&blob));
+            }
+        }
+        for _ in 0..5000 {
+            let len = rng.below(48);
+            let _ = parse(rng.bytes(len));
+        }
+    }
 }
@@ -2,7 +2,8 @@
 //!
 //! # Limitations
 //!
-//! - Targets Percona and Oracle MySQL 5.6 and 5.7. Has not been tested with MariaDB, MySQL 8.0, or older versions of MySQL
+//! - Targets Percona and Oracle MySQL 5.6 and 5.7, plus the MariaDB-specific GTID, annotate and
+//!   compressed events. Has not been tested with older versions of MySQL
 //! - Like all 5.6/5.7 MySQL implementations, UNSIGNED BIGINT cannot safely represent numbers between `2^63` and `2^64` because `i64` is used internally for all integral data types
 //!
 //! # Example
@@ -17,21 +18,30 @@
 //! }
 //! ```
 
+use std::fmt;
 use std::fs::File;
 use std::io::{Read, Seek};
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
 
 pub mod binlog_file;
-mod bit_set;
+pub mod binlog_writer;
+pub mod bit_set;
 pub mod column_types;
+pub mod de;
+pub mod encoder;
 pub mod errors;
 pub mod event;
+pub mod geometry;
 mod jsonb;
+pub mod lazy;
 mod packet_helpers;
 pub mod table_map;
 mod tell;
 pub mod value;
 
+use crate::sql::statement::{classify, Statement};
 use event::EventData;
 use serde_derive::Serialize;
 
@@ -57,6 +67,53 @@ impl ToString for Gtid {
     }
 }
 
+impl Gtid {
+    pub fn uuid(&self) -> uuid::Uuid {
+        self.0
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// MariaDB Global Transaction ID (`domain-server-sequence`)
+pub struct MariadbGtid {
+    pub domain_id: u32,
+    pub server_id: u32,
+    pub sequence: u64,
+}
+
+impl serde::Serialize for MariadbGtid {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl fmt::Display for MariadbGtid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.domain_id, self.server_id, self.sequence)
+    }
+}
+
+impl FromStr for MariadbGtid {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, '-');
+        let mut next = || parts.next().unwrap_or("");
+        Ok(MariadbGtid {
+            domain_id: next().parse()?,
+            server_id: next().parse()?,
+            sequence: next().parse()?,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct LogicalTimestamp {
     last_committed: u64,
@@ -71,11 +128,16 @@ pub struct BinlogEvent {
     // warning: Y2038 Problem ahead
     pub timestamp: u32,
     pub gtid: Option<Gtid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mariadb_gtid: Option<MariadbGtid>,
     pub logical_timestamp: Option<LogicalTimestamp>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub schema_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub table_name: Option<String>,
+    /// Table map the rows were decoded with, including column names when the server sent them
+    #[serde(skip)]
+    pub table: Option<Arc<table_map::SingleTableMap>>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub rows: Vec<event::RowEvent>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -83,12 +145,125 @@ pub struct BinlogEvent {
     pub offset: u64,
 }
 
+impl BinlogEvent {
+    /// What kind of statement a query event carries; see [`EventData::statement`]
+    pub fn statement(&self) -> Option<Statement> {
+        let query = self.query.as_ref()?;
+        Some(classify(query, self.schema_name.as_deref()))
+    }
+}
+
+/// The per-stream state needed to turn decoded [`EventData`] into [`BinlogEvent`]s, namely the
+/// GTID of the transaction currently being read. Shared by [`EventIterator`] and the network
+/// `Runner`.
+#[derive(Default)]
+pub struct EventContext {
+    current_gtid: Option<Gtid>,
+    current_mariadb_gtid: Option<MariadbGtid>,
+    logical_timestamp: Option<LogicalTimestamp>,
+}
+
+impl EventContext {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Feed one decoded event. GTID events update the context, query and rows events come back
+    /// as a [`BinlogEvent`]. Table map events are not handled here; the caller must apply them
+    /// to `table_map` before decoding the rows events that reference them.
+    pub fn handle(
+        &mut self,
+        type_code: event::TypeCode,
+        timestamp: u32,
+        server_id: u32,
+        offset: u64,
+        data: EventData,
+        table_map: &table_map::TableMap,
+    ) -> Option<BinlogEvent> {
+        match data {
+            EventData::GtidLogEvent {
+                uuid,
+                coordinate,
+                last_committed,
+                sequence_number,
+                ..
+            } => {
+                self.current_gtid = Some(Gtid(uuid, coordinate));
+                if let (Some(last_committed), Some(sequence_number)) =
+                    (last_committed, sequence_number)
+                {
+                    self.logical_timestamp = Some(LogicalTimestamp {
+                        last_committed,
+                        sequence_number,
+                    });
+                } else {
+                    self.logical_timestamp = None;
+                }
+                None
+            }
+            EventData::MariadbGtidEvent {
+                seq_no, domain_id, ..
+            } => {
+                self.current_mariadb_gtid = Some(MariadbGtid {
+                    domain_id,
+                    server_id,
+                    sequence: seq_no,
+                });
+                None
+            }
+            EventData::QueryEvent { query, schema, .. } => Some(BinlogEvent {
+                offset,
+                type_code,
+                timestamp,
+                gtid: self.current_gtid,
+                mariadb_gtid: self.current_mariadb_gtid,
+                logical_timestamp: self.logical_timestamp,
+                table_name: None,
+                // the default database the statement ran in
+                schema_name: Some(schema).filter(|s| !s.is_empty()),
+                table: None,
+                rows: Vec::new(),
+                query: Some(query),
+            }),
+            EventData::WriteRowsEvent { table_id, rows }
+            | EventData::UpdateRowsEvent { table_id, rows }
+            | EventData::DeleteRowsEvent { table_id, rows } => {
+                let maybe_table = table_map.get_shared(table_id);
+                Some(BinlogEvent {
+                    offset,
+                    type_code,
+                    timestamp,
+                    gtid: self.current_gtid,
+                    mariadb_gtid: self.current_mariadb_gtid,
+                    logical_timestamp: self.logical_timestamp,
+                    table_name: maybe_table.as_ref().map(|a| a.table_name.to_owned()),
+                    schema_name: maybe_table.as_ref().map(|a| a.schema_name.to_owned()),
+                    table: maybe_table,
+                    rows,
+                    query: None,
+                })
+            }
+            EventData::TableMapEvent { .. }
+            | EventData::XIDEvent { .. }
+            | EventData::RotateEvent { .. }
+            | EventData::FormatDescriptionEvent { .. }
+            | EventData::MariadbGtidListEvent { .. }
+            | EventData::BinlogCheckpointEvent { .. }
+            | EventData::AnnotateRowsEvent { .. }
+            | EventData::PreviousGtidsLogEvent { .. } => None,
+            u => {
+                eprintln!("unhandled event: {:?}", u);
+                None
+            }
+        }
+    }
+}
+
 /// Iterator over [`BinlogEvent`]s
 pub struct EventIterator<BR: Read + Seek> {
     events: binlog_file::BinlogEvents<BR>,
     table_map: table_map::TableMap,
-    current_gtid: Option<Gtid>,
-    logical_timestamp: Option<LogicalTimestamp>,
+    context: EventContext,
 }
 
 impl<BR: Read + Seek> EventIterator<BR> {
@@ -96,10 +271,14 @@ impl<BR: Read + Seek> EventIterator<BR> {
         EventIterator {
             events: bf.events(start_offset),
             table_map: table_map::TableMap::new(),
-            current_gtid: None,
-            logical_timestamp: None,
+            context: EventContext::new(),
         }
     }
+
+    /// Tables seen so far in this binlog
+    pub fn table_map(&self) -> &table_map::TableMap {
+        &self.table_map
+    }
 }
 
 impl<BR: Read + Seek> Iterator for EventIterator<BR> {
@@ -111,75 +290,38 @@ impl<BR: Read + Seek> Iterator for EventIterator<BR> {
                 Ok(event) => event,
                 Err(e) => return Some(Err(e)),
             };
-            let offset = event.offset();
             match event.inner(Some(&self.table_map)) {
-                Ok(Some(e)) => match e {
-                    EventData::GtidLogEvent {
-                        uuid,
-                        coordinate,
-                        last_committed,
-                        sequence_number,
-                        ..
-                    } => {
-                        self.current_gtid = Some(Gtid(uuid, coordinate));
-                        if let (Some(last_committed), Some(sequence_number)) =
-                            (last_committed, sequence_number)
-                        {
-                            self.logical_timestamp = Some(LogicalTimestamp {
-                                last_committed,
-                                sequence_number,
-                            });
-                        } else {
-                            self.logical_timestamp = None;
-                        }
-                    }
-                    EventData::TableMapEvent {
+                Ok(Some(EventData::TableMapEvent {
+                    table_id,
+                    schema_name,
+                    table_name,
+                    columns,
+                    metadata,
+                    ..
+                })) => {
+                    self.table_map.handle_with_metadata(
                         table_id,
                         schema_name,
                         table_name,
                         columns,
-                        ..
-                    } => {
-                        self.table_map
-                            .handle(table_id, schema_name, table_name, columns);
-                    }
-                    EventData::QueryEvent { query, .. } => {
-                        return Some(Ok(BinlogEvent {
-                            offset,
-                            type_code: event.type_code(),
-                            timestamp: event.timestamp(),
-                            gtid: self.current_gtid,
-                            logical_timestamp: self.logical_timestamp,
-                            table_name: None,
-                            schema_name: None,
-                            rows: Vec::new(),
-                            query: Some(query),
-                        }))
-                    }
-                    EventData::WriteRowsEvent { table_id, rows }
-                    | EventData::UpdateRowsEvent { table_id, rows }
-                    | EventData::DeleteRowsEvent { table_id, rows } => {
-                        let maybe_table = self.table_map.get(table_id);
-                        let message = BinlogEvent {
-                            offset,
-                            type_code: event.type_code(),
-                            timestamp: event.timestamp(),
-                            gtid: self.current_gtid,
-                            logical_timestamp: self.logical_timestamp,
-                            table_name: maybe_table.as_ref().map(|a| a.table_name.to_owned()),
-                            schema_name: maybe_table.as_ref().map(|a| a.schema_name.to_owned()),
-                            rows,
-                            query: None,
-                        };
+                        metadata,
+                    );
+                }
+                Ok(Some(e)) => {
+                    if let Some(message) = self.context.handle(
+                        event.type_code(),
+                        event.timestamp(),
+                        event.server_id(),
+                        event.offset(),
+                        e,
+                        &self.table_map,
+                    ) {
                         return Some(Ok(message));
                     }
-                    u => {
-                        eprintln!("unhandled event: {:?}", u);
-                    }
-                },
+                }
                 Ok(None) => {
                     // this event doesn't have an inner type, which means we don't currently
-                    // care about it. Example: PreviousGtidEvent
+                    // care about it. Example: StopEvent
                 }
                 Err(e) => return Some(Err(e)),
             }
@@ -256,10 +398,43 @@ mod tests {
     use bigdecimal::BigDecimal;
 
     use super::{parse_file, parse_reader};
-    use crate::event::TypeCode;
     use crate::mysql_binlog::event::TypeCode;
     use crate::mysql_binlog::value::MySQLValue;
-    use crate::value::MySQLValue;
+
+    /// A xorshift generator, to make up test input without pulling in a dependency
+    pub(crate) struct Xorshift(u64);
+
+    impl Xorshift {
+        pub(crate) fn new(seed: u64) -> Self {
+            Xorshift(seed | 1)
+        }
+
+        pub(crate) fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A number in `0..n`
+        pub(crate) fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        pub(crate) fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+
+        /// `data` with a few bytes overwritten
+        pub(crate) fn corrupt(&mut self, data: &[u8]) -> Vec<u8> {
+            let mut corrupted = data.to_vec();
+            for _ in 0..1 + self.below(4) {
+                let index = self.below(corrupted.len());
+                corrupted[index] = self.next() as u8;
+            }
+            corrupted
+        }
+    }
 
     #[test]
     fn test_parse_file() {
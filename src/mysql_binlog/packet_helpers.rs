@@ -10,22 +10,24 @@ use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
 //
 // It's all garbage all the way down.
 
+/// The error for bytes which can't be what they are read as
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
 pub(crate) fn read_variable_length_integer<R: Read>(r: &mut R) -> io::Result<i64> {
     let first = r.read_u8()?;
     if first < 0xfb {
-        Ok(i64::from(first as i8))
+        Ok(i64::from(first))
     } else if first == 0xfc {
-        Ok(i64::from(r.read_i16::<LittleEndian>()?))
+        Ok(i64::from(r.read_u16::<LittleEndian>()?))
     } else if first == 0xfd {
         // why are there three byte integers fucking mysql
-        let mut buf = [0u8; 4];
-        r.read_exact(&mut buf[0..3])?;
-        // TODO: sign-extend to fill that top byte
-        Ok(i64::from(LittleEndian::read_i32(&buf)))
+        Ok(i64::from(read_uint24(r)?))
     } else if first == 0xfe {
         r.read_i64::<LittleEndian>()
     } else {
-        unreachable!();
+        Err(invalid_data("invalid length-encoded integer"))
     }
 }
 
@@ -46,7 +48,7 @@ pub(crate) fn read_known_length_integer_be<R: Read>(r: &mut R, bytes: usize) ->
             }
         }
         4 => i64::from(r.read_i32::<BigEndian>()?),
-        _ => unimplemented!(),
+        _ => return Err(invalid_data("integers are 1 to 4 bytes long")),
     })
 }
 
@@ -86,7 +88,7 @@ pub(crate) fn read_var_byte_length_prefixed_bytes<R: Read>(
         }
         4 => r.read_u32::<LittleEndian>()? as usize,
         8 => r.read_u64::<LittleEndian>()? as usize,
-        l => unreachable!(format!("got unexpected length {0:?}", l)),
+        _ => return Err(invalid_data("invalid length prefix size")),
     };
     read_nbytes(r, len)
 }
@@ -105,8 +107,13 @@ pub(crate) fn read_nbytes<R: Read, S: Into<usize>>(
     r: &mut R,
     desired_bytes: S,
 ) -> io::Result<Vec<u8>> {
-    let mut into = vec![0u8; desired_bytes.into()];
-    r.read_exact(&mut into)?;
+    // lengths come from the data, so don't allocate more than is actually there
+    let desired_bytes = desired_bytes.into();
+    let mut into = Vec::new();
+    r.take(desired_bytes as u64).read_to_end(&mut into)?;
+    if into.len() < desired_bytes {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
     Ok(into)
 }
 
@@ -119,7 +126,7 @@ pub(crate) fn read_variable_length_bytes<R: Read>(r: &mut R) -> io::Result<Vec<u
         length |= ((byte & 0x7f) as usize) << shbits;
         shbits += 7;
         if shbits >= 57 {
-            panic!("illegal shift, shbits={}", shbits);
+            return Err(invalid_data("variable length too long"));
         }
     }
     read_nbytes(r, length)
@@ -132,6 +139,17 @@ pub(crate) fn read_variable_length_string<R: Read>(r: &mut R) -> io::Result<Stri
 
 const DECIMAL_DIGITS_PER_INTEGER: u8 = 9;
 
+/// The number of bytes a DECIMAL(precision, decimal) value takes
+pub(crate) fn new_decimal_length(precision: u8, decimal: u8) -> usize {
+    let compressed_byte_map = [0usize, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+    let integral = precision.saturating_sub(decimal);
+    let length = |digits: u8| {
+        usize::from(digits / DECIMAL_DIGITS_PER_INTEGER) * 4
+            + compressed_byte_map[usize::from(digits % DECIMAL_DIGITS_PER_INTEGER)]
+    };
+    length(integral) + length(decimal)
+}
+
 pub(crate) fn read_new_decimal<R: Read>(
     r: &mut R,
     precision: u8,
@@ -141,6 +159,12 @@ pub(crate) fn read_new_decimal<R: Read>(
     // is a transliteration of https://github.com/jeremycole/mysql_binlog/blob/master/lib/mysql_binlog/binlog_field_parser.rb#L233
     // because this format is bananas
     let compressed_byte_map = [0usize, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+    if precision == 0 || decimal > precision {
+        return Err(DecimalParseError::InvalidPrecision {
+            precision,
+            scale: decimal,
+        });
+    }
     let integral = precision - decimal;
     let uncompressed_integers: usize = (integral / DECIMAL_DIGITS_PER_INTEGER).into();
     let uncompressed_decimals: usize = (decimal / DECIMAL_DIGITS_PER_INTEGER).into();
@@ -149,18 +173,17 @@ pub(crate) fn read_new_decimal<R: Read>(
     let compressed_decimals: usize =
         decimal as usize - (uncompressed_decimals * DECIMAL_DIGITS_PER_INTEGER as usize);
 
-    let bytes_to_read: usize = uncompressed_integers * 4
-        + compressed_byte_map[compressed_integers]
-        + uncompressed_decimals * 4
-        + compressed_byte_map[compressed_decimals];
-
-    let mut buf = read_nbytes(r, bytes_to_read)?;
+    let mut buf = read_nbytes(r, new_decimal_length(precision, decimal))?;
 
     let mut components = Vec::new();
 
     let is_negative = (buf[0] & 0x80) == 0;
     buf[0] ^= 0x80;
     if is_negative {
+        // negative values are stored with every byte inverted
+        for b in buf.iter_mut() {
+            *b = !*b;
+        }
         components.push("-".to_owned());
     }
     let mut r = Cursor::new(buf);
@@ -174,28 +197,38 @@ pub(crate) fn read_new_decimal<R: Read>(
     }
     components.push(".".to_owned());
     for _ in 0..uncompressed_decimals {
-        components.push(format!("{:09}", r.read_u32::<LittleEndian>()?));
+        components.push(format!("{:09}", r.read_u32::<BigEndian>()?));
     }
     if compressed_decimals != 0 {
-        components.push(
-            read_known_length_integer_be(&mut r, compressed_byte_map[compressed_decimals])?
-                .to_string(),
-        )
+        components.push(format!(
+            "{:0width$}",
+            read_known_length_integer_be(&mut r, compressed_byte_map[compressed_decimals])?,
+            width = compressed_decimals
+        ))
     }
     let decimal = components.join("").parse::<BigDecimal>()?;
     Ok(decimal)
 }
 
+/// The number of bytes the fractional seconds of a temporal value with `pack_length` digits take
+pub(crate) fn subsecond_length(pack_length: u8) -> usize {
+    match pack_length {
+        1..=6 => usize::from(pack_length + 1) / 2,
+        _ => 0,
+    }
+}
+
+/// The fractional seconds of a DATETIME2 or TIMESTAMP2 value in microseconds; one byte is stored
+/// per two digits of precision
 pub(crate) fn read_datetime_subsecond_part<R: Read>(r: &mut R, pack_length: u8) -> io::Result<u32> {
     Ok(match pack_length {
-        0 => 0u32,
-        1 | 2 => read_known_length_integer_be(r, 1)? as u32,
-        3 | 4 => read_known_length_integer_be(r, 2)? as u32,
-        5 | 6 => read_known_length_integer_be(r, 3)? as u32,
+        1 | 2 => u32::from(r.read_u8()?) * 10_000,
+        3 | 4 => u32::from(r.read_u16::<BigEndian>()?) * 100,
+        5 | 6 => r.read_u24::<BigEndian>()?,
         _ => 0u32,
     })
 }
-pub(crate) fn decode_bit<R: Read>(r: &mut R, nbits: u16, length: u8) -> io::Result<i64> {
+pub(crate) fn decode_bit<R: Read>(r: &mut R, nbits: u16, length: u16) -> io::Result<i64> {
     if nbits > 1 {
         match length {
             1..=8 => {
@@ -259,7 +292,7 @@ mod tests {
             read_new_decimal(&mut uut, 10, 0).expect("should parse"),
             one
         );
-        let mut uut = Cursor::new(vec![0x80, 0x00, 0x01, 0x00, 0x00]);
+        let mut uut = Cursor::new(vec![0x80, 0x27, 0x10]);
         let zero_point_one = "0.100".parse::<BigDecimal>().unwrap();
         assert_eq!(
             read_new_decimal(&mut uut, 5, 5).expect("should parse"),
@@ -271,6 +304,31 @@ mod tests {
             read_new_decimal(&mut uut, 10, 5).expect("should parse"),
             expected
         );
+        // DECIMAL(12, 10) -1.0000000005: inverted bytes, a full fraction group and a
+        // compressed one with a leading zero
+        let mut uut = Cursor::new(vec![0x7e, 0xff, 0xff, 0xff, 0xff, 0xfa]);
+        let expected = "-1.0000000005".parse::<BigDecimal>().unwrap();
+        assert_eq!(
+            read_new_decimal(&mut uut, 12, 10).expect("should parse"),
+            expected
+        );
+        // the examples of decimal2bin() in MySQL's strings/decimal.cc, DECIMAL(14, 4)
+        for (bytes, expected) in &[
+            (
+                vec![0x81, 0x0d, 0xfb, 0x38, 0xd2, 0x04, 0xd2],
+                "1234567890.1234",
+            ),
+            (
+                vec![0x7e, 0xf2, 0x04, 0xc7, 0x2d, 0xfb, 0x2d],
+                "-1234567890.1234",
+            ),
+        ] {
+            let mut uut = Cursor::new(bytes.clone());
+            assert_eq!(
+                read_new_decimal(&mut uut, 14, 4).expect("should parse"),
+                expected.parse::<BigDecimal>().unwrap()
+            );
+        }
     }
 
     #[test]
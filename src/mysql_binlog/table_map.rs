@@ -1,13 +1,73 @@
 use std::collections::BTreeMap;
+use std::sync::Arc;
 
 use crate::mysql_binlog::column_types::ColumnType;
 
+#[derive(Debug, Default, Clone)]
+/// Optional metadata appended to a TableMapEvent by MySQL 8.0 (see `binlog_row_metadata`).
+/// Every field is empty when the server did not send it.
+pub struct TableMetadata {
+    /// One name per column
+    pub column_names: Vec<String>,
+    /// One flag per column; only ever set for numeric columns
+    pub unsigned_columns: Vec<bool>,
+    /// Indexes of the primary key columns, in key order
+    pub primary_key: Vec<usize>,
+    /// The labels of each ENUM column, in column order
+    pub enum_values: Vec<Vec<String>>,
+    /// The labels of each SET column, in column order
+    pub set_values: Vec<Vec<String>>,
+}
+
 #[derive(Debug)]
 /// Opaque reference to a table map, intended to be consumed by [`Event`]
 pub struct SingleTableMap {
     pub(crate) schema_name: String,
     pub(crate) table_name: String,
     pub(crate) columns: Vec<ColumnType>,
+    pub(crate) metadata: TableMetadata,
+}
+
+impl SingleTableMap {
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn columns(&self) -> &[ColumnType] {
+        &self.columns
+    }
+
+    pub fn metadata(&self) -> &TableMetadata {
+        &self.metadata
+    }
+
+    /// The labels of column `index`, if it is an ENUM or SET column and the metadata carries
+    /// them
+    pub fn labels(&self, index: usize) -> Option<&[String]> {
+        let before = &self.columns[..index.min(self.columns.len())];
+        let labels = match self.columns.get(index)? {
+            ColumnType::Enum(_) => {
+                let n = before
+                    .iter()
+                    .filter(|c| matches!(c, ColumnType::Enum(_)))
+                    .count();
+                self.metadata.enum_values.get(n)
+            }
+            ColumnType::Set(_) => {
+                let n = before
+                    .iter()
+                    .filter(|c| matches!(c, ColumnType::Set(_)))
+                    .count();
+                self.metadata.set_values.get(n)
+            }
+            _ => None,
+        };
+        labels.map(Vec::as_slice)
+    }
 }
 
 /// A MySQL binary log includes Table Map events; the first time a table is referenced in a given
@@ -15,7 +75,7 @@ pub struct SingleTableMap {
 /// binlog-unique identifier. The TableMap object is used to keep track of that mapping.
 #[derive(Default)]
 pub struct TableMap {
-    inner: BTreeMap<u64, SingleTableMap>,
+    inner: BTreeMap<u64, Arc<SingleTableMap>>,
 }
 
 impl TableMap {
@@ -31,16 +91,48 @@ impl TableMap {
         schema_name: String,
         table_name: String,
         columns: Vec<ColumnType>,
+    ) {
+        self.handle_with_metadata(
+            table_id,
+            schema_name,
+            table_name,
+            columns,
+            TableMetadata::default(),
+        )
+    }
+
+    pub fn handle_with_metadata(
+        &mut self,
+        table_id: u64,
+        schema_name: String,
+        table_name: String,
+        columns: Vec<ColumnType>,
+        metadata: TableMetadata,
     ) {
         let map = SingleTableMap {
             schema_name,
             table_name,
             columns,
+            metadata,
         };
-        self.inner.insert(table_id, map);
+        self.inner.insert(table_id, Arc::new(map));
     }
 
     pub fn get(&self, table_id: u64) -> Option<&SingleTableMap> {
-        self.inner.get(&table_id)
+        self.inner.get(&table_id).map(|t| t.as_ref())
+    }
+
+    /// Like [`TableMap::get`], but returns a shared handle that outlives this map
+    pub fn get_shared(&self, table_id: u64) -> Option<Arc<SingleTableMap>> {
+        self.inner.get(&table_id).cloned()
+    }
+
+    /// A map holding only `table_id`, sharing its definition with this one
+    pub(crate) fn snapshot(&self, table_id: u64) -> TableMap {
+        let mut snapshot = TableMap::new();
+        if let Some(table) = self.inner.get(&table_id) {
+            snapshot.inner.insert(table_id, Arc::clone(table));
+        }
+        snapshot
     }
 }
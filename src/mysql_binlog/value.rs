@@ -1,12 +1,18 @@
 use base64;
 
 use std::borrow::Cow;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
 
+use bigdecimal::BigDecimal;
 use serde::{Serialize, Serializer};
 use serde_derive::Serialize;
 use serde_json;
 
-#[derive(Debug)]
+use crate::mysql_binlog::errors::{GeometryParseError, ValueConversionError};
+use crate::mysql_binlog::geometry::Geometry;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Wrapper for the SQL BLOB (Binary Large OBject) type
 ///
 /// Serializes as Base64
@@ -18,6 +24,18 @@ impl From<Vec<u8>> for Blob {
     }
 }
 
+impl From<Blob> for Vec<u8> {
+    fn from(b: Blob) -> Self {
+        b.0
+    }
+}
+
+impl AsRef<[u8]> for Blob {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl Serialize for Blob {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -28,22 +46,51 @@ impl Serialize for Blob {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 /// Normalized representation of types which are present in MySQL
+///
+/// Values convert with `TryFrom` into the Rust types they fit: integers, `bool`, floats,
+/// [`BigDecimal`], `String`, `Vec<u8>` and, with the `chrono` feature, the chrono date and time
+/// types. Display prints them as MySQL literals, like [`value_literal`] without a column.
+///
+/// A value doesn't know its column: UNSIGNED integers are stored sign-extended, and fractional
+/// seconds are microseconds whatever the column's precision. Conversions and Display take them as
+/// they are; see [`value_literal`] for undoing the first and rounding the second to the column
+/// definition.
+///
+/// [`value_literal`]: crate::sql::value_literal
 pub enum MySQLValue {
     SignedInteger(i64),
     Float(f32),
     Double(f64),
     String(String),
-    Enum(i16),
+    /// The 1-based index of an ENUM value, 0 for the empty string MySQL stores for invalid
+    /// values, and its label when the table map metadata carries the labels
+    Enum {
+        index: u16,
+        label: Option<String>,
+    },
+    /// The labels of the members of a SET value. Without labels in the table map metadata, SET
+    /// values are `SignedInteger` bitmasks.
+    Set(Vec<String>),
     Blob(Blob),
+    /// A GEOMETRY value: its spatial reference system and the geometry in Well-Known Binary,
+    /// which [`MySQLValue::geometry`] parses
+    Geometry {
+        srid: u32,
+        wkb: Blob,
+    },
     Year(u32),
+    /// A DATE value; the zero date `0000-00-00` and dates with a zero month or day are kept as
+    /// they are
     Date {
         year: u32,
         month: u32,
         day: u32,
     },
+    /// A TIME value, a duration of up to 838:59:59.999999 either way
     Time {
+        negative: bool,
         hours: u32,
         minutes: u32,
         seconds: u32,
@@ -68,14 +115,470 @@ pub enum MySQLValue {
 }
 
 impl MySQLValue {
-    /// Turn this type into a serde_json::Value
+    /// Turn this type into a serde_json::Value: JSON documents as they are, NULL as `null` and
+    /// everything else as it serializes
     ///
     /// Tries to avoid round-tripping through Serialize if it can
-    pub(crate) fn as_value(&self) -> Result<Cow<serde_json::Value>, serde_json::error::Error> {
+    pub fn as_value(&self) -> Result<Cow<'_, serde_json::Value>, serde_json::error::Error> {
         match *self {
             MySQLValue::Json(ref j) => Ok(Cow::Borrowed(j)),
             MySQLValue::Null => Ok(Cow::Owned(serde_json::Value::Null)),
             ref j => Ok(Cow::Owned(serde_json::to_value(j)?)),
         }
     }
+
+    /// Parse a GEOMETRY value, for rendering as WKT or GeoJSON. `None` for other values.
+    pub fn geometry(&self) -> Option<Result<Geometry, GeometryParseError>> {
+        match self {
+            MySQLValue::Geometry { wkb, .. } => Some(Geometry::from_wkb(wkb.as_ref())),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, MySQLValue::Null)
+    }
+
+    /// The name of the variant, as used in conversion errors
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MySQLValue::SignedInteger(_) => "SignedInteger",
+            MySQLValue::Float(_) => "Float",
+            MySQLValue::Double(_) => "Double",
+            MySQLValue::String(_) => "String",
+            MySQLValue::Enum { .. } => "Enum",
+            MySQLValue::Set(_) => "Set",
+            MySQLValue::Blob(_) => "Blob",
+            MySQLValue::Geometry { .. } => "Geometry",
+            MySQLValue::Year(_) => "Year",
+            MySQLValue::Date { .. } => "Date",
+            MySQLValue::Time { .. } => "Time",
+            MySQLValue::DateTime { .. } => "DateTime",
+            MySQLValue::Json(_) => "Json",
+            MySQLValue::Decimal(_) => "Decimal",
+            MySQLValue::Timestamp { .. } => "Timestamp",
+            MySQLValue::Null => "Null",
+        }
+    }
+
+    fn conversion_error(&self, target: &'static str) -> ValueConversionError {
+        ValueConversionError {
+            value: self.kind(),
+            target,
+        }
+    }
+
+    /// The integer an integer, YEAR or ENUM value holds
+    fn integer(&self, target: &'static str) -> Result<i64, ValueConversionError> {
+        match *self {
+            MySQLValue::SignedInteger(i) => Ok(i),
+            MySQLValue::Year(y) => Ok(i64::from(y)),
+            MySQLValue::Enum { index, .. } => Ok(i64::from(index)),
+            _ => Err(self.conversion_error(target)),
+        }
+    }
+}
+
+/// A MySQL literal, like [`value_literal`](crate::sql::value_literal) without a column
+impl fmt::Display for MySQLValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&crate::sql::value_literal(self, None))
+    }
+}
+
+macro_rules! integer_conversions {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<&MySQLValue> for $t {
+                type Error = ValueConversionError;
+
+                fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+                    value
+                        .integer(stringify!($t))?
+                        .try_into()
+                        .map_err(|_| value.conversion_error(stringify!($t)))
+                }
+            }
+
+            impl TryFrom<MySQLValue> for $t {
+                type Error = ValueConversionError;
+
+                fn try_from(value: MySQLValue) -> Result<Self, Self::Error> {
+                    <$t>::try_from(&value)
+                }
+            }
+        )*
+    };
+}
+
+integer_conversions!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+/// Nonzero integers are true, like MySQL's BOOL
+impl TryFrom<&MySQLValue> for bool {
+    type Error = ValueConversionError;
+
+    fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+        Ok(value.integer("bool")? != 0)
+    }
+}
+
+impl TryFrom<&MySQLValue> for f64 {
+    type Error = ValueConversionError;
+
+    fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+        match *value {
+            MySQLValue::Float(f) => Ok(f64::from(f)),
+            MySQLValue::Double(d) => Ok(d),
+            MySQLValue::SignedInteger(i) => Ok(i as f64),
+            _ => Err(value.conversion_error("f64")),
+        }
+    }
+}
+
+impl TryFrom<&MySQLValue> for f32 {
+    type Error = ValueConversionError;
+
+    fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+        match *value {
+            MySQLValue::Float(f) => Ok(f),
+            _ => Err(value.conversion_error("f32")),
+        }
+    }
+}
+
+impl TryFrom<&MySQLValue> for BigDecimal {
+    type Error = ValueConversionError;
+
+    fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+        match *value {
+            MySQLValue::Decimal(ref d) => Ok(d.clone()),
+            MySQLValue::SignedInteger(i) => Ok(BigDecimal::from(i)),
+            _ => Err(value.conversion_error("BigDecimal")),
+        }
+    }
+}
+
+/// Strings, blobs which are UTF-8, as TEXT columns are logged as blobs, and labelled ENUM and SET
+/// values, the members of a SET separated by commas
+impl TryFrom<&MySQLValue> for String {
+    type Error = ValueConversionError;
+
+    fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+        match *value {
+            MySQLValue::String(ref s) => Ok(s.clone()),
+            MySQLValue::Enum {
+                label: Some(ref label),
+                ..
+            } => Ok(label.clone()),
+            MySQLValue::Set(ref labels) => Ok(labels.join(",")),
+            MySQLValue::Blob(ref b) => {
+                String::from_utf8(b.0.clone()).map_err(|_| value.conversion_error("String"))
+            }
+            _ => Err(value.conversion_error("String")),
+        }
+    }
+}
+
+impl TryFrom<MySQLValue> for String {
+    type Error = ValueConversionError;
+
+    fn try_from(value: MySQLValue) -> Result<Self, Self::Error> {
+        match value {
+            MySQLValue::String(s) => Ok(s),
+            MySQLValue::Blob(b) => String::from_utf8(b.0).map_err(|_| ValueConversionError {
+                value: "Blob",
+                target: "String",
+            }),
+            value => Err(value.conversion_error("String")),
+        }
+    }
+}
+
+/// The bytes of blobs and strings
+impl TryFrom<&MySQLValue> for Vec<u8> {
+    type Error = ValueConversionError;
+
+    fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+        match *value {
+            MySQLValue::Blob(ref b) => Ok(b.0.clone()),
+            MySQLValue::String(ref s) => Ok(s.as_bytes().to_vec()),
+            _ => Err(value.conversion_error("Vec<u8>")),
+        }
+    }
+}
+
+impl TryFrom<MySQLValue> for Vec<u8> {
+    type Error = ValueConversionError;
+
+    fn try_from(value: MySQLValue) -> Result<Self, Self::Error> {
+        match value {
+            MySQLValue::Blob(b) => Ok(b.0),
+            MySQLValue::String(s) => Ok(s.into_bytes()),
+            value => Err(value.conversion_error("Vec<u8>")),
+        }
+    }
+}
+
+macro_rules! owned_conversions {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<MySQLValue> for $t {
+                type Error = ValueConversionError;
+
+                fn try_from(value: MySQLValue) -> Result<Self, Self::Error> {
+                    <$t>::try_from(&value)
+                }
+            }
+        )*
+    };
+}
+
+owned_conversions!(bool, f64, f32, BigDecimal);
+
+#[cfg(feature = "chrono")]
+mod chrono_conversions {
+    use std::convert::TryFrom;
+
+    use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+    use super::MySQLValue;
+    use crate::mysql_binlog::errors::ValueConversionError;
+
+    /// DATE values; zero dates and dates with a zero month or day don't convert
+    impl TryFrom<&MySQLValue> for NaiveDate {
+        type Error = ValueConversionError;
+
+        fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+            match *value {
+                MySQLValue::Date { year, month, day } => {
+                    NaiveDate::from_ymd_opt(year as i32, month, day)
+                }
+                _ => None,
+            }
+            .ok_or_else(|| value.conversion_error("NaiveDate"))
+        }
+    }
+
+    /// TIME values within a day
+    impl TryFrom<&MySQLValue> for NaiveTime {
+        type Error = ValueConversionError;
+
+        fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+            match *value {
+                MySQLValue::Time {
+                    negative: false,
+                    hours,
+                    minutes,
+                    seconds,
+                    subseconds,
+                } => NaiveTime::from_hms_micro_opt(hours, minutes, seconds, subseconds),
+                _ => None,
+            }
+            .ok_or_else(|| value.conversion_error("NaiveTime"))
+        }
+    }
+
+    /// TIME values, which may be more than a day
+    impl TryFrom<&MySQLValue> for Duration {
+        type Error = ValueConversionError;
+
+        fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+            match *value {
+                MySQLValue::Time {
+                    negative,
+                    hours,
+                    minutes,
+                    seconds,
+                    subseconds,
+                } => {
+                    let duration = Duration::hours(i64::from(hours))
+                        + Duration::minutes(i64::from(minutes))
+                        + Duration::seconds(i64::from(seconds))
+                        + Duration::microseconds(i64::from(subseconds));
+                    Ok(if negative { -duration } else { duration })
+                }
+                _ => Err(value.conversion_error("Duration")),
+            }
+        }
+    }
+
+    /// DATETIME and DATE values, and TIMESTAMP values in UTC
+    impl TryFrom<&MySQLValue> for NaiveDateTime {
+        type Error = ValueConversionError;
+
+        fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+            match *value {
+                MySQLValue::DateTime {
+                    year,
+                    month,
+                    day,
+                    hour,
+                    minute,
+                    second,
+                    subsecond,
+                } => NaiveDate::from_ymd_opt(year as i32, month, day)
+                    .and_then(|date| date.and_hms_micro_opt(hour, minute, second, subsecond)),
+                MySQLValue::Date { year, month, day } => {
+                    NaiveDate::from_ymd_opt(year as i32, month, day)
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                }
+                MySQLValue::Timestamp {
+                    unix_time,
+                    subsecond,
+                } if subsecond < 1_000_000 => {
+                    NaiveDateTime::from_timestamp_opt(i64::from(unix_time), subsecond * 1000)
+                }
+                _ => None,
+            }
+            .ok_or_else(|| value.conversion_error("NaiveDateTime"))
+        }
+    }
+
+    /// TIMESTAMP values
+    impl TryFrom<&MySQLValue> for DateTime<Utc> {
+        type Error = ValueConversionError;
+
+        fn try_from(value: &MySQLValue) -> Result<Self, Self::Error> {
+            match *value {
+                MySQLValue::Timestamp { .. } => NaiveDateTime::try_from(value)
+                    .map(|naive| DateTime::from_utc(naive, Utc))
+                    .map_err(|_| value.conversion_error("DateTime<Utc>")),
+                _ => Err(value.conversion_error("DateTime<Utc>")),
+            }
+        }
+    }
+
+    owned_conversions!(NaiveDate, NaiveTime, Duration, NaiveDateTime, DateTime<Utc>);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use bigdecimal::BigDecimal;
+
+    use super::MySQLValue;
+    use crate::mysql_binlog::errors::ValueConversionError;
+
+    #[test]
+    fn test_conversions() {
+        let value = MySQLValue::SignedInteger(300);
+        assert_eq!(i64::try_from(&value), Ok(300));
+        assert_eq!(u16::try_from(&value), Ok(300));
+        assert_eq!(
+            i8::try_from(&value),
+            Err(ValueConversionError {
+                value: "SignedInteger",
+                target: "i8"
+            })
+        );
+        assert_eq!(u64::try_from(MySQLValue::SignedInteger(-1)).ok(), None);
+        assert_eq!(bool::try_from(&value), Ok(true));
+        assert_eq!(f64::try_from(&value), Ok(300.0));
+        assert_eq!(
+            BigDecimal::try_from(&value),
+            Ok("300".parse::<BigDecimal>().unwrap())
+        );
+        assert_eq!(u32::try_from(MySQLValue::Year(2021)), Ok(2021));
+        assert_eq!(f32::try_from(MySQLValue::Float(1.5)), Ok(1.5));
+        assert!(f32::try_from(MySQLValue::Double(1.5)).is_err());
+
+        let text = MySQLValue::Blob(b"caf\xc3\xa9".to_vec().into());
+        assert_eq!(String::try_from(&text).unwrap(), "café");
+        assert_eq!(String::try_from(text.clone()).unwrap(), "café");
+        assert!(String::try_from(MySQLValue::Blob(vec![0xff].into())).is_err());
+        assert_eq!(
+            Vec::<u8>::try_from(MySQLValue::String("ab".to_owned())),
+            Ok(b"ab".to_vec())
+        );
+        let err = i64::try_from(MySQLValue::Null).unwrap_err();
+        assert_eq!(err.to_string(), "cannot convert Null to i64");
+        assert!(MySQLValue::Null.is_null());
+        assert_eq!(text, text.clone());
+        assert_ne!(MySQLValue::Double(f64::NAN), MySQLValue::Double(f64::NAN));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(MySQLValue::SignedInteger(-7).to_string(), "-7");
+        assert_eq!(
+            MySQLValue::String("it's".to_owned()).to_string(),
+            "'it\\'s'"
+        );
+        assert_eq!(
+            MySQLValue::Date {
+                year: 2021,
+                month: 7,
+                day: 1
+            }
+            .to_string(),
+            "'2021-07-01'"
+        );
+        assert_eq!(MySQLValue::Null.to_string(), "NULL");
+        assert_eq!(
+            MySQLValue::Json(serde_json::json!({"a": 1}))
+                .as_value()
+                .unwrap()
+                .into_owned(),
+            serde_json::json!({"a": 1})
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono_conversions() {
+        use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+        let datetime = MySQLValue::DateTime {
+            year: 2021,
+            month: 7,
+            day: 1,
+            hour: 12,
+            minute: 30,
+            second: 5,
+            subsecond: 250_000,
+        };
+        assert_eq!(
+            NaiveDateTime::try_from(&datetime).unwrap(),
+            NaiveDate::from_ymd(2021, 7, 1).and_hms_micro(12, 30, 5, 250_000)
+        );
+        let timestamp = MySQLValue::Timestamp {
+            unix_time: 1_625_142_605,
+            subsecond: 250_000,
+        };
+        assert_eq!(
+            DateTime::<Utc>::try_from(&timestamp).unwrap().naive_utc(),
+            NaiveDateTime::try_from(&datetime).unwrap()
+        );
+        let time = MySQLValue::Time {
+            negative: false,
+            hours: 25,
+            minutes: 0,
+            seconds: 1,
+            subseconds: 0,
+        };
+        assert!(NaiveTime::try_from(&time).is_err());
+        assert_eq!(
+            Duration::try_from(time).unwrap(),
+            Duration::seconds(25 * 3600 + 1)
+        );
+        let negative = MySQLValue::Time {
+            negative: true,
+            hours: 0,
+            minutes: 0,
+            seconds: 1,
+            subseconds: 500_000,
+        };
+        assert!(NaiveTime::try_from(&negative).is_err());
+        assert_eq!(
+            Duration::try_from(negative).unwrap(),
+            Duration::microseconds(-1_500_000)
+        );
+        assert!(NaiveDate::try_from(MySQLValue::Date {
+            year: 0,
+            month: 0,
+            day: 0
+        })
+        .is_err());
+    }
 }
@@ -0,0 +1,73 @@
+use crate::mysql_binlog::MariadbGtid;
+use crate::pkg::err::Result;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A MariaDB GTID position: at most one `domain-server-sequence` per replication domain,
+/// as used by `@slave_connect_state` and `gtid_slave_pos`
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct MariadbGtidSet(BTreeMap<u32, MariadbGtid>);
+
+impl MariadbGtidSet {
+    pub fn update_gtid(&mut self, gtid: MariadbGtid) {
+        self.0.insert(gtid.domain_id, gtid);
+    }
+    pub fn get(&self, domain_id: u32) -> Option<&MariadbGtid> {
+        self.0.get(&domain_id)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// Parse comma-separated `domain-server-sequence` GTIDs, failing on any which is not
+    /// one. An empty string is the empty position.
+    pub fn parse(input: &str) -> Result<MariadbGtidSet> {
+        let mut rsl = MariadbGtidSet::default();
+        if input.trim().is_empty() {
+            return Ok(rsl);
+        }
+        for data in input.split(',') {
+            match data.parse::<MariadbGtid>() {
+                Ok(d) => rsl.update_gtid(d),
+                Err(e) => {
+                    return Err(format!("invalid MariaDB GTID {:?}: {}", data.trim(), e).into())
+                }
+            };
+        }
+        Ok(rsl)
+    }
+}
+impl FromStr for MariadbGtidSet {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        MariadbGtidSet::parse(s)
+    }
+}
+impl fmt::Display for MariadbGtidSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let gtids: Vec<String> = self.0.values().map(|g| g.to_string()).collect();
+        write!(f, "{}", gtids.join(","))
+    }
+}
+
+#[test]
+fn test_mariadb_gtid_set_parse() {
+    for bad in &["bogus", "0-1-100,bogus", "0-1", "0-1-100,", "0-1-x"] {
+        assert!(
+            MariadbGtidSet::parse(bad).is_err(),
+            "{:?} should not parse",
+            bad
+        );
+    }
+    assert!(MariadbGtidSet::parse("").unwrap().is_empty());
+    let mut g: MariadbGtidSet = "0-1-100, 1-2-200".parse().unwrap();
+    assert_eq!(g.to_string(), "0-1-100,1-2-200");
+    g.update_gtid(MariadbGtid {
+        domain_id: 0,
+        server_id: 3,
+        sequence: 101,
+    });
+    assert_eq!(g.get(0).unwrap().server_id, 3);
+    assert_eq!(g.to_string(), "0-3-101,1-2-200");
+}
@@ -1,4 +1,5 @@
 pub mod err;
 pub mod event;
+pub mod mariadb_gtid;
 pub mod mysql_gtid;
 pub mod ops;
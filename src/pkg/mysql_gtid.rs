@@ -1,7 +1,7 @@
 use crate::pkg::err::Result;
-use byteorder::{LittleEndian, WriteBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::collections::HashMap;
-use std::io::Write;
+use std::io::{Cursor, Read, Write};
 use std::str::FromStr;
 
 #[derive(Default, Debug, Clone)]
@@ -16,14 +16,83 @@ impl Gtid {
             gtid_data.write_all(uid.as_bytes())?;
             gtid_data.write_i64::<LittleEndian>(range.len() as i64)?;
             for i_item in range {
+                // intervals are sent end-exclusive
                 gtid_data.write_i64::<LittleEndian>(i_item.0)?;
-                gtid_data.write_i64::<LittleEndian>(i_item.1)?;
+                gtid_data.write_i64::<LittleEndian>(i_item.1 + 1)?;
             }
         }
         Ok(gtid_data)
     }
+    /// Inverse of `encode`, reads the GTID set a replica sends with COM_BINLOG_DUMP_GTID
+    pub fn decode(data: &[u8]) -> Result<Gtid> {
+        let mut cursor = Cursor::new(data);
+        let mut rsl: HashMap<String, Vec<(i64, i64)>> = Default::default();
+        let sid_count = cursor.read_u64::<LittleEndian>()?;
+        for _ in 0..sid_count {
+            let mut uid = [0u8; 16];
+            cursor.read_exact(&mut uid)?;
+            let interval_count = cursor.read_i64::<LittleEndian>()?;
+            let mut range = vec![];
+            for _ in 0..interval_count {
+                let start = cursor.read_i64::<LittleEndian>()?;
+                let end = cursor.read_i64::<LittleEndian>()?;
+                range.push((start, end - 1));
+            }
+            rsl.insert(uuid::Uuid::from_bytes(uid).to_string(), range);
+        }
+        Ok(Gtid(rsl))
+    }
+    /// A set from end-exclusive intervals, as a PreviousGtidsLogEvent carries them
+    pub fn from_intervals(sets: &[(uuid::Uuid, Vec<(u64, u64)>)]) -> Gtid {
+        let mut rsl: HashMap<String, Vec<(i64, i64)>> = Default::default();
+        for (uid, intervals) in sets {
+            rsl.entry(uid.to_string())
+                .or_default()
+                .extend(intervals.iter().map(|(s, e)| (*s as i64, *e as i64 - 1)));
+        }
+        Gtid(rsl)
+    }
+    /// Whether every transaction of this set is part of `other`
+    pub fn is_subset(&self, other: &Gtid) -> bool {
+        self.0.iter().all(|(id, ranges)| {
+            let mut covering = other.0.get(id).cloned().unwrap_or_default();
+            covering.sort_unstable();
+            ranges.iter().all(|&(start, end)| {
+                // walk the covering ranges as far as they reach without a gap
+                let mut next = start;
+                for &(s, e) in &covering {
+                    if s <= next && e >= next {
+                        next = e + 1;
+                    }
+                }
+                next > end
+            })
+        })
+    }
+    /// Like `Gtid::from`, but fails on a malformed set instead of skipping the bad parts
+    pub fn parse(input: &str) -> Result<Gtid> {
+        let mut rsl: HashMap<String, Vec<(i64, i64)>> = Default::default();
+        for data in input.trim().split(',').filter(|d| !d.trim().is_empty()) {
+            let (uuid, ranges) = parse_uuid_start_ends(data)?;
+            if uuid::Uuid::parse_str(&uuid).is_err()
+                || ranges.is_empty()
+                || ranges.iter().any(|r| r.0 < 1 || r.0 > r.1)
+            {
+                return Err(Box::from(format!("invalid GTID set {:?}", data.trim())));
+            }
+            rsl.entry(uuid).or_default().extend(ranges);
+        }
+        Ok(Gtid(rsl))
+    }
     // todo
     pub fn update_gitd(&mut self, _other: &Gtid) {}
+    /// Whether the transaction `uuid:gno` is part of this set
+    pub fn contains(&self, uuid: &str, gno: i64) -> bool {
+        match self.0.get(uuid) {
+            Some(ranges) => ranges.iter().any(|r| r.0 <= gno && gno <= r.1),
+            None => false,
+        }
+    }
 }
 impl<S: AsRef<str>> From<S> for Gtid {
     fn from(input: S) -> Gtid {
@@ -43,7 +112,8 @@ fn parse_uuid_start_ends<S: AsRef<str>>(input: S) -> Result<(String, Vec<(i64, i
     for data in input.as_ref().trim().split(':') {
         split_count += 1;
         if split_count == 0 {
-            rsl.0 = data.to_string();
+            // servers print UUIDs in lowercase, so keys compare equal whatever case was typed
+            rsl.0 = data.trim().to_ascii_lowercase();
         } else {
             let mut range = (0, 0);
             let mut s_count = -1;
@@ -51,6 +121,8 @@ fn parse_uuid_start_ends<S: AsRef<str>>(input: S) -> Result<(String, Vec<(i64, i
                 s_count += 1;
                 if s_count == 0 {
                     range.0 = data_.parse::<i64>()?;
+                    // a single transaction, `uuid:7`
+                    range.1 = range.0;
                 } else {
                     range.1 = data_.parse::<i64>()?
                 }
@@ -67,3 +139,75 @@ fn test_gtid_from() {
     dbg!(g.encode().unwrap());
     dbg!(g);
 }
+
+#[test]
+fn test_gtid_encode() {
+    // the COM_BINLOG_DUMP_GTID payload mysqld's Gtid_set::add_gtid_encoding reads,
+    // `3E11FA47-71CA-11E1-9E33-C80AA9429562:1-10:12` sent as [1, 11) and [12, 13)
+    let g = Gtid::from("3e11fa47-71ca-11e1-9e33-c80aa9429562:1-10:12");
+    let mut expected = vec![];
+    expected.extend_from_slice(&1u64.to_le_bytes());
+    expected.extend_from_slice(&[
+        0x3e, 0x11, 0xfa, 0x47, 0x71, 0xca, 0x11, 0xe1, 0x9e, 0x33, 0xc8, 0x0a, 0xa9, 0x42, 0x95,
+        0x62,
+    ]);
+    for n in &[2i64, 1, 11, 12, 13] {
+        expected.extend_from_slice(&n.to_le_bytes());
+    }
+    assert_eq!(g.encode().unwrap(), expected);
+}
+
+#[test]
+fn test_gtid_parse() {
+    let g = Gtid::parse(
+        "0575a804-6403-11ea-8d3d-e454e8d4a4fe:1-10, 0575a804-6403-11ea-8d3d-e454e8d4a4fe:12",
+    )
+    .unwrap();
+    assert!(g.contains("0575a804-6403-11ea-8d3d-e454e8d4a4fe", 12));
+    assert!(!g.contains("0575a804-6403-11ea-8d3d-e454e8d4a4fe", 11));
+    let g = Gtid::parse("0575A804-6403-11EA-8D3D-E454E8D4A4FE:3").unwrap();
+    assert!(g.contains("0575a804-6403-11ea-8d3d-e454e8d4a4fe", 3));
+    for bad in &[
+        "not-a-gtid",
+        "0575a804-6403-11ea-8d3d-e454e8d4a4fe",
+        "0575a804-6403-11ea-8d3d-e454e8d4a4fe:5-2",
+    ] {
+        assert!(Gtid::parse(bad).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_gtid_contains() {
+    let g = Gtid::from("0575a804-6403-11ea-8d3d-e454e8d4a4fe:1-10:12");
+    let uuid = "0575a804-6403-11ea-8d3d-e454e8d4a4fe";
+    assert!(g.contains(uuid, 1));
+    assert!(g.contains(uuid, 10));
+    assert!(!g.contains(uuid, 11));
+    assert!(g.contains(uuid, 12));
+    assert!(!g.contains("58e3d520-98aa-11ea-a85e-5254000fe6fe", 1));
+}
+
+#[test]
+fn test_gtid_encode_decode() {
+    let g = Gtid::from("0575a804-6403-11ea-8d3d-e454e8d4a4fe:1-10:12");
+    let decoded = Gtid::decode(&g.encode().unwrap()).unwrap();
+    let uuid = "0575a804-6403-11ea-8d3d-e454e8d4a4fe";
+    assert!(decoded.contains(uuid, 10));
+    assert!(!decoded.contains(uuid, 11));
+    assert!(decoded.contains(uuid, 12));
+    assert!(!decoded.contains(uuid, 13));
+}
+
+#[test]
+fn test_gtid_is_subset() {
+    let uuid = "0575a804-6403-11ea-8d3d-e454e8d4a4fe";
+    let executed = Gtid::from(format!("{}:1-10:12-20", uuid));
+    assert!(Gtid::from(format!("{}:1-5", uuid)).is_subset(&executed));
+    assert!(Gtid::from(format!("{}:12-20", uuid)).is_subset(&executed));
+    assert!(!Gtid::from(format!("{}:1-12", uuid)).is_subset(&executed));
+    assert!(!Gtid::from("58e3d520-98aa-11ea-a85e-5254000fe6fe:1").is_subset(&executed));
+    assert!(Gtid::default().is_subset(&executed));
+    let previous = Gtid::from_intervals(&[(uuid::Uuid::from_str(uuid).unwrap(), vec![(1, 11)])]);
+    assert!(previous.is_subset(&executed));
+    assert!(!previous.contains(uuid, 11));
+}
@@ -0,0 +1,921 @@
+//! Serves a directory of binlog files to MySQL replicas, as an intermediate tier between a
+//! source and its replicas (in the spirit of Ripple or the MaxScale binlog router).
+//!
+//! The directory is typically the one a [`Runner`] writes with `enable_relay_log`: files
+//! named after the source's and ending in a RotateEvent, the last one growing as events
+//! arrive. A replica connecting with `MASTER_AUTO_POSITION=1` is started from the newest file
+//! whose PreviousGtidsLogEvent it has fully executed, and the transactions it already has are
+//! left out. At the end of the last file the dump waits for more events, sending heartbeats
+//! at the period the replica asked for.
+//!
+//! [`Runner`]: crate::client::sync::Runner
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use mysql::consts::Command;
+
+use crate::mysql_binlog::binlog_file::BinlogFile;
+use crate::mysql_binlog::encoder::{frame_event, with_checksum_algorithm, BINLOG_MAGIC};
+use crate::mysql_binlog::event::{Event, EventData, TypeCode};
+use crate::pkg::mysql_gtid::Gtid;
+use crate::server::protocol::{
+    auth_switch_packet, eof_packet, err_packet, handshake_packet, native_password_scramble,
+    new_nonce, ok_packet, HandshakeResponse, PacketStream, AUTH_PLUGIN_NAME,
+};
+
+const COM_QUIT: u8 = Command::COM_QUIT as u8;
+const COM_INIT_DB: u8 = Command::COM_INIT_DB as u8;
+const COM_QUERY: u8 = Command::COM_QUERY as u8;
+const COM_PING: u8 = Command::COM_PING as u8;
+const COM_BINLOG_DUMP: u8 = Command::COM_BINLOG_DUMP as u8;
+const COM_REGISTER_SLAVE: u8 = Command::COM_REGISTER_SLAVE as u8;
+const COM_BINLOG_DUMP_GTID: u8 = Command::COM_BINLOG_DUMP_GTID as u8;
+
+// BINLOG_DUMP_NON_BLOCK, answer with EOF instead of waiting at the end of the binlog
+const BINLOG_DUMP_NON_BLOCK: u16 = 0x01;
+// LOG_EVENT_ARTIFICIAL_F, set on the fake RotateEvent a dump starts each file with
+const LOG_EVENT_ARTIFICIAL_F: u16 = 0x20;
+const ER_ACCESS_DENIED_ERROR: u16 = 1045;
+const ER_UNKNOWN_COM_ERROR: u16 = 1047;
+const ER_PARSE_ERROR: u16 = 1064;
+const ER_UNKNOWN_SYSTEM_VARIABLE: u16 = 1193;
+const ER_MASTER_FATAL_ERROR_READING_BINLOG: u16 = 1236;
+
+/// How the server presents itself and what it serves
+#[derive(Debug, Clone)]
+pub struct BinlogServerConfig {
+    /// Directory holding the binlog files to serve, in file name order
+    pub binlog_dir: PathBuf,
+    /// Sent in the handshake and returned by `SELECT VERSION()`
+    pub server_version: String,
+    /// Must differ from the replicas' own server ids
+    pub server_id: u32,
+    pub server_uuid: String,
+    /// User and password replicas log in with. `None` lets anyone who can connect read
+    /// every binlog file served, so only set it on a trusted network.
+    pub credentials: Option<(String, String)>,
+    /// Wait for more events at the end of the last file instead of hanging up
+    pub follow: bool,
+    /// How often the last file is checked for new events while following
+    pub poll_interval: Duration,
+    /// Answers the queries the server rejects otherwise; the mock server installs one
+    pub(crate) query_hook: Option<QueryHook>,
+}
+
+impl BinlogServerConfig {
+    /// Serve `binlog_dir` to replicas logging in as `user` with `password`
+    pub fn new<P: Into<PathBuf>>(binlog_dir: P, user: &str, password: &str) -> Self {
+        BinlogServerConfig {
+            binlog_dir: binlog_dir.into(),
+            server_version: "5.7.30-log".to_owned(),
+            server_id: 1,
+            server_uuid: "0575a804-6403-11ea-8d3d-e454e8d4a4fe".to_owned(),
+            credentials: Some((user.to_owned(), password.to_owned())),
+            follow: true,
+            poll_interval: Duration::from_millis(100),
+            query_hook: None,
+        }
+    }
+}
+
+/// What a [`QueryHook`] answers a query with
+pub(crate) enum QueryReply {
+    Ok,
+    ResultSet(Vec<String>, Vec<Vec<Option<String>>>),
+}
+
+type QueryFn = dyn Fn(&str) -> io::Result<Option<QueryReply>> + Send + Sync;
+
+/// Called with the queries the server does not understand, before they are rejected;
+/// `None` leaves the query rejected
+#[derive(Clone)]
+pub(crate) struct QueryHook(pub(crate) Arc<QueryFn>);
+
+impl fmt::Debug for QueryHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("QueryHook")
+    }
+}
+
+/// A listening binlog server. Every replica is served on its own thread.
+pub struct BinlogServer {
+    listener: TcpListener,
+    config: Arc<BinlogServerConfig>,
+    connection_id: AtomicU32,
+}
+
+impl BinlogServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A, config: BinlogServerConfig) -> io::Result<Self> {
+        Ok(BinlogServer {
+            listener: TcpListener::bind(addr)?,
+            config: Arc::new(config),
+            connection_id: AtomicU32::new(0),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept replicas until the listener fails
+    pub fn serve(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let connection_id = self.connection_id.fetch_add(1, Ordering::SeqCst) + 1;
+            let config = self.config.clone();
+            thread::spawn(move || serve_connection(stream, config, connection_id));
+        }
+        Ok(())
+    }
+}
+
+/// Serve one client until it quits, its dump ends or the connection fails
+pub(crate) fn serve_connection(
+    stream: TcpStream,
+    config: Arc<BinlogServerConfig>,
+    connection_id: u32,
+) {
+    if let Ok(session) = Session::new(stream, config, connection_id) {
+        let _ = session.run();
+    }
+}
+
+struct Session {
+    stream: PacketStream<TcpStream>,
+    config: Arc<BinlogServerConfig>,
+    connection_id: u32,
+    // user variables, `@master_binlog_checksum` and `@master_heartbeat_period` among them
+    user_variables: HashMap<String, String>,
+    checksum: bool,
+    heartbeat_period: Option<Duration>,
+    last_sent: Instant,
+}
+
+impl Session {
+    fn new(
+        stream: TcpStream,
+        config: Arc<BinlogServerConfig>,
+        connection_id: u32,
+    ) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Session {
+            stream: PacketStream::new(stream),
+            config,
+            connection_id,
+            user_variables: HashMap::new(),
+            checksum: false,
+            heartbeat_period: None,
+            last_sent: Instant::now(),
+        })
+    }
+
+    fn run(mut self) -> io::Result<()> {
+        if !self.authenticate()? {
+            return Ok(());
+        }
+        while let Some(packet) = self.stream.read_packet()? {
+            match packet.first().copied() {
+                Some(COM_QUIT) => return Ok(()),
+                Some(COM_QUERY) => self.query(&String::from_utf8_lossy(&packet[1..]))?,
+                Some(COM_PING) | Some(COM_INIT_DB) | Some(COM_REGISTER_SLAVE) => {
+                    self.reply(&ok_packet())?
+                }
+                Some(COM_BINLOG_DUMP) => return self.binlog_dump(&packet[1..]),
+                Some(COM_BINLOG_DUMP_GTID) => return self.binlog_dump_gtid(&packet[1..]),
+                _ => self.reply(&err_packet(
+                    ER_UNKNOWN_COM_ERROR,
+                    "08S01",
+                    "Unknown command",
+                ))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn authenticate(&mut self) -> io::Result<bool> {
+        let nonce = new_nonce()?;
+        let handshake = handshake_packet(self.connection_id, &self.config.server_version, &nonce);
+        self.reply(&handshake)?;
+        let response = match self.stream.read_packet()? {
+            Some(response) => HandshakeResponse::parse(&response)?,
+            None => return Ok(false),
+        };
+        let mut auth_response = response.auth_response;
+        if response
+            .auth_plugin
+            .as_deref()
+            .is_some_and(|plugin| !plugin.is_empty() && plugin != AUTH_PLUGIN_NAME)
+        {
+            self.reply(&auth_switch_packet(&nonce))?;
+            auth_response = match self.stream.read_packet()? {
+                Some(auth_response) => auth_response,
+                None => return Ok(false),
+            };
+        }
+        let allowed = match self.config.credentials {
+            Some((ref user, ref password)) => {
+                response.user == *user
+                    && auth_response == native_password_scramble(password, &nonce)
+            }
+            None => true,
+        };
+        if allowed {
+            self.reply(&ok_packet())?;
+        } else {
+            self.reply(&err_packet(
+                ER_ACCESS_DENIED_ERROR,
+                "28000",
+                &format!("Access denied for user '{}'", response.user),
+            ))?;
+        }
+        Ok(allowed)
+    }
+
+    fn reply(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.stream.write_packet(packet)?;
+        self.stream.flush()
+    }
+
+    fn variables(&self) -> Vec<(String, String)> {
+        vec![
+            ("binlog_checksum", "CRC32".to_owned()),
+            ("binlog_format", "ROW".to_owned()),
+            ("character_set_server", "utf8".to_owned()),
+            ("collation_server", "utf8_general_ci".to_owned()),
+            ("gtid_mode", "ON".to_owned()),
+            ("log_bin", "ON".to_owned()),
+            ("max_allowed_packet", "67108864".to_owned()),
+            ("server_id", self.config.server_id.to_string()),
+            ("server_uuid", self.config.server_uuid.clone()),
+            ("socket", "".to_owned()),
+            ("time_zone", "SYSTEM".to_owned()),
+            ("version", self.config.server_version.clone()),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value))
+        .collect()
+    }
+
+    fn variable(&self, name: &str) -> Option<String> {
+        let name = name
+            .trim_start_matches("global.")
+            .trim_start_matches("session.");
+        self.variables()
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v)
+    }
+
+    fn query(&mut self, query: &str) -> io::Result<()> {
+        let query = query.trim().trim_end_matches(';').trim();
+        let lower = query.to_ascii_lowercase();
+        if let Some(assignments) = lower.strip_prefix("set ") {
+            self.set(&query["set ".len()..], assignments);
+            return self.reply(&ok_packet());
+        }
+        if lower == "select version()" {
+            let version = self.config.server_version.clone();
+            return self
+                .stream
+                .write_result_set(&[&query[7..]], &[vec![Some(version)]]);
+        }
+        if lower == "select unix_timestamp()" {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            return self
+                .stream
+                .write_result_set(&[&query[7..]], &[vec![Some(now.to_string())]]);
+        }
+        if let Some(name) = lower.strip_prefix("select @@") {
+            return match self.variable(name) {
+                Some(value) => self
+                    .stream
+                    .write_result_set(&[&query[7..]], &[vec![Some(value)]]),
+                None => self.reply(&err_packet(
+                    ER_UNKNOWN_SYSTEM_VARIABLE,
+                    "HY000",
+                    &format!("Unknown system variable '{}'", name),
+                )),
+            };
+        }
+        if let Some(name) = lower.strip_prefix("select @") {
+            let value = self.user_variables.get(name).cloned();
+            return self.stream.write_result_set(&[&query[7..]], &[vec![value]]);
+        }
+        if lower == "show binary logs" || lower == "show master logs" {
+            let mut rows = vec![];
+            for name in binlog_files(&self.config.binlog_dir)? {
+                let size = fs::metadata(self.config.binlog_dir.join(&name))?.len();
+                rows.push(vec![Some(name), Some(size.to_string())]);
+            }
+            return self
+                .stream
+                .write_result_set(&["Log_name", "File_size"], &rows);
+        }
+        if lower.starts_with("show ") {
+            if let Some(at) = lower.find("variables like ") {
+                let pattern = lower[at + "variables like ".len()..]
+                    .trim()
+                    .trim_matches(|c| c == '\'' || c == '"');
+                let rows: Vec<Vec<Option<String>>> = self
+                    .variables()
+                    .into_iter()
+                    .filter(|(name, _)| like(pattern.as_bytes(), name.as_bytes()))
+                    .map(|(name, value)| vec![Some(name), Some(value)])
+                    .collect();
+                return self
+                    .stream
+                    .write_result_set(&["Variable_name", "Value"], &rows);
+            }
+        }
+        if let Some(hook) = self.config.query_hook.clone() {
+            match (hook.0)(query)? {
+                Some(QueryReply::Ok) => return self.reply(&ok_packet()),
+                Some(QueryReply::ResultSet(columns, rows)) => {
+                    let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+                    return self.stream.write_result_set(&columns, &rows);
+                }
+                None => {}
+            }
+        }
+        self.reply(&err_packet(
+            ER_PARSE_ERROR,
+            "42000",
+            &format!("the binlog server does not understand '{}'", query),
+        ))
+    }
+
+    /// Record the user variables of a SET statement; system variables are accepted and
+    /// ignored. `lower` is the lower-cased `assignments`.
+    fn set(&mut self, assignments: &str, lower: &str) {
+        for (assignment, lower) in split_assignments(assignments)
+            .into_iter()
+            .zip(split_assignments(lower))
+        {
+            let (name, value) = match (lower.find('='), assignment.find('=')) {
+                (Some(at), Some(original_at)) => {
+                    (lower[..at].trim(), assignment[original_at + 1..].trim())
+                }
+                _ => continue,
+            };
+            let name = match name.strip_prefix('@') {
+                Some(name) if !name.starts_with('@') => name,
+                _ => continue,
+            };
+            let value = if let Some(system) = value.strip_prefix("@@") {
+                self.variable(&system.to_ascii_lowercase())
+                    .unwrap_or_default()
+            } else {
+                value.trim_matches(|c| c == '\'' || c == '"').to_owned()
+            };
+            match name {
+                "master_binlog_checksum" => {
+                    self.checksum = !value.is_empty() && !value.eq_ignore_ascii_case("none")
+                }
+                "master_heartbeat_period" => {
+                    self.heartbeat_period = value
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|ns| *ns > 0)
+                        .map(Duration::from_nanos)
+                }
+                _ => {}
+            }
+            self.user_variables.insert(name.to_owned(), value);
+        }
+    }
+
+    fn binlog_dump(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut cursor = Cursor::new(payload);
+        let pos = cursor.read_u32::<LittleEndian>()?;
+        let flags = cursor.read_u16::<LittleEndian>()?;
+        let _server_id = cursor.read_u32::<LittleEndian>()?;
+        let mut file_name = String::new();
+        cursor.read_to_string(&mut file_name)?;
+        self.dump(&file_name, u64::from(pos), flags, None)
+    }
+
+    fn binlog_dump_gtid(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut cursor = Cursor::new(payload);
+        let flags = cursor.read_u16::<LittleEndian>()?;
+        let _server_id = cursor.read_u32::<LittleEndian>()?;
+        let name_len = cursor.read_u32::<LittleEndian>()?;
+        let mut file_name = vec![0u8; name_len as usize];
+        cursor.read_exact(&mut file_name)?;
+        let pos = cursor.read_u64::<LittleEndian>()?;
+        let data_len = cursor.read_u32::<LittleEndian>()?;
+        let mut data = vec![0u8; data_len as usize];
+        cursor.read_exact(&mut data)?;
+        let executed = Gtid::decode(&data).map_err(invalid_data)?;
+        let file_name = String::from_utf8_lossy(&file_name).into_owned();
+        self.dump(&file_name, pos, flags, Some(executed))
+    }
+
+    fn fatal(&mut self, message: &str) -> io::Result<()> {
+        self.reply(&err_packet(
+            ER_MASTER_FATAL_ERROR_READING_BINLOG,
+            "HY000",
+            message,
+        ))
+    }
+
+    /// Stream from `file_name` at `pos` to the end of the binlog, leaving out the
+    /// transactions of `executed`. Without a file name the start is found from `executed`.
+    fn dump(
+        &mut self,
+        file_name: &str,
+        pos: u64,
+        flags: u16,
+        executed: Option<Gtid>,
+    ) -> io::Result<()> {
+        let files = binlog_files(&self.config.binlog_dir)?;
+        let first = if !file_name.is_empty() {
+            files.iter().position(|f| f == file_name)
+        } else if let Some(ref executed) = executed {
+            match first_file_for(&self.config.binlog_dir, &files, executed)? {
+                Ok(first) => first,
+                Err(message) => return self.fatal(message),
+            }
+        } else if !files.is_empty() {
+            Some(0)
+        } else {
+            None
+        };
+        let mut name = match first {
+            Some(first) => files[first].clone(),
+            None => {
+                return self.fatal("Could not find first log file name in binary log index file")
+            }
+        };
+        let mut start = pos.max(4);
+        let mut skipping = false;
+        loop {
+            let end = self.send_file(&name, start, executed.as_ref(), &mut skipping)?;
+            loop {
+                match next_file(&self.config.binlog_dir, &name)? {
+                    Some(next) => {
+                        name = next;
+                        start = 4;
+                        break;
+                    }
+                    None if self.config.follow && flags & BINLOG_DUMP_NON_BLOCK == 0 => {
+                        self.idle(&name, end)?
+                    }
+                    None => {
+                        if flags & BINLOG_DUMP_NON_BLOCK != 0 {
+                            self.stream.write_packet(&eof_packet())?;
+                        }
+                        self.stream.flush()?;
+                        // nothing more will come, so hang up rather than keep the client waiting
+                        return self.stream.get_ref().shutdown(Shutdown::Both);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send one file from `start`, returning the position it was sent up to. While following,
+    /// this only returns once the file is finished: it ended with a rotate or a later file
+    /// showed up.
+    fn send_file(
+        &mut self,
+        name: &str,
+        start: u64,
+        executed: Option<&Gtid>,
+        skipping: &mut bool,
+    ) -> io::Result<u64> {
+        let path = self.config.binlog_dir.join(name);
+        let fde = loop {
+            // a file may be seen before its FormatDescriptionEvent has been written
+            match BinlogFile::try_from_path(&path).map(|f| f.events(Some(4)).next()) {
+                Ok(Some(Ok(fde))) => break fde,
+                Ok(Some(Err(e))) => return Err(invalid_data(e)),
+                _ if self.config.follow => self.idle(name, 4)?,
+                Ok(None) => return Ok(4),
+                Err(e) => return Err(invalid_data(e)),
+            }
+        };
+        let mut rotate = start.to_le_bytes().to_vec();
+        rotate.extend_from_slice(name.as_bytes());
+        self.send_event(
+            0,
+            TypeCode::RotateEvent,
+            self.config.server_id,
+            0,
+            LOG_EVENT_ARTIFICIAL_F,
+            &rotate,
+        )?;
+        // announce the checksum the events are actually sent with, and don't move the
+        // client's position when the dump starts in the middle of the file
+        let fde_body = with_checksum_algorithm(fde.data(), self.checksum as u8);
+        let fde_pos = if start > 4 {
+            0
+        } else {
+            fde.next_position() as u32
+        };
+        self.send_event(
+            fde.timestamp(),
+            fde.type_code(),
+            fde.server_id(),
+            fde_pos,
+            fde.flags(),
+            &fde_body,
+        )?;
+        let mut offset = start.max(fde.next_position());
+        loop {
+            let events = BinlogFile::try_from_path(&path)
+                .map_err(invalid_data)?
+                .events(Some(offset));
+            for event in events {
+                let event = event.map_err(invalid_data)?;
+                offset = event.next_position();
+                if let Some(executed) = executed {
+                    if let TypeCode::GtidLogEvent | TypeCode::AnonymousGtidLogEvent =
+                        event.type_code()
+                    {
+                        *skipping = is_executed(executed, &event);
+                    }
+                    if *skipping && !is_file_event(event.type_code()) {
+                        continue;
+                    }
+                }
+                self.send_event(
+                    event.timestamp(),
+                    event.type_code(),
+                    event.server_id(),
+                    event.next_position() as u32,
+                    event.flags(),
+                    event.data(),
+                )?;
+                if event.type_code() == TypeCode::RotateEvent {
+                    self.stream.flush()?;
+                    return Ok(offset);
+                }
+            }
+            self.stream.flush()?;
+            if !self.config.follow || next_file(&self.config.binlog_dir, name)?.is_some() {
+                return Ok(offset);
+            }
+            self.idle(name, offset)?;
+        }
+    }
+
+    /// Wait for the binlog to grow, keeping the replica's heartbeat going
+    fn idle(&mut self, name: &str, pos: u64) -> io::Result<()> {
+        thread::sleep(self.config.poll_interval);
+        if let Some(period) = self.heartbeat_period {
+            if self.last_sent.elapsed() >= period {
+                self.send_event(
+                    0,
+                    TypeCode::HeartbeatLogEvent,
+                    self.config.server_id,
+                    pos as u32,
+                    0,
+                    name.as_bytes(),
+                )?;
+                self.stream.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn send_event(
+        &mut self,
+        timestamp: u32,
+        type_code: TypeCode,
+        server_id: u32,
+        log_pos: u32,
+        flags: u16,
+        body: &[u8],
+    ) -> io::Result<()> {
+        let mut packet = vec![0u8];
+        packet.extend_from_slice(&frame_event(
+            timestamp,
+            type_code,
+            server_id,
+            log_pos,
+            flags,
+            body,
+            self.checksum,
+        ));
+        self.last_sent = Instant::now();
+        self.stream.write_packet(&packet)
+    }
+}
+
+/// The file to serve a replica which has executed `executed` from: the newest one whose
+/// PreviousGtidsLogEvent it has all of. Files without one can only be the first.
+fn first_file_for(
+    dir: &Path,
+    files: &[String],
+    executed: &Gtid,
+) -> io::Result<Result<Option<usize>, &'static str>> {
+    for (i, name) in files.iter().enumerate().rev() {
+        match previous_gtids(&dir.join(name))? {
+            Some(previous) if previous.is_subset(executed) => return Ok(Ok(Some(i))),
+            Some(_) if i == 0 => {
+                return Ok(Err("The slave is connecting using CHANGE MASTER TO \
+                     MASTER_AUTO_POSITION = 1, but the master has purged binary logs \
+                     containing GTIDs that the slave requires."))
+            }
+            _ => {}
+        }
+    }
+    Ok(Ok(if files.is_empty() { None } else { Some(0) }))
+}
+
+/// The GTID set of the PreviousGtidsLogEvent following the file's FormatDescriptionEvent
+fn previous_gtids(path: &Path) -> io::Result<Option<Gtid>> {
+    let first = BinlogFile::try_from_path(path)
+        .map_err(invalid_data)?
+        .events(None)
+        .next();
+    Ok(match first {
+        Some(Ok(event)) => match event.inner(None) {
+            Ok(Some(EventData::PreviousGtidsLogEvent { gtid_sets })) => {
+                Some(Gtid::from_intervals(&gtid_sets))
+            }
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Events describing the file rather than a transaction, sent even while skipping
+fn is_file_event(type_code: TypeCode) -> bool {
+    matches!(
+        type_code,
+        TypeCode::RotateEvent
+            | TypeCode::FormatDescriptionEvent
+            | TypeCode::PreviousGtidsLogEvent
+            | TypeCode::StopEvent
+    )
+}
+
+fn is_executed(executed: &Gtid, event: &Event) -> bool {
+    match event.inner(None) {
+        Ok(Some(EventData::GtidLogEvent {
+            uuid, coordinate, ..
+        })) => executed.contains(&uuid.to_string(), coordinate as i64),
+        _ => false,
+    }
+}
+
+/// Names of the binlog files in `dir`, in order
+pub(crate) fn binlog_files(dir: &Path) -> io::Result<Vec<String>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let mut magic = [0u8; 4];
+        let is_binlog = File::open(entry.path())
+            .and_then(|mut f| f.read_exact(&mut magic))
+            .is_ok()
+            && magic == BINLOG_MAGIC;
+        if is_binlog {
+            files.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn next_file(dir: &Path, name: &str) -> io::Result<Option<String>> {
+    Ok(binlog_files(dir)?.into_iter().find(|f| f.as_str() > name))
+}
+
+/// Split a SET statement's assignments on the commas outside of quotes
+fn split_assignments(s: &str) -> Vec<&str> {
+    let mut out = vec![];
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, ',') => {
+                out.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&s[start..]);
+    out
+}
+
+/// MySQL's LIKE, case-insensitive, on the lower-case names used here. Matched in linear
+/// passes, going back only to just after the last `%`, so no pattern takes exponential time.
+pub(crate) fn like(pattern: &[u8], s: &[u8]) -> bool {
+    // the pattern's bytes with escapes resolved, and whether each is a wildcard
+    let mut tokens = Vec::with_capacity(pattern.len());
+    let mut bytes = pattern.iter().copied();
+    while let Some(c) = bytes.next() {
+        match c {
+            b'\\' => tokens.push((bytes.next().unwrap_or(b'\\'), false)),
+            b'%' | b'_' => tokens.push((c, true)),
+            c => tokens.push((c, false)),
+        }
+    }
+    let (mut p, mut i) = (0, 0);
+    // the last `%` and where in `s` it stopped matching
+    let mut any: Option<(usize, usize)> = None;
+    while i < s.len() {
+        match tokens.get(p) {
+            Some(&(b'%', true)) => {
+                any = Some((p, i));
+                p += 1;
+            }
+            Some(&(c, wildcard)) if wildcard || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match any {
+                Some((any_p, any_i)) => {
+                    any = Some((any_p, any_i + 1));
+                    p = any_p + 1;
+                    i = any_i + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[p..].iter().all(|&token| token == (b'%', true))
+}
+
+fn invalid_data<E: std::fmt::Display>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{like, split_assignments, BinlogServer, BinlogServerConfig};
+    use crate::mysql_binlog::binlog_writer::BinlogFileWriter;
+    use crate::mysql_binlog::event::EventData;
+    use crate::pkg::mysql_gtid::Gtid;
+    use crate::{OffsetConfig, Runner};
+
+    const UUID: &str = "0575a804-6403-11ea-8d3d-e454e8d4a4fe";
+
+    fn transaction(gno: u64, q: &str) -> Vec<EventData> {
+        vec![
+            EventData::GtidLogEvent {
+                flags: 1,
+                uuid: uuid::Uuid::parse_str(UUID).unwrap(),
+                coordinate: gno,
+                last_committed: None,
+                sequence_number: None,
+            },
+            EventData::QueryEvent {
+                thread_id: 1,
+                exec_time: 0,
+                error_code: 0,
+                schema: "shop".to_owned(),
+                query: q.to_owned(),
+            },
+        ]
+    }
+
+    /// A file opening with `previous` executed and holding one transaction per query
+    fn write_file(path: &Path, previous: u64, queries: &[&str], rotate_to: Option<&str>) {
+        let mut writer = BinlogFileWriter::create(path, 1).unwrap();
+        let gtid_sets = if previous == 0 {
+            vec![]
+        } else {
+            vec![(
+                uuid::Uuid::parse_str(UUID).unwrap(),
+                vec![(1, previous + 1)],
+            )]
+        };
+        writer
+            .write_event(1, &EventData::PreviousGtidsLogEvent { gtid_sets })
+            .unwrap();
+        for (i, q) in queries.iter().enumerate() {
+            for event in transaction(previous + 1 + i as u64, q) {
+                writer.write_event(1, &event).unwrap();
+            }
+        }
+        match rotate_to {
+            Some(next) => drop(writer.rotate(next).unwrap()),
+            None => drop(writer.finish().unwrap()),
+        }
+    }
+
+    fn start(dir: &Path, config: impl FnOnce(&mut BinlogServerConfig)) -> String {
+        let mut server_config = BinlogServerConfig::new(dir, "repl", "secret");
+        config(&mut server_config);
+        let server = BinlogServer::bind("127.0.0.1:0", server_config).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.serve());
+        format!("mysql://repl:secret@{}", addr)
+    }
+
+    fn gtid_sync(url: &str, executed: &str) -> Runner {
+        let mut runner = Runner::new(url, 2222).unwrap();
+        runner
+            .start_sync(OffsetConfig {
+                pos: None,
+                gtid: Some(Gtid::from(executed)),
+                mariadb_gtid: None,
+            })
+            .unwrap();
+        runner
+    }
+
+    #[test]
+    fn test_serve_replica_by_gtid() {
+        let dir = std::env::temp_dir().join(format!("binlog-server-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        write_file(
+            &dir.join("mysql-bin.000001"),
+            0,
+            &["CREATE TABLE a (id INT)", "CREATE TABLE b (id INT)"],
+            Some("mysql-bin.000002"),
+        );
+        write_file(
+            &dir.join("mysql-bin.000002"),
+            2,
+            &["CREATE TABLE c (id INT)"],
+            None,
+        );
+        let url = start(&dir, |c| {
+            c.poll_interval = Duration::from_millis(10);
+        });
+
+        // has everything of the first file but its last transaction
+        let mut runner = gtid_sync(&url, &format!("{}:1", UUID));
+        let e = runner.get_binlog_event().unwrap();
+        assert_eq!(e.query.as_deref(), Some("CREATE TABLE b (id INT)"));
+        let e = runner.get_binlog_event().unwrap();
+        assert_eq!(e.query.as_deref(), Some("CREATE TABLE c (id INT)"));
+
+        // starts in the second file, then follows it as it grows
+        let mut runner = gtid_sync(&url, &format!("{}:1-2", UUID));
+        let e = runner.get_binlog_event().unwrap();
+        assert_eq!(e.query.as_deref(), Some("CREATE TABLE c (id INT)"));
+        let file = fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join("mysql-bin.000002"))
+            .unwrap();
+        let position = file.metadata().unwrap().len();
+        let mut writer = BinlogFileWriter::resume(file, 1, position);
+        for event in transaction(4, "CREATE TABLE d (id INT)") {
+            writer.write_event(1, &event).unwrap();
+        }
+        writer.finish().unwrap();
+        let e = runner.get_binlog_event().unwrap();
+        assert_eq!(e.query.as_deref(), Some("CREATE TABLE d (id INT)"));
+        assert_eq!(e.gtid.unwrap().sequence(), 4);
+
+        // the transactions of the first file are gone once it has been purged
+        fs::remove_file(dir.join("mysql-bin.000001")).unwrap();
+        let mut runner = gtid_sync(&url, &format!("{}:1", UUID));
+        let err = runner.get_event().unwrap_err();
+        assert!(err.to_string().contains("purged binary logs"));
+
+        let bad = url.replace("secret", "wrong");
+        assert!(Runner::new(&bad, 2222).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_split_assignments() {
+        assert_eq!(
+            split_assignments("@a = 1, @b = '0-1-5,1-2-3'"),
+            vec!["@a = 1", " @b = '0-1-5,1-2-3'"]
+        );
+    }
+
+    #[test]
+    fn test_like() {
+        assert!(like(b"binlog_checksum", b"binlog_checksum"));
+        assert!(like(b"binlog%", b"binlog_format"));
+        assert!(like(b"%_format", b"binlog_format"));
+        assert!(like(b"log\\_bin", b"log_bin"));
+        assert!(!like(b"log\\_bin", b"logxbin"));
+        assert!(!like(b"rpl_semi_sync_master_enabled", b"log_bin"));
+        assert!(like(b"%", b""));
+        assert!(!like(b"_", b""));
+        assert!(like(b"b%g%_%t", b"binlog_format"));
+        // would take exponential time backtracking into every `%`
+        let pattern = format!("{}b", "%a".repeat(40));
+        assert!(!like(pattern.as_bytes(), "a".repeat(60).as_bytes()));
+    }
+}
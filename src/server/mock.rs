@@ -0,0 +1,255 @@
+//! An in-process MySQL server which streams the binlog files of a local directory, so the
+//! network path of [`Runner`] can be exercised without a database.
+//!
+//! It is a [`BinlogServer`] which logs in any user and, once the last file has been sent,
+//! closes the connection, so a client sees an error instead of waiting for events forever.
+//! Given a [`SnapshotSource`] it also answers the queries a [`Snapshot`] starts with.
+//!
+//! [`Runner`]: crate::client::sync::Runner
+//! [`BinlogServer`]: crate::server::binlog_server::BinlogServer
+//! [`Snapshot`]: crate::client::snapshot::Snapshot
+
+use std::fs;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::server::binlog_server::{
+    binlog_files, like, serve_connection, BinlogServerConfig, QueryHook, QueryReply,
+};
+
+/// What the mock server pretends to be
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    /// Directory holding the binlog files to serve, in file name order
+    pub binlog_dir: PathBuf,
+    /// Sent in the handshake and returned by `SELECT VERSION()`; include `MariaDB` to be
+    /// taken for one
+    pub server_version: String,
+    pub server_id: u32,
+    pub server_uuid: String,
+    /// Answer the queries of a snapshot as a source would
+    pub snapshot: Option<SnapshotSource>,
+}
+
+/// What the mock reports to a snapshot. The position is always the end of the last file.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotSource {
+    /// Report the position through the `Binlog_snapshot_*` status variables, as MariaDB and
+    /// Percona Server do; without them a snapshot has to lock the tables and read
+    /// `SHOW MASTER STATUS`
+    pub status_variables: bool,
+    /// The `Executed_Gtid_Set` of `SHOW MASTER STATUS`
+    pub gtid_executed: String,
+    /// Schema and name of the tables `information_schema.TABLES` lists
+    pub tables: Vec<(String, String)>,
+}
+
+impl MockConfig {
+    pub fn new<P: Into<PathBuf>>(binlog_dir: P) -> Self {
+        MockConfig {
+            binlog_dir: binlog_dir.into(),
+            server_version: "5.7.30-log".to_owned(),
+            server_id: 1,
+            server_uuid: "0575a804-6403-11ea-8d3d-e454e8d4a4fe".to_owned(),
+            snapshot: None,
+        }
+    }
+}
+
+/// A mock server listening on a free port of 127.0.0.1. Every client is served on its own
+/// thread until the server is dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    pub fn start(config: MockConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let stop = shutdown.clone();
+        let binlog_dir = config.binlog_dir.clone();
+        let query_hook = config.snapshot.map(|snapshot| {
+            QueryHook(Arc::new(move |query: &str| {
+                snapshot_query(&snapshot, &binlog_dir, query)
+            }))
+        });
+        let config = Arc::new(BinlogServerConfig {
+            binlog_dir: config.binlog_dir,
+            server_version: config.server_version,
+            server_id: config.server_id,
+            server_uuid: config.server_uuid,
+            credentials: None,
+            follow: false,
+            query_hook,
+            ..BinlogServerConfig::new("", "", "")
+        });
+        let handle = thread::spawn(move || {
+            let mut connection_id = 0u32;
+            for stream in listener.incoming() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                connection_id += 1;
+                let config = config.clone();
+                thread::spawn(move || serve_connection(stream, config, connection_id));
+            }
+        });
+        Ok(MockServer {
+            addr,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// A URL for `Runner::new`. Any user and password are accepted.
+    pub fn url(&self) -> String {
+        format!("mysql://root:mock@{}", self.addr)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // wake the accept loop up so it sees the flag
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Answer the queries a snapshot issues as a source would, `None` for any other
+fn snapshot_query(
+    snapshot: &SnapshotSource,
+    binlog_dir: &Path,
+    query: &str,
+) -> io::Result<Option<QueryReply>> {
+    let lower = query.to_ascii_lowercase();
+    let result_set = |columns: &[&str], rows: Vec<Vec<Option<String>>>| {
+        let columns = columns.iter().map(|c| c.to_string()).collect();
+        Ok(Some(QueryReply::ResultSet(columns, rows)))
+    };
+    match lower.as_str() {
+        "start transaction with consistent snapshot"
+        | "rollback"
+        | "commit"
+        | "flush tables with read lock"
+        | "unlock tables" => return Ok(Some(QueryReply::Ok)),
+        "show master status" | "show binary log status" => {
+            let (file, position) = binlog_end(binlog_dir)?;
+            return result_set(
+                &[
+                    "File",
+                    "Position",
+                    "Binlog_Do_DB",
+                    "Binlog_Ignore_DB",
+                    "Executed_Gtid_Set",
+                ],
+                vec![vec![
+                    Some(file),
+                    Some(position.to_string()),
+                    Some(String::new()),
+                    Some(String::new()),
+                    Some(snapshot.gtid_executed.clone()),
+                ]],
+            );
+        }
+        _ => {}
+    }
+    if let Some(pattern) = lower.strip_prefix("show status like ") {
+        let mut status = vec![];
+        if snapshot.status_variables {
+            let (file, position) = binlog_end(binlog_dir)?;
+            status.push(("Binlog_snapshot_file", file));
+            status.push(("Binlog_snapshot_position", position.to_string()));
+            status.push((
+                "Binlog_snapshot_gtid_executed",
+                snapshot.gtid_executed.clone(),
+            ));
+        }
+        let pattern = pattern.trim().trim_matches(|c| c == '\'' || c == '"');
+        let rows = status
+            .into_iter()
+            .filter(|(name, _)| like(pattern.as_bytes(), name.to_ascii_lowercase().as_bytes()))
+            .map(|(name, value)| vec![Some(name.to_owned()), Some(value)])
+            .collect();
+        return result_set(&["Variable_name", "Value"], rows);
+    }
+    if lower.starts_with("select table_schema, table_name from information_schema.tables") {
+        // the schema is either excluded with NOT IN or picked with =
+        let (excluded, schema) = match (
+            lower.find("table_schema not in"),
+            lower.find("table_schema = "),
+        ) {
+            (Some(at), _) => (string_literals(&query[at..]), None),
+            (None, Some(at)) => (vec![], string_literals(&query[at..]).into_iter().next()),
+            (None, None) => (vec![], None),
+        };
+        let mut tables: Vec<(String, String)> = snapshot
+            .tables
+            .iter()
+            .filter(|(s, _)| !excluded.contains(s))
+            .filter(|(s, _)| schema.as_ref().is_none_or(|schema| s == schema))
+            .cloned()
+            .collect();
+        tables.sort();
+        let rows = tables
+            .into_iter()
+            .map(|(schema, table)| vec![Some(schema), Some(table)])
+            .collect();
+        return result_set(&["TABLE_SCHEMA", "TABLE_NAME"], rows);
+    }
+    Ok(None)
+}
+
+/// The last binlog file and its size, where the next transaction would be written
+fn binlog_end(binlog_dir: &Path) -> io::Result<(String, u64)> {
+    let file = binlog_files(binlog_dir)?
+        .pop()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no binlog files"))?;
+    let size = fs::metadata(binlog_dir.join(&file))?.len();
+    Ok((file, size))
+}
+
+/// The values of the single-quoted string literals in `s`
+fn string_literals(s: &str) -> Vec<String> {
+    let mut literals = vec![];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\'' {
+            continue;
+        }
+        let mut literal = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => break,
+                '\\' => match chars.next() {
+                    Some('0') => literal.push('\0'),
+                    Some('n') => literal.push('\n'),
+                    Some('r') => literal.push('\r'),
+                    Some('Z') => literal.push('\x1a'),
+                    Some(c) => literal.push(c),
+                    None => break,
+                },
+                c => literal.push(c),
+            }
+        }
+        literals.push(literal);
+    }
+    literals
+}
@@ -0,0 +1,5 @@
+//! The server end of replication: enough of the MySQL protocol to let a client log in and
+//! ask for a binlog dump.
+pub mod binlog_server;
+pub mod mock;
+pub mod protocol;
@@ -0,0 +1,344 @@
+//! Server side of the MySQL client/server protocol: packet framing, the handshake and the
+//! generic replies (OK, ERR, EOF and text result sets).
+//!
+//! Only what a client needs to log in, run simple queries and ask for a binlog dump is
+//! covered. There is no TLS, compression or `CLIENT_DEPRECATE_EOF`.
+
+use std::io::{self, Cursor, ErrorKind, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::mysql_binlog::encoder::write_variable_length_integer;
+
+/// Payloads this long are continued in the next packet
+pub const MAX_PAYLOAD_LEN: usize = 0x00ff_ffff;
+
+pub const CLIENT_LONG_PASSWORD: u32 = 0x0000_0001;
+pub const CLIENT_CONNECT_WITH_DB: u32 = 0x0000_0008;
+pub const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+pub const CLIENT_TRANSACTIONS: u32 = 0x0000_2000;
+pub const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+pub const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+pub const CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA: u32 = 0x0020_0000;
+
+/// Capabilities announced in the handshake
+pub const SERVER_CAPABILITIES: u32 = CLIENT_LONG_PASSWORD
+    | CLIENT_CONNECT_WITH_DB
+    | CLIENT_PROTOCOL_41
+    | CLIENT_TRANSACTIONS
+    | CLIENT_SECURE_CONNECTION
+    | CLIENT_PLUGIN_AUTH;
+
+pub const AUTH_PLUGIN_NAME: &str = "mysql_native_password";
+
+const SERVER_STATUS_AUTOCOMMIT: u16 = 0x0002;
+const UTF8_GENERAL_CI: u8 = 33;
+const MYSQL_TYPE_VAR_STRING: u8 = 0xfd;
+const NULL_COLUMN: u8 = 0xfb;
+
+/// A connection speaking MySQL packets: a 3 byte payload length, a sequence id and the
+/// payload. The sequence id of a reply follows the one of the packet last read.
+pub struct PacketStream<S> {
+    stream: S,
+    seq: u8,
+}
+
+impl<S: Read + Write> PacketStream<S> {
+    pub fn new(stream: S) -> Self {
+        PacketStream { stream, seq: 0 }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Read one payload, joining the packets of payloads longer than `MAX_PAYLOAD_LEN`.
+    /// Returns `None` when the client closed the connection between packets.
+    pub fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut payload = vec![];
+        loop {
+            let mut header = [0u8; 4];
+            match self.stream.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == ErrorKind::UnexpectedEof && payload.is_empty() => {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            }
+            let len = Cursor::new(&header[..3]).read_u24::<LittleEndian>()? as usize;
+            self.seq = header[3].wrapping_add(1);
+            let start = payload.len();
+            payload.resize(start + len, 0);
+            self.stream.read_exact(&mut payload[start..])?;
+            if len < MAX_PAYLOAD_LEN {
+                return Ok(Some(payload));
+            }
+        }
+    }
+
+    /// Write one payload, split into as many packets as its length needs
+    pub fn write_packet(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut chunks = payload.chunks(MAX_PAYLOAD_LEN);
+        let mut last_len = 0;
+        for chunk in &mut chunks {
+            self.write_chunk(chunk)?;
+            last_len = chunk.len();
+        }
+        // an empty payload, or one ending on a packet boundary, is closed by an empty packet
+        if last_len == 0 || last_len == MAX_PAYLOAD_LEN {
+            self.write_chunk(&[])?;
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.stream.write_u24::<LittleEndian>(chunk.len() as u32)?;
+        self.stream.write_u8(self.seq)?;
+        self.seq = self.seq.wrapping_add(1);
+        self.stream.write_all(chunk)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+
+    /// Send a text result set of string columns, `None` being NULL
+    pub fn write_result_set(
+        &mut self,
+        columns: &[&str],
+        rows: &[Vec<Option<String>>],
+    ) -> io::Result<()> {
+        let mut count = vec![];
+        write_variable_length_integer(&mut count, columns.len() as u64)?;
+        self.write_packet(&count)?;
+        for column in columns {
+            self.write_packet(&column_definition(column)?)?;
+        }
+        self.write_packet(&eof_packet())?;
+        for row in rows {
+            let mut out = vec![];
+            for value in row {
+                match value {
+                    Some(value) => write_length_encoded_string(&mut out, value.as_bytes())?,
+                    None => out.push(NULL_COLUMN),
+                }
+            }
+            self.write_packet(&out)?;
+        }
+        self.write_packet(&eof_packet())?;
+        self.flush()
+    }
+}
+
+/// Scramble sent with the handshake, 20 bytes without NULs from the OS random source
+pub fn new_nonce() -> io::Result<[u8; 20]> {
+    let mut nonce = [0u8; 20];
+    getrandom::getrandom(&mut nonce).map_err(io::Error::from)?;
+    for b in nonce.iter_mut() {
+        // kept in the printable range like the server does
+        *b = 0x21 + *b % 0x5e;
+    }
+    Ok(nonce)
+}
+
+/// The initial HandshakeV10 packet
+pub fn handshake_packet(connection_id: u32, server_version: &str, nonce: &[u8; 20]) -> Vec<u8> {
+    let mut out = vec![10u8];
+    out.extend_from_slice(server_version.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&connection_id.to_le_bytes());
+    out.extend_from_slice(&nonce[..8]);
+    out.push(0);
+    out.extend_from_slice(&(SERVER_CAPABILITIES as u16).to_le_bytes());
+    out.push(UTF8_GENERAL_CI);
+    out.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+    out.extend_from_slice(&((SERVER_CAPABILITIES >> 16) as u16).to_le_bytes());
+    out.push(nonce.len() as u8 + 1);
+    out.extend_from_slice(&[0u8; 10]);
+    out.extend_from_slice(&nonce[8..]);
+    out.push(0);
+    out.extend_from_slice(AUTH_PLUGIN_NAME.as_bytes());
+    out.push(0);
+    out
+}
+
+/// What a client answers the handshake with (HandshakeResponse41)
+#[derive(Debug, Clone)]
+pub struct HandshakeResponse {
+    pub capabilities: u32,
+    pub user: String,
+    pub auth_response: Vec<u8>,
+    pub database: Option<String>,
+    pub auth_plugin: Option<String>,
+}
+
+impl HandshakeResponse {
+    pub fn parse(payload: &[u8]) -> io::Result<Self> {
+        let mut cursor = Cursor::new(payload);
+        let capabilities = cursor.read_u32::<LittleEndian>()?;
+        if capabilities & CLIENT_PROTOCOL_41 == 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "client does not speak protocol 4.1",
+            ));
+        }
+        // max packet size, character set and filler
+        cursor.set_position(4 + 4 + 1 + 23);
+        let user = read_nul_string(&mut cursor)?;
+        let auth_response = if capabilities & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+            let len = read_variable_length_integer(&mut cursor)? as usize;
+            read_bytes(&mut cursor, len)?
+        } else if capabilities & CLIENT_SECURE_CONNECTION != 0 {
+            let len = cursor.read_u8()? as usize;
+            read_bytes(&mut cursor, len)?
+        } else {
+            read_nul_string(&mut cursor)?.into_bytes()
+        };
+        let database = if capabilities & CLIENT_CONNECT_WITH_DB != 0 {
+            Some(read_nul_string(&mut cursor)?)
+        } else {
+            None
+        };
+        let auth_plugin = if capabilities & CLIENT_PLUGIN_AUTH != 0 {
+            Some(read_nul_string(&mut cursor)?)
+        } else {
+            None
+        };
+        Ok(HandshakeResponse {
+            capabilities,
+            user,
+            auth_response,
+            database,
+            auth_plugin,
+        })
+    }
+}
+
+/// Asks the client to answer again with our auth plugin
+pub fn auth_switch_packet(nonce: &[u8; 20]) -> Vec<u8> {
+    let mut out = vec![0xfe];
+    out.extend_from_slice(AUTH_PLUGIN_NAME.as_bytes());
+    out.push(0);
+    out.extend_from_slice(nonce);
+    out.push(0);
+    out
+}
+
+/// What a client logging in with `password` answers the `nonce` with under
+/// mysql_native_password: `SHA1(password) XOR SHA1(nonce + SHA1(SHA1(password)))`
+pub fn native_password_scramble(password: &str, nonce: &[u8]) -> Vec<u8> {
+    if password.is_empty() {
+        return vec![];
+    }
+    let stage1 = sha1::Sha1::from(password.as_bytes()).digest().bytes();
+    let stage2 = sha1::Sha1::from(&stage1[..]).digest().bytes();
+    let mut hasher = sha1::Sha1::new();
+    hasher.update(nonce);
+    hasher.update(&stage2);
+    let key = hasher.digest().bytes();
+    stage1.iter().zip(key.iter()).map(|(a, b)| a ^ b).collect()
+}
+
+pub fn ok_packet() -> Vec<u8> {
+    let mut out = vec![0x00, 0, 0];
+    out.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out
+}
+
+pub fn eof_packet() -> Vec<u8> {
+    let mut out = vec![0xfe];
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+    out
+}
+
+pub fn err_packet(code: u16, state: &str, message: &str) -> Vec<u8> {
+    let mut out = vec![0xff];
+    out.extend_from_slice(&code.to_le_bytes());
+    out.push(b'#');
+    out.extend_from_slice(state.as_bytes());
+    out.extend_from_slice(message.as_bytes());
+    out
+}
+
+fn column_definition(name: &str) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    for field in &["def", "", "", "", name, ""] {
+        write_length_encoded_string(&mut out, field.as_bytes())?;
+    }
+    // length of the fixed fields
+    out.push(0x0c);
+    out.write_u16::<LittleEndian>(u16::from(UTF8_GENERAL_CI))?;
+    out.write_u32::<LittleEndian>(1024)?;
+    out.push(MYSQL_TYPE_VAR_STRING);
+    // flags, decimals and filler
+    out.extend_from_slice(&[0, 0, 0, 0, 0]);
+    Ok(out)
+}
+
+fn write_length_encoded_string(out: &mut Vec<u8>, value: &[u8]) -> io::Result<()> {
+    write_variable_length_integer(out, value.len() as u64)?;
+    out.extend_from_slice(value);
+    Ok(())
+}
+
+fn read_variable_length_integer<R: Read>(r: &mut R) -> io::Result<u64> {
+    match r.read_u8()? {
+        0xfc => r.read_u16::<LittleEndian>().map(u64::from),
+        0xfd => r.read_u24::<LittleEndian>().map(u64::from),
+        0xfe => r.read_u64::<LittleEndian>(),
+        b => Ok(u64::from(b)),
+    }
+}
+
+fn read_bytes<R: Read>(r: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut out = vec![0u8; len];
+    r.read_exact(&mut out)?;
+    Ok(out)
+}
+
+fn read_nul_string<R: Read>(r: &mut R) -> io::Result<String> {
+    let mut out = vec![];
+    loop {
+        match r.read_u8() {
+            Ok(0) => break,
+            Ok(b) => out.push(b),
+            // trailing fields may be cut short
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{new_nonce, PacketStream, MAX_PAYLOAD_LEN};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_split_payload_round_trip() {
+        let payload: Vec<u8> = (0..MAX_PAYLOAD_LEN + 10).map(|i| i as u8).collect();
+        let mut out = PacketStream::new(Cursor::new(vec![]));
+        out.write_packet(&payload).unwrap();
+        out.write_packet(&[]).unwrap();
+        let written = out.get_ref().get_ref().clone();
+        // two packets for the payload, one for the empty one
+        assert_eq!(written.len(), payload.len() + 3 * 4);
+        assert_eq!(written[3], 0);
+        assert_eq!(written[4 + MAX_PAYLOAD_LEN + 3], 1);
+
+        let mut input = PacketStream::new(Cursor::new(written));
+        assert_eq!(input.read_packet().unwrap(), Some(payload));
+        assert_eq!(input.read_packet().unwrap(), Some(vec![]));
+        assert_eq!(input.read_packet().unwrap(), None);
+    }
+
+    #[test]
+    fn test_new_nonce() {
+        let nonce = new_nonce().unwrap();
+        assert!(nonce.iter().all(|b| (0x21..0x7f).contains(b)));
+        assert_ne!(nonce, new_nonce().unwrap());
+    }
+}
@@ -0,0 +1,346 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::result;
+use std::time::{Duration, Instant};
+
+use crate::client::sync::{EndOfBinlog, OffsetConfig, Runner};
+use crate::mysql_binlog::event::{EventData, FL_STANDALONE};
+use crate::mysql_binlog::{BinlogEvent, EventContext};
+use crate::sql::statement::{classify, Statement};
+
+use super::{Checkpoint, Sink};
+
+type Result<T> = result::Result<T, Box<dyn Error>>;
+
+/// Turns an event into the record written to the sink, or skips it
+pub type Encoder = Box<dyn FnMut(&BinlogEvent) -> Option<Vec<u8>>>;
+
+/// Streams the events of a [`Runner`] into a [`Sink`].
+///
+/// Query and rows events are encoded into records, by default as the JSON the
+/// [`BinlogEvent`] serializes to. Records are handed to the sink in batches of up to
+/// `batch_size`. Once a transaction has been read, and the batch size or the flush interval
+/// has been reached since the last flush, the sink is flushed and the checkpoint moved to the
+/// end of that transaction. The source sends a heartbeat every 30 seconds when it has nothing
+/// else to send, which flushes the last transactions read before it went quiet.
+pub struct Pipeline<S: Sink> {
+    runner: Runner,
+    sink: S,
+    encoder: Encoder,
+    batch_size: usize,
+    flush_interval: Duration,
+    checkpoint_file: Option<PathBuf>,
+    checkpoint: Option<Checkpoint>,
+    context: EventContext,
+}
+
+impl<S: Sink> Pipeline<S> {
+    pub fn new(runner: Runner, sink: S) -> Self {
+        Pipeline {
+            runner,
+            sink,
+            encoder: Box::new(|event| serde_json::to_vec(event).ok()),
+            batch_size: 1000,
+            flush_interval: Duration::from_secs(1),
+            checkpoint_file: None,
+            checkpoint: None,
+            context: EventContext::new(),
+        }
+    }
+
+    pub fn set_encoder<F: FnMut(&BinlogEvent) -> Option<Vec<u8>> + 'static>(&mut self, encoder: F) {
+        self.encoder = Box::new(encoder);
+    }
+
+    /// The most records handed to the sink at once, and the number of records after which the
+    /// sink is flushed at the next transaction boundary. Defaults to 1000.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// How long records may wait for a flush, checked at transaction boundaries. Defaults to a
+    /// second; zero flushes after every transaction.
+    pub fn set_flush_interval(&mut self, interval: Duration) {
+        self.flush_interval = interval;
+    }
+
+    /// Store every checkpoint in `path`, and start `run` from the one stored there if any
+    pub fn set_checkpoint_file<P: Into<PathBuf>>(&mut self, path: P) {
+        self.checkpoint_file = Some(path.into());
+    }
+
+    /// Where the records flushed so far reach in the source's binlog
+    pub fn checkpoint(&self) -> Option<&Checkpoint> {
+        self.checkpoint.as_ref()
+    }
+
+    pub fn runner(&self) -> &Runner {
+        &self.runner
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+
+    /// Stream from the stored checkpoint, or from `start` when there is none. Returns once a
+    /// non-blocking dump (see `Runner::set_non_block`) has reached the end of the binlog, after
+    /// flushing; on an error the records since the last checkpoint may or may not have been
+    /// delivered.
+    pub fn run(&mut self, start: OffsetConfig) -> Result<()> {
+        if let Some(ref path) = self.checkpoint_file {
+            self.checkpoint = Checkpoint::load(path)?;
+        }
+        let offset = match self.checkpoint {
+            Some(ref checkpoint) => checkpoint.offset_config(),
+            None => start,
+        };
+        self.runner.start_sync(offset)?;
+        let mut batch = Vec::new();
+        // records handed to the sink since it was last flushed, including `batch`
+        let mut unflushed = 0;
+        let mut last_flush = Instant::now();
+        let mut file = String::new();
+        let mut in_transaction = false;
+        // the last transaction boundary read
+        let mut boundary = self.checkpoint.clone();
+        loop {
+            let event = match self.runner.get_event() {
+                Ok(event) => event,
+                Err(e) if e.is::<EndOfBinlog>() => break,
+                Err(e) => return Err(e),
+            };
+            let (timestamp, type_code, server_id, event_size, log_pos) = match event.header {
+                EventData::EventHeader {
+                    timestamp,
+                    event_type,
+                    server_id,
+                    event_size,
+                    log_pos,
+                    ..
+                } => (timestamp, event_type, server_id, event_size, log_pos),
+                _ => continue,
+            };
+            let mut ends_transaction = false;
+            match event.event {
+                Some(EventData::RotateEvent {
+                    ref next_log_name,
+                    pos,
+                }) => {
+                    file = next_log_name.clone();
+                    if !in_transaction {
+                        boundary = Some(Checkpoint {
+                            file: file.clone(),
+                            pos,
+                        });
+                    }
+                }
+                Some(EventData::MariadbGtidEvent { flags, .. }) => {
+                    in_transaction = flags & FL_STANDALONE == 0
+                }
+                Some(EventData::QueryEvent { ref query, .. }) => match classify(query, None) {
+                    Statement::Begin => in_transaction = true,
+                    Statement::Commit | Statement::Rollback => {
+                        in_transaction = false;
+                        ends_transaction = true;
+                    }
+                    _ if !in_transaction => ends_transaction = true,
+                    _ => {}
+                },
+                Some(EventData::XIDEvent { .. }) => {
+                    in_transaction = false;
+                    ends_transaction = true;
+                }
+                _ => {}
+            }
+            // log_pos is where the event ends
+            if ends_transaction && log_pos != 0 {
+                boundary = Some(Checkpoint {
+                    file: file.clone(),
+                    pos: u64::from(log_pos),
+                });
+            }
+            if let Some(data) = event.event {
+                let offset = u64::from(log_pos.saturating_sub(event_size));
+                if let Some(event) = self.context.handle(
+                    type_code,
+                    timestamp,
+                    server_id,
+                    offset,
+                    data,
+                    self.runner.table_map(),
+                ) {
+                    if let Some(record) = (self.encoder)(&event) {
+                        batch.push(record);
+                        unflushed += 1;
+                    }
+                }
+            }
+            if batch.len() >= self.batch_size {
+                self.sink.write_batch(&batch)?;
+                batch.clear();
+            }
+            if !in_transaction
+                && (unflushed > 0 || boundary != self.checkpoint)
+                && (unflushed >= self.batch_size || last_flush.elapsed() >= self.flush_interval)
+            {
+                self.deliver(&mut batch, boundary.clone())?;
+                unflushed = 0;
+                last_flush = Instant::now();
+            }
+        }
+        self.deliver(&mut batch, boundary)
+    }
+
+    /// Write and flush the records held back, then move the checkpoint to `boundary`
+    fn deliver(&mut self, batch: &mut Vec<Vec<u8>>, boundary: Option<Checkpoint>) -> Result<()> {
+        if !batch.is_empty() {
+            self.sink.write_batch(batch)?;
+            batch.clear();
+        }
+        self.sink.flush()?;
+        if let Some(checkpoint) = boundary {
+            if self.checkpoint.as_ref() != Some(&checkpoint) {
+                self.sink.commit(&checkpoint)?;
+                if let Some(ref path) = self.checkpoint_file {
+                    checkpoint.store(path)?;
+                }
+                self.checkpoint = Some(checkpoint);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use super::Pipeline;
+    use crate::client::sync::{write_fixture, OffsetConfig, Runner};
+    use crate::server::mock::{MockConfig, MockServer};
+    use crate::sink::{Checkpoint, Sink};
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Write(Vec<String>),
+        Flush,
+        Commit(String, u64),
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<Call>);
+
+    impl Sink for Recorder {
+        fn write_batch(&mut self, records: &[Vec<u8>]) -> io::Result<()> {
+            let records = records
+                .iter()
+                .map(|r| {
+                    let event: serde_json::Value = serde_json::from_slice(r).unwrap();
+                    event["query"].as_str().unwrap_or("rows").to_owned()
+                })
+                .collect();
+            self.0.push(Call::Write(records));
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.push(Call::Flush);
+            Ok(())
+        }
+
+        fn commit(&mut self, checkpoint: &Checkpoint) -> io::Result<()> {
+            self.0
+                .push(Call::Commit(checkpoint.file.clone(), checkpoint.pos));
+            Ok(())
+        }
+    }
+
+    fn pipeline(server: &MockServer) -> Pipeline<Recorder> {
+        let mut runner = Runner::new(&server.url(), 1111).unwrap();
+        runner.set_non_block(true);
+        Pipeline::new(runner, Recorder::default())
+    }
+
+    fn start() -> OffsetConfig {
+        OffsetConfig {
+            pos: Some(("mysql-bin.000001".to_owned(), 4)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let dir = std::env::temp_dir().join(format!("sink-pipeline-{}", std::process::id()));
+        write_fixture(&dir);
+        let end = std::fs::metadata(dir.join("mysql-bin.000002"))
+            .unwrap()
+            .len();
+        let server = MockServer::start(MockConfig::new(&dir)).unwrap();
+
+        // everything fits into one batch, delivered at the end of the dump
+        let mut batched = pipeline(&server);
+        batched.run(start()).unwrap();
+        assert_eq!(
+            batched.into_sink().0,
+            vec![
+                Call::Write(vec![
+                    "BEGIN".to_owned(),
+                    "rows".to_owned(),
+                    "CREATE TABLE t2 (id INT)".to_owned()
+                ]),
+                Call::Flush,
+                Call::Commit("mysql-bin.000002".to_owned(), end),
+            ]
+        );
+
+        // flushing after every transaction; records are never committed before a flush
+        let checkpoint_file = dir.join("checkpoint");
+        let mut eager = pipeline(&server);
+        eager.set_batch_size(1);
+        eager.set_flush_interval(Duration::from_secs(0));
+        eager.set_checkpoint_file(&checkpoint_file);
+        eager.run(start()).unwrap();
+        let calls = eager.into_sink().0;
+        let commits: Vec<&Call> = calls
+            .iter()
+            .filter(|c| matches!(c, Call::Commit(..)))
+            .collect();
+        assert!(
+            matches!(commits[1], Call::Commit(file, pos) if file == "mysql-bin.000001" && *pos > 4)
+        );
+        assert_eq!(
+            commits.last(),
+            Some(&&Call::Commit("mysql-bin.000002".to_owned(), end))
+        );
+        for (i, call) in calls.iter().enumerate() {
+            if let Call::Commit(..) = call {
+                assert_eq!(calls[i - 1], Call::Flush, "{:?}", calls);
+            }
+        }
+        // the dump starts with a rotate to the start position, a boundary of its own
+        assert_eq!(calls[1], Call::Commit("mysql-bin.000001".to_owned(), 4));
+        assert_eq!(calls[2], Call::Write(vec!["BEGIN".to_owned()]));
+
+        // a restart resumes from the stored checkpoint, past everything delivered
+        let mut resumed = pipeline(&server);
+        resumed.set_checkpoint_file(&checkpoint_file);
+        resumed.run(start()).unwrap();
+        assert_eq!(
+            resumed.checkpoint(),
+            Some(&Checkpoint {
+                file: "mysql-bin.000002".to_owned(),
+                pos: end
+            })
+        );
+        assert!(!resumed
+            .into_sink()
+            .0
+            .iter()
+            .any(|c| matches!(c, Call::Write(_))));
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::{self, BufWriter, Stdout, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use super::Sink;
+
+/// Writes records to any writer, one per line
+pub struct LineSink<W: Write> {
+    out: BufWriter<W>,
+}
+
+impl<W: Write> LineSink<W> {
+    pub fn new(out: W) -> Self {
+        LineSink {
+            out: BufWriter::new(out),
+        }
+    }
+}
+
+impl LineSink<Stdout> {
+    pub fn stdout() -> Self {
+        LineSink::new(io::stdout())
+    }
+}
+
+impl<W: Write> Sink for LineSink<W> {
+    fn write_batch(&mut self, records: &[Vec<u8>]) -> io::Result<()> {
+        for record in records {
+            self.out.write_all(record)?;
+            self.out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Sends records over a stream socket, each prefixed with its length as a 4 byte big-endian
+/// number
+pub struct SocketSink<S: Write> {
+    stream: BufWriter<S>,
+}
+
+impl<S: Write> SocketSink<S> {
+    pub fn new(stream: S) -> Self {
+        SocketSink {
+            stream: BufWriter::new(stream),
+        }
+    }
+}
+
+impl SocketSink<TcpStream> {
+    pub fn connect_tcp<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(SocketSink::new(stream))
+    }
+}
+
+#[cfg(unix)]
+impl SocketSink<UnixStream> {
+    pub fn connect_unix<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(SocketSink::new(UnixStream::connect(path)?))
+    }
+}
+
+impl<S: Write> Sink for SocketSink<S> {
+    fn write_batch(&mut self, records: &[Vec<u8>]) -> io::Result<()> {
+        for record in records {
+            if record.len() > u32::MAX as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "record longer than 4 GiB",
+                ));
+            }
+            self.stream.write_u32::<BigEndian>(record.len() as u32)?;
+            self.stream.write_all(record)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    use byteorder::{BigEndian, ReadBytesExt};
+
+    use super::{LineSink, SocketSink};
+    use crate::sink::Sink;
+
+    fn read_records<R: Read>(mut stream: R) -> Vec<String> {
+        let mut records = vec![];
+        while let Ok(length) = stream.read_u32::<BigEndian>() {
+            let mut record = vec![0; length as usize];
+            stream.read_exact(&mut record).unwrap();
+            records.push(String::from_utf8(record).unwrap());
+        }
+        records
+    }
+
+    #[test]
+    fn test_stream_sinks() {
+        let mut lines = LineSink::new(Vec::new());
+        lines
+            .write_batch(&[b"{}".to_vec(), b"[]".to_vec()])
+            .unwrap();
+        lines.flush().unwrap();
+        assert_eq!(lines.out.get_ref(), b"{}\n[]\n");
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let reader = std::thread::spawn(move || read_records(listener.accept().unwrap().0));
+        let mut sink = SocketSink::connect_tcp(addr).unwrap();
+        sink.write_batch(&[b"first".to_vec(), Vec::new()]).unwrap();
+        sink.write_batch(&[b"second".to_vec()]).unwrap();
+        sink.flush().unwrap();
+        drop(sink);
+        assert_eq!(reader.join().unwrap(), vec!["first", "", "second"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_sink() {
+        use std::os::unix::net::UnixListener;
+        let path = std::env::temp_dir().join(format!("sink-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let reader = std::thread::spawn(move || read_records(listener.accept().unwrap().0));
+        let mut sink = SocketSink::connect_unix(&path).unwrap();
+        sink.write_batch(&[b"row".to_vec()]).unwrap();
+        sink.flush().unwrap();
+        drop(sink);
+        assert_eq!(reader.join().unwrap(), vec!["row"]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
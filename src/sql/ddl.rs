@@ -0,0 +1,613 @@
+//! Parse the DDL statements that change table definitions.
+//!
+//! Only the parts of a statement that matter for the columns and primary key of a table are
+//! read; indexes, options and partitioning are skipped over. Statements this parser doesn't
+//! understand, and DDL on temporary tables, which never reach a row-based binlog's rows events,
+//! give `None`.
+use crate::sql::lexer::{tokenize, Parser, Token};
+use crate::sql::quote_string;
+use crate::sql::types::parse_column_type;
+use crate::sql::{ColumnDef, TableDef};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TableName {
+    pub schema_name: String,
+    pub table_name: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Ddl {
+    CreateDatabase(String),
+    DropDatabase(String),
+    CreateTable(TableDef),
+    CreateTableLike {
+        table: TableName,
+        like: TableName,
+    },
+    AlterTable {
+        table: TableName,
+        specs: Vec<AlterSpec>,
+    },
+    /// Renames done one after the other, as `RENAME TABLE a TO b, b TO c` does
+    RenameTable(Vec<(TableName, TableName)>),
+    DropTable(Vec<TableName>),
+}
+
+/// Where `ADD`, `CHANGE` and `MODIFY` put a column
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnPosition {
+    /// At the end for a new column, where it was for a changed one
+    Default,
+    First,
+    After(String),
+}
+
+/// One change an `ALTER TABLE` makes to a table definition
+#[derive(Debug, Clone)]
+pub enum AlterSpec {
+    AddColumn {
+        column: ColumnDef,
+        position: ColumnPosition,
+        primary_key: bool,
+    },
+    /// `CHANGE old_name ...`, or `MODIFY` when the name stays the same
+    ChangeColumn {
+        old_name: String,
+        column: ColumnDef,
+        position: ColumnPosition,
+    },
+    RenameColumn {
+        old_name: String,
+        new_name: String,
+    },
+    DropColumn(String),
+    AddPrimaryKey(Vec<String>),
+    DropPrimaryKey,
+    Rename(TableName),
+}
+
+/// Parse a DDL statement run with `default_schema` as the default database, as a query event
+/// records it
+pub fn parse_ddl(query: &str, default_schema: Option<&str>) -> Option<Ddl> {
+    let tokens = tokenize(query);
+    let mut parser = Parser::new(&tokens, default_schema);
+    let ddl = parser.ddl()?;
+    parser.eat_symbol(';');
+    if parser.pos < tokens.len() {
+        return None;
+    }
+    Some(ddl)
+}
+
+impl Parser<'_> {
+    fn ddl(&mut self) -> Option<Ddl> {
+        if self.eat_keyword("CREATE") {
+            self.eat_keywords(&["OR", "REPLACE"]);
+            if self.eat_keyword("DATABASE") || self.eat_keyword("SCHEMA") {
+                self.eat_keywords(&["IF", "NOT", "EXISTS"]);
+                return self.identifier().map(Ddl::CreateDatabase);
+            }
+            if !self.eat_keyword("TABLE") {
+                return None;
+            }
+            self.eat_keywords(&["IF", "NOT", "EXISTS"]);
+            self.create_table()
+        } else if self.eat_keyword("ALTER") {
+            self.eat_keyword("ONLINE");
+            self.eat_keyword("IGNORE");
+            if !self.eat_keyword("TABLE") {
+                return None;
+            }
+            let table = self.table_name()?;
+            let mut specs = Vec::new();
+            loop {
+                specs.extend(self.alter_spec().unwrap_or_default());
+                self.skip_item();
+                if !self.eat_symbol(',') {
+                    break;
+                }
+            }
+            Some(Ddl::AlterTable { table, specs })
+        } else if self.eat_keyword("RENAME") {
+            if !self.eat_keyword("TABLE") {
+                return None;
+            }
+            let mut renames = Vec::new();
+            loop {
+                let from = self.table_name()?;
+                if !self.eat_keyword("TO") {
+                    return None;
+                }
+                renames.push((from, self.table_name()?));
+                if !self.eat_symbol(',') {
+                    return Some(Ddl::RenameTable(renames));
+                }
+            }
+        } else if self.eat_keyword("DROP") {
+            if self.eat_keyword("DATABASE") || self.eat_keyword("SCHEMA") {
+                self.eat_keywords(&["IF", "EXISTS"]);
+                return self.identifier().map(Ddl::DropDatabase);
+            }
+            if !self.eat_keyword("TABLE") {
+                return None;
+            }
+            self.eat_keywords(&["IF", "EXISTS"]);
+            let tables = self.table_names()?;
+            let _ = self.eat_keyword("RESTRICT") || self.eat_keyword("CASCADE");
+            Some(Ddl::DropTable(tables))
+        } else {
+            None
+        }
+    }
+
+    fn create_table(&mut self) -> Option<Ddl> {
+        let table = self.table_name()?;
+        if self.eat_keyword("LIKE") {
+            let like = self.table_name()?;
+            return Some(Ddl::CreateTableLike { table, like });
+        }
+        if !self.eat_symbol('(') {
+            // CREATE TABLE ... SELECT without a column list
+            return None;
+        }
+        if self.eat_keyword("LIKE") {
+            let like = self.table_name()?;
+            return self
+                .eat_symbol(')')
+                .then_some(Ddl::CreateTableLike { table, like });
+        }
+        let mut def = TableDef {
+            schema_name: table.schema_name,
+            table_name: table.table_name,
+            columns: Vec::new(),
+            primary_key: Vec::new(),
+        };
+        let mut primary_key = Vec::new();
+        loop {
+            if let Some(key) = self.primary_key() {
+                primary_key = key;
+            } else if !self.skip_index() {
+                let (column, _, is_key) = self.column_definition(None)?;
+                if is_key {
+                    primary_key = vec![column.name.clone()];
+                }
+                def.columns.push(column);
+            }
+            self.skip_item();
+            if !self.eat_symbol(',') {
+                break;
+            }
+        }
+        if !self.eat_symbol(')') {
+            return None;
+        }
+        // table options, partitioning and a trailing SELECT don't change the columns
+        self.pos = self.tokens.len();
+        def.primary_key = primary_key
+            .iter()
+            .filter_map(|key| column_index(&def.columns, key))
+            .collect();
+        Some(Ddl::CreateTable(def))
+    }
+
+    /// `[CONSTRAINT [symbol]] PRIMARY KEY (columns)`, if that is what starts here
+    fn primary_key(&mut self) -> Option<Vec<String>> {
+        let start = self.pos;
+        if self.eat_keyword("CONSTRAINT") && !self.peek_keyword("PRIMARY") {
+            self.identifier();
+        }
+        if !self.eat_keywords(&["PRIMARY", "KEY"]) {
+            self.pos = start;
+            return None;
+        }
+        // an index type may come before the columns
+        if self.eat_keyword("USING") {
+            self.identifier();
+        }
+        if !self.eat_symbol('(') {
+            self.pos = start;
+            return None;
+        }
+        let mut columns = Vec::new();
+        loop {
+            columns.extend(self.identifier());
+            self.skip_item();
+            if !self.eat_symbol(',') {
+                break;
+            }
+        }
+        self.eat_symbol(')');
+        Some(columns)
+    }
+
+    /// Whether an index, key or constraint other than a primary key starts here
+    fn skip_index(&mut self) -> bool {
+        let index = [
+            "CONSTRAINT",
+            "INDEX",
+            "KEY",
+            "UNIQUE",
+            "FULLTEXT",
+            "SPATIAL",
+            "FOREIGN",
+            "CHECK",
+            "PERIOD",
+        ]
+        .iter()
+        .any(|k| self.peek_keyword(k));
+        if index {
+            self.skip_item();
+        }
+        index
+    }
+
+    /// A column definition: its name unless `name` is given, type and attributes. Also returns
+    /// the position `FIRST` or `AFTER` gives and whether the column is declared `PRIMARY KEY`.
+    fn column_definition(
+        &mut self,
+        name: Option<String>,
+    ) -> Option<(ColumnDef, ColumnPosition, bool)> {
+        let name = match name {
+            Some(name) => name,
+            None => self.identifier()?,
+        };
+        let mut type_name = match self.peek()? {
+            Token::Word(w) => w.to_ascii_lowercase(),
+            _ => return None,
+        };
+        self.pos += 1;
+        // two word type names
+        if type_name == "double" {
+            self.eat_keyword("PRECISION");
+        } else if type_name == "national" || type_name == "character" {
+            type_name = self.identifier()?.to_ascii_lowercase();
+        }
+        if self.eat_keyword("VARYING") {
+            type_name = "varchar".to_owned();
+        }
+        let mut definition = type_name;
+        if self.eat_symbol('(') {
+            let mut args = Vec::new();
+            while let Some(token) = self.peek() {
+                self.pos += 1;
+                match token {
+                    Token::Symbol(')') => break,
+                    Token::Str(s) => args.push(quote_string(s)),
+                    Token::Word(w) | Token::Quoted(w) => args.push(w.clone()),
+                    Token::Symbol(c) => args.push(c.to_string()),
+                }
+            }
+            definition = format!("{}({})", definition, args.concat());
+        }
+        let mut unsigned = false;
+        let mut position = ColumnPosition::Default;
+        let mut primary_key = false;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Symbol(',') | Token::Symbol(')') | Token::Symbol(';') => break,
+                Token::Symbol('(') => self.skip_group(),
+                t if t.is_keyword("UNSIGNED") || t.is_keyword("ZEROFILL") => {
+                    unsigned = true;
+                    self.pos += 1;
+                }
+                t if t.is_keyword("PRIMARY") => {
+                    primary_key = true;
+                    self.pos += 1;
+                }
+                t if t.is_keyword("FIRST") => {
+                    position = ColumnPosition::First;
+                    self.pos += 1;
+                }
+                t if t.is_keyword("AFTER") => {
+                    self.pos += 1;
+                    position = ColumnPosition::After(self.identifier()?);
+                }
+                _ => self.pos += 1,
+            }
+        }
+        if unsigned {
+            definition.push_str(" unsigned");
+        }
+        let parsed = parse_column_type(&definition);
+        let column = ColumnDef {
+            name,
+            unsigned: parsed
+                .as_ref()
+                .is_some_and(|p| p.unsigned && p.column_type.is_numeric()),
+            column_type: parsed.map(|p| p.column_type),
+        };
+        Some((column, position, primary_key))
+    }
+
+    fn alter_spec(&mut self) -> Option<Vec<AlterSpec>> {
+        if self.eat_keyword("ADD") {
+            if let Some(key) = self.primary_key() {
+                return Some(vec![AlterSpec::AddPrimaryKey(key)]);
+            }
+            if self.skip_index() || self.peek_keyword("PARTITION") {
+                return None;
+            }
+            self.eat_keyword("COLUMN");
+            self.eat_keywords(&["IF", "NOT", "EXISTS"]);
+            // `ADD (a int, b int)` adds several columns at the end
+            let several = self.eat_symbol('(');
+            let mut specs = Vec::new();
+            loop {
+                let (column, position, primary_key) = self.column_definition(None)?;
+                specs.push(AlterSpec::AddColumn {
+                    column,
+                    position,
+                    primary_key,
+                });
+                if !several || !self.eat_symbol(',') {
+                    break;
+                }
+            }
+            if several && !self.eat_symbol(')') {
+                return None;
+            }
+            Some(specs)
+        } else if self.eat_keyword("DROP") {
+            if self.eat_keywords(&["PRIMARY", "KEY"]) {
+                return Some(vec![AlterSpec::DropPrimaryKey]);
+            }
+            let other = [
+                "INDEX",
+                "KEY",
+                "FOREIGN",
+                "CHECK",
+                "CONSTRAINT",
+                "PARTITION",
+            ];
+            if other.iter().any(|k| self.peek_keyword(k)) {
+                return None;
+            }
+            self.eat_keyword("COLUMN");
+            self.eat_keywords(&["IF", "EXISTS"]);
+            Some(vec![AlterSpec::DropColumn(self.identifier()?)])
+        } else if self.eat_keyword("MODIFY") {
+            self.eat_keyword("COLUMN");
+            self.eat_keywords(&["IF", "EXISTS"]);
+            let (column, position, _) = self.column_definition(None)?;
+            Some(vec![AlterSpec::ChangeColumn {
+                old_name: column.name.clone(),
+                column,
+                position,
+            }])
+        } else if self.eat_keyword("CHANGE") {
+            self.eat_keyword("COLUMN");
+            self.eat_keywords(&["IF", "EXISTS"]);
+            let old_name = self.identifier()?;
+            let (column, position, _) = self.column_definition(None)?;
+            Some(vec![AlterSpec::ChangeColumn {
+                old_name,
+                column,
+                position,
+            }])
+        } else if self.eat_keyword("RENAME") {
+            if self.eat_keyword("COLUMN") {
+                let old_name = self.identifier()?;
+                if !self.eat_keyword("TO") {
+                    return None;
+                }
+                let new_name = self.identifier()?;
+                return Some(vec![AlterSpec::RenameColumn { old_name, new_name }]);
+            }
+            if self.peek_keyword("INDEX") || self.peek_keyword("KEY") {
+                return None;
+            }
+            let _ = self.eat_keyword("TO") || self.eat_keyword("AS");
+            Some(vec![AlterSpec::Rename(self.table_name()?)])
+        } else {
+            None
+        }
+    }
+}
+
+fn column_index(columns: &[ColumnDef], name: &str) -> Option<usize> {
+    // column names are case insensitive
+    columns
+        .iter()
+        .position(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+impl TableDef {
+    /// Apply the changes of an `ALTER TABLE` to this definition, except for renaming the table.
+    /// Changes to columns this definition doesn't have are skipped.
+    pub fn alter(&mut self, specs: &[AlterSpec]) {
+        // the key is followed by name while columns move around
+        let mut primary_key: Vec<String> = self
+            .primary_key
+            .iter()
+            .filter_map(|&i| self.columns.get(i).map(|c| c.name.clone()))
+            .collect();
+        for spec in specs {
+            match spec {
+                AlterSpec::AddColumn {
+                    column,
+                    position,
+                    primary_key: is_key,
+                } => {
+                    if column_index(&self.columns, &column.name).is_some() {
+                        continue;
+                    }
+                    let index = self.insert_index(position, self.columns.len());
+                    self.columns.insert(index, column.clone());
+                    if *is_key {
+                        primary_key = vec![column.name.clone()];
+                    }
+                }
+                AlterSpec::ChangeColumn {
+                    old_name,
+                    column,
+                    position,
+                } => {
+                    let old = match column_index(&self.columns, old_name) {
+                        Some(old) => old,
+                        None => continue,
+                    };
+                    self.columns.remove(old);
+                    let index = self.insert_index(position, old);
+                    self.columns.insert(index, column.clone());
+                    rename(&mut primary_key, old_name, &column.name);
+                }
+                AlterSpec::RenameColumn { old_name, new_name } => {
+                    if let Some(i) = column_index(&self.columns, old_name) {
+                        self.columns[i].name = new_name.clone();
+                        rename(&mut primary_key, old_name, new_name);
+                    }
+                }
+                AlterSpec::DropColumn(name) => {
+                    if let Some(i) = column_index(&self.columns, name) {
+                        self.columns.remove(i);
+                        primary_key.retain(|k| !k.eq_ignore_ascii_case(name));
+                    }
+                }
+                AlterSpec::AddPrimaryKey(key) => primary_key = key.clone(),
+                AlterSpec::DropPrimaryKey => primary_key.clear(),
+                AlterSpec::Rename(_) => {}
+            }
+        }
+        self.primary_key = primary_key
+            .iter()
+            .filter_map(|key| column_index(&self.columns, key))
+            .collect();
+    }
+
+    fn insert_index(&self, position: &ColumnPosition, default: usize) -> usize {
+        match position {
+            ColumnPosition::Default => default.min(self.columns.len()),
+            ColumnPosition::First => 0,
+            ColumnPosition::After(name) => match column_index(&self.columns, name) {
+                Some(i) => i + 1,
+                None => self.columns.len(),
+            },
+        }
+    }
+}
+
+fn rename(names: &mut [String], old_name: &str, new_name: &str) {
+    for name in names.iter_mut() {
+        if name.eq_ignore_ascii_case(old_name) {
+            *name = new_name.to_owned();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_ddl, AlterSpec, ColumnPosition, Ddl, TableName};
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::sql::TableDef;
+
+    fn create(query: &str) -> TableDef {
+        match parse_ddl(query, Some("shop")) {
+            Some(Ddl::CreateTable(def)) => def,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    fn names(def: &TableDef) -> Vec<&str> {
+        def.columns.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    fn table(schema_name: &str, table_name: &str) -> TableName {
+        TableName {
+            schema_name: schema_name.to_owned(),
+            table_name: table_name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_create_table() {
+        let def = create(
+            "CREATE TABLE IF NOT EXISTS `orders` (\n\
+             `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT 'the, id',\n\
+             `status` enum('new','paid') DEFAULT 'new',\n\
+             total decimal(10,2) GENERATED ALWAYS AS (price * (1 + tax)) VIRTUAL,\n\
+             note varchar(32) CHARACTER SET utf8mb4 /* inline, comment */,\n\
+             KEY idx_status (status),\n\
+             CONSTRAINT pk PRIMARY KEY (`status`, id(4) DESC),\n\
+             CONSTRAINT fk FOREIGN KEY (id) REFERENCES other (id)\n\
+             ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+        );
+        assert_eq!(def.schema_name, "shop");
+        assert_eq!(def.table_name, "orders");
+        assert_eq!(names(&def), vec!["id", "status", "total", "note"]);
+        assert_eq!(def.columns[0].column_type, Some(ColumnType::LongLong));
+        assert!(def.columns[0].unsigned);
+        assert_eq!(def.columns[1].column_type, Some(ColumnType::Enum(1)));
+        assert_eq!(
+            def.columns[2].column_type,
+            Some(ColumnType::NewDecimal(10, 2))
+        );
+        assert_eq!(def.primary_key, vec![1, 0]);
+
+        let def = create("create table other.t (a int primary key, b double precision)");
+        assert_eq!(def.schema_name, "other");
+        assert_eq!(def.primary_key, vec![0]);
+        assert_eq!(def.columns[1].column_type, Some(ColumnType::Double(8)));
+    }
+
+    #[test]
+    fn test_other_statements() {
+        assert!(parse_ddl("CREATE TEMPORARY TABLE t (a int)", Some("shop")).is_none());
+        assert!(parse_ddl("CREATE TABLE t SELECT 1", Some("shop")).is_none());
+        assert!(parse_ddl("CREATE TABLE t (a int)", None).is_none());
+        assert!(parse_ddl("INSERT INTO t VALUES (1)", Some("shop")).is_none());
+        assert!(matches!(
+            parse_ddl("CREATE TABLE t LIKE other.s", Some("shop")),
+            Some(Ddl::CreateTableLike { table: t, like }) if t == table("shop", "t") && like == table("other", "s")
+        ));
+        assert!(matches!(
+            parse_ddl("DROP TABLE IF EXISTS `t`, other.s /* generated by server */", Some("shop")),
+            Some(Ddl::DropTable(tables)) if tables == vec![table("shop", "t"), table("other", "s")]
+        ));
+        assert!(matches!(
+            parse_ddl("RENAME TABLE a TO b, b TO other.c;", Some("shop")),
+            Some(Ddl::RenameTable(renames)) if renames.len() == 2 && renames[1].1 == table("other", "c")
+        ));
+        assert!(matches!(
+            parse_ddl("CREATE DATABASE /*!32312 IF NOT EXISTS*/ `new`", None),
+            Some(Ddl::CreateDatabase(name)) if name == "new"
+        ));
+        assert!(matches!(
+            parse_ddl("drop schema if exists old", None),
+            Some(Ddl::DropDatabase(name)) if name == "old"
+        ));
+    }
+
+    #[test]
+    fn test_alter_table() {
+        let mut def = create("CREATE TABLE t (id int, a int, b int, PRIMARY KEY (id))");
+        let specs = match parse_ddl(
+            "ALTER TABLE t ADD COLUMN c varchar(10) NOT NULL AFTER id, ADD INDEX (a), \
+             DROP COLUMN a, CHANGE id `key` bigint unsigned FIRST, MODIFY b text FIRST, \
+             RENAME COLUMN c TO d, ALGORITHM=INPLACE, ADD (e int, f int), RENAME TO t2",
+            Some("shop"),
+        ) {
+            Some(Ddl::AlterTable { table, specs }) if table.table_name == "t" => specs,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(specs.len(), 8);
+        assert!(
+            matches!(&specs[0], AlterSpec::AddColumn { position: ColumnPosition::After(a), .. } if a == "id")
+        );
+        assert!(matches!(&specs[7], AlterSpec::Rename(t) if *t == table("shop", "t2")));
+        def.alter(&specs);
+        assert_eq!(names(&def), vec!["b", "key", "d", "e", "f"]);
+        assert_eq!(def.columns[0].column_type, Some(ColumnType::Blob(2)));
+        assert!(def.columns[1].unsigned);
+        assert_eq!(def.primary_key, vec![1]);
+
+        let specs = match parse_ddl(
+            "ALTER TABLE t DROP PRIMARY KEY, ADD PRIMARY KEY (d, b)",
+            Some("shop"),
+        ) {
+            Some(Ddl::AlterTable { specs, .. }) => specs,
+            other => panic!("{:?}", other),
+        };
+        def.alter(&specs);
+        assert_eq!(def.primary_key, vec![2, 0]);
+    }
+}
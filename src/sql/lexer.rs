@@ -0,0 +1,311 @@
+use crate::sql::ddl::TableName;
+
+/// A token of a MySQL statement, as far as telling statements apart needs
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    /// A keyword, unquoted identifier or number
+    Word(String),
+    /// A backquoted identifier, unquoted
+    Quoted(String),
+    /// A string literal, unescaped
+    Str(String),
+    Symbol(char),
+}
+
+impl Token {
+    /// Whether this is the unquoted word `keyword`, in any case
+    pub(crate) fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    /// The identifier this token names, quoted or not
+    pub(crate) fn identifier(&self) -> Option<&str> {
+        match self {
+            Token::Word(w) | Token::Quoted(w) => Some(w),
+            _ => None,
+        }
+    }
+}
+
+/// Split a statement into tokens. Comments are dropped, except for the contents of the
+/// executable comments `/*!50100 ... */` and `/*M!100100 ... */` which the server runs.
+pub(crate) fn tokenize(sql: &str) -> Vec<Token> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut in_executable_comment = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '#' => i = skip_line(&chars, i),
+            '-' if next == Some('-') && chars.get(i + 2).is_none_or(|c| c.is_whitespace()) => {
+                i = skip_line(&chars, i)
+            }
+            '/' if next == Some('*') => {
+                let marker = match (chars.get(i + 2), chars.get(i + 3)) {
+                    (Some('!'), _) => Some(i + 3),
+                    (Some('M'), Some('!')) => Some(i + 4),
+                    _ => None,
+                };
+                match marker {
+                    Some(mut start) => {
+                        while chars.get(start).is_some_and(|c| c.is_ascii_digit()) {
+                            start += 1;
+                        }
+                        in_executable_comment = true;
+                        i = start;
+                    }
+                    None => {
+                        i += 2;
+                        while i < chars.len()
+                            && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/'))
+                        {
+                            i += 1;
+                        }
+                        i += 2;
+                    }
+                }
+            }
+            '*' if next == Some('/') && in_executable_comment => {
+                in_executable_comment = false;
+                i += 2;
+            }
+            '\'' | '"' | '`' => {
+                let (text, end) = quoted(&chars, i);
+                tokens.push(if c == '`' {
+                    Token::Quoted(text)
+                } else {
+                    Token::Str(text)
+                });
+                i = end;
+            }
+            c if is_word_char(c) => {
+                let start = i;
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect()));
+            }
+            c => {
+                tokens.push(Token::Symbol(c));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn skip_line(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && chars[i] != '\n' {
+        i += 1;
+    }
+    i
+}
+
+/// The text of the quoted string or identifier starting at `start`, and the index after it.
+/// Doubled quotes stand for one; backslash escapes are only read in string literals.
+fn quoted(chars: &[char], start: usize) -> (String, usize) {
+    let quote = chars[start];
+    let mut text = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if quote != '`' && i + 1 < chars.len() => {
+                text.push(match chars[i + 1] {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    'Z' => '\x1a',
+                    c => c,
+                });
+                i += 2;
+            }
+            c if c == quote && chars.get(i + 1) == Some(&quote) => {
+                text.push(quote);
+                i += 2;
+            }
+            c if c == quote => return (text, i + 1),
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    (text, i)
+}
+
+/// A cursor over the tokens of a statement
+pub(crate) struct Parser<'a> {
+    pub(crate) tokens: &'a [Token],
+    pub(crate) pos: usize,
+    /// The schema of table names which don't name one
+    pub(crate) default_schema: Option<&'a str>,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(tokens: &'a [Token], default_schema: Option<&'a str>) -> Self {
+        Parser {
+            tokens,
+            pos: 0,
+            default_schema,
+        }
+    }
+
+    pub(crate) fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    pub(crate) fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_some_and(|t| t.is_keyword(keyword))
+    }
+
+    pub(crate) fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    pub(crate) fn eat_keywords(&mut self, keywords: &[&str]) -> bool {
+        let found = keywords.iter().enumerate().all(|(i, k)| {
+            self.tokens
+                .get(self.pos + i)
+                .is_some_and(|t| t.is_keyword(k))
+        });
+        if found {
+            self.pos += keywords.len();
+        }
+        found
+    }
+
+    pub(crate) fn peek_symbol(&self, symbol: char) -> bool {
+        self.peek() == Some(&Token::Symbol(symbol))
+    }
+
+    pub(crate) fn eat_symbol(&mut self, symbol: char) -> bool {
+        let found = self.peek_symbol(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    pub(crate) fn identifier(&mut self) -> Option<String> {
+        let name = self.peek()?.identifier()?.to_owned();
+        self.pos += 1;
+        Some(name)
+    }
+
+    pub(crate) fn table_name(&mut self) -> Option<TableName> {
+        let first = self.identifier()?;
+        if self.eat_symbol('.') {
+            let table_name = self.identifier()?;
+            return Some(TableName {
+                schema_name: first,
+                table_name,
+            });
+        }
+        Some(TableName {
+            schema_name: self.default_schema?.to_owned(),
+            table_name: first,
+        })
+    }
+
+    pub(crate) fn table_names(&mut self) -> Option<Vec<TableName>> {
+        let mut names = vec![self.table_name()?];
+        while self.eat_symbol(',') {
+            names.push(self.table_name()?);
+        }
+        Some(names)
+    }
+
+    /// Skip a parenthesized group, if one starts here
+    pub(crate) fn skip_group(&mut self) {
+        if !self.peek_symbol('(') {
+            return;
+        }
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            self.pos += 1;
+            match token {
+                Token::Symbol('(') => depth += 1,
+                Token::Symbol(')') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Skip to the `,` or `)` ending the current list item, or to the end
+    pub(crate) fn skip_item(&mut self) {
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Symbol(',') | Token::Symbol(')') | Token::Symbol(';') => return,
+                Token::Symbol('(') => self.skip_group(),
+                _ => self.pos += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, Token};
+
+    fn word(w: &str) -> Token {
+        Token::Word(w.to_owned())
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("/* lead */ DROP TABLE `we``ird` -- trailing\n# more\n, t2"),
+            vec![
+                word("DROP"),
+                word("TABLE"),
+                Token::Quoted("we`ird".to_owned()),
+                Token::Symbol(','),
+                word("t2"),
+            ]
+        );
+        assert_eq!(
+            tokenize("SELECT 'it''s', \"a\\\"b\"--1"),
+            vec![
+                word("SELECT"),
+                Token::Str("it's".to_owned()),
+                Token::Symbol(','),
+                Token::Str("a\"b".to_owned()),
+                Token::Symbol('-'),
+                Token::Symbol('-'),
+                word("1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_executable_comments() {
+        assert_eq!(
+            tokenize("CREATE /*!32312 TEMPORARY */ TABLE t /*M!100100 x*/"),
+            vec![
+                word("CREATE"),
+                word("TEMPORARY"),
+                word("TABLE"),
+                word("t"),
+                word("x"),
+            ]
+        );
+        assert_eq!(tokenize("/* unterminated"), Vec::new());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::mysql_binlog::column_types::ColumnType;
+use crate::mysql_binlog::geometry;
+use crate::mysql_binlog::value::MySQLValue;
+use crate::sql::ColumnDef;
+
+/// Quote an identifier with backticks, doubling any backtick inside it
+pub fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+/// Quote a string literal, escaping the characters MySQL treats specially
+pub fn quote_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('\'');
+    for c in s.chars() {
+        match c {
+            '\0' => out.push_str("\\0"),
+            '\'' => out.push_str("\\'"),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\x1a' => out.push_str("\\Z"),
+            c => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+/// Render a decoded value as a MySQL literal.
+///
+/// The column definition is optional; when present it is used to undo the sign extension of
+/// UNSIGNED integers and to print fractional seconds with the column's precision.
+pub fn value_literal(value: &MySQLValue, column: Option<&ColumnDef>) -> String {
+    let column_type = column.and_then(|c| c.column_type.as_ref());
+    match value {
+        MySQLValue::SignedInteger(i) => {
+            if column.map(|c| c.unsigned).unwrap_or(false) {
+                unsigned_value(*i, column_type).to_string()
+            } else {
+                i.to_string()
+            }
+        }
+        MySQLValue::Float(f) if f.is_finite() => f.to_string(),
+        MySQLValue::Double(f) if f.is_finite() => f.to_string(),
+        MySQLValue::Float(_) | MySQLValue::Double(_) => "NULL".to_owned(),
+        MySQLValue::String(s) => quote_string(s),
+        MySQLValue::Enum {
+            label: Some(label), ..
+        } => quote_string(label),
+        MySQLValue::Enum { index, .. } => index.to_string(),
+        MySQLValue::Set(labels) => quote_string(&labels.join(",")),
+        MySQLValue::Blob(b) => hex_literal(b.as_ref()),
+        // MySQL takes geometries in its internal format
+        MySQLValue::Geometry { srid, wkb } => {
+            hex_literal(&geometry::internal_bytes(*srid, wkb.as_ref()))
+        }
+        MySQLValue::Year(y) => y.to_string(),
+        MySQLValue::Date { year, month, day } => {
+            format!("'{:04}-{:02}-{:02}'", year, month, day)
+        }
+        MySQLValue::Time {
+            negative,
+            hours,
+            minutes,
+            seconds,
+            subseconds,
+        } => format!(
+            "'{}{:02}:{:02}:{:02}{}'",
+            if *negative { "-" } else { "" },
+            hours,
+            minutes,
+            seconds,
+            fraction(*subseconds, column_type)
+        ),
+        MySQLValue::DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            subsecond,
+        } => format!(
+            "'{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}'",
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            fraction(*subsecond, column_type)
+        ),
+        MySQLValue::Json(j) => quote_string(&j.to_string()),
+        MySQLValue::Decimal(d) => d.to_string(),
+        MySQLValue::Timestamp {
+            unix_time,
+            subsecond,
+        } => format!(
+            "FROM_UNIXTIME({}{})",
+            unix_time,
+            fraction(*subsecond, column_type)
+        ),
+        MySQLValue::Null => "NULL".to_owned(),
+    }
+}
+
+fn hex_literal(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        "''".to_owned()
+    } else {
+        let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        format!("X'{}'", hex)
+    }
+}
+
+pub(crate) fn unsigned_value(i: i64, column_type: Option<&ColumnType>) -> u64 {
+    match column_type {
+        Some(ColumnType::Tiny) => u64::from(i as u8),
+        Some(ColumnType::Short) => u64::from(i as u16),
+        Some(ColumnType::Int24) => (i as u64) & 0x00ff_ffff,
+        Some(ColumnType::Long) => u64::from(i as u32),
+        _ => i as u64,
+    }
+}
+
+/// The fractional seconds precision of a temporal column type, if it has one
+pub(crate) fn precision(column_type: Option<&ColumnType>) -> Option<u32> {
+    match column_type {
+        Some(ColumnType::Time2(fsp))
+        | Some(ColumnType::DateTime2(fsp))
+        | Some(ColumnType::Timestamp2(fsp)) => Some(u32::from(*fsp).min(6)),
+        _ => None,
+    }
+}
+
+/// The fractional seconds of a temporal value as printed, `.` and the column's precision worth
+/// of digits
+pub(crate) fn fraction(micros: u32, column_type: Option<&ColumnType>) -> String {
+    match precision(column_type) {
+        Some(0) => String::new(),
+        Some(fsp) => format!(
+            ".{:0width$}",
+            micros / 10u32.pow(6 - fsp),
+            width = fsp as usize
+        ),
+        None if micros == 0 => String::new(),
+        None => format!(".{:06}", micros),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote_identifier, quote_string, value_literal};
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::value::MySQLValue;
+    use crate::sql::ColumnDef;
+
+    fn column(column_type: ColumnType, unsigned: bool) -> ColumnDef {
+        ColumnDef {
+            name: "c".to_owned(),
+            column_type: Some(column_type),
+            unsigned,
+        }
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote_identifier("we`ird"), "`we``ird`");
+        assert_eq!(quote_string("it's a\\b\n\0"), "'it\\'s a\\\\b\\n\\0'");
+    }
+
+    #[test]
+    fn test_value_literal() {
+        assert_eq!(value_literal(&MySQLValue::SignedInteger(-1), None), "-1");
+        assert_eq!(
+            value_literal(
+                &MySQLValue::SignedInteger(-1),
+                Some(&column(ColumnType::Tiny, true))
+            ),
+            "255"
+        );
+        assert_eq!(
+            value_literal(
+                &MySQLValue::SignedInteger(-1),
+                Some(&column(ColumnType::LongLong, true))
+            ),
+            "18446744073709551615"
+        );
+        assert_eq!(
+            value_literal(&MySQLValue::Blob(vec![0xde, 0xad].into()), None),
+            "X'DEAD'"
+        );
+        assert_eq!(
+            value_literal(&MySQLValue::Decimal("-1.50".parse().unwrap()), None),
+            "-1.50"
+        );
+        assert_eq!(value_literal(&MySQLValue::Null, None), "NULL");
+        let dt = MySQLValue::DateTime {
+            year: 2021,
+            month: 7,
+            day: 1,
+            hour: 13,
+            minute: 5,
+            second: 9,
+            subsecond: 120_000,
+        };
+        assert_eq!(
+            value_literal(&dt, Some(&column(ColumnType::DateTime2(2), false))),
+            "'2021-07-01 13:05:09.12'"
+        );
+        assert_eq!(
+            value_literal(&dt, Some(&column(ColumnType::DateTime2(3), false))),
+            "'2021-07-01 13:05:09.120'"
+        );
+        assert_eq!(
+            value_literal(
+                &MySQLValue::Timestamp {
+                    unix_time: 1625144709,
+                    subsecond: 0,
+                },
+                Some(&column(ColumnType::Timestamp2(0), false))
+            ),
+            "FROM_UNIXTIME(1625144709)"
+        );
+    }
+}
@@ -0,0 +1,407 @@
+//! Render decoded rows events as executable SQL statements.
+//!
+//! This is the executable counterpart of `mysqlbinlog -v`: every row of a rows event becomes an
+//! `INSERT`, `UPDATE` or `DELETE` statement. Column names come from a [`TableDef`], which is
+//! built from the table map when the source runs with `binlog_row_metadata=FULL` and can be
+//! filled in by hand otherwise.
+//!
+//! ```no_run
+//! use mysqlbinlog_network::mysql_binlog::parse_file;
+//! use mysqlbinlog_network::sql;
+//!
+//! for event in parse_file("binlog.000001").unwrap() {
+//!     for statement in sql::binlog_event_statements(&event.unwrap()) {
+//!         println!("{}", statement);
+//!     }
+//! }
+//! ```
+pub mod ddl;
+mod lexer;
+mod literal;
+pub mod statement;
+pub mod types;
+
+pub(crate) use literal::{fraction, precision, unsigned_value};
+pub use literal::{quote_identifier, quote_string, value_literal};
+
+use crate::mysql_binlog::column_types::ColumnType;
+use crate::mysql_binlog::event::{RowData, RowEvent};
+use crate::mysql_binlog::table_map::SingleTableMap;
+use crate::mysql_binlog::value::MySQLValue;
+use crate::mysql_binlog::BinlogEvent;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnDef {
+    pub name: String,
+    pub column_type: Option<ColumnType>,
+    pub unsigned: bool,
+}
+
+/// The parts of a table definition needed to render statements against it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableDef {
+    pub schema_name: String,
+    pub table_name: String,
+    pub columns: Vec<ColumnDef>,
+    /// Indexes into `columns`; when empty every column is used to identify a row
+    pub primary_key: Vec<usize>,
+}
+
+impl TableDef {
+    /// Build a definition from a table map. Columns without a name in the table map metadata
+    /// are called `@1`, `@2`, ... like `mysqlbinlog -v` does.
+    pub fn from_table_map(table: &SingleTableMap) -> Self {
+        let metadata = table.metadata();
+        let columns = table
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, column_type)| ColumnDef {
+                name: metadata
+                    .column_names
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| format!("@{}", i + 1)),
+                column_type: Some(column_type.clone()),
+                unsigned: metadata.unsigned_columns.get(i).copied().unwrap_or(false),
+            })
+            .collect();
+        TableDef {
+            schema_name: table.schema_name().to_owned(),
+            table_name: table.table_name().to_owned(),
+            columns,
+            primary_key: metadata.primary_key.clone(),
+        }
+    }
+
+    /// Build a definition from a table map, filling in what its metadata lacks from `resolver`:
+    /// the column names when the definitions have as many columns, and the primary key.
+    pub fn resolve(table: &SingleTableMap, resolver: &dyn SchemaResolver) -> Self {
+        let mut def = TableDef::from_table_map(table);
+        let known = match resolver.table_def(&def.schema_name, &def.table_name) {
+            Some(known) => known,
+            None => return def,
+        };
+        let metadata = table.metadata();
+        if metadata.column_names.is_empty() && known.columns.len() == def.columns.len() {
+            for (column, known) in def.columns.iter_mut().zip(&known.columns) {
+                column.name = known.name.clone();
+                column.unsigned |= known.unsigned;
+            }
+        }
+        if def.primary_key.is_empty() && known.primary_key.iter().all(|&i| i < def.columns.len()) {
+            def.primary_key = known.primary_key.clone();
+        }
+        def
+    }
+
+    /// The values of the primary key columns of a row image, in key order. `None` when the
+    /// table has no primary key or a key column is missing from the image.
+    pub fn key(&self, cols: &RowData) -> Option<Vec<MySQLValue>> {
+        if self.primary_key.is_empty() {
+            return None;
+        }
+        self.primary_key
+            .iter()
+            .map(|&i| cols.get(i).cloned().flatten())
+            .collect()
+    }
+
+    fn qualified_name(&self) -> String {
+        format!(
+            "{}.{}",
+            quote_identifier(&self.schema_name),
+            quote_identifier(&self.table_name)
+        )
+    }
+
+    fn column_name(&self, i: usize) -> String {
+        match self.columns.get(i) {
+            Some(c) => quote_identifier(&c.name),
+            None => quote_identifier(&format!("@{}", i + 1)),
+        }
+    }
+
+    fn literal(&self, i: usize, value: &MySQLValue) -> String {
+        value_literal(value, self.columns.get(i))
+    }
+
+    /// `col=value` pairs for the columns identifying a row image. Uses the primary key when
+    /// every key column is present in the image, otherwise every present column but the
+    /// FLOAT, DOUBLE and JSON ones, which never compare equal to their literals. `None` when
+    /// the image has no column to match on.
+    fn where_clause(&self, cols: &RowData) -> Option<String> {
+        let key_present = !self.primary_key.is_empty()
+            && self
+                .primary_key
+                .iter()
+                .all(|i| matches!(cols.get(*i), Some(Some(_))));
+        let indexes: Vec<usize> = if key_present {
+            self.primary_key.clone()
+        } else {
+            present_columns(cols)
+                .filter(|i| self.comparable(*i, cols))
+                .collect()
+        };
+        let conditions: Vec<String> = indexes
+            .into_iter()
+            .filter_map(|i| cols.get(i).and_then(|c| c.as_ref()).map(|v| (i, v)))
+            .map(|(i, value)| match value {
+                MySQLValue::Null => format!("{} IS NULL", self.column_name(i)),
+                v => format!("{}={}", self.column_name(i), self.literal(i, v)),
+            })
+            .collect();
+        if conditions.is_empty() {
+            return None;
+        }
+        Some(conditions.join(" AND "))
+    }
+
+    /// Whether `col=value` can find the row by column `i`
+    fn comparable(&self, i: usize, cols: &RowData) -> bool {
+        let column_type = self.columns.get(i).and_then(|c| c.column_type.as_ref());
+        let value = cols.get(i).and_then(|c| c.as_ref());
+        !matches!(
+            column_type,
+            Some(ColumnType::Float(_)) | Some(ColumnType::Double(_)) | Some(ColumnType::Json(_))
+        ) && !matches!(
+            value,
+            Some(MySQLValue::Float(_)) | Some(MySQLValue::Double(_)) | Some(MySQLValue::Json(_))
+        )
+    }
+}
+
+/// Where definitions come from for tables whose table map doesn't say enough, when the source
+/// doesn't run with `binlog_row_metadata=FULL`
+pub trait SchemaResolver {
+    /// The definition of `schema_name.table_name`, if known
+    fn table_def(&self, schema_name: &str, table_name: &str) -> Option<&TableDef>;
+}
+
+impl SchemaResolver for Vec<TableDef> {
+    fn table_def(&self, schema_name: &str, table_name: &str) -> Option<&TableDef> {
+        self.iter()
+            .find(|t| t.schema_name == schema_name && t.table_name == table_name)
+    }
+}
+
+fn present_columns(cols: &RowData) -> impl Iterator<Item = usize> + '_ {
+    cols.iter()
+        .enumerate()
+        .filter(|(_, c)| c.is_some())
+        .map(|(i, _)| i)
+}
+
+pub fn insert_statement(table: &TableDef, cols: &RowData) -> String {
+    let names: Vec<String> = present_columns(cols)
+        .map(|i| table.column_name(i))
+        .collect();
+    let values: Vec<String> = cols
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| c.as_ref().map(|v| table.literal(i, v)))
+        .collect();
+    format!(
+        "INSERT INTO {} ({}) VALUES ({});",
+        table.qualified_name(),
+        names.join(", "),
+        values.join(", ")
+    )
+}
+
+/// `None` when either image is empty, as there is nothing to match or nothing to set
+pub fn update_statement(table: &TableDef, before: &RowData, after: &RowData) -> Option<String> {
+    let assignments: Vec<String> = after
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| {
+            c.as_ref()
+                .map(|v| format!("{}={}", table.column_name(i), table.literal(i, v)))
+        })
+        .collect();
+    if assignments.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "UPDATE {} SET {} WHERE {} LIMIT 1;",
+        table.qualified_name(),
+        assignments.join(", "),
+        table.where_clause(before)?
+    ))
+}
+
+/// `None` when the row image is empty, as there is nothing to match the row on
+pub fn delete_statement(table: &TableDef, cols: &RowData) -> Option<String> {
+    Some(format!(
+        "DELETE FROM {} WHERE {} LIMIT 1;",
+        table.qualified_name(),
+        table.where_clause(cols)?
+    ))
+}
+
+/// The statement making the change a row event records, `None` for an update or delete whose
+/// images don't say which row it is
+pub fn row_statement(table: &TableDef, row: &RowEvent) -> Option<String> {
+    match row {
+        RowEvent::NewRow { cols } => Some(insert_statement(table, cols)),
+        RowEvent::DeletedRow { cols } => delete_statement(table, cols),
+        RowEvent::UpdatedRow {
+            before_cols,
+            after_cols,
+        } => update_statement(table, before_cols, after_cols),
+    }
+}
+
+/// One statement per row of a rows event, using the table map the rows were decoded with.
+/// Returns nothing for other events, or when the rows could not be decoded. Rows
+/// [`row_statement`] can't render are left out.
+pub fn binlog_event_statements(event: &BinlogEvent) -> Vec<String> {
+    match event.table {
+        Some(ref table) => {
+            let table = TableDef::from_table_map(table);
+            event
+                .rows
+                .iter()
+                .filter_map(|row| row_statement(&table, row))
+                .collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{row_statement, ColumnDef, TableDef};
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::event::RowEvent;
+    use crate::mysql_binlog::table_map::TableMap;
+    use crate::mysql_binlog::value::MySQLValue;
+
+    fn table(primary_key: Vec<usize>) -> TableDef {
+        let column = |name: &str, column_type| ColumnDef {
+            name: name.to_owned(),
+            column_type: Some(column_type),
+            unsigned: false,
+        };
+        TableDef {
+            schema_name: "shop".to_owned(),
+            table_name: "orders".to_owned(),
+            columns: vec![
+                column("id", ColumnType::LongLong),
+                column("note", ColumnType::VarChar(255)),
+                column("amount", ColumnType::NewDecimal(10, 2)),
+            ],
+            primary_key,
+        }
+    }
+
+    fn row(id: i64, note: Option<&str>) -> Vec<Option<MySQLValue>> {
+        vec![
+            Some(MySQLValue::SignedInteger(id)),
+            Some(match note {
+                Some(n) => MySQLValue::String(n.to_owned()),
+                None => MySQLValue::Null,
+            }),
+            Some(MySQLValue::Decimal("9.90".parse().unwrap())),
+        ]
+    }
+
+    #[test]
+    fn test_insert() {
+        let insert = RowEvent::NewRow {
+            cols: row(1, Some("o'neil")),
+        };
+        assert_eq!(
+            row_statement(&table(vec![0]), &insert).unwrap(),
+            "INSERT INTO `shop`.`orders` (`id`, `note`, `amount`) VALUES (1, 'o\\'neil', 9.90);"
+        );
+    }
+
+    #[test]
+    fn test_update_uses_primary_key() {
+        let update = RowEvent::UpdatedRow {
+            before_cols: row(1, None),
+            after_cols: row(1, Some("paid")),
+        };
+        assert_eq!(
+            row_statement(&table(vec![0]), &update).unwrap(),
+            "UPDATE `shop`.`orders` SET `id`=1, `note`='paid', `amount`=9.90 WHERE `id`=1 LIMIT 1;"
+        );
+    }
+
+    #[test]
+    fn test_delete_without_primary_key() {
+        let delete = RowEvent::DeletedRow { cols: row(2, None) };
+        assert_eq!(
+            row_statement(&table(vec![]), &delete).unwrap(),
+            "DELETE FROM `shop`.`orders` WHERE `id`=2 AND `note` IS NULL AND `amount`=9.90 LIMIT 1;"
+        );
+        // nothing in the image to match the row on
+        let delete = RowEvent::DeletedRow {
+            cols: vec![None, None, None],
+        };
+        assert_eq!(row_statement(&table(vec![0]), &delete), None);
+        let update = RowEvent::UpdatedRow {
+            before_cols: vec![None, None, None],
+            after_cols: row(2, None),
+        };
+        assert_eq!(row_statement(&table(vec![]), &update), None);
+    }
+
+    #[test]
+    fn test_where_skips_inexact_columns() {
+        let mut def = table(vec![]);
+        for (name, column_type) in [
+            ("ratio", ColumnType::Float(4)),
+            ("attrs", ColumnType::Json(4)),
+        ] {
+            def.columns.push(ColumnDef {
+                name: name.to_owned(),
+                column_type: Some(column_type),
+                unsigned: false,
+            });
+        }
+        let mut cols = row(2, Some("a"));
+        cols.push(Some(MySQLValue::Float(1.1)));
+        cols.push(Some(MySQLValue::Json(serde_json::json!({"a": 1}))));
+        assert_eq!(
+            row_statement(&def, &RowEvent::DeletedRow { cols }).unwrap(),
+            "DELETE FROM `shop`.`orders` WHERE `id`=2 AND `note`='a' AND `amount`=9.90 LIMIT 1;"
+        );
+        // nothing left to match on
+        let cols = vec![None, None, None, Some(MySQLValue::Double(1.1)), None];
+        assert_eq!(row_statement(&def, &RowEvent::DeletedRow { cols }), None);
+    }
+
+    #[test]
+    fn test_resolve_and_key() {
+        let mut table_map = TableMap::new();
+        let columns = vec![
+            ColumnType::LongLong,
+            ColumnType::VarChar(255),
+            ColumnType::NewDecimal(10, 2),
+        ];
+        table_map.handle(1, "shop".to_owned(), "orders".to_owned(), columns.clone());
+        table_map.handle(2, "shop".to_owned(), "refunds".to_owned(), columns);
+        let known = vec![table(vec![1, 0])];
+
+        let def = TableDef::resolve(table_map.get(1).unwrap(), &known);
+        assert_eq!(def.columns[1].name, "note");
+        assert_eq!(def.primary_key, vec![1, 0]);
+        assert_eq!(
+            def.key(&row(7, Some("a"))),
+            Some(vec![
+                MySQLValue::String("a".to_owned()),
+                MySQLValue::SignedInteger(7)
+            ])
+        );
+        // a key column missing from the image
+        assert_eq!(def.key(&vec![None, Some(MySQLValue::Null)]), None);
+
+        let def = TableDef::resolve(table_map.get(2).unwrap(), &known);
+        assert_eq!(def.columns[1].name, "@2");
+        assert!(def.primary_key.is_empty());
+        assert_eq!(def.key(&row(7, None)), None);
+    }
+}
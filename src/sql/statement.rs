@@ -0,0 +1,638 @@
+//! Tell what kind of statement a query event carries.
+//!
+//! Query events log transaction control (`BEGIN`, `COMMIT`, savepoints, `XA`), DDL, account
+//! management and, with `binlog_format=STATEMENT` or `MIXED`, data changes, all as plain SQL.
+//! [`classify`] reads just enough of a statement to say which it is and, for DDL and data
+//! changes, the schemas and tables it touches. Comments, including the `/* generated by
+//! server */` the server appends to some statements, are skipped, and unqualified table names
+//! are resolved against the event's default schema.
+//!
+//! ```
+//! use mysqlbinlog_network::sql::statement::{classify, Statement};
+//!
+//! match classify("/* app */ ALTER TABLE orders ADD note text", Some("shop")) {
+//!     Statement::Ddl { command, tables, .. } => {
+//!         assert_eq!(command, "ALTER TABLE");
+//!         assert_eq!(tables[0].schema_name, "shop");
+//!     }
+//!     other => panic!("{:?}", other),
+//! }
+//! ```
+use crate::sql::ddl::{parse_ddl, AlterSpec, Ddl, TableName};
+use crate::sql::lexer::{tokenize, Parser, Token};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// `BEGIN` or `START TRANSACTION`
+    Begin,
+    Commit,
+    Rollback,
+    Savepoint(String),
+    RollbackToSavepoint(String),
+    ReleaseSavepoint(String),
+    Xa {
+        command: XaCommand,
+        xid: Xid,
+    },
+    /// Data definition. `command` is the statement's leading keywords without modifiers, like
+    /// `CREATE TABLE` or `DROP INDEX`; `schemas` holds the databases a `CREATE`, `ALTER` or
+    /// `DROP DATABASE` names.
+    Ddl {
+        command: String,
+        schemas: Vec<String>,
+        tables: Vec<TableName>,
+    },
+    /// Account and privilege management, like `GRANT` or `CREATE USER`
+    Dcl {
+        command: String,
+    },
+    /// A data change logged as a statement: `INSERT`, `REPLACE`, `UPDATE`, `DELETE` or
+    /// `LOAD DATA`, with the tables it writes to
+    Dml {
+        command: String,
+        tables: Vec<TableName>,
+    },
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XaCommand {
+    Start,
+    End,
+    Prepare,
+    Commit,
+    Rollback,
+}
+
+/// The identifier of an XA transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xid {
+    pub gtrid: Vec<u8>,
+    pub bqual: Vec<u8>,
+    pub format_id: u64,
+}
+
+impl Statement {
+    /// Whether this statement starts, ends or otherwise steers a transaction
+    pub fn is_transaction_control(&self) -> bool {
+        matches!(
+            self,
+            Statement::Begin
+                | Statement::Commit
+                | Statement::Rollback
+                | Statement::Savepoint(_)
+                | Statement::RollbackToSavepoint(_)
+                | Statement::ReleaseSavepoint(_)
+                | Statement::Xa { .. }
+        )
+    }
+}
+
+/// Classify a statement run with `default_schema` as the default database. Unqualified table
+/// names get an empty schema name when there is no default database.
+pub fn classify(query: &str, default_schema: Option<&str>) -> Statement {
+    let tokens = tokenize(query);
+    let mut parser = Parser::new(&tokens, Some(default_schema.unwrap_or("")));
+    parser.statement().unwrap_or(Statement::Other)
+}
+
+/// Words which may come between `CREATE`, `ALTER` or `DROP` and the kind of object
+const MODIFIERS: [&str; 11] = [
+    "TEMPORARY",
+    "UNIQUE",
+    "FULLTEXT",
+    "SPATIAL",
+    "ONLINE",
+    "OFFLINE",
+    "IGNORE",
+    "UNDO",
+    "AGGREGATE",
+    "EXTERNAL",
+    "PUBLIC",
+];
+
+/// Words which may follow `ALTER DATABASE` when it names no database
+const DATABASE_OPTIONS: [&str; 7] = [
+    "DEFAULT",
+    "CHARACTER",
+    "CHARSET",
+    "COLLATE",
+    "ENCRYPTION",
+    "READ",
+    "COMMENT",
+];
+
+impl Parser<'_> {
+    fn keyword(&self) -> Option<String> {
+        match self.peek()? {
+            Token::Word(w) => Some(w.to_ascii_uppercase()),
+            _ => None,
+        }
+    }
+
+    fn statement(&mut self) -> Option<Statement> {
+        let keyword = self.keyword()?;
+        self.pos += 1;
+        Some(match keyword.as_str() {
+            "BEGIN" => Statement::Begin,
+            "START" if self.eat_keyword("TRANSACTION") => Statement::Begin,
+            "COMMIT" => Statement::Commit,
+            "ROLLBACK" => {
+                self.eat_keyword("WORK");
+                if self.eat_keyword("TO") {
+                    self.eat_keyword("SAVEPOINT");
+                    Statement::RollbackToSavepoint(self.identifier()?)
+                } else {
+                    Statement::Rollback
+                }
+            }
+            "SAVEPOINT" => Statement::Savepoint(self.identifier()?),
+            "RELEASE" if self.eat_keyword("SAVEPOINT") => {
+                Statement::ReleaseSavepoint(self.identifier()?)
+            }
+            "XA" => self.xa()?,
+            "GRANT" | "REVOKE" => Statement::Dcl { command: keyword },
+            "SET" if self.peek_keyword("PASSWORD") => Statement::Dcl {
+                command: "SET PASSWORD".to_owned(),
+            },
+            "SET" if self.eat_keywords(&["DEFAULT", "ROLE"]) => Statement::Dcl {
+                command: "SET DEFAULT ROLE".to_owned(),
+            },
+            "CREATE" | "ALTER" | "DROP" | "RENAME" => self.definition(keyword)?,
+            "TRUNCATE" => {
+                self.eat_keyword("TABLE");
+                Statement::Ddl {
+                    command: "TRUNCATE TABLE".to_owned(),
+                    schemas: Vec::new(),
+                    tables: self.table_name().into_iter().collect(),
+                }
+            }
+            "INSERT" | "REPLACE" => {
+                while ["LOW_PRIORITY", "DELAYED", "HIGH_PRIORITY", "IGNORE", "INTO"]
+                    .iter()
+                    .any(|k| self.eat_keyword(k))
+                {}
+                Statement::Dml {
+                    command: keyword,
+                    tables: self.table_name().into_iter().collect(),
+                }
+            }
+            "UPDATE" => {
+                while ["LOW_PRIORITY", "IGNORE"]
+                    .iter()
+                    .any(|k| self.eat_keyword(k))
+                {}
+                Statement::Dml {
+                    command: keyword,
+                    tables: self.table_references(&["SET"]),
+                }
+            }
+            "DELETE" => {
+                while ["LOW_PRIORITY", "QUICK", "IGNORE"]
+                    .iter()
+                    .any(|k| self.eat_keyword(k))
+                {}
+                // `DELETE FROM t ...`, `DELETE FROM t1, t2 USING ...` or `DELETE t1, t2 FROM ...`
+                let tables = if self.eat_keyword("FROM") {
+                    self.table_references(&["WHERE", "USING", "ORDER", "LIMIT", "PARTITION"])
+                } else {
+                    self.table_references(&["FROM"])
+                };
+                Statement::Dml {
+                    command: keyword,
+                    tables,
+                }
+            }
+            "LOAD" if self.peek_keyword("DATA") || self.peek_keyword("XML") => {
+                let command = format!("LOAD {}", self.keyword()?);
+                while self.peek().is_some() && !self.eat_keywords(&["INTO", "TABLE"]) {
+                    self.pos += 1;
+                }
+                Statement::Dml {
+                    command,
+                    tables: self.table_name().into_iter().collect(),
+                }
+            }
+            // a common table expression ahead of the data change
+            "WITH" => {
+                while let Some(token) = self.peek() {
+                    if ["INSERT", "REPLACE", "UPDATE", "DELETE"]
+                        .iter()
+                        .any(|k| token.is_keyword(k))
+                    {
+                        return self.statement();
+                    }
+                    if self.peek_symbol('(') {
+                        self.skip_group();
+                    } else {
+                        self.pos += 1;
+                    }
+                }
+                Statement::Other
+            }
+            _ => Statement::Other,
+        })
+    }
+
+    /// `CREATE`, `ALTER`, `DROP` or `RENAME` of some object
+    fn definition(&mut self, verb: String) -> Option<Statement> {
+        let start = self.pos - 1;
+        loop {
+            if self.eat_keywords(&["OR", "REPLACE"])
+                || MODIFIERS.iter().any(|k| self.eat_keyword(k))
+            {
+                continue;
+            }
+            // ALGORITHM = ..., DEFINER = user@host, SQL SECURITY ...
+            if self.eat_keywords(&["SQL", "SECURITY"]) {
+                self.pos += 1;
+                continue;
+            }
+            if self.eat_keyword("ALGORITHM") || self.eat_keyword("DEFINER") {
+                self.eat_symbol('=');
+                self.pos += 1;
+                while self.peek_symbol('@') || self.peek_symbol('(') || self.peek_symbol(')') {
+                    self.pos += 2;
+                }
+                continue;
+            }
+            break;
+        }
+        let object = self.keyword()?;
+        self.pos += 1;
+        let object = match object.as_str() {
+            "SCHEMA" => "DATABASE".to_owned(),
+            "LOGFILE" | "RESOURCE" | "SPATIAL" => {
+                let second = self.keyword().unwrap_or_default();
+                self.pos += 1;
+                format!("{} {}", object, second)
+            }
+            _ => object,
+        };
+        let command = format!("{} {}", verb, object);
+        let mut schemas = Vec::new();
+        let mut tables = Vec::new();
+        match object.as_str() {
+            "USER" | "ROLE" => return Some(Statement::Dcl { command }),
+            "DATABASE" => {
+                self.eat_keywords(&["IF", "NOT", "EXISTS"]);
+                self.eat_keywords(&["IF", "EXISTS"]);
+                // ALTER DATABASE without a name changes the default database
+                let option = DATABASE_OPTIONS.iter().any(|k| self.peek_keyword(k));
+                match self.identifier() {
+                    Some(name) if !option => schemas.push(name),
+                    _ => schemas.extend(self.default_schema.map(str::to_owned)),
+                }
+            }
+            "TABLE" => tables = self.definition_tables(start),
+            "INDEX" => {
+                if verb == "ALTER" {
+                    // MariaDB has no ALTER INDEX, MySQL's is ALTER TABLE ... ALTER INDEX
+                    return Some(Statement::Other);
+                }
+                self.identifier();
+                while self.peek().is_some() && !self.eat_keyword("ON") {
+                    self.pos += 1;
+                }
+                tables.extend(self.table_name());
+            }
+            "VIEW" | "SEQUENCE" => {
+                self.eat_keywords(&["IF", "NOT", "EXISTS"]);
+                self.eat_keywords(&["IF", "EXISTS"]);
+                tables = self.table_names().unwrap_or_default();
+            }
+            "TRIGGER" => {
+                while self.peek().is_some() && !self.eat_keyword("ON") {
+                    self.pos += 1;
+                }
+                tables.extend(self.table_name());
+            }
+            _ => {}
+        }
+        Some(Statement::Ddl {
+            command,
+            schemas,
+            tables,
+        })
+    }
+
+    /// The tables a `CREATE`, `ALTER`, `DROP` or `RENAME TABLE` starting at token `start` names
+    fn definition_tables(&mut self, start: usize) -> Vec<TableName> {
+        let rest = &self.tokens[start..];
+        let ddl = {
+            let mut parser = Parser::new(rest, self.default_schema);
+            parser.ddl_tables()
+        };
+        if let Some(tables) = ddl {
+            return tables;
+        }
+        // statements the DDL parser skips, like CREATE TABLE ... SELECT
+        self.eat_keywords(&["IF", "NOT", "EXISTS"]);
+        self.eat_keywords(&["IF", "EXISTS"]);
+        self.table_names().unwrap_or_default()
+    }
+
+    fn ddl_tables(&mut self) -> Option<Vec<TableName>> {
+        let text: Vec<String> = self.tokens.iter().map(token_text).collect();
+        Some(match parse_ddl(&text.join(" "), self.default_schema)? {
+            Ddl::CreateTable(def) => vec![TableName {
+                schema_name: def.schema_name,
+                table_name: def.table_name,
+            }],
+            Ddl::CreateTableLike { table, .. } => vec![table],
+            Ddl::AlterTable { table, specs } => std::iter::once(table)
+                .chain(specs.into_iter().filter_map(|spec| match spec {
+                    AlterSpec::Rename(to) => Some(to),
+                    _ => None,
+                }))
+                .collect(),
+            Ddl::RenameTable(renames) => renames
+                .into_iter()
+                .flat_map(|(from, to)| vec![from, to])
+                .collect(),
+            Ddl::DropTable(tables) => tables,
+            Ddl::CreateDatabase(_) | Ddl::DropDatabase(_) => return None,
+        })
+    }
+
+    /// Table names in a list of table references up to one of the `stop` keywords: the first
+    /// and every one after a `,` or a `JOIN`
+    fn table_references(&mut self, stop: &[&str]) -> Vec<TableName> {
+        let mut tables = Vec::new();
+        let mut expect_table = true;
+        while let Some(token) = self.peek() {
+            if stop.iter().any(|k| token.is_keyword(k)) {
+                break;
+            }
+            if token == &Token::Symbol('(') {
+                self.skip_group();
+                expect_table = false;
+                continue;
+            }
+            if expect_table {
+                tables.extend(self.table_name());
+                expect_table = false;
+                continue;
+            }
+            expect_table = token == &Token::Symbol(',')
+                || token.is_keyword("JOIN")
+                || token.is_keyword("STRAIGHT_JOIN");
+            self.pos += 1;
+        }
+        tables
+    }
+
+    fn xa(&mut self) -> Option<Statement> {
+        let command = match self.keyword()?.as_str() {
+            "START" | "BEGIN" => XaCommand::Start,
+            "END" => XaCommand::End,
+            "PREPARE" => XaCommand::Prepare,
+            "COMMIT" => XaCommand::Commit,
+            "ROLLBACK" => XaCommand::Rollback,
+            _ => return Some(Statement::Other),
+        };
+        self.pos += 1;
+        let gtrid = self.xid_part()?;
+        let mut xid = Xid {
+            gtrid,
+            bqual: Vec::new(),
+            format_id: 1,
+        };
+        if self.eat_symbol(',') {
+            xid.bqual = self.xid_part()?;
+            if self.eat_symbol(',') {
+                xid.format_id = self.identifier()?.parse().ok()?;
+            }
+        }
+        Some(Statement::Xa { command, xid })
+    }
+
+    /// A string literal, either quoted or in hex as `X'...'` or `0x...`
+    fn xid_part(&mut self) -> Option<Vec<u8>> {
+        let bytes = match (self.peek()?, self.tokens.get(self.pos + 1)) {
+            (Token::Str(s), _) => s.as_bytes().to_vec(),
+            (Token::Word(x), Some(Token::Str(hex))) if x.eq_ignore_ascii_case("x") => {
+                self.pos += 1;
+                unhex(hex)?
+            }
+            (Token::Word(w), _) if w.starts_with("0x") => unhex(&w[2..])?,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(bytes)
+    }
+}
+
+/// The token as it could be written back, for reparsing
+fn token_text(token: &Token) -> String {
+    match token {
+        Token::Word(w) => w.clone(),
+        Token::Quoted(name) => crate::sql::quote_identifier(name),
+        Token::Str(s) => crate::sql::quote_string(s),
+        Token::Symbol(c) => c.to_string(),
+    }
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    // an odd number of digits has an implied leading zero
+    let padded = if hex.len() % 2 == 1 {
+        format!("0{}", hex)
+    } else {
+        hex.to_owned()
+    };
+    (0..padded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(padded.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, Statement, XaCommand, Xid};
+    use crate::sql::ddl::TableName;
+
+    fn table(schema_name: &str, table_name: &str) -> TableName {
+        TableName {
+            schema_name: schema_name.to_owned(),
+            table_name: table_name.to_owned(),
+        }
+    }
+
+    fn ddl(query: &str) -> (String, Vec<String>, Vec<TableName>) {
+        match classify(query, Some("shop")) {
+            Statement::Ddl {
+                command,
+                schemas,
+                tables,
+            } => (command, schemas, tables),
+            other => panic!("{}: {:?}", query, other),
+        }
+    }
+
+    fn dml(query: &str) -> (String, Vec<TableName>) {
+        match classify(query, Some("shop")) {
+            Statement::Dml { command, tables } => (command, tables),
+            other => panic!("{}: {:?}", query, other),
+        }
+    }
+
+    #[test]
+    fn test_transaction_control() {
+        let cases = [
+            ("BEGIN", Statement::Begin),
+            ("  begin work;", Statement::Begin),
+            ("START TRANSACTION READ ONLY", Statement::Begin),
+            ("/* x */ COMMIT", Statement::Commit),
+            ("ROLLBACK", Statement::Rollback),
+            ("SAVEPOINT `sp 1`", Statement::Savepoint("sp 1".to_owned())),
+            (
+                "ROLLBACK WORK TO SAVEPOINT sp",
+                Statement::RollbackToSavepoint("sp".to_owned()),
+            ),
+            (
+                "RELEASE SAVEPOINT sp",
+                Statement::ReleaseSavepoint("sp".to_owned()),
+            ),
+            ("START SLAVE", Statement::Other),
+            ("", Statement::Other),
+        ];
+        for (query, expected) in cases.iter() {
+            assert_eq!(&classify(query, None), expected, "{}", query);
+        }
+        assert!(classify("commit", None).is_transaction_control());
+        assert!(!classify("GRANT ALL ON *.* TO u", None).is_transaction_control());
+    }
+
+    #[test]
+    fn test_xa() {
+        assert_eq!(
+            classify("XA START X'7878',X'',1", None),
+            Statement::Xa {
+                command: XaCommand::Start,
+                xid: Xid {
+                    gtrid: b"xx".to_vec(),
+                    bqual: Vec::new(),
+                    format_id: 1,
+                },
+            }
+        );
+        assert_eq!(
+            classify("XA COMMIT 'gt', 0x62, 7 ONE PHASE", None),
+            Statement::Xa {
+                command: XaCommand::Commit,
+                xid: Xid {
+                    gtrid: b"gt".to_vec(),
+                    bqual: b"b".to_vec(),
+                    format_id: 7,
+                },
+            }
+        );
+        assert_eq!(classify("XA RECOVER", None), Statement::Other);
+    }
+
+    #[test]
+    fn test_ddl() {
+        assert_eq!(
+            ddl("CREATE TABLE IF NOT EXISTS `o` (id int) ENGINE=InnoDB"),
+            ("CREATE TABLE".to_owned(), vec![], vec![table("shop", "o")])
+        );
+        assert_eq!(
+            ddl("CREATE TEMPORARY TABLE tmp SELECT * FROM o").2,
+            vec![table("shop", "tmp")]
+        );
+        assert_eq!(
+            ddl("ALTER TABLE o ADD c int, RENAME TO other.o2").2,
+            vec![table("shop", "o"), table("other", "o2")]
+        );
+        assert_eq!(
+            ddl("DROP TABLE `a`,b /* generated by server */").2,
+            vec![table("shop", "a"), table("shop", "b")]
+        );
+        assert_eq!(ddl("RENAME TABLE a TO b").2.len(), 2);
+        assert_eq!(
+            ddl("TRUNCATE o"),
+            (
+                "TRUNCATE TABLE".to_owned(),
+                vec![],
+                vec![table("shop", "o")]
+            )
+        );
+        assert_eq!(
+            ddl("CREATE UNIQUE INDEX idx ON other.o (c)"),
+            ("CREATE INDEX".to_owned(), vec![], vec![table("other", "o")])
+        );
+        assert_eq!(
+            ddl("CREATE DEFINER=`root`@`%` SQL SECURITY DEFINER VIEW v AS SELECT 1"),
+            ("CREATE VIEW".to_owned(), vec![], vec![table("shop", "v")])
+        );
+        assert_eq!(
+            ddl("CREATE DEFINER=`root`@`localhost` TRIGGER t BEFORE INSERT ON o FOR EACH ROW SET @x = 1").2,
+            vec![table("shop", "o")]
+        );
+        assert_eq!(
+            ddl("CREATE SCHEMA /*!32312 IF NOT EXISTS*/ `new`"),
+            ("CREATE DATABASE".to_owned(), vec!["new".to_owned()], vec![])
+        );
+        assert_eq!(ddl("ALTER DATABASE CHARACTER SET utf8mb4").1, vec!["shop"]);
+        assert_eq!(ddl("DROP PROCEDURE IF EXISTS p").0, "DROP PROCEDURE");
+    }
+
+    #[test]
+    fn test_dcl() {
+        for query in [
+            "GRANT SELECT ON shop.* TO 'u'@'%'",
+            "REVOKE ALL PRIVILEGES ON *.* FROM u",
+            "CREATE USER IF NOT EXISTS 'u'@'%' IDENTIFIED BY 'p'",
+            "DROP ROLE r",
+            "SET PASSWORD FOR u = 'x'",
+        ]
+        .iter()
+        {
+            assert!(
+                matches!(classify(query, None), Statement::Dcl { .. }),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_dml() {
+        assert_eq!(
+            dml("INSERT IGNORE INTO `o`(id) VALUES (1)"),
+            ("INSERT".to_owned(), vec![table("shop", "o")])
+        );
+        assert_eq!(
+            dml("replace other.o values (1)").1,
+            vec![table("other", "o")]
+        );
+        assert_eq!(
+            dml("UPDATE o AS x JOIN other.p ON x.id = p.id, (SELECT 1) d SET x.a = 1").1,
+            vec![table("shop", "o"), table("other", "p")]
+        );
+        assert_eq!(
+            dml("DELETE FROM o WHERE id IN (SELECT id FROM p)").1,
+            vec![table("shop", "o")]
+        );
+        assert_eq!(
+            dml("DELETE o, p FROM o JOIN p").1,
+            vec![table("shop", "o"), table("shop", "p")]
+        );
+        assert_eq!(
+            dml("LOAD DATA LOCAL INFILE '/tmp/o.csv' REPLACE INTO TABLE o"),
+            ("LOAD DATA".to_owned(), vec![table("shop", "o")])
+        );
+        assert_eq!(
+            dml("WITH c AS (SELECT 1) UPDATE o SET a = 1").1,
+            vec![table("shop", "o")]
+        );
+        assert_eq!(
+            classify("INSERT INTO o VALUES (1)", None),
+            Statement::Dml {
+                command: "INSERT".to_owned(),
+                tables: vec![table("", "o")],
+            }
+        );
+        assert_eq!(classify("SELECT 1", None), Statement::Other);
+    }
+}
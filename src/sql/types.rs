@@ -0,0 +1,200 @@
+use crate::mysql_binlog::column_types::ColumnType;
+
+/// A column type as SQL spells it, such as `int(10) unsigned` or `enum('a','b')`, turned into
+/// what a table map would say about the column
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlColumnType {
+    pub column_type: ColumnType,
+    pub unsigned: bool,
+    /// The labels of an ENUM or SET column
+    pub labels: Vec<String>,
+}
+
+/// Bytes per character assumed for character columns, that of `utf8mb4`
+const MAX_CHAR_BYTES: u32 = 4;
+
+/// Parse a column type the way `information_schema.COLUMNS.COLUMN_TYPE` and `CREATE TABLE`
+/// write it. Attributes after the type other than `UNSIGNED` and `ZEROFILL` are ignored.
+/// Lengths of character columns assume `utf8mb4`. `None` for types MySQL doesn't have.
+pub fn parse_column_type(definition: &str) -> Option<SqlColumnType> {
+    let definition = definition.trim();
+    let name_end = definition
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(definition.len());
+    let name = definition[..name_end].to_ascii_lowercase();
+    let rest = definition[name_end..].trim_start();
+    let (args, attributes) = match rest.strip_prefix('(') {
+        Some(inner) => {
+            let end = closing_paren(inner)?;
+            (Some(&inner[..end]), &inner[end + 1..])
+        }
+        None => (None, rest),
+    };
+    let attributes = attributes.to_ascii_lowercase();
+    let unsigned = attributes
+        .split_whitespace()
+        .any(|a| a == "unsigned" || a == "zerofill");
+    let numbers: Vec<u32> = args
+        .map(|a| a.split(',').filter_map(|n| n.trim().parse().ok()).collect())
+        .unwrap_or_default();
+    let number = |i: usize, default: u32| numbers.get(i).copied().unwrap_or(default);
+    let chars = |default: u32| (number(0, default) * MAX_CHAR_BYTES).min(65535) as u16;
+    let mut labels = Vec::new();
+    let column_type = match name.as_str() {
+        "tinyint" | "bool" | "boolean" => ColumnType::Tiny,
+        "smallint" => ColumnType::Short,
+        "mediumint" => ColumnType::Int24,
+        "int" | "integer" => ColumnType::Long,
+        "bigint" | "serial" => ColumnType::LongLong,
+        "float" if number(0, 0) > 24 => ColumnType::Double(8),
+        "float" => ColumnType::Float(4),
+        "double" | "real" => ColumnType::Double(8),
+        "decimal" | "numeric" | "dec" | "fixed" => {
+            ColumnType::NewDecimal(number(0, 10) as u8, number(1, 0) as u8)
+        }
+        "bit" => {
+            let bits = number(0, 1);
+            ColumnType::Bit((bits % 8) as u8, (bits / 8) as u8)
+        }
+        "date" => ColumnType::Date,
+        "time" => ColumnType::Time2(number(0, 0) as u8),
+        "datetime" => ColumnType::DateTime2(number(0, 0) as u8),
+        "timestamp" => ColumnType::Timestamp2(number(0, 0) as u8),
+        "year" => ColumnType::Year,
+        "char" => ColumnType::MyString(chars(1)),
+        "binary" => ColumnType::MyString(number(0, 1) as u16),
+        "varchar" => ColumnType::VarChar(chars(0)),
+        "varbinary" => ColumnType::VarChar(number(0, 0) as u16),
+        "tinytext" | "tinyblob" => ColumnType::Blob(1),
+        "text" | "blob" => ColumnType::Blob(2),
+        "mediumtext" | "mediumblob" | "long" => ColumnType::Blob(3),
+        "longtext" | "longblob" => ColumnType::Blob(4),
+        "enum" => {
+            labels = quoted_list(args?)?;
+            ColumnType::Enum(if labels.len() > 255 { 2 } else { 1 })
+        }
+        "set" => {
+            labels = quoted_list(args?)?;
+            ColumnType::Set(match labels.len().div_ceil(8) {
+                0 | 1 => 1,
+                2 => 2,
+                3 => 3,
+                4 => 4,
+                _ => 8,
+            })
+        }
+        "json" => ColumnType::Json(4),
+        "geometry" | "point" | "linestring" | "polygon" | "multipoint" | "multilinestring"
+        | "multipolygon" | "geometrycollection" | "geomcollection" => ColumnType::Geometry(4),
+        _ => return None,
+    };
+    Some(SqlColumnType {
+        column_type,
+        unsigned,
+        labels,
+    })
+}
+
+/// The index in `s` of the `)` closing a `(` just before it, skipping quoted strings
+fn closing_paren(s: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, ')') => return Some(i),
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+/// The strings of a list like `'a','it''s'`, unescaped
+fn quoted_list(s: &str) -> Option<Vec<String>> {
+    let mut items = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let quote = match chars.next() {
+            Some(q @ '\'') | Some(q @ '"') => q,
+            None => return Some(items),
+            Some(_) => return None,
+        };
+        let mut item = String::new();
+        loop {
+            match chars.next()? {
+                '\\' => item.push(match chars.next()? {
+                    'n' => '\n',
+                    't' => '\t',
+                    '0' => '\0',
+                    c => c,
+                }),
+                c if c == quote && chars.peek() == Some(&quote) => {
+                    chars.next();
+                    item.push(quote);
+                }
+                c if c == quote => break,
+                c => item.push(c),
+            }
+        }
+        items.push(item);
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        match chars.next() {
+            Some(',') => {}
+            None => return Some(items),
+            Some(_) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_column_type;
+    use crate::mysql_binlog::column_types::ColumnType;
+
+    fn column_type(definition: &str) -> ColumnType {
+        parse_column_type(definition).unwrap().column_type
+    }
+
+    #[test]
+    fn test_parse_column_type() {
+        assert_eq!(column_type("int(11)"), ColumnType::Long);
+        assert_eq!(column_type("BIGINT"), ColumnType::LongLong);
+        assert_eq!(column_type("decimal(12,3)"), ColumnType::NewDecimal(12, 3));
+        assert_eq!(column_type("varchar(64)"), ColumnType::VarChar(256));
+        assert_eq!(column_type("varbinary(16)"), ColumnType::VarChar(16));
+        assert_eq!(column_type("char(2)"), ColumnType::MyString(8));
+        assert_eq!(column_type("mediumtext"), ColumnType::Blob(3));
+        assert_eq!(column_type("datetime(6)"), ColumnType::DateTime2(6));
+        assert_eq!(column_type("timestamp"), ColumnType::Timestamp2(0));
+        assert_eq!(column_type("time(3)"), ColumnType::Time2(3));
+        assert_eq!(column_type("bit(10)"), ColumnType::Bit(2, 1));
+        assert_eq!(column_type("float(30)"), ColumnType::Double(8));
+        assert_eq!(column_type("point"), ColumnType::Geometry(4));
+        assert_eq!(parse_column_type("money"), None);
+
+        let parsed = parse_column_type("int(10) unsigned zerofill").unwrap();
+        assert!(parsed.unsigned);
+        assert!(!parse_column_type("int NOT NULL").unwrap().unsigned);
+    }
+
+    #[test]
+    fn test_labels() {
+        let parsed = parse_column_type("enum('new','it''s','a,b','x\\'y')").unwrap();
+        assert_eq!(parsed.column_type, ColumnType::Enum(1));
+        assert_eq!(parsed.labels, vec!["new", "it's", "a,b", "x'y"]);
+        let labels: Vec<String> = (0..9).map(|i| format!("'{}'", i)).collect();
+        let parsed = parse_column_type(&format!("set({})", labels.join(","))).unwrap();
+        assert_eq!(parsed.column_type, ColumnType::Set(2));
+        assert_eq!(parsed.labels.len(), 9);
+        assert_eq!(parse_column_type("enum('a'"), None);
+    }
+}
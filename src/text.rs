@@ -0,0 +1,868 @@
+//! Print events the way `mysqlbinlog --base64-output=decode-rows -vv` does.
+//!
+//! Every event gets its `# at` and `#yymmdd hh:mm:ss server id ...` lines, statements are
+//! printed with their `SET TIMESTAMP` and delimiter, and rows events are decoded into
+//! `### INSERT INTO` pseudo statements with one `@n=value /* TYPE meta=.. */` line per column.
+//!
+//! A [`TextWriter`] is fed every event of a binlog, including the internal ones, as a
+//! [`EventInfo`] with the event's body and its decoded data:
+//!
+//! ```no_run
+//! use mysqlbinlog_network::mysql_binlog::binlog_file::BinlogFile;
+//! use mysqlbinlog_network::text::{EventInfo, TextWriter};
+//!
+//! let mut out = std::io::stdout();
+//! let mut writer = TextWriter::new();
+//! writer.begin(&mut out).unwrap();
+//! for event in BinlogFile::try_from_path("binlog.000001").unwrap().events(Some(4)) {
+//!     let event = event.unwrap();
+//!     let data = event.inner(Some(writer.table_map())).unwrap();
+//!     writer
+//!         .write_event(&mut out, &EventInfo::from_event(&event), event.data(), data.as_ref())
+//!         .unwrap();
+//! }
+//! writer.end(&mut out).unwrap();
+//! ```
+//!
+//! Differences from `mysqlbinlog`: times are printed in UTC rather than the local time zone,
+//! and the session variables `mysqlbinlog` restores from a query's status variables
+//! (`sql_mode`, character sets, ...) are not printed.
+use std::io::{self, Write};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::mysql_binlog::column_types::ColumnType;
+use crate::mysql_binlog::event::{
+    Event, EventData, RowData, RowEvent, TypeCode, EVENT_HEADER_SIZE, FL_STANDALONE,
+};
+use crate::mysql_binlog::geometry;
+use crate::mysql_binlog::table_map::TableMap;
+use crate::mysql_binlog::value::MySQLValue;
+use crate::sql::{fraction, quote_identifier, unsigned_value};
+
+const DELIMITER: &str = "/*!*/;";
+// LOG_EVENT_BINLOG_IN_USE_F, set in the FDE until the server closes the file
+const LOG_EVENT_BINLOG_IN_USE_F: u16 = 0x1;
+// STMT_END_F in the flags of a rows event
+const STMT_END_F: u16 = 0x1;
+
+/// The common header of an event, as printed in its `#` line
+#[derive(Debug, Clone, Copy)]
+pub struct EventInfo {
+    /// Where the event starts in its binlog file
+    pub offset: u64,
+    pub timestamp: u32,
+    pub type_code: TypeCode,
+    pub server_id: u32,
+    /// Where the event ends in its binlog file
+    pub log_pos: u32,
+    pub flags: u16,
+    pub checksum: Option<u32>,
+}
+
+impl EventInfo {
+    pub fn from_event(event: &Event) -> Self {
+        EventInfo {
+            offset: event.offset(),
+            timestamp: event.timestamp(),
+            type_code: event.type_code(),
+            server_id: event.server_id(),
+            log_pos: event.next_position() as u32,
+            flags: event.flags(),
+            checksum: event.checksum(),
+        }
+    }
+
+    /// Split an event as received from a binlog dump (common header, body and a checksum of
+    /// `checksum_length` bytes) into its header and its body
+    pub fn from_bytes(bytes: &[u8], checksum_length: usize) -> Option<(Self, &[u8])> {
+        if bytes.len() < EVENT_HEADER_SIZE + checksum_length {
+            return None;
+        }
+        let event_size = LittleEndian::read_u32(&bytes[9..]);
+        let log_pos = LittleEndian::read_u32(&bytes[13..]);
+        let body_end = bytes.len() - checksum_length;
+        let info = EventInfo {
+            offset: u64::from(log_pos.saturating_sub(event_size)),
+            timestamp: LittleEndian::read_u32(bytes),
+            type_code: TypeCode::from_byte(bytes[4]),
+            server_id: LittleEndian::read_u32(&bytes[5..]),
+            log_pos,
+            flags: LittleEndian::read_u16(&bytes[17..]),
+            checksum: if checksum_length == 4 {
+                Some(LittleEndian::read_u32(&bytes[body_end..]))
+            } else {
+                None
+            },
+        };
+        Some((info, &bytes[EVENT_HEADER_SIZE..body_end]))
+    }
+}
+
+/// What the rows events of a table are printed with
+struct TableInfo {
+    table_id: u64,
+    schema_name: String,
+    table_name: String,
+    columns: Vec<ColumnType>,
+    nullable: Vec<bool>,
+}
+
+/// Prints a stream of events. It keeps the state `mysqlbinlog` does between events, such as the
+/// current database, so one writer should see all events in order.
+#[derive(Default)]
+pub struct TextWriter {
+    db: Option<String>,
+    thread_id: Option<u32>,
+    gtid_next: bool,
+    tables: Vec<TableInfo>,
+    table_map: TableMap,
+}
+
+impl TextWriter {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The tables mapped so far, for decoding the rows events to pass in
+    pub fn table_map(&self) -> &TableMap {
+        &self.table_map
+    }
+
+    /// The session setup `mysqlbinlog` starts its output with
+    pub fn begin(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "/*!50530 SET @@SESSION.PSEUDO_SLAVE_MODE=1*/;")?;
+        writeln!(
+            out,
+            "/*!50003 SET @OLD_COMPLETION_TYPE=@@COMPLETION_TYPE,COMPLETION_TYPE=0*/;"
+        )?;
+        writeln!(out, "DELIMITER {}", DELIMITER)
+    }
+
+    /// The session teardown `mysqlbinlog` ends its output with
+    pub fn end(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        if self.gtid_next {
+            writeln!(
+                out,
+                "SET @@SESSION.GTID_NEXT= 'AUTOMATIC' /* added by mysqlbinlog */ {}",
+                DELIMITER
+            )?;
+        }
+        writeln!(out, "DELIMITER ;")?;
+        writeln!(out, "# End of log file")?;
+        writeln!(out, "/*!50003 SET COMPLETION_TYPE=@OLD_COMPLETION_TYPE*/;")?;
+        writeln!(out, "/*!50530 SET @@SESSION.PSEUDO_SLAVE_MODE=0*/;")
+    }
+
+    /// Print one event. `body` is the event without its common header and checksum, `data`
+    /// what it decoded to, if anything.
+    pub fn write_event(
+        &mut self,
+        out: &mut dyn Write,
+        info: &EventInfo,
+        body: &[u8],
+        data: Option<&EventData>,
+    ) -> io::Result<()> {
+        // not decoded by the parser, but laid out like a GTID event
+        let anonymous_gtid;
+        let data = match (info.type_code, data) {
+            (TypeCode::AnonymousGtidLogEvent, None) => {
+                anonymous_gtid = EventData::from_data(TypeCode::GtidLogEvent, body, None)
+                    .ok()
+                    .flatten();
+                anonymous_gtid.as_ref()
+            }
+            (_, data) => data,
+        };
+        writeln!(out, "# at {}", info.offset)?;
+        write!(
+            out,
+            "#{} server id {}  end_log_pos {} ",
+            short_datetime(info.timestamp),
+            info.server_id,
+            info.log_pos
+        )?;
+        if let Some(checksum) = info.checksum {
+            write!(out, "CRC32 0x{:08x} ", checksum)?;
+        }
+        match data {
+            Some(EventData::FormatDescriptionEvent {
+                binlog_version,
+                server_version,
+                create_timestamp,
+                ..
+            }) => {
+                write!(
+                    out,
+                    "\tStart: binlog v {}, server v {} created ",
+                    binlog_version, server_version
+                )?;
+                if *create_timestamp != 0 {
+                    write!(out, "{} at startup", short_datetime(*create_timestamp))?;
+                }
+                writeln!(out)?;
+                if info.flags & LOG_EVENT_BINLOG_IN_USE_F != 0 {
+                    writeln!(
+                        out,
+                        "# Warning: this binlog is either in use or was not closed properly."
+                    )?;
+                }
+                if *create_timestamp != 0 {
+                    writeln!(out, "ROLLBACK{}", DELIMITER)?;
+                }
+            }
+            Some(EventData::PreviousGtidsLogEvent { gtid_sets }) => {
+                writeln!(out, "\tPrevious-GTIDs")?;
+                if gtid_sets.is_empty() {
+                    writeln!(out, "# [empty]")?;
+                } else {
+                    let sets: Vec<String> = gtid_sets
+                        .iter()
+                        .map(|(uuid, intervals)| {
+                            let mut set = uuid.to_hyphenated().to_string();
+                            for (start, end) in intervals {
+                                // intervals are half-open on the wire
+                                if end - start > 1 {
+                                    set.push_str(&format!(":{}-{}", start, end - 1));
+                                } else {
+                                    set.push_str(&format!(":{}", start));
+                                }
+                            }
+                            set
+                        })
+                        .collect();
+                    writeln!(out, "# {}", sets.join(",\n"))?;
+                }
+            }
+            Some(EventData::GtidLogEvent {
+                flags,
+                uuid,
+                coordinate,
+                last_committed,
+                sequence_number,
+            }) => {
+                let anonymous = info.type_code == TypeCode::AnonymousGtidLogEvent;
+                write!(
+                    out,
+                    "\t{}",
+                    if anonymous { "Anonymous_GTID" } else { "GTID" }
+                )?;
+                if let (Some(last_committed), Some(sequence_number)) =
+                    (last_committed, sequence_number)
+                {
+                    write!(
+                        out,
+                        "\tlast_committed={}\tsequence_number={}",
+                        last_committed, sequence_number
+                    )?;
+                }
+                // FLAG_MAY_HAVE_SBR
+                let rbr_only = flags & 0x1 == 0;
+                writeln!(out, "\trbr_only={}", if rbr_only { "yes" } else { "no" })?;
+                if rbr_only {
+                    writeln!(
+                        out,
+                        "/*!50718 SET TRANSACTION ISOLATION LEVEL READ COMMITTED*/{}",
+                        DELIMITER
+                    )?;
+                }
+                let gtid_next = if anonymous {
+                    "ANONYMOUS".to_owned()
+                } else {
+                    format!("{}:{}", uuid.to_hyphenated(), coordinate)
+                };
+                writeln!(out, "SET @@SESSION.GTID_NEXT= '{}'{}", gtid_next, DELIMITER)?;
+                self.gtid_next = true;
+            }
+            Some(EventData::QueryEvent {
+                thread_id,
+                exec_time,
+                error_code,
+                schema,
+                query,
+            }) => {
+                writeln!(
+                    out,
+                    "\tQuery\tthread_id={}\texec_time={}\terror_code={}",
+                    thread_id, exec_time, error_code
+                )?;
+                if !schema.is_empty() && self.db.as_ref() != Some(schema) {
+                    writeln!(out, "use {}{}", quote_identifier(schema), DELIMITER)?;
+                    self.db = Some(schema.clone());
+                }
+                writeln!(out, "SET TIMESTAMP={}{}", info.timestamp, DELIMITER)?;
+                if self.thread_id != Some(*thread_id) {
+                    writeln!(
+                        out,
+                        "SET @@session.pseudo_thread_id={}{}",
+                        thread_id, DELIMITER
+                    )?;
+                    self.thread_id = Some(*thread_id);
+                }
+                writeln!(out, "{}\n{}", query, DELIMITER)?;
+            }
+            Some(EventData::TableMapEvent {
+                table_id,
+                schema_name,
+                table_name,
+                columns,
+                null_bitmap,
+                metadata,
+            }) => {
+                writeln!(
+                    out,
+                    "\tTable_map: {}.{} mapped to number {}",
+                    quote_identifier(schema_name),
+                    quote_identifier(table_name),
+                    table_id
+                )?;
+                self.tables.retain(|t| t.table_id != *table_id);
+                self.tables.push(TableInfo {
+                    table_id: *table_id,
+                    schema_name: schema_name.clone(),
+                    table_name: table_name.clone(),
+                    columns: columns.clone(),
+                    nullable: null_bitmap.as_vec(),
+                });
+                self.table_map.handle_with_metadata(
+                    *table_id,
+                    schema_name.clone(),
+                    table_name.clone(),
+                    columns.clone(),
+                    metadata.clone(),
+                );
+            }
+            Some(EventData::WriteRowsEvent { table_id, rows })
+            | Some(EventData::UpdateRowsEvent { table_id, rows })
+            | Some(EventData::DeleteRowsEvent { table_id, rows }) => {
+                let name = match data {
+                    Some(EventData::WriteRowsEvent { .. }) => "Write_rows",
+                    Some(EventData::UpdateRowsEvent { .. }) => "Update_rows",
+                    _ => "Delete_rows",
+                };
+                let flags = if body.len() >= 8 {
+                    LittleEndian::read_u16(&body[6..])
+                } else {
+                    0
+                };
+                writeln!(
+                    out,
+                    "\t{}: table id {}{}",
+                    name,
+                    table_id,
+                    if flags & STMT_END_F != 0 {
+                        " flags: STMT_END_F"
+                    } else {
+                        ""
+                    }
+                )?;
+                if let Some(table) = self.tables.iter().find(|t| t.table_id == *table_id) {
+                    for row in rows {
+                        write_row(out, table, row)?;
+                    }
+                }
+            }
+            Some(EventData::XIDEvent { xid }) => {
+                writeln!(out, "\tXid = {}", xid)?;
+                writeln!(out, "COMMIT{}", DELIMITER)?;
+            }
+            Some(EventData::RotateEvent { pos, next_log_name }) => {
+                writeln!(out, "\tRotate to {}  pos: {}", next_log_name, pos)?;
+            }
+            Some(EventData::MariadbGtidEvent {
+                seq_no,
+                domain_id,
+                flags,
+                ..
+            }) => {
+                let standalone = flags & FL_STANDALONE != 0;
+                writeln!(
+                    out,
+                    "\tGTID {}-{}-{}{}",
+                    domain_id,
+                    info.server_id,
+                    seq_no,
+                    if standalone { "" } else { " trans" }
+                )?;
+                writeln!(
+                    out,
+                    "/*!100001 SET @@session.gtid_domain_id={}*/{}",
+                    domain_id, DELIMITER
+                )?;
+                writeln!(
+                    out,
+                    "/*!100001 SET @@session.server_id={}*/{}",
+                    info.server_id, DELIMITER
+                )?;
+                writeln!(
+                    out,
+                    "/*!100001 SET @@session.gtid_seq_no={}*/{}",
+                    seq_no, DELIMITER
+                )?;
+                if !standalone {
+                    writeln!(out, "START TRANSACTION\n{}", DELIMITER)?;
+                }
+            }
+            Some(EventData::MariadbGtidListEvent { gtids, .. }) => {
+                let gtids: Vec<String> = gtids.iter().map(|g| g.to_string()).collect();
+                writeln!(out, "\tGtid list [{}]", gtids.join(",\n# "))?;
+            }
+            Some(EventData::BinlogCheckpointEvent { log_name }) => {
+                writeln!(out, "\tBinlog checkpoint {}", log_name)?;
+            }
+            Some(EventData::AnnotateRowsEvent { query }) => {
+                writeln!(out, "\tAnnotate_rows:")?;
+                for line in query.lines() {
+                    writeln!(out, "#Q> {}", line)?;
+                }
+            }
+            Some(EventData::EventHeader { .. }) | None => match info.type_code {
+                TypeCode::StopEvent => writeln!(out, "\tStop")?,
+                other => writeln!(out, "\t{:?}", other)?,
+            },
+        }
+        Ok(())
+    }
+}
+
+fn write_row(out: &mut dyn Write, table: &TableInfo, row: &RowEvent) -> io::Result<()> {
+    let name = format!(
+        "{}.{}",
+        quote_identifier(&table.schema_name),
+        quote_identifier(&table.table_name)
+    );
+    match row {
+        RowEvent::NewRow { cols } => {
+            writeln!(out, "### INSERT INTO {}", name)?;
+            writeln!(out, "### SET")?;
+            write_columns(out, table, cols)
+        }
+        RowEvent::DeletedRow { cols } => {
+            writeln!(out, "### DELETE FROM {}", name)?;
+            writeln!(out, "### WHERE")?;
+            write_columns(out, table, cols)
+        }
+        RowEvent::UpdatedRow {
+            before_cols,
+            after_cols,
+        } => {
+            writeln!(out, "### UPDATE {}", name)?;
+            writeln!(out, "### WHERE")?;
+            write_columns(out, table, before_cols)?;
+            writeln!(out, "### SET")?;
+            write_columns(out, table, after_cols)
+        }
+    }
+}
+
+fn write_columns(out: &mut dyn Write, table: &TableInfo, cols: &RowData) -> io::Result<()> {
+    for (i, value) in cols.iter().enumerate() {
+        let (value, column_type) = match (value, table.columns.get(i)) {
+            (Some(value), Some(column_type)) => (value, column_type),
+            _ => continue,
+        };
+        let (type_name, meta) = type_comment(column_type);
+        writeln!(
+            out,
+            "###   @{}={} /* {} meta={} nullable={} is_null={} */",
+            i + 1,
+            value_text(value, column_type),
+            type_name,
+            meta,
+            table.nullable.get(i).copied().unwrap_or(false) as u8,
+            matches!(value, MySQLValue::Null) as u8
+        )?;
+    }
+    Ok(())
+}
+
+/// The type name and metadata word `mysqlbinlog -vv` shows for a column
+fn type_comment(column_type: &ColumnType) -> (String, u16) {
+    match *column_type {
+        ColumnType::Tiny => ("TINYINT".to_owned(), 0),
+        ColumnType::Short => ("SHORTINT".to_owned(), 0),
+        ColumnType::Int24 => ("MEDIUMINT".to_owned(), 0),
+        ColumnType::Long => ("INT".to_owned(), 0),
+        ColumnType::LongLong => ("LONGINT".to_owned(), 0),
+        ColumnType::NewDecimal(precision, decimals) => (
+            format!("DECIMAL({},{})", precision, decimals),
+            u16::from(precision) << 8 | u16::from(decimals),
+        ),
+        ColumnType::Decimal => ("DECIMAL".to_owned(), 0),
+        ColumnType::Float(pack_length) => ("FLOAT".to_owned(), u16::from(pack_length)),
+        ColumnType::Double(pack_length) => ("DOUBLE".to_owned(), u16::from(pack_length)),
+        ColumnType::Bit(bits, bytes) => (
+            format!("BIT({})", u16::from(bytes) * 8 + u16::from(bits)),
+            u16::from(bytes) << 8 | u16::from(bits),
+        ),
+        ColumnType::Timestamp => ("TIMESTAMP".to_owned(), 0),
+        ColumnType::Timestamp2(fsp) => (format!("TIMESTAMP({})", fsp), u16::from(fsp)),
+        ColumnType::DateTime => ("DATETIME".to_owned(), 0),
+        ColumnType::DateTime2(fsp) => (format!("DATETIME({})", fsp), u16::from(fsp)),
+        ColumnType::Time => ("TIME".to_owned(), 0),
+        ColumnType::Time2(fsp) => (format!("TIME({})", fsp), u16::from(fsp)),
+        ColumnType::Date | ColumnType::NewDate => ("DATE".to_owned(), 0),
+        ColumnType::Year => ("YEAR".to_owned(), 0),
+        ColumnType::Enum(size) => (
+            format!("ENUM({} bytes)", size & 0xff),
+            247 << 8 | (size & 0xff),
+        ),
+        ColumnType::Set(size) => (
+            format!("SET({} bytes)", size & 0xff),
+            248 << 8 | (size & 0xff),
+        ),
+        ColumnType::TinyBlob => ("TINYBLOB/TINYTEXT".to_owned(), 1),
+        ColumnType::MediumBlob => ("MEDIUMBLOB/MEDIUMTEXT".to_owned(), 3),
+        ColumnType::LongBlob => ("LONGBLOB/LONGTEXT".to_owned(), 4),
+        ColumnType::Blob(pack_length) => (
+            match pack_length {
+                1 => "TINYBLOB/TINYTEXT",
+                3 => "MEDIUMBLOB/MEDIUMTEXT",
+                4 => "LONGBLOB/LONGTEXT",
+                _ => "BLOB/TEXT",
+            }
+            .to_owned(),
+            u16::from(pack_length),
+        ),
+        ColumnType::VarChar(max_length) => (format!("VARSTRING({})", max_length), max_length),
+        ColumnType::VarString => ("VARSTRING".to_owned(), 0),
+        ColumnType::MyString(length) => (format!("STRING({})", length), 254 << 8 | length),
+        ColumnType::Geometry(pack_length) => ("GEOMETRY".to_owned(), u16::from(pack_length)),
+        ColumnType::Json(pack_length) => ("JSON".to_owned(), u16::from(pack_length)),
+        ColumnType::Null => ("NULL".to_owned(), 0),
+    }
+}
+
+/// A value as `mysqlbinlog -vv` prints it, which is not always valid SQL: dates use colons,
+/// integers are followed by their unsigned reading when negative and bit fields are printed
+/// as binary strings
+fn value_text(value: &MySQLValue, column_type: &ColumnType) -> String {
+    match value {
+        MySQLValue::SignedInteger(i) => match *column_type {
+            ColumnType::Bit(bits, bytes) => {
+                bit_string(*i as u64, u32::from(bytes) * 8 + u32::from(bits))
+            }
+            ColumnType::Set(size) => bit_string(*i as u64, u32::from(size & 0xff) * 8),
+            _ if *i < 0 => format!("{} ({})", i, unsigned_value(*i, Some(column_type))),
+            _ => i.to_string(),
+        },
+        MySQLValue::Float(f) => format!("{:<20}", f),
+        MySQLValue::Double(f) => f.to_string(),
+        MySQLValue::String(s) => quote_bytes(s.as_bytes()),
+        MySQLValue::Blob(b) => quote_bytes(b.as_ref()),
+        MySQLValue::Geometry { srid, wkb } => {
+            quote_bytes(&geometry::internal_bytes(*srid, wkb.as_ref()))
+        }
+        MySQLValue::Enum {
+            label: Some(label), ..
+        } => quote_bytes(label.as_bytes()),
+        MySQLValue::Enum { index, .. } => index.to_string(),
+        MySQLValue::Set(labels) => quote_bytes(labels.join(",").as_bytes()),
+        MySQLValue::Year(y) => y.to_string(),
+        MySQLValue::Date { year, month, day } => format!("'{:04}:{:02}:{:02}'", year, month, day),
+        MySQLValue::Time {
+            negative,
+            hours,
+            minutes,
+            seconds,
+            subseconds,
+        } => format!(
+            "'{}{:02}:{:02}:{:02}{}'",
+            if *negative { "-" } else { "" },
+            hours,
+            minutes,
+            seconds,
+            fraction(*subseconds, Some(column_type))
+        ),
+        MySQLValue::DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            subsecond,
+        } => {
+            let text = format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}",
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                fraction(*subsecond, Some(column_type))
+            );
+            // only the MySQL 5.6 encoding is quoted
+            match column_type {
+                ColumnType::DateTime => text,
+                _ => format!("'{}'", text),
+            }
+        }
+        MySQLValue::Timestamp {
+            unix_time,
+            subsecond,
+        } => format!("{}{}", unix_time, fraction(*subsecond, Some(column_type))),
+        MySQLValue::Json(j) => quote_bytes(j.to_string().as_bytes()),
+        MySQLValue::Decimal(d) => d.to_string(),
+        MySQLValue::Null => "NULL".to_owned(),
+    }
+}
+
+fn bit_string(value: u64, bits: u32) -> String {
+    let digits: String = (0..bits.min(64))
+        .rev()
+        .map(|bit| if value >> bit & 1 == 1 { '1' } else { '0' })
+        .collect();
+    format!("b'{}'", digits)
+}
+
+/// Quote like `mysqlbinlog` does: control characters, quotes and backslashes as `\x..`
+fn quote_bytes(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() + 2);
+    out.push('\'');
+    let mut start = 0;
+    for (i, b) in bytes.iter().enumerate() {
+        if *b <= 0x1f || *b == b'\'' || *b == b'\\' {
+            out.push_str(&String::from_utf8_lossy(&bytes[start..i]));
+            out.push_str(&format!("\\x{:02x}", b));
+            start = i + 1;
+        }
+    }
+    out.push_str(&String::from_utf8_lossy(&bytes[start..]));
+    out.push('\'');
+    out
+}
+
+/// `yymmdd hh:mm:ss` with the hour padded by a space, in UTC
+fn short_datetime(timestamp: u32) -> String {
+    let (year, month, day, hour, minute, second) = civil_datetime(timestamp);
+    format!(
+        "{:02}{:02}{:02} {:>2}:{:02}:{:02}",
+        year % 100,
+        month,
+        day,
+        hour,
+        minute,
+        second
+    )
+}
+
+/// Split a unix timestamp into its UTC date and time
+pub fn civil_datetime(timestamp: u32) -> (i64, u32, u32, u32, u32, u32) {
+    let days = i64::from(timestamp / 86400);
+    let seconds = timestamp % 86400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+    )
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::mysql_binlog::binlog_file::BinlogFile;
+    use crate::mysql_binlog::binlog_writer::BinlogFileWriter;
+    use crate::mysql_binlog::bit_set::BitSet;
+    use crate::mysql_binlog::column_types::ColumnType;
+    use crate::mysql_binlog::event::{EventData, RowEvent};
+    use crate::mysql_binlog::table_map::TableMetadata;
+    use crate::mysql_binlog::value::MySQLValue;
+
+    use super::{civil_datetime, days_from_civil, value_text, EventInfo, TextWriter};
+
+    #[test]
+    fn test_civil_datetime() {
+        assert_eq!(civil_datetime(0), (1970, 1, 1, 0, 0, 0));
+        assert_eq!(civil_datetime(1625140800), (2021, 7, 1, 12, 0, 0));
+        assert_eq!(civil_datetime(951782400), (2000, 2, 29, 0, 0, 0));
+        assert_eq!(days_from_civil(2000, 2, 29), 951782400 / 86400);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+    }
+
+    #[test]
+    fn test_value_text() {
+        let cases = vec![
+            (
+                MySQLValue::SignedInteger(-1),
+                ColumnType::Long,
+                "-1 (4294967295)",
+            ),
+            (
+                MySQLValue::SignedInteger(5),
+                ColumnType::Bit(3, 0),
+                "b'101'",
+            ),
+            (
+                MySQLValue::String("o'neil\n".to_owned()),
+                ColumnType::VarChar(64),
+                "'o\\x27neil\\x0a'",
+            ),
+            (
+                MySQLValue::Date {
+                    year: 2021,
+                    month: 7,
+                    day: 1,
+                },
+                ColumnType::Date,
+                "'2021:07:01'",
+            ),
+            (
+                MySQLValue::DateTime {
+                    year: 2021,
+                    month: 7,
+                    day: 1,
+                    hour: 12,
+                    minute: 0,
+                    second: 0,
+                    subsecond: 500_000,
+                },
+                ColumnType::DateTime2(1),
+                "'2021-07-01 12:00:00.5'",
+            ),
+            (
+                MySQLValue::Timestamp {
+                    unix_time: 1625140800,
+                    subsecond: 0,
+                },
+                ColumnType::Timestamp2(0),
+                "1625140800",
+            ),
+        ];
+        for (value, column_type, expected) in cases {
+            assert_eq!(value_text(&value, &column_type), expected);
+        }
+    }
+
+    #[test]
+    fn test_write_events() {
+        let mut writer = BinlogFileWriter::new(Cursor::new(Vec::new()), 1).unwrap();
+        for event in &[
+            EventData::GtidLogEvent {
+                flags: 0,
+                uuid: uuid::Uuid::parse_str("0575a804-6403-11ea-8d3d-e454e8d4a4fe").unwrap(),
+                coordinate: 7,
+                last_committed: Some(6),
+                sequence_number: Some(7),
+            },
+            EventData::QueryEvent {
+                thread_id: 12,
+                exec_time: 0,
+                error_code: 0,
+                schema: "shop".to_owned(),
+                query: "BEGIN".to_owned(),
+            },
+            EventData::TableMapEvent {
+                table_id: 108,
+                schema_name: "shop".to_owned(),
+                table_name: "orders".to_owned(),
+                columns: vec![ColumnType::Long, ColumnType::VarChar(64)],
+                null_bitmap: {
+                    let mut nullable = BitSet::new(2);
+                    nullable.set(1);
+                    nullable
+                },
+                metadata: TableMetadata::default(),
+            },
+            EventData::UpdateRowsEvent {
+                table_id: 108,
+                rows: vec![RowEvent::UpdatedRow {
+                    before_cols: vec![Some(MySQLValue::SignedInteger(1)), Some(MySQLValue::Null)],
+                    after_cols: vec![
+                        Some(MySQLValue::SignedInteger(1)),
+                        Some(MySQLValue::String("paid".to_owned())),
+                    ],
+                }],
+            },
+            EventData::XIDEvent { xid: 10 },
+        ] {
+            writer.write_event(1625140800, event).unwrap();
+        }
+        let binlog = writer.finish().unwrap().into_inner();
+
+        let mut text = TextWriter::new();
+        let mut out = Vec::new();
+        for event in BinlogFile::try_from_reader(Cursor::new(binlog))
+            .unwrap()
+            .events(Some(4))
+        {
+            let event = event.unwrap();
+            let data = event.inner(Some(text.table_map())).unwrap();
+            text.write_event(
+                &mut out,
+                &EventInfo::from_event(&event),
+                event.data(),
+                data.as_ref(),
+            )
+            .unwrap();
+        }
+        let out = String::from_utf8(out).unwrap();
+        // drop the CRC32s, which are not interesting here
+        let lines: Vec<String> = out
+            .lines()
+            .map(|l| match l.find(" CRC32 0x") {
+                Some(i) => format!("{}{}", &l[..i], &l[i + 18..]),
+                None => l.to_owned(),
+            })
+            .collect();
+        assert_eq!(lines[0], "# at 4");
+        // the format description event is stamped with the time it was written
+        assert!(
+            lines[1].contains(" server id 1  end_log_pos 123\tStart: binlog v 4, server v "),
+            "{}",
+            lines[1]
+        );
+        let body = lines.join("\n");
+        let expected = "\
+#210701 12:00:00 server id 1  end_log_pos 188\tGTID\tlast_committed=6\tsequence_number=7\trbr_only=yes
+/*!50718 SET TRANSACTION ISOLATION LEVEL READ COMMITTED*//*!*/;
+SET @@SESSION.GTID_NEXT= '0575a804-6403-11ea-8d3d-e454e8d4a4fe:7'/*!*/;
+# at 188
+#210701 12:00:00 server id 1  end_log_pos 234\tQuery\tthread_id=12\texec_time=0\terror_code=0
+use `shop`/*!*/;
+SET TIMESTAMP=1625140800/*!*/;
+SET @@session.pseudo_thread_id=12/*!*/;
+BEGIN
+/*!*/;
+# at 234
+#210701 12:00:00 server id 1  end_log_pos 286\tTable_map: `shop`.`orders` mapped to number 108
+# at 286
+#210701 12:00:00 server id 1  end_log_pos 337\tUpdate_rows: table id 108 flags: STMT_END_F
+### UPDATE `shop`.`orders`
+### WHERE
+###   @1=1 /* INT meta=0 nullable=0 is_null=0 */
+###   @2=NULL /* VARSTRING(64) meta=64 nullable=1 is_null=1 */
+### SET
+###   @1=1 /* INT meta=0 nullable=0 is_null=0 */
+###   @2='paid' /* VARSTRING(64) meta=64 nullable=1 is_null=0 */
+# at 337
+#210701 12:00:00 server id 1  end_log_pos 368\tXid = 10
+COMMIT/*!*/;";
+        assert!(body.contains(expected), "{}", body);
+    }
+}